@@ -0,0 +1,152 @@
+//! REST API behind `kmobile serve`. Errors keep their `KMobileError` kind: the HTTP
+//! status comes from `KMobileError::http_status` and the body carries `error_code`.
+
+use anyhow::Result;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::config::Config;
+use crate::device_basic::DeviceManager;
+use crate::error::KMobileError;
+use crate::project::ProjectManager;
+use crate::simulator_basic::SimulatorManager;
+use crate::testing::{TestRunOptions, TestRunner};
+
+/// Error response body, the same shape `--output json` prints for CLI errors
+pub struct ApiError(anyhow::Error);
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(error: E) -> Self {
+        Self(error.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let kind = KMobileError::find(&self.0);
+        let status = kind
+            .and_then(|kind| StatusCode::from_u16(kind.http_status()).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = json!({
+            "error": {
+                "code": kind.map_or("UNKNOWN_ERROR", KMobileError::error_code),
+                "message": self.0.to_string(),
+                "recoverable": kind.is_some_and(KMobileError::is_recoverable),
+            }
+        });
+        (status, Json(body)).into_response()
+    }
+}
+
+type ApiResult = std::result::Result<Json<Value>, ApiError>;
+
+#[derive(Debug, Deserialize)]
+struct DeployRequest {
+    device_id: String,
+    project: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TestRunRequest {
+    suite: Option<String>,
+    device_id: Option<String>,
+}
+
+/// Managers are created per request so device lists are never stale
+pub fn router(config: Config) -> Router {
+    Router::new()
+        .route("/devices", get(devices))
+        .route("/simulators", get(simulators))
+        .route("/deploy", post(deploy))
+        .route("/test/run", post(run_tests))
+        .route("/project/status", get(project_status))
+        .with_state(Arc::new(config))
+}
+
+pub async fn serve(config: Config, host: &str, port: u16) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind((host, port))
+        .await
+        .map_err(|e| KMobileError::NetworkError(format!("Cannot bind {host}:{port}: {e}")))?;
+    info!("API server listening on http://{}:{}", host, port);
+    axum::serve(listener, router(config))
+        .await
+        .map_err(|e| KMobileError::NetworkError(e.to_string()))?;
+    Ok(())
+}
+
+async fn devices(State(config): State<Arc<Config>>) -> ApiResult {
+    let devices = DeviceManager::new(&config).await?.list_devices().await?;
+    Ok(Json(json!(devices)))
+}
+
+async fn simulators(State(config): State<Arc<Config>>) -> ApiResult {
+    let simulators = SimulatorManager::new(&config)
+        .await?
+        .list_simulators()
+        .await?;
+    Ok(Json(json!(simulators)))
+}
+
+async fn deploy(
+    State(config): State<Arc<Config>>,
+    Json(request): Json<DeployRequest>,
+) -> ApiResult {
+    DeviceManager::new(&config)
+        .await?
+        .deploy_project(&request.device_id, request.project.as_deref())
+        .await?;
+    Ok(Json(json!({
+        "success": true,
+        "message": format!("Deployed project to device: {}", request.device_id),
+    })))
+}
+
+async fn run_tests(
+    State(config): State<Arc<Config>>,
+    request: Option<Json<TestRunRequest>>,
+) -> ApiResult {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let report = TestRunner::new(&config)
+        .await?
+        .run_tests(
+            request.suite.as_deref(),
+            request.device_id.as_deref(),
+            &TestRunOptions::default(),
+        )
+        .await?;
+    Ok(Json(json!(report)))
+}
+
+async fn project_status(State(config): State<Arc<Config>>) -> ApiResult {
+    let status = ProjectManager::new(&config)
+        .await?
+        .get_project_status()
+        .await?;
+    Ok(Json(json!(status)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_status_follows_error_kind() {
+        let response =
+            ApiError::from(KMobileError::DeviceNotFound("emulator-5554".into())).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let error = anyhow::Error::from(KMobileError::InvalidInput("suite".into()));
+        let response = ApiError::from(error.context("parsing request")).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = ApiError::from(anyhow::anyhow!("boom")).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,538 @@
+//! Android Virtual Devices through the SDK command-line tools: `sdkmanager` installs
+//! system images, `avdmanager` creates, lists and deletes AVDs, and hardware settings
+//! are edited in the AVD's `config.ini`
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use crate::config::Config;
+use crate::error::KMobileError;
+
+/// System image variant used when `--tag` isn't given
+pub const DEFAULT_TAG: &str = "google_apis";
+
+/// Values the emulator accepts for `hw.gpu.mode`
+pub const GPU_MODES: &[&str] = &[
+    "auto",
+    "host",
+    "swiftshader_indirect",
+    "angle_indirect",
+    "guest",
+    "off",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AvdInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    pub path: PathBuf,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_level: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abi: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sdcard: Option<String>,
+    /// Why avdmanager could not load the AVD
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Hardware settings written to an AVD's `config.ini`. `None` leaves a setting as is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AvdHardware {
+    pub ram_mb: Option<u32>,
+    pub heap_mb: Option<u32>,
+    pub gpu_mode: Option<String>,
+}
+
+impl AvdHardware {
+    pub fn is_empty(&self) -> bool {
+        self.ram_mb.is_none() && self.heap_mb.is_none() && self.gpu_mode.is_none()
+    }
+
+    /// The `config.ini` entries for the settings that are given
+    pub fn entries(&self) -> Result<Vec<(&'static str, String)>> {
+        let mut entries = Vec::new();
+        if let Some(ram) = self.ram_mb {
+            entries.push(("hw.ramSize", format!("{ram}M")));
+        }
+        if let Some(heap) = self.heap_mb {
+            entries.push(("vm.heapSize", format!("{heap}M")));
+        }
+        if let Some(mode) = &self.gpu_mode {
+            if !GPU_MODES.contains(&mode.as_str()) {
+                return Err(KMobileError::InvalidInput(format!(
+                    "Unknown GPU mode '{mode}', expected one of: {}",
+                    GPU_MODES.join(", ")
+                ))
+                .into());
+            }
+            let enabled = if mode == "off" { "no" } else { "yes" };
+            entries.push(("hw.gpu.enabled", enabled.to_string()));
+            entries.push(("hw.gpu.mode", mode.clone()));
+        }
+        Ok(entries)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateAvdOptions {
+    /// Derived from the device and API level when not given, e.g. `pixel_7_API_34`
+    pub name: Option<String>,
+    pub api_level: u32,
+    /// Defaults to the host's architecture
+    pub abi: Option<String>,
+    pub tag: String,
+    /// Hardware profile from `avdmanager list device`, e.g. `pixel_7`
+    pub device: Option<String>,
+    /// Replace an existing AVD with the same name
+    pub force: bool,
+    pub hardware: AvdHardware,
+}
+
+impl CreateAvdOptions {
+    pub fn avd_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            format!(
+                "{}_API_{}",
+                self.device.as_deref().unwrap_or("kmobile"),
+                self.api_level
+            )
+        })
+    }
+
+    pub fn abi(&self) -> String {
+        self.abi.clone().unwrap_or_else(|| host_abi().to_string())
+    }
+
+    /// sdkmanager package id, e.g. `system-images;android-34;google_apis;x86_64`
+    pub fn system_image(&self) -> String {
+        system_image_package(self.api_level, &self.tag, &self.abi())
+    }
+}
+
+pub fn system_image_package(api_level: u32, tag: &str, abi: &str) -> String {
+    format!("system-images;android-{api_level};{tag};{abi}")
+}
+
+/// ABI that runs accelerated on this machine
+pub fn host_abi() -> &'static str {
+    if cfg!(target_arch = "aarch64") {
+        "arm64-v8a"
+    } else {
+        "x86_64"
+    }
+}
+
+/// Runs `sdkmanager` and `avdmanager` from the configured SDK
+pub struct AvdManager {
+    sdk_path: Option<PathBuf>,
+    avdmanager: PathBuf,
+    sdkmanager: PathBuf,
+}
+
+impl AvdManager {
+    pub fn new(config: &Config) -> Result<Self> {
+        let sdk_path = config.android.sdk_path.clone();
+        let find = |tool: &str| {
+            sdk_path
+                .as_deref()
+                .and_then(|sdk| find_sdk_tool(sdk, tool))
+                .or_else(|| which::which(tool).ok())
+                .ok_or_else(|| {
+                    KMobileError::ConfigError(format!(
+                        "{tool} not found; install the Android SDK command-line tools"
+                    ))
+                })
+        };
+        Ok(Self {
+            avdmanager: find("avdmanager")?,
+            sdkmanager: find("sdkmanager")?,
+            sdk_path,
+        })
+    }
+
+    pub async fn list_avds(&self) -> Result<Vec<AvdInfo>> {
+        debug!("Running avdmanager list avd");
+        let output = run(Command::new(&self.avdmanager).args(["list", "avd"]), None).await?;
+        let mut avds = parse_avd_list(&output);
+        for avd in &mut avds {
+            if avd.api_level.is_none() {
+                avd.api_level = read_config_ini(&avd.path)
+                    .ok()
+                    .and_then(|entries| api_level_from_config(&entries));
+            }
+        }
+        Ok(avds)
+    }
+
+    /// Installs the system image if needed, creates the AVD and applies its hardware
+    /// settings
+    pub async fn create_avd(&self, options: &CreateAvdOptions) -> Result<AvdInfo> {
+        let name = options.avd_name();
+        let package = options.system_image();
+        let hardware = options.hardware.entries()?;
+
+        if self.is_installed(options) {
+            debug!("{} is already installed", package);
+        } else {
+            info!("Installing system image {}", package);
+            // sdkmanager asks once per license that hasn't been accepted yet
+            run(
+                Command::new(&self.sdkmanager).args(["--install", &package]),
+                Some(&"y\n".repeat(32)),
+            )
+            .await?;
+        }
+
+        info!("Creating AVD {}", name);
+        let mut create = Command::new(&self.avdmanager);
+        create.args(["create", "avd", "--name", &name, "--package", &package]);
+        if let Some(device) = &options.device {
+            create.args(["--device", device]);
+        }
+        if options.force {
+            create.arg("--force");
+        }
+        // Declines the "custom hardware profile" prompt
+        run(&mut create, Some("no\n")).await?;
+
+        let avd = self
+            .list_avds()
+            .await?
+            .into_iter()
+            .find(|avd| avd.name == name)
+            .ok_or_else(|| {
+                KMobileError::CommandError(format!("avdmanager did not create {name}"))
+            })?;
+        if !hardware.is_empty() {
+            edit_config_ini(&avd.path.join("config.ini"), &hardware)?;
+        }
+        Ok(avd)
+    }
+
+    pub async fn delete_avd(&self, name: &str) -> Result<()> {
+        info!("Deleting AVD {}", name);
+        run(
+            Command::new(&self.avdmanager).args(["delete", "avd", "--name", name]),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Applies hardware settings to an existing AVD
+    pub async fn configure_avd(&self, name: &str, hardware: &AvdHardware) -> Result<AvdInfo> {
+        let avd = self
+            .list_avds()
+            .await?
+            .into_iter()
+            .find(|avd| avd.name == name)
+            .ok_or_else(|| KMobileError::SimulatorNotFound(name.to_string()))?;
+        edit_config_ini(&avd.path.join("config.ini"), &hardware.entries()?)?;
+        Ok(avd)
+    }
+
+    fn is_installed(&self, options: &CreateAvdOptions) -> bool {
+        self.sdk_path.as_deref().is_some_and(|sdk| {
+            sdk.join("system-images")
+                .join(format!("android-{}", options.api_level))
+                .join(&options.tag)
+                .join(options.abi())
+                .join("system.img")
+                .exists()
+        })
+    }
+}
+
+/// Where AVDs live: `ANDROID_AVD_HOME`, else `avd` under `ANDROID_USER_HOME` or
+/// `~/.android`
+pub fn avd_home() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    var("ANDROID_AVD_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("ANDROID_USER_HOME").map(|home| PathBuf::from(home).join("avd")))
+        .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".android").join("avd")))
+}
+
+/// The AVD's directory, from the `path=` line of `<name>.ini` next to it
+pub fn avd_path(name: &str) -> Option<PathBuf> {
+    let home = avd_home()?;
+    let ini = std::fs::read_to_string(home.join(format!("{name}.ini"))).ok();
+    ini.as_deref()
+        .and_then(|ini| {
+            ini.lines()
+                .filter_map(|line| line.split_once('='))
+                .find(|(key, _)| key.trim() == "path")
+                .map(|(_, path)| PathBuf::from(path.trim()))
+        })
+        .or_else(|| Some(home.join(format!("{name}.avd"))))
+        .filter(|path| path.is_dir())
+}
+
+/// `cmdline-tools/latest` is where the SDK manager installs the tools; `tools/bin` is
+/// the pre-2020 layout
+pub fn find_sdk_tool(sdk_path: &Path, tool: &str) -> Option<PathBuf> {
+    [
+        sdk_path.join("cmdline-tools").join("latest").join("bin"),
+        sdk_path.join("tools").join("bin"),
+    ]
+    .into_iter()
+    .map(|dir| dir.join(tool))
+    .find(|path| path.exists())
+}
+
+async fn run(command: &mut Command, input: Option<&str>) -> Result<String> {
+    command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command.spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // The tool may exit before reading every answer
+        let _ = stdin.write_all(input.as_bytes()).await;
+    }
+    let output = child.wait_with_output().await?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr
+            .lines()
+            .chain(stdout.lines())
+            .find(|line| line.contains("Error"))
+            .unwrap_or_else(|| stderr.trim());
+        return Err(KMobileError::CommandError(message.trim().to_string()).into());
+    }
+    Ok(stdout)
+}
+
+/// Parses `avdmanager list avd`. AVDs listed under "could not be loaded" are kept with
+/// their error.
+pub fn parse_avd_list(output: &str) -> Vec<AvdInfo> {
+    let mut avds = Vec::new();
+    let mut current: Option<AvdInfo> = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("---") || trimmed.starts_with("The following") {
+            avds.extend(current.take());
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if key == "Name" {
+            avds.extend(current.take());
+            current = Some(AvdInfo {
+                name: value.to_string(),
+                ..AvdInfo::default()
+            });
+            continue;
+        }
+        let Some(avd) = current.as_mut() else {
+            continue;
+        };
+        match key {
+            "Device" => avd.device = Some(value.to_string()),
+            "Path" => avd.path = PathBuf::from(value),
+            "Target" => avd.target = value.to_string(),
+            "Based on" => parse_based_on(avd, value),
+            "Tag/ABI" => parse_tag_abi(avd, value),
+            "Skin" => avd.skin = Some(value.to_string()),
+            "Sdcard" => avd.sdcard = Some(value.to_string()),
+            "Error" => avd.error = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    avds.extend(current);
+    avds
+}
+
+/// `Android 14.0 ("UpsideDownCake") Tag/ABI: google_apis/x86_64` or
+/// `Android API 30 Tag/ABI: default/x86_64`
+fn parse_based_on(avd: &mut AvdInfo, value: &str) {
+    let (platform, tag_abi) = match value.split_once("Tag/ABI:") {
+        Some((platform, tag_abi)) => (platform.trim(), Some(tag_abi)),
+        None => (value, None),
+    };
+    if let Some(level) = platform.strip_prefix("Android API ") {
+        avd.api_level = level.trim().parse().ok();
+    }
+    if let Some(tag_abi) = tag_abi {
+        parse_tag_abi(avd, tag_abi);
+    }
+}
+
+fn parse_tag_abi(avd: &mut AvdInfo, value: &str) {
+    if let Some((tag, abi)) = value.trim().split_once('/') {
+        avd.tag = Some(tag.to_string());
+        avd.abi = Some(abi.to_string());
+    }
+}
+
+/// `image.sysdir.1=system-images/android-34/google_apis/x86_64/`
+fn api_level_from_config(entries: &[(String, String)]) -> Option<u32> {
+    let (_, sysdir) = entries.iter().find(|(key, _)| key == "image.sysdir.1")?;
+    sysdir
+        .split(['/', '\\'])
+        .find_map(|part| part.strip_prefix("android-"))?
+        .parse()
+        .ok()
+}
+
+pub fn read_config_ini(avd_path: &Path) -> Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(avd_path.join("config.ini"))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect())
+}
+
+/// Sets `entries` in a `config.ini`, replacing existing keys in place and appending
+/// new ones. Other lines are kept as they are.
+pub fn edit_config_ini(path: &Path, entries: &[(&str, String)]) -> Result<()> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        KMobileError::FileSystemError(format!("Cannot read {}: {e}", path.display()))
+    })?;
+    std::fs::write(path, apply_config_entries(&content, entries))?;
+    Ok(())
+}
+
+fn apply_config_entries(content: &str, entries: &[(&str, String)]) -> String {
+    let mut remaining: Vec<&(&str, String)> = entries.iter().collect();
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            let key = line.split_once('=').map(|(key, _)| key.trim());
+            match remaining.iter().position(|(name, _)| Some(*name) == key) {
+                Some(index) => {
+                    let (name, value) = remaining.remove(index);
+                    format!("{name}={value}")
+                }
+                None => line.to_string(),
+            }
+        })
+        .collect();
+    lines.extend(
+        remaining
+            .into_iter()
+            .map(|(name, value)| format!("{name}={value}")),
+    );
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AVD_LIST: &str = "\
+Available Android Virtual Devices:
+    Name: Pixel_7_API_34
+  Device: pixel_7 (Google)
+    Path: /home/dev/.android/avd/Pixel_7_API_34.avd
+  Target: Google APIs (Google Inc.)
+          Based on: Android 14.0 (\"UpsideDownCake\") Tag/ABI: google_apis/x86_64
+    Skin: pixel_7
+  Sdcard: 512M
+---------
+    Name: ci
+    Path: /home/dev/.android/avd/ci.avd
+  Target: Default Android System Image
+          Based on: Android API 30 Tag/ABI: default/x86_64
+  Sdcard: 512 MB
+
+The following Android Virtual Devices could not be loaded:
+    Name: broken
+    Path: /home/dev/.android/avd/broken.avd
+   Error: Missing system image for google_apis x86 Nexus_5.
+";
+
+    #[test]
+    fn test_parse_avd_list() {
+        let avds = parse_avd_list(AVD_LIST);
+        assert_eq!(avds.len(), 3);
+
+        assert_eq!(avds[0].name, "Pixel_7_API_34");
+        assert_eq!(avds[0].device.as_deref(), Some("pixel_7 (Google)"));
+        assert_eq!(
+            avds[0].path,
+            PathBuf::from("/home/dev/.android/avd/Pixel_7_API_34.avd")
+        );
+        assert_eq!(avds[0].target, "Google APIs (Google Inc.)");
+        assert_eq!(avds[0].tag.as_deref(), Some("google_apis"));
+        assert_eq!(avds[0].abi.as_deref(), Some("x86_64"));
+        assert_eq!(avds[0].skin.as_deref(), Some("pixel_7"));
+        assert_eq!(avds[0].api_level, None);
+
+        assert_eq!(avds[1].name, "ci");
+        assert_eq!(avds[1].api_level, Some(30));
+        assert_eq!(avds[1].sdcard.as_deref(), Some("512 MB"));
+        assert!(avds[1].error.is_none());
+
+        assert_eq!(avds[2].name, "broken");
+        assert!(avds[2].error.as_deref().unwrap().starts_with("Missing"));
+    }
+
+    #[test]
+    fn test_edit_config_ini() {
+        let hardware = AvdHardware {
+            ram_mb: Some(4096),
+            heap_mb: None,
+            gpu_mode: Some("swiftshader_indirect".to_string()),
+        };
+        let content = "avd.ini.encoding=UTF-8\nhw.ramSize=1536M\nimage.sysdir.1=system-images/android-34/google_apis/x86_64/\n";
+        let edited = apply_config_entries(content, &hardware.entries().unwrap());
+        assert_eq!(
+            edited,
+            "avd.ini.encoding=UTF-8\nhw.ramSize=4096M\nimage.sysdir.1=system-images/android-34/google_apis/x86_64/\nhw.gpu.enabled=yes\nhw.gpu.mode=swiftshader_indirect\n"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("config.ini"), edited).unwrap();
+        let entries = read_config_ini(dir.path()).unwrap();
+        assert_eq!(api_level_from_config(&entries), Some(34));
+
+        let invalid = AvdHardware {
+            gpu_mode: Some("fast".to_string()),
+            ..AvdHardware::default()
+        };
+        assert!(invalid.entries().is_err());
+    }
+
+    #[test]
+    fn test_create_options_defaults() {
+        let options = CreateAvdOptions {
+            name: None,
+            api_level: 34,
+            abi: Some("x86_64".to_string()),
+            tag: DEFAULT_TAG.to_string(),
+            device: Some("pixel_7".to_string()),
+            force: false,
+            hardware: AvdHardware::default(),
+        };
+        assert_eq!(options.avd_name(), "pixel_7_API_34");
+        assert_eq!(
+            options.system_image(),
+            "system-images;android-34;google_apis;x86_64"
+        );
+    }
+}
@@ -1,15 +1,237 @@
 use anyhow::Result;
-use tracing::{info, warn};
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::info;
 
-use crate::config::Config;
+use crate::avd::CreateAvdOptions;
+use crate::config::{self, Config, ConfigCommands, LoadedConfig, Severity};
 use crate::device_basic::{DeviceCommands, DeviceManager};
+use crate::doctor;
+use crate::error::KMobileError;
+use crate::farm::{EmulatorFarm, FarmOptions};
+use crate::hardware_emulator::route::Route;
+use crate::hardware_emulator::HardwareEmulator;
 use crate::mcp::McpServer;
+use crate::output::{Outcome, Output, OutputFormat};
 use crate::project::{ProjectCommands, ProjectManager};
-use crate::simulator_basic::{SimulatorCommands, SimulatorManager};
-use crate::testing::{TestCommands, TestRunner};
+use crate::simulator_basic::{SimulatorCommands, SimulatorManager, SnapshotCommands};
+use crate::testing::maestro;
+use crate::testing::native::NativeTestOptions;
+use crate::testing::{TestCommands, TestFilter, TestReport, TestRunOptions, TestRunner};
+use crate::utils::{retry_with_backoff, DEVICE_RETRIES, DEVICE_RETRY_DELAY};
+
+/// Config commands only need the loaded layers, so they run without device managers
+pub fn handle_config_command(
+    loaded: &LoadedConfig,
+    command: ConfigCommands,
+    output: &Output,
+) -> Result<()> {
+    let target = |user: bool| -> Result<PathBuf> {
+        if user {
+            Ok(loaded.user_file.clone().ok_or_else(|| {
+                KMobileError::ConfigError("Cannot locate the user config directory".to_string())
+            })?)
+        } else {
+            Ok(loaded.project_file.clone())
+        }
+    };
+
+    let outcome = match command {
+        ConfigCommands::Show { origin: false } => return output.document(&loaded.config),
+        ConfigCommands::Show { origin: true } => {
+            if let Some(profile) = &loaded.profile {
+                output.heading(&format!("⚙️  Configuration (profile {profile}):"));
+            } else {
+                output.heading("⚙️  Configuration:");
+            }
+            return output.list(&loaded.entries()?);
+        }
+        ConfigCommands::Get { key } => {
+            let value = loaded.get(&key)?;
+            match (output.format(), &value) {
+                (OutputFormat::Json | OutputFormat::Yaml, _)
+                | (_, serde_json::Value::Object(_)) => return output.document(&value),
+                (_, serde_json::Value::String(text)) => println!("{text}"),
+                (_, serde_json::Value::Null) => {}
+                (_, value) => println!("{value}"),
+            }
+            return Ok(());
+        }
+        ConfigCommands::Set { key, value, user } => {
+            let path = target(user)?;
+            config::edit::set_value(&path, &key, &value)?;
+            Outcome::new(
+                "config.set",
+                Some(&key),
+                format!("Set {key} = {value} in {}", path.display()),
+            )
+        }
+        ConfigCommands::Unset { key, user } => {
+            let path = target(user)?;
+            if !config::edit::unset_value(&path, &key)? {
+                return Err(KMobileError::InvalidInput(format!(
+                    "{key} is not set in {}",
+                    path.display()
+                ))
+                .into());
+            }
+            Outcome::new(
+                "config.unset",
+                Some(&key),
+                format!("Removed {key} from {}", path.display()),
+            )
+        }
+        ConfigCommands::Validate => {
+            let issues = loaded.config.validate();
+            if issues.is_empty() {
+                Outcome::new("config.validate", None, "Configuration is valid")
+            } else {
+                output.heading("⚙️  Configuration problems:");
+                output.list(&issues)?;
+                let errors = issues
+                    .iter()
+                    .filter(|issue| issue.severity == Severity::Error)
+                    .count();
+                if errors > 0 {
+                    return Err(KMobileError::ConfigError(format!(
+                        "{errors} configuration error(s) found"
+                    ))
+                    .into());
+                }
+                return Ok(());
+            }
+        }
+        ConfigCommands::Init { user, force } => {
+            let path = target(user)?;
+            if path.exists() && !force {
+                return Err(KMobileError::InvalidInput(format!(
+                    "{} already exists; use --force to overwrite it",
+                    path.display()
+                ))
+                .into());
+            }
+            if let Some(parent) = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(parent)?;
+            }
+            Config::default().save(&path.to_string_lossy())?;
+            Outcome::new(
+                "config.init",
+                None,
+                format!("Wrote default configuration to {}", path.display()),
+            )
+            .with_details(vec![path.display().to_string()])
+        }
+        ConfigCommands::Schema => {
+            let schema = config::schema::config_schema();
+            match output.format() {
+                OutputFormat::Yaml => return output.document(&schema),
+                _ => println!("{schema:#}"),
+            }
+            return Ok(());
+        }
+    };
+    output.outcome(&outcome)
+}
+
+/// Runs `kmobile doctor`. Discovered paths are written to the config file with
+/// `write_config`, or after asking when stdin is a terminal.
+pub async fn handle_doctor_command(
+    loaded: &LoadedConfig,
+    write_config: bool,
+    user: bool,
+    output: &Output,
+) -> Result<()> {
+    let mut report = doctor::run_doctor(&loaded.config).await?;
+    // Structured formats get one document, printed once the config is written
+    let structured = matches!(output.format(), OutputFormat::Json | OutputFormat::Yaml);
+
+    if !structured {
+        let system = &report.system;
+        output.heading(&format!(
+            "🩺 {} {} ({})",
+            system.os, system.version, system.arch
+        ));
+        output.list(&report.checks)?;
+    }
+
+    if !report.discovered.is_empty() {
+        let path = if user {
+            loaded.user_file.clone().ok_or_else(|| {
+                KMobileError::ConfigError("Cannot locate the user config directory".to_string())
+            })?
+        } else {
+            loaded.project_file.clone()
+        };
+
+        let interactive = output.format() == OutputFormat::Table && std::io::stdin().is_terminal();
+        if output.format() == OutputFormat::Table {
+            println!("\nDiscovered paths not in your config:");
+            for discovered in &report.discovered {
+                println!("  {} = {}", discovered.key, discovered.path.display());
+            }
+        }
+        if write_config || (interactive && confirm(&format!("Write them to {}?", path.display()))?)
+        {
+            for discovered in &report.discovered {
+                config::edit::set_value(
+                    &path,
+                    &discovered.key,
+                    &discovered.path.to_string_lossy(),
+                )?;
+            }
+            if !structured {
+                output.outcome(
+                    &Outcome::new(
+                        "doctor.write_config",
+                        None,
+                        format!(
+                            "Wrote {} path(s) to {}",
+                            report.discovered.len(),
+                            path.display()
+                        ),
+                    )
+                    .with_details(
+                        report
+                            .discovered
+                            .iter()
+                            .map(|discovered| discovered.key.clone())
+                            .collect(),
+                    ),
+                )?;
+            }
+            report.written_config = Some(path);
+        }
+    }
+
+    if structured {
+        output.document(&report)?;
+    }
+
+    let errors = report.errors();
+    if errors > 0 {
+        return Err(KMobileError::ConfigError(format!(
+            "{errors} problem(s) found by kmobile doctor"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{question} [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
 
 pub struct KMobileCli {
     config: Config,
+    output: Output,
     device_manager: DeviceManager,
     simulator_manager: SimulatorManager,
     project_manager: ProjectManager,
@@ -17,7 +239,7 @@ pub struct KMobileCli {
 }
 
 impl KMobileCli {
-    pub async fn new(config: Config) -> Result<Self> {
+    pub async fn new(config: Config, output: Output) -> Result<Self> {
         let device_manager = DeviceManager::new(&config).await?;
         let simulator_manager = SimulatorManager::new(&config).await?;
         let project_manager = ProjectManager::new(&config).await?;
@@ -25,6 +247,7 @@ impl KMobileCli {
 
         Ok(Self {
             config,
+            output,
             device_manager,
             simulator_manager,
             project_manager,
@@ -35,117 +258,513 @@ impl KMobileCli {
     pub async fn init_project(&self, name: &str, template: Option<&str>) -> Result<()> {
         info!("Initializing project: {}", name);
         self.project_manager.init_project(name, template).await?;
-        println!("✅ Project '{name}' initialized successfully");
-        Ok(())
+        self.output.outcome(&Outcome::new(
+            "init",
+            Some(name),
+            format!("Project '{name}' initialized successfully"),
+        ))
     }
 
     pub async fn handle_device_command(&self, command: DeviceCommands) -> Result<()> {
-        match command {
+        let outcome = match command {
             DeviceCommands::List => {
                 let devices = self.device_manager.list_devices().await?;
-                println!("📱 Connected Devices:");
-                for device in devices {
-                    println!("  {} - {} ({})", device.id, device.name, device.platform);
-                }
+                self.output.heading("📱 Connected Devices:");
+                return self.output.list(&devices);
             }
             DeviceCommands::Connect { id } => {
-                self.device_manager.connect_device(&id).await?;
-                println!("✅ Connected to device: {id}");
+                retry_device(|| self.device_manager.connect_device(&id)).await?;
+                Outcome::new(
+                    "device.connect",
+                    Some(&id),
+                    format!("Connected to device: {id}"),
+                )
             }
             DeviceCommands::Install { id, app } => {
-                self.device_manager.install_app(&id, &app).await?;
-                println!("✅ Installed app on device: {id}");
+                retry_device(|| self.device_manager.install_app(&id, &app)).await?;
+                Outcome::new(
+                    "device.install",
+                    Some(&id),
+                    format!("Installed app on device: {id}"),
+                )
             }
             DeviceCommands::Deploy { id, project } => {
                 self.device_manager
                     .deploy_project(&id, project.as_deref())
                     .await?;
-                println!("✅ Deployed project to device: {id}");
+                Outcome::new(
+                    "device.deploy",
+                    Some(&id),
+                    format!("Deployed project to device: {id}"),
+                )
             }
             DeviceCommands::Test { id, suite } => {
-                self.test_runner
+                let report = self
+                    .test_runner
                     .run_device_tests(&id, suite.as_deref())
                     .await?;
-                println!("✅ Tests completed on device: {id}");
+                return self.finish_test_run(&report);
             }
-        }
-        Ok(())
+        };
+        self.output.outcome(&outcome)
     }
 
     pub async fn handle_simulator_command(&self, command: SimulatorCommands) -> Result<()> {
-        match command {
+        let outcome = match command {
             SimulatorCommands::List => {
                 let simulators = self.simulator_manager.list_simulators().await?;
-                println!("🔧 Available Simulators:");
-                for sim in simulators {
-                    println!("  {} - {} ({})", sim.id, sim.name, sim.platform);
-                }
+                self.output.heading("🔧 Available Simulators:");
+                return self.output.list(&simulators);
             }
-            SimulatorCommands::Start { id } => {
-                self.simulator_manager.start_simulator(&id).await?;
-                println!("✅ Started simulator: {id}");
+            SimulatorCommands::Start {
+                id,
+                wait,
+                timeout,
+                boot,
+            } => {
+                let boot = boot.into();
+                retry_device(|| self.simulator_manager.start_simulator_with(&id, &boot)).await?;
+                if wait {
+                    self.simulator_manager
+                        .wait_until_booted(&id, Duration::from_secs(timeout))
+                        .await?;
+                }
+                Outcome::new(
+                    "simulator.start",
+                    Some(&id),
+                    format!("Started simulator: {id}"),
+                )
             }
             SimulatorCommands::Stop { id } => {
-                self.simulator_manager.stop_simulator(&id).await?;
-                println!("✅ Stopped simulator: {id}");
+                retry_device(|| self.simulator_manager.stop_simulator(&id)).await?;
+                Outcome::new(
+                    "simulator.stop",
+                    Some(&id),
+                    format!("Stopped simulator: {id}"),
+                )
             }
             SimulatorCommands::Reset { id } => {
                 self.simulator_manager.reset_simulator(&id).await?;
-                println!("✅ Reset simulator: {id}");
+                Outcome::new(
+                    "simulator.reset",
+                    Some(&id),
+                    format!("Reset simulator: {id}"),
+                )
+            }
+            SimulatorCommands::Snapshot { command } => {
+                return self.handle_snapshot_command(command).await;
             }
             SimulatorCommands::Install { id, app } => {
-                self.simulator_manager.install_app(&id, &app).await?;
-                println!("✅ Installed app on simulator: {id}");
+                retry_device(|| self.simulator_manager.install_app(&id, &app)).await?;
+                Outcome::new(
+                    "simulator.install",
+                    Some(&id),
+                    format!("Installed app on simulator: {id}"),
+                )
             }
-        }
-        Ok(())
+            SimulatorCommands::Create {
+                name,
+                api,
+                abi,
+                tag,
+                device,
+                force,
+                hardware,
+            } => {
+                let options = CreateAvdOptions {
+                    name,
+                    api_level: api,
+                    abi,
+                    tag,
+                    device,
+                    force,
+                    hardware: hardware.into(),
+                };
+                let avd = self
+                    .simulator_manager
+                    .create_android_emulator(&options)
+                    .await?;
+                Outcome::new(
+                    "simulator.create",
+                    Some(&avd.name),
+                    format!("Created emulator: {}", avd.name),
+                )
+                .with_details(vec![
+                    format!("Image: {}", options.system_image()),
+                    format!("Path: {}", avd.path.display()),
+                ])
+            }
+            SimulatorCommands::Delete { id } => {
+                self.simulator_manager.delete_simulator(&id).await?;
+                Outcome::new(
+                    "simulator.delete",
+                    Some(&id),
+                    format!("Deleted simulator: {id}"),
+                )
+            }
+            SimulatorCommands::Configure { id, hardware } => {
+                let avd = self
+                    .simulator_manager
+                    .configure_simulator(&id, &hardware.into())
+                    .await?;
+                Outcome::new(
+                    "simulator.configure",
+                    Some(&id),
+                    format!("Updated emulator settings: {id}"),
+                )
+                .with_details(vec![format!(
+                    "Config: {}",
+                    avd.path.join("config.ini").display()
+                )])
+            }
+            SimulatorCommands::Route { id, file, playback } => {
+                let device_id = self.simulator_manager.location_target(&id).await?;
+                let route = Route::load(&file)?;
+                let options = playback.into();
+                let hardware = HardwareEmulator::new().await?;
+                tokio::select! {
+                    result = hardware.play_route(&device_id, &route, &options) => result?,
+                    result = tokio::signal::ctrl_c() => result?,
+                }
+                Outcome::new(
+                    "simulator.route",
+                    Some(&id),
+                    format!("Played route on simulator: {id}"),
+                )
+                .with_details(vec![
+                    format!("Points: {}", route.points.len()),
+                    format!("Length: {:.0} m", route.length()),
+                ])
+            }
+            SimulatorCommands::Farm { avd, farm } => {
+                return self.run_farm(farm.options(&avd)).await;
+            }
+        };
+        self.output.outcome(&outcome)
+    }
+
+    /// Keeps the farm up, restarting crashed instances, until Ctrl-C
+    async fn run_farm(&self, options: FarmOptions) -> Result<()> {
+        let avd = options.avd.clone();
+        let mut farm = EmulatorFarm::start(&self.config, options).await?;
+        self.output
+            .heading("🚜 Emulator farm is up; press Ctrl-C to shut it down");
+        self.output.list(&farm.instances().await)?;
+
+        let result = tokio::signal::ctrl_c().await;
+        info!("Shutting down emulator farm");
+        farm.shutdown().await;
+        result?;
+
+        self.output.outcome(&Outcome::new(
+            "simulator.farm",
+            Some(&avd),
+            "Emulator farm shut down",
+        ))
+    }
+
+    async fn handle_snapshot_command(&self, command: SnapshotCommands) -> Result<()> {
+        let manager = &self.simulator_manager;
+        let outcome = match command {
+            SnapshotCommands::Save { id, name } => {
+                manager.save_snapshot(&id, &name).await?;
+                Outcome::new(
+                    "simulator.snapshot.save",
+                    Some(&id),
+                    format!("Saved snapshot {name} of {id}"),
+                )
+            }
+            SnapshotCommands::Load { id, name } => {
+                manager.load_snapshot(&id, &name).await?;
+                Outcome::new(
+                    "simulator.snapshot.load",
+                    Some(&id),
+                    format!("Loaded snapshot {name} on {id}"),
+                )
+            }
+            SnapshotCommands::List { id } => {
+                let snapshots = manager.list_snapshots(&id).await?;
+                self.output.heading(&format!("📸 Snapshots of {id}:"));
+                return self.output.list(&snapshots);
+            }
+            SnapshotCommands::Delete { id, name } => {
+                manager.delete_snapshot(&id, &name).await?;
+                Outcome::new(
+                    "simulator.snapshot.delete",
+                    Some(&id),
+                    format!("Deleted snapshot {name} of {id}"),
+                )
+            }
+        };
+        self.output.outcome(&outcome)
     }
 
     pub async fn handle_project_command(&self, command: ProjectCommands) -> Result<()> {
-        match command {
+        let outcome = match command {
             ProjectCommands::Build { target } => {
                 self.project_manager
                     .build_project(target.as_deref())
                     .await?;
-                println!("✅ Project built successfully");
+                Outcome::new(
+                    "project.build",
+                    target.as_deref(),
+                    "Project built successfully",
+                )
             }
             ProjectCommands::Clean => {
                 self.project_manager.clean_project().await?;
-                println!("✅ Project cleaned");
+                Outcome::new("project.clean", None, "Project cleaned")
             }
             ProjectCommands::Status => {
                 let status = self.project_manager.get_project_status().await?;
-                println!("📊 Project Status: {status}");
+                self.output.heading("📊 Project Status:");
+                return self.output.item(&status);
             }
+        };
+        self.output.outcome(&outcome)
+    }
+
+    /// Prints the report, then fails with `TestExecutionError` if any case failed so the
+    /// exit code reflects the run
+    fn finish_test_run(&self, report: &TestReport) -> Result<()> {
+        self.output.report(report)?;
+        let failed = report.summary.failed + report.summary.timeout;
+        if failed > 0 {
+            return Err(KMobileError::TestExecutionError(format!(
+                "{failed} of {} test(s) failed in '{}'",
+                report.summary.total, report.suite_name
+            ))
+            .into());
         }
         Ok(())
     }
 
     pub async fn handle_test_command(&self, command: TestCommands) -> Result<()> {
-        match command {
-            TestCommands::Run { suite, device } => {
+        let outcome = match command {
+            TestCommands::Run {
+                suite,
+                device,
+                reporter,
+                grep,
+                tags,
+                exclude_tags,
+                shard,
+                only_failed,
+                farm,
+                farm_args,
+            } => {
+                let options = TestRunOptions {
+                    reporters: reporter,
+                    filter: TestFilter {
+                        grep,
+                        tags,
+                        exclude_tags,
+                        shard,
+                        only_failed,
+                    },
+                    ..Default::default()
+                };
+                let report = match farm {
+                    Some(avd) => {
+                        let mut farm =
+                            EmulatorFarm::start(&self.config, farm_args.options(&avd)).await?;
+                        let report = self
+                            .test_runner
+                            .run_tests_on_pool(suite.as_deref(), &farm.pool().await, &options)
+                            .await;
+                        farm.shutdown().await;
+                        report?
+                    }
+                    None => {
+                        self.test_runner
+                            .run_tests(suite.as_deref(), device.as_deref(), &options)
+                            .await?
+                    }
+                };
+                return self.finish_test_run(&report);
+            }
+            TestCommands::Record {
+                output,
+                device,
+                duration,
+            } => {
                 self.test_runner
-                    .run_tests(suite.as_deref(), device.as_deref())
+                    .record_test(
+                        &output,
+                        device.as_deref(),
+                        duration.map(std::time::Duration::from_secs),
+                    )
                     .await?;
-                println!("✅ Tests completed");
+                Outcome::new(
+                    "test.record",
+                    device.as_deref(),
+                    format!("Test recorded to: {output}"),
+                )
+                .with_details(vec![output])
             }
-            TestCommands::Record { output } => {
-                self.test_runner.record_test(&output).await?;
-                println!("✅ Test recorded to: {output}");
+            TestCommands::Replay { file, device, step } => {
+                self.test_runner
+                    .replay_test(&file, device.as_deref(), step)
+                    .await?;
+                Outcome::new(
+                    "test.replay",
+                    device.as_deref(),
+                    format!("Test replayed from: {file}"),
+                )
             }
-            TestCommands::Replay { file } => {
-                self.test_runner.replay_test(&file).await?;
-                println!("✅ Test replayed from: {file}");
+            TestCommands::Maestro {
+                flows,
+                device,
+                skip_unsupported,
+                reporter,
+            } => {
+                let options = TestRunOptions {
+                    reporters: reporter,
+                    ..Default::default()
+                };
+                let report = self
+                    .test_runner
+                    .run_maestro_flows(&flows, device.as_deref(), skip_unsupported, &options)
+                    .await?;
+                return self.finish_test_run(&report);
             }
-        }
-        Ok(())
+            TestCommands::ImportMaestro { flow, output } => {
+                let imported = maestro::import_flow(&flow)?;
+                std::fs::write(&output, serde_json::to_string_pretty(&imported.test_case)?)?;
+                Outcome::new(
+                    "test.import_maestro",
+                    None,
+                    format!(
+                        "Imported {} steps into: {}",
+                        imported.test_case.steps.len(),
+                        output.display()
+                    ),
+                )
+                .with_details(vec![output.display().to_string()])
+                .with_warnings(
+                    imported
+                        .unsupported
+                        .iter()
+                        .map(|command| format!("Skipped {command}"))
+                        .collect(),
+                )
+            }
+            TestCommands::Native {
+                device,
+                connected,
+                reporter,
+            } => {
+                let project = self.project_manager.current_project().ok_or_else(|| {
+                    KMobileError::ProjectNotFound(
+                        "No project found in current directory".to_string(),
+                    )
+                })?;
+                let options = NativeTestOptions {
+                    device_id: device,
+                    connected,
+                    run: TestRunOptions {
+                        reporters: reporter,
+                        ..Default::default()
+                    },
+                };
+                let report = self.test_runner.run_native_tests(project, &options).await?;
+                return self.finish_test_run(&report);
+            }
+            TestCommands::Stats {
+                suite,
+                window,
+                flaky,
+            } => {
+                let stats = self
+                    .test_runner
+                    .test_stats(suite.as_deref(), window, flaky)?;
+                if stats.is_empty() && self.output.format() == OutputFormat::Table {
+                    println!("No test history recorded yet");
+                    return Ok(());
+                }
+                self.output
+                    .heading(&format!("📈 Test stats over the last {window} runs:"));
+                return self.output.list(&stats);
+            }
+            TestCommands::SaveAppData {
+                package,
+                output,
+                device,
+            } => {
+                self.test_runner
+                    .save_app_data(&package, &output, device.as_deref())
+                    .await?;
+                Outcome::new(
+                    "test.save_app_data",
+                    Some(&package),
+                    format!("Saved app data for {package} to: {output}"),
+                )
+                .with_details(vec![output])
+            }
+            TestCommands::Approve {
+                suite,
+                case,
+                profile,
+            } => {
+                #[cfg(feature = "visual")]
+                {
+                    let approved = self.test_runner.approve_baselines(
+                        suite.as_deref(),
+                        case.as_deref(),
+                        profile.as_deref(),
+                    )?;
+                    Outcome::new(
+                        "test.approve",
+                        suite.as_deref(),
+                        format!("Approved {} baseline(s)", approved.len()),
+                    )
+                    .with_details(
+                        approved
+                            .iter()
+                            .map(|path| path.display().to_string())
+                            .collect(),
+                    )
+                }
+                #[cfg(not(feature = "visual"))]
+                {
+                    let _ = (suite, case, profile);
+                    return Err(crate::error::KMobileError::ConfigError(
+                        "kmobile was built without the `visual` feature".to_string(),
+                    )
+                    .into());
+                }
+            }
+        };
+        self.output.outcome(&outcome)
     }
 
+    #[cfg(feature = "api")]
     pub async fn start_api_server(&self, host: &str, port: u16) -> Result<()> {
         info!("Starting API server on {}:{}", host, port);
-        // TODO: Implement API server
-        warn!("API server not yet implemented");
-        Ok(())
+        crate::api::serve(self.config.clone(), host, port).await
+    }
+
+    #[cfg(not(feature = "api"))]
+    pub async fn start_api_server(&self, _host: &str, _port: u16) -> Result<()> {
+        Err(
+            KMobileError::InvalidInput("kmobile was built without the `api` feature".to_string())
+                .into(),
+        )
+    }
+
+    #[cfg(feature = "webdriver")]
+    pub async fn start_webdriver_server(
+        &self,
+        host: &str,
+        port: u16,
+        device: Option<String>,
+    ) -> Result<()> {
+        let adb_path = self.config.android.adb_path.clone().ok_or_else(|| {
+            KMobileError::ConfigError("android.adb_path is required for WebDriver".to_string())
+        })?;
+        let runner = TestRunner::new(&self.config).await?;
+        let backend = crate::webdriver::AdbBackend::new(runner, adb_path, device);
+        crate::webdriver::serve(backend, host, port).await
     }
 
     pub async fn start_mcp_server(&self, config_path: Option<&str>) -> Result<()> {
@@ -155,10 +774,25 @@ impl KMobileCli {
         Ok(())
     }
 
+    #[cfg(feature = "tui")]
     pub async fn start_tui(&self) -> Result<()> {
         info!("Starting TUI interface");
-        // TODO: Implement TUI
-        warn!("TUI not yet implemented");
-        Ok(())
+        crate::tui::run(self.config.clone()).await
     }
+
+    #[cfg(not(feature = "tui"))]
+    pub async fn start_tui(&self) -> Result<()> {
+        Err(KMobileError::InvalidInput(
+            "kmobile was built without the `tui` feature; rebuild with --features tui".to_string(),
+        )
+        .into())
+    }
+}
+
+async fn retry_device<F, Fut>(operation: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    retry_with_backoff(operation, DEVICE_RETRIES, DEVICE_RETRY_DELAY).await
 }
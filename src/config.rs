@@ -1,9 +1,63 @@
 use anyhow::Result;
+use clap::Subcommand;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::path::PathBuf;
+use tracing::warn;
+
+use crate::error::KMobileError;
+
+pub mod edit;
+pub mod schema;
+pub mod validate;
+
+pub use validate::{Severity, ValidationIssue};
+
+/// Project configuration file looked up in the current directory
+pub const PROJECT_CONFIG_FILE: &str = "kmobile.toml";
+/// Prefix of environment variables that override settings, e.g. `KMOBILE_MCP__PORT`
+const ENV_PREFIX: &str = "KMOBILE_";
+/// Selects a profile when `--profile` isn't given
+const PROFILE_ENV: &str = "KMOBILE_PROFILE";
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Show the effective configuration
+    Show {
+        #[arg(long, help = "Show which layer set each value")]
+        origin: bool,
+    },
+    /// Print the effective value of a key, e.g. `mcp.port`
+    Get { key: String },
+    /// Set a key in the project config file
+    Set {
+        key: String,
+        value: String,
+        #[arg(long, help = "Edit the user config instead")]
+        user: bool,
+    },
+    /// Remove a key from the project config file
+    Unset {
+        key: String,
+        #[arg(long, help = "Edit the user config instead")]
+        user: bool,
+    },
+    /// Check that paths exist, ports don't clash and values are known
+    Validate,
+    /// Write a config file with the defaults
+    Init {
+        #[arg(long, help = "Write the user config instead")]
+        user: bool,
+        #[arg(long, help = "Overwrite an existing file")]
+        force: bool,
+    },
+    /// Print the JSON Schema of the config file for editor completion
+    Schema,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub name: String,
     pub version: String,
@@ -24,6 +78,21 @@ pub struct AndroidConfig {
     pub build_tools_version: Option<String>,
 }
 
+impl AndroidConfig {
+    /// Fills unset `adb` and `emulator` paths from `search_path`, e.g. for
+    /// platform-tools installed by a package manager without the rest of the SDK
+    fn detect_tools_on_path(&mut self, search_path: Option<std::ffi::OsString>) {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let find = |tool: &str| which::which_in(tool, search_path.as_ref(), &cwd).ok();
+        if self.adb_path.is_none() {
+            self.adb_path = find("adb");
+        }
+        if self.emulator_path.is_none() {
+            self.emulator_path = find("emulator");
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct IosConfig {
     pub xcode_path: Option<PathBuf>,
@@ -40,7 +109,44 @@ pub struct TestingConfig {
     pub parallel: bool,
     pub screenshot_on_failure: bool,
     pub video_recording: bool,
+    /// Only keep recorded videos for test cases that did not pass
+    #[serde(default)]
+    pub video_on_failure_only: bool,
     pub output_dir: PathBuf,
+    #[serde(default)]
+    pub visual: VisualConfig,
+}
+
+/// Screenshot comparison against approved baselines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualConfig {
+    pub enabled: bool,
+    pub baseline_dir: PathBuf,
+    pub mode: DiffMode,
+    /// Largest per-channel difference (0-255) still counted as the same pixel
+    pub pixel_threshold: u8,
+    /// Fraction of differing pixels tolerated in `pixel` mode
+    pub max_diff_ratio: f64,
+    /// Lowest mean SSIM accepted in `perceptual` mode
+    pub min_similarity: f64,
+    /// Areas such as clocks or carets that are never compared
+    pub ignore_regions: Vec<IgnoreRegion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    Pixel,
+    Perceptual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgnoreRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +178,7 @@ pub struct ProjectConfig {
     pub platform: String,
     pub build_command: Option<String>,
     pub test_command: Option<String>,
+    #[serde(default)]
     pub metadata: HashMap<String, String>,
 }
 
@@ -98,7 +205,23 @@ impl Default for TestingConfig {
             parallel: true,
             screenshot_on_failure: true,
             video_recording: false,
+            video_on_failure_only: false,
             output_dir: PathBuf::from("./test-results"),
+            visual: VisualConfig::default(),
+        }
+    }
+}
+
+impl Default for VisualConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            baseline_dir: PathBuf::from("./test-baselines"),
+            mode: DiffMode::Pixel,
+            pixel_threshold: 16,
+            max_diff_ratio: 0.001,
+            min_similarity: 0.98,
+            ignore_regions: Vec::new(),
         }
     }
 }
@@ -131,18 +254,13 @@ impl Default for ApiConfig {
 }
 
 impl Config {
+    /// Loads the layered configuration; `path` replaces the project `kmobile.toml`
     pub fn load(path: Option<&str>) -> Result<Self> {
-        let config_path = path.unwrap_or("kmobile.toml");
-
-        if std::path::Path::new(config_path).exists() {
-            let content = std::fs::read_to_string(config_path)?;
-            let config: Config = toml::from_str(&content)?;
-            Ok(config)
-        } else {
-            let default_config = Config::default();
-            default_config.save(config_path)?;
-            Ok(default_config)
+        let mut loader = ConfigLoader::new();
+        if let Some(path) = path {
+            loader = loader.with_project_file(path);
         }
+        Ok(loader.load()?.config)
     }
 
     pub fn save(&self, path: &str) -> Result<()> {
@@ -155,49 +273,627 @@ impl Config {
         &self.name
     }
 
-    #[allow(dead_code)]
+    /// Fills unset Android paths from `ANDROID_SDK_ROOT`/`ANDROID_HOME` or the SDK's
+    /// default install location, and `adb` and `emulator` from `PATH` if the SDK
+    /// doesn't provide them
     pub fn detect_android_sdk(&mut self) -> Result<()> {
-        // Try to detect Android SDK path
-        if let Ok(sdk_path) = std::env::var("ANDROID_SDK_ROOT") {
-            self.android.sdk_path = Some(PathBuf::from(sdk_path));
-        } else if let Ok(sdk_path) = std::env::var("ANDROID_HOME") {
-            self.android.sdk_path = Some(PathBuf::from(sdk_path));
+        if self.android.sdk_path.is_none() {
+            let home = std::env::var_os("HOME").map(PathBuf::from);
+            self.android.sdk_path = ["ANDROID_SDK_ROOT", "ANDROID_HOME"]
+                .iter()
+                .filter_map(|var| std::env::var_os(var).map(PathBuf::from))
+                .chain(home.iter().flat_map(|home| {
+                    [
+                        home.join("Android").join("Sdk"),
+                        home.join("Library").join("Android").join("sdk"),
+                    ]
+                }))
+                .find(|path| path.is_dir());
         }
 
-        // Try to detect ADB path
         if let Some(sdk_path) = &self.android.sdk_path {
             let adb_path = sdk_path.join("platform-tools").join("adb");
-            if adb_path.exists() {
+            if self.android.adb_path.is_none() && adb_path.exists() {
                 self.android.adb_path = Some(adb_path);
             }
+            let emulator_path = sdk_path.join("emulator").join("emulator");
+            if self.android.emulator_path.is_none() && emulator_path.exists() {
+                self.android.emulator_path = Some(emulator_path);
+            }
         }
+        self.android.detect_tools_on_path(std::env::var_os("PATH"));
 
         Ok(())
     }
 
-    #[allow(dead_code)]
+    /// Fills unset Xcode paths from `xcode-select` and `xcrun`
     pub fn detect_ios_tools(&mut self) -> Result<()> {
-        // Try to detect Xcode path
-        if let Ok(output) = std::process::Command::new("xcode-select")
-            .arg("-p")
-            .output()
-        {
-            if output.status.success() {
-                let path_str = String::from_utf8_lossy(&output.stdout);
-                let path = path_str.trim();
-                self.ios.xcode_path = Some(PathBuf::from(path));
+        if self.ios.xcode_path.is_none() {
+            self.ios.xcode_path = command_path("xcode-select", &["-p"]);
+        }
+        if self.ios.simctl_path.is_none() {
+            self.ios.simctl_path = command_path("xcrun", &["--find", "simctl"]);
+        }
+        Ok(())
+    }
+}
+
+/// First line of a successful command's output, as a path
+fn command_path(program: &str, args: &[&str]) -> Option<PathBuf> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// The layer a setting came from, in increasing precedence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    Default,
+    UserFile(PathBuf),
+    ProjectFile(PathBuf),
+    /// A `[profiles.<name>]` table, applied right after the file that defines it
+    Profile {
+        name: String,
+        file: PathBuf,
+    },
+    Env(String),
+    Cli,
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::Default => write!(f, "default"),
+            ConfigOrigin::UserFile(path) => write!(f, "user config {}", path.display()),
+            ConfigOrigin::ProjectFile(path) => write!(f, "project config {}", path.display()),
+            ConfigOrigin::Profile { name, file } => {
+                write!(f, "profile {name} in {}", file.display())
             }
+            ConfigOrigin::Env(var) => write!(f, "env {var}"),
+            ConfigOrigin::Cli => write!(f, "--set"),
         }
+    }
+}
+
+/// One effective setting and the layer it came from, for `config show --origin`
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub origin: String,
+}
+
+/// The effective configuration together with where each value came from
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: Config,
+    pub profile: Option<String>,
+    /// `kmobile.toml` or the `--config` file, whether or not it exists
+    pub project_file: PathBuf,
+    pub user_file: Option<PathBuf>,
+    origins: BTreeMap<String, ConfigOrigin>,
+}
+
+impl LoadedConfig {
+    pub fn origin(&self, key: &str) -> Option<&ConfigOrigin> {
+        self.origins.get(key)
+    }
+
+    /// Effective value of a dotted key; a table for keys such as `testing.visual`.
+    /// Known but unset optional keys are `null`.
+    pub fn get(&self, key: &str) -> Result<serde_json::Value> {
+        let mut value = serde_json::to_value(&self.config)?;
+        for part in key.split('.') {
+            value = value
+                .get_mut(part)
+                .map(serde_json::Value::take)
+                .ok_or_else(|| KMobileError::InvalidInput(format!("Unknown config key: {key}")))?;
+        }
+        Ok(value)
+    }
+
+    /// Every set value as a dotted key. Unset optional values are left out.
+    pub fn entries(&self) -> Result<Vec<ConfigEntry>> {
+        let mut values = BTreeMap::new();
+        flatten(&serde_json::to_value(&self.config)?, "", &mut values);
+        Ok(values
+            .into_iter()
+            .map(|(key, value)| {
+                let origin = self
+                    .origins
+                    .get(&key)
+                    .unwrap_or(&ConfigOrigin::Default)
+                    .to_string();
+                ConfigEntry { key, value, origin }
+            })
+            .collect())
+    }
+}
+
+/// Builds the configuration from, in increasing precedence: built-in defaults, the
+/// user config, the project `kmobile.toml`, `KMOBILE_*` environment variables and
+/// `--set` overrides. Loading never writes files.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    user_file: Option<PathBuf>,
+    project_file: Option<PathBuf>,
+    profile: Option<String>,
+    overrides: Vec<(String, String)>,
+    env: Option<HashMap<String, String>>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self {
+            user_file: user_config_path(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_user_file(mut self, path: Option<PathBuf>) -> Self {
+        self.user_file = path;
+        self
+    }
+
+    /// Uses `path` instead of `./kmobile.toml`. Unlike the default, it must exist.
+    pub fn with_project_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.project_file = Some(path.into());
+        self
+    }
+
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_override(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    /// Reads `KMOBILE_*` variables from `vars` instead of the process environment
+    pub fn with_env(mut self, vars: HashMap<String, String>) -> Self {
+        self.env = Some(vars);
+        self
+    }
+
+    pub fn load(&self) -> Result<LoadedConfig> {
+        let mut origins = BTreeMap::new();
+        let mut merged = defaults_table()?;
+        record(&merged, &ConfigOrigin::Default, &mut origins);
+
+        let env: BTreeMap<String, String> = self
+            .env
+            .clone()
+            .unwrap_or_else(|| std::env::vars().collect())
+            .into_iter()
+            .filter(|(var, _)| var.starts_with(ENV_PREFIX))
+            .collect();
+        let profile = self
+            .profile
+            .clone()
+            .or_else(|| env.get(PROFILE_ENV).cloned());
+
+        let mut files = Vec::new();
+        if let Some(path) = &self.user_file {
+            files.push((path.clone(), false, ConfigOrigin::UserFile(path.clone())));
+        }
+        let (project_file, required) = match &self.project_file {
+            Some(path) => (path.clone(), true),
+            None => (PathBuf::from(PROJECT_CONFIG_FILE), false),
+        };
+        files.push((
+            project_file.clone(),
+            required,
+            ConfigOrigin::ProjectFile(project_file.clone()),
+        ));
+
+        let mut profile_found = false;
+        for (path, required, origin) in files {
+            if !path.exists() {
+                if required {
+                    return Err(KMobileError::ConfigError(format!(
+                        "Config file not found: {}",
+                        path.display()
+                    ))
+                    .into());
+                }
+                continue;
+            }
+            let content = std::fs::read_to_string(&path)?;
+            let mut table: toml::Table = toml::from_str(&content)
+                .map_err(|e| KMobileError::ConfigError(format!("{}: {e}", path.display())))?;
+            let profiles = table.remove("profiles");
+            record(&table, &origin, &mut origins);
+            merge(&mut merged, table);
+            check_layer(&merged, &origin)?;
 
-        // Try to detect simctl path
-        if let Ok(output) = std::process::Command::new("which").arg("simctl").output() {
-            if output.status.success() {
-                let path_str = String::from_utf8_lossy(&output.stdout);
-                let path = path_str.trim();
-                self.ios.simctl_path = Some(PathBuf::from(path));
+            let profile_table = profile.as_ref().and_then(|name| {
+                profiles
+                    .as_ref()
+                    .and_then(|profiles| profiles.get(name))
+                    .and_then(toml::Value::as_table)
+            });
+            if let (Some(name), Some(profile_table)) = (&profile, profile_table) {
+                profile_found = true;
+                let origin = ConfigOrigin::Profile {
+                    name: name.clone(),
+                    file: path.clone(),
+                };
+                record(profile_table, &origin, &mut origins);
+                merge(&mut merged, profile_table.clone());
+                check_layer(&merged, &origin)?;
             }
         }
+        if let Some(name) = profile.as_ref().filter(|_| !profile_found) {
+            return Err(KMobileError::ConfigError(format!(
+                "Profile '{name}' is not defined in any config file"
+            ))
+            .into());
+        }
 
-        Ok(())
+        for (var, value) in env.iter().filter(|(var, _)| *var != PROFILE_ENV) {
+            let key = env_key(var);
+            if !schema::has_key(&key) {
+                warn!("Ignoring {}: {} is not a config key", var, key);
+                continue;
+            }
+            let origin = ConfigOrigin::Env(var.clone());
+            set_path(&mut merged, &key, value).map_err(|e| layer_error(&origin, e))?;
+            check_layer(&merged, &origin)?;
+            origins.insert(key, origin);
+        }
+        for (key, value) in &self.overrides {
+            if !schema::has_key(key) {
+                return Err(KMobileError::ConfigError(format!(
+                    "{}: unknown config key '{key}'",
+                    ConfigOrigin::Cli
+                ))
+                .into());
+            }
+            set_path(&mut merged, key, value).map_err(|e| layer_error(&ConfigOrigin::Cli, e))?;
+            check_layer(&merged, &ConfigOrigin::Cli)?;
+            origins.insert(key.clone(), ConfigOrigin::Cli);
+        }
+
+        Ok(LoadedConfig {
+            config: deserialize(merged)?,
+            profile,
+            project_file,
+            user_file: self.user_file.clone(),
+            origins,
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/kmobile/config.toml`, falling back to `~/.config/kmobile/config.toml`
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("kmobile").join("config.toml"))
+}
+
+/// Parses a `--set key=value` argument
+pub fn parse_override(arg: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{arg}'"))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("missing key in '{arg}'"));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Deep-merges `layer` into `base`. Tables merge key by key; anything else, arrays
+/// included, is replaced.
+pub(crate) fn merge(base: &mut toml::Table, layer: toml::Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Sets a dotted key from an env var or `--set`. The text is read as a TOML value when
+/// the setting already holds a number, bool or array, and kept as a string otherwise,
+/// so `KMOBILE_IOS__DEFAULT_SIMULATOR=1234` stays a name.
+fn set_path(table: &mut toml::Table, key: &str, raw: &str) -> Result<()> {
+    let invalid = || KMobileError::InvalidInput(format!("Invalid config key: {key}"));
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts
+        .pop()
+        .filter(|last| !last.is_empty())
+        .ok_or_else(invalid)?;
+    let mut current = table;
+    for part in parts {
+        let entry = current
+            .entry(part.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        current = entry.as_table_mut().ok_or_else(invalid)?;
+    }
+
+    let value = parse_value(current.get(last), key, raw)?;
+    current.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// Reads `raw` as the same kind of value as `existing`; see `set_path`
+pub(crate) fn parse_value(
+    existing: Option<&toml::Value>,
+    key: &str,
+    raw: &str,
+) -> Result<toml::Value> {
+    match existing {
+        None | Some(toml::Value::String(_)) => Ok(toml::Value::String(raw.to_string())),
+        Some(_) => {
+            let parsed: toml::Table = toml::from_str(&format!("value = {raw}")).map_err(|_| {
+                KMobileError::InvalidInput(format!("Invalid value for {key}: {raw}"))
+            })?;
+            Ok(parsed["value"].clone())
+        }
+    }
+}
+
+/// Built-in defaults as a TOML table, the base every layer is merged onto
+pub(crate) fn defaults_table() -> Result<toml::Table> {
+    Ok(toml::Table::try_from(Config::default())?)
+}
+
+pub(crate) fn deserialize(table: toml::Table) -> Result<Config> {
+    Ok(toml::Value::Table(table)
+        .try_into::<Config>()
+        .map_err(|e| KMobileError::ConfigError(e.to_string()))?)
+}
+
+/// Fails with the layer's name when it leaves the config unreadable, such as a port
+/// set to a string
+fn check_layer(merged: &toml::Table, origin: &ConfigOrigin) -> Result<()> {
+    deserialize(merged.clone())
+        .map(|_| ())
+        .map_err(|e| layer_error(origin, e))
+}
+
+fn layer_error(origin: &ConfigOrigin, error: anyhow::Error) -> anyhow::Error {
+    let message = KMobileError::find(&error)
+        .map(|kind| match kind {
+            KMobileError::ConfigError(message) | KMobileError::InvalidInput(message) => {
+                message.clone()
+            }
+            other => other.to_string(),
+        })
+        .unwrap_or_else(|| error.to_string());
+    let message = message.split_whitespace().collect::<Vec<_>>().join(" ");
+    KMobileError::ConfigError(format!("{origin}: {message}")).into()
+}
+
+fn record(
+    table: &toml::Table,
+    origin: &ConfigOrigin,
+    origins: &mut BTreeMap<String, ConfigOrigin>,
+) {
+    let mut values = BTreeMap::new();
+    if let Ok(value) = serde_json::to_value(table) {
+        flatten(&value, "", &mut values);
+    }
+    for key in values.into_keys() {
+        origins.insert(key, origin.clone());
+    }
+}
+
+/// Flattens nested tables into dotted keys. Arrays are single values, like in TOML.
+fn flatten(value: &serde_json::Value, prefix: &str, out: &mut BTreeMap<String, serde_json::Value>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(value, &key, out);
+            }
+        }
+        serde_json::Value::Null => {}
+        value => {
+            out.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+/// `KMOBILE_ANDROID__ADB_PATH` -> `android.adb_path`
+fn env_key(var: &str) -> String {
+    var[ENV_PREFIX.len()..].to_lowercase().replace("__", ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &std::path::Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_layers_apply_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write(
+            dir.path(),
+            "user.toml",
+            "[mcp]\nport = 4000\nhost = \"0.0.0.0\"\n[testing]\ntimeout = 60\n",
+        );
+        let project = write(
+            dir.path(),
+            "kmobile.toml",
+            "[mcp]\nport = 5000\n\n[[projects]]\nname = \"App\"\npath = \".\"\nplatform = \"android\"\nmetadata = { BundleId = \"com.example\" }\n\n[profiles.ci.testing]\ntimeout = 300\nparallel = false\n",
+        );
+        let env = HashMap::from([
+            ("KMOBILE_API__PORT".to_string(), "8080".to_string()),
+            (
+                "KMOBILE_IOS__DEFAULT_SIMULATOR".to_string(),
+                "1234".to_string(),
+            ),
+            ("KMOBILE_PROFILE".to_string(), "ci".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ]);
+
+        let loaded = ConfigLoader::default()
+            .with_user_file(Some(user.clone()))
+            .with_project_file(&project)
+            .with_env(env)
+            .with_override("testing.timeout", "900")
+            .load()
+            .unwrap();
+
+        let config = &loaded.config;
+        assert_eq!(config.mcp.port, 5000);
+        assert_eq!(config.mcp.host, "0.0.0.0");
+        assert_eq!(config.api.port, 8080);
+        assert_eq!(config.testing.timeout, 900);
+        assert!(!config.testing.parallel);
+        assert_eq!(config.testing.framework, "kmobile");
+        assert_eq!(config.ios.default_simulator.as_deref(), Some("1234"));
+        assert_eq!(config.projects[0].metadata["BundleId"], "com.example");
+        assert_eq!(loaded.profile.as_deref(), Some("ci"));
+
+        assert_eq!(
+            loaded.origin("mcp.host"),
+            Some(&ConfigOrigin::UserFile(user))
+        );
+        assert_eq!(
+            loaded.origin("mcp.port"),
+            Some(&ConfigOrigin::ProjectFile(project.clone()))
+        );
+        assert_eq!(
+            loaded.origin("testing.parallel"),
+            Some(&ConfigOrigin::Profile {
+                name: "ci".to_string(),
+                file: project
+            })
+        );
+        assert_eq!(
+            loaded.origin("api.port"),
+            Some(&ConfigOrigin::Env("KMOBILE_API__PORT".to_string()))
+        );
+        assert_eq!(loaded.origin("testing.timeout"), Some(&ConfigOrigin::Cli));
+
+        let entries = loaded.entries().unwrap();
+        let framework = entries
+            .iter()
+            .find(|entry| entry.key == "testing.framework")
+            .unwrap();
+        assert_eq!(framework.origin, "default");
+        assert!(!entries
+            .iter()
+            .any(|entry| entry.key.starts_with("profiles")));
+    }
+
+    #[test]
+    fn test_unknown_env_vars_are_ignored() {
+        let env = HashMap::from([
+            ("KMOBILE_LOG_LEVEL".to_string(), "debug".to_string()),
+            ("KMOBILE_HEADLESS".to_string(), "true".to_string()),
+            ("KMOBILE_API".to_string(), "1".to_string()),
+            ("KMOBILE_MCP__PORT".to_string(), "4000".to_string()),
+        ]);
+        let loaded = ConfigLoader::default()
+            .with_user_file(None)
+            .with_env(env)
+            .load()
+            .unwrap();
+        assert_eq!(loaded.config.mcp.port, 4000);
+        assert_eq!(loaded.config.api.port, Config::default().api.port);
+        assert_eq!(loaded.origin("api"), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_adb_on_path_is_detected() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let adb = write(dir.path(), "adb", "#!/bin/sh\n");
+        std::fs::set_permissions(&adb, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut android = AndroidConfig::default();
+        android.detect_tools_on_path(Some(dir.path().as_os_str().to_owned()));
+        assert_eq!(android.adb_path, Some(adb));
+        assert_eq!(android.emulator_path, None);
+
+        let configured = PathBuf::from("/opt/sdk/platform-tools/adb");
+        let mut android = AndroidConfig {
+            adb_path: Some(configured.clone()),
+            ..Default::default()
+        };
+        android.detect_tools_on_path(Some(dir.path().as_os_str().to_owned()));
+        assert_eq!(android.adb_path, Some(configured));
+    }
+
+    #[test]
+    fn test_missing_files_are_not_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("missing.toml");
+        let loaded = ConfigLoader::default()
+            .with_user_file(Some(user.clone()))
+            .with_env(HashMap::new())
+            .load()
+            .unwrap();
+        assert_eq!(loaded.config.mcp.port, 3001);
+        assert!(!user.exists());
+
+        let error = ConfigLoader::default()
+            .with_project_file(dir.path().join("kmobile.toml"))
+            .with_env(HashMap::new())
+            .load()
+            .unwrap_err();
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_profile_is_an_error() {
+        let error = ConfigLoader::default()
+            .with_env(HashMap::new())
+            .with_profile(Some("nightly".to_string()))
+            .load()
+            .unwrap_err();
+        assert!(error.to_string().contains("nightly"));
+    }
+
+    #[test]
+    fn test_unknown_override_is_an_error() {
+        let error = ConfigLoader::default()
+            .with_user_file(None)
+            .with_env(HashMap::new())
+            .with_override("testing.timeot", "900")
+            .load()
+            .unwrap_err();
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::ConfigError(message)) if message.contains("testing.timeot")
+        ));
+    }
+
+    #[test]
+    fn test_parse_override() {
+        assert_eq!(
+            parse_override("mcp.port=4000").unwrap(),
+            ("mcp.port".to_string(), "4000".to_string())
+        );
+        assert!(parse_override("mcp.port").is_err());
+        assert!(parse_override("=1").is_err());
     }
 }
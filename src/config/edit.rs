@@ -0,0 +1,163 @@
+//! In-place edits of a config file for `kmobile config set/unset`. Comments and
+//! layout are kept, and a change is only written if the file still loads.
+
+use anyhow::Result;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use super::{defaults_table, deserialize, merge, parse_value, schema};
+use crate::error::KMobileError;
+
+/// Sets `key` in the file at `path`, creating the file if needed. `raw` is read like
+/// an env override: as TOML for numbers, bools and arrays, as a string otherwise.
+pub fn set_value(path: &Path, key: &str, raw: &str) -> Result<()> {
+    let (parents, last) = split_key(key)?;
+    if !schema::has_key(key) {
+        return Err(KMobileError::InvalidInput(format!("Unknown config key: {key}")).into());
+    }
+    let mut document = read_document(path)?;
+
+    let defaults = defaults_table()?;
+    let existing = lookup(&defaults, schema::profile_key(key).unwrap_or(key));
+    let value = parse_value(existing, key, raw)?;
+    let value: toml_edit::Value = value
+        .to_string()
+        .parse()
+        .map_err(|_| KMobileError::InvalidInput(format!("Invalid value for {key}: {raw}")))?;
+
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for part in parents {
+        table = table
+            .entry(part)
+            .or_insert_with(implicit_table)
+            .as_table_like_mut()
+            .ok_or_else(|| KMobileError::InvalidInput(format!("{part} in {key} is not a table")))?;
+    }
+    table.insert(last, Item::Value(value));
+
+    write_checked(path, &document)
+}
+
+/// Removes `key` from the file at `path`. Returns whether it was set there.
+pub fn unset_value(path: &Path, key: &str) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let mut document = read_document(path)?;
+    let (parents, last) = split_key(key)?;
+
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for part in parents {
+        match table.get_mut(part).and_then(Item::as_table_like_mut) {
+            Some(child) => table = child,
+            None => return Ok(false),
+        }
+    }
+    if table.remove(last).is_none() {
+        return Ok(false);
+    }
+
+    write_checked(path, &document)?;
+    Ok(true)
+}
+
+fn read_document(path: &Path) -> Result<DocumentMut> {
+    if !path.exists() {
+        return Ok(DocumentMut::new());
+    }
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .parse()
+        .map_err(|e| KMobileError::ConfigError(format!("{}: {e}", path.display())))?)
+}
+
+fn write_checked(path: &Path, document: &DocumentMut) -> Result<()> {
+    let content = document.to_string();
+    let mut table: toml::Table = toml::from_str(&content)?;
+    table.remove("profiles");
+    let mut merged = defaults_table()?;
+    merge(&mut merged, table);
+    deserialize(merged)
+        .map_err(|e| KMobileError::ConfigError(format!("{}: {e}", path.display())))?;
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// A table that only gets a `[header]` once it holds values of its own
+fn implicit_table() -> Item {
+    let mut table = toml_edit::Table::new();
+    table.set_implicit(true);
+    Item::Table(table)
+}
+
+fn split_key(key: &str) -> Result<(Vec<&str>, &str)> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap_or_default();
+    if last.is_empty() || parts.iter().any(|part| part.is_empty()) {
+        return Err(KMobileError::InvalidInput(format!("Invalid config key: {key}")).into());
+    }
+    Ok((parts, last))
+}
+
+fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_unset_keep_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kmobile.toml");
+        std::fs::write(&path, "# team settings\n[mcp]\nport = 4000 # shared box\n").unwrap();
+
+        set_value(&path, "mcp.port", "4100").unwrap();
+        set_value(&path, "android.adb_path", "/opt/sdk/platform-tools/adb").unwrap();
+        set_value(&path, "ios.default_simulator", "1234").unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# team settings\n"));
+        assert!(content.contains("port = 4100"));
+        assert!(content.contains("adb_path = \"/opt/sdk/platform-tools/adb\""));
+        assert!(content.contains("default_simulator = \"1234\""));
+
+        set_value(&path, "profiles.ci.testing.timeout", "300").unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("timeout = 300"));
+
+        let error = set_value(&path, "mcp.prot", "4100").unwrap_err();
+        assert!(error.to_string().contains("Unknown config key"));
+        let error = set_value(&path, "mcp.port", "many").unwrap_err();
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::InvalidInput(_))
+        ));
+        let error = set_value(&path, "mcp.port", "70000").unwrap_err();
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::ConfigError(_))
+        ));
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("port = 4100"));
+
+        assert!(unset_value(&path, "mcp.port").unwrap());
+        assert!(!unset_value(&path, "mcp.port").unwrap());
+        assert!(!unset_value(&path, "nothing.here").unwrap());
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("port"));
+    }
+}
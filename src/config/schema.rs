@@ -0,0 +1,325 @@
+//! JSON Schema for `kmobile.toml`, printed by `kmobile config schema` for editor
+//! completion. Defaults are filled in from `Config::default()`.
+
+use serde_json::{json, Map, Value};
+
+use super::validate::{KNOWN_PLATFORMS, KNOWN_TEST_FRAMEWORKS};
+use super::Config;
+
+pub fn config_schema() -> Value {
+    let mut schema = object(
+        "KMobile configuration",
+        [
+            ("name", string("Display name")),
+            ("version", string("Configuration version")),
+            ("android", android()),
+            ("ios", ios()),
+            ("testing", testing()),
+            ("mcp", mcp()),
+            ("api", api()),
+            (
+                "projects",
+                json!({
+                    "type": "array",
+                    "description": "Projects KMobile builds and tests",
+                    "items": project(),
+                }),
+            ),
+        ],
+    );
+
+    let defaults = serde_json::to_value(Config::default()).unwrap_or_default();
+    apply_defaults(&mut schema, &defaults);
+
+    let root = schema.as_object_mut().expect("schema root is an object");
+    root.insert(
+        "$schema".to_string(),
+        json!("http://json-schema.org/draft-07/schema#"),
+    );
+    root.insert("title".to_string(), json!("kmobile.toml"));
+    root["properties"]["profiles"] = json!({
+        "type": "object",
+        "description": "Named overrides applied with --profile <name>",
+        "additionalProperties": { "$ref": "#" },
+    });
+    schema
+}
+
+/// Whether `key` is a dotted path to a known setting, e.g. `testing.visual.mode`.
+/// `profiles.<name>.` is allowed in front of any key. Sections such as `testing`
+/// hold settings but aren't one.
+pub fn has_key(key: &str) -> bool {
+    let key = profile_key(key).unwrap_or(key);
+    let schema = config_schema();
+    let mut node = &schema;
+    for part in key.split('.') {
+        match node["properties"].get(part) {
+            Some(property) => node = property,
+            None => return false,
+        }
+    }
+    node.get("properties").is_none()
+}
+
+/// The key inside a `profiles.<name>.<key>` path
+pub fn profile_key(key: &str) -> Option<&str> {
+    let rest = key.strip_prefix("profiles.")?;
+    rest.split_once('.').map(|(_, key)| key)
+}
+
+fn android() -> Value {
+    object(
+        "Android SDK tools",
+        [
+            ("sdk_path", string("Android SDK root")),
+            ("adb_path", string("adb executable")),
+            ("emulator_path", string("emulator executable")),
+            ("default_emulator", string("AVD started when none is given")),
+            (
+                "build_tools_version",
+                string("Build tools version, e.g. 34.0.0"),
+            ),
+        ],
+    )
+}
+
+fn ios() -> Value {
+    object(
+        "Xcode tools",
+        [
+            ("xcode_path", string("Xcode developer directory")),
+            ("simctl_path", string("simctl executable")),
+            (
+                "default_simulator",
+                string("Simulator started when none is given"),
+            ),
+            ("developer_team", string("Signing team ID")),
+            ("provisioning_profile", string("Provisioning profile name")),
+        ],
+    )
+}
+
+fn testing() -> Value {
+    object(
+        "Test runs",
+        [
+            (
+                "framework",
+                enumeration("Test framework", KNOWN_TEST_FRAMEWORKS),
+            ),
+            ("timeout", integer("Per-test timeout in seconds", 1, None)),
+            ("parallel", boolean("Run suites on several devices at once")),
+            (
+                "screenshot_on_failure",
+                boolean("Capture a screenshot when a case fails"),
+            ),
+            ("video_recording", boolean("Record a video of each case")),
+            (
+                "video_on_failure_only",
+                boolean("Only keep videos of cases that did not pass"),
+            ),
+            ("output_dir", string("Directory for reports and artifacts")),
+            ("visual", visual()),
+        ],
+    )
+}
+
+fn visual() -> Value {
+    object(
+        "Screenshot comparison against approved baselines",
+        [
+            ("enabled", boolean("Compare screenshots against baselines")),
+            ("baseline_dir", string("Directory of approved baselines")),
+            (
+                "mode",
+                enumeration("Comparison mode", &["pixel", "perceptual"]),
+            ),
+            (
+                "pixel_threshold",
+                integer(
+                    "Largest per-channel difference still counted as the same pixel",
+                    0,
+                    Some(255),
+                ),
+            ),
+            (
+                "max_diff_ratio",
+                ratio("Fraction of differing pixels tolerated in pixel mode"),
+            ),
+            (
+                "min_similarity",
+                ratio("Lowest mean SSIM accepted in perceptual mode"),
+            ),
+            (
+                "ignore_regions",
+                json!({
+                    "type": "array",
+                    "description": "Areas that are never compared",
+                    "items": object(
+                        "Region in screenshot pixels",
+                        [
+                            ("x", integer("Left edge", 0, None)),
+                            ("y", integer("Top edge", 0, None)),
+                            ("width", integer("Width", 0, None)),
+                            ("height", integer("Height", 0, None)),
+                        ],
+                    ),
+                }),
+            ),
+        ],
+    )
+}
+
+fn mcp() -> Value {
+    object(
+        "MCP server",
+        [
+            ("enabled", boolean("Enable the MCP server")),
+            ("port", port()),
+            ("host", string("Host to bind")),
+            (
+                "tools",
+                json!({
+                    "type": "array",
+                    "description": "Tools exposed to clients",
+                    "items": { "type": "string" },
+                }),
+            ),
+        ],
+    )
+}
+
+fn api() -> Value {
+    object(
+        "REST API server",
+        [
+            ("enabled", boolean("Enable the API server")),
+            ("port", port()),
+            ("host", string("Host to bind")),
+            (
+                "auth",
+                object(
+                    "API authentication",
+                    [
+                        ("method", string("Authentication method")),
+                        ("secret", string("Shared secret")),
+                    ],
+                ),
+            ),
+        ],
+    )
+}
+
+fn project() -> Value {
+    let mut project = object(
+        "A project",
+        [
+            ("name", string("Project name")),
+            ("path", string("Project directory")),
+            ("platform", enumeration("Project platform", KNOWN_PLATFORMS)),
+            ("build_command", string("Command that builds the project")),
+            ("test_command", string("Command that runs the native tests")),
+            (
+                "metadata",
+                json!({
+                    "type": "object",
+                    "description": "Free-form key/value pairs",
+                    "additionalProperties": { "type": "string" },
+                }),
+            ),
+        ],
+    );
+    project["required"] = json!(["name", "path", "platform"]);
+    project
+}
+
+fn object<const N: usize>(description: &str, properties: [(&str, Value); N]) -> Value {
+    let properties: Map<String, Value> = properties
+        .into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect();
+    json!({
+        "type": "object",
+        "description": description,
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn boolean(description: &str) -> Value {
+    json!({ "type": "boolean", "description": description })
+}
+
+fn integer(description: &str, minimum: u64, maximum: Option<u64>) -> Value {
+    let mut schema = json!({ "type": "integer", "description": description, "minimum": minimum });
+    if let Some(maximum) = maximum {
+        schema["maximum"] = json!(maximum);
+    }
+    schema
+}
+
+fn ratio(description: &str) -> Value {
+    json!({ "type": "number", "description": description, "minimum": 0, "maximum": 1 })
+}
+
+fn port() -> Value {
+    integer("TCP port", 1, Some(u64::from(u16::MAX)))
+}
+
+fn enumeration(description: &str, values: &[&str]) -> Value {
+    json!({ "type": "string", "description": description, "enum": values })
+}
+
+fn apply_defaults(schema: &mut Value, defaults: &Value) {
+    let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for (name, property) in properties {
+        match defaults.get(name) {
+            Some(Value::Null) | None => {}
+            Some(default @ Value::Object(_)) => apply_defaults(property, default),
+            Some(default) => property["default"] = default.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every key `Config` serializes must be described, or editors flag valid files
+    fn assert_covered(schema: &Value, value: &Value, path: &str) {
+        let Value::Object(map) = value else {
+            return;
+        };
+        for (key, value) in map {
+            let property = &schema["properties"][key];
+            assert!(property.is_object(), "schema is missing {path}{key}");
+            assert_covered(property, value, &format!("{path}{key}."));
+        }
+    }
+
+    #[test]
+    fn test_schema_covers_config() {
+        let schema = config_schema();
+        let mut config = Config::default();
+        config.api.auth = Some(crate::config::AuthConfig {
+            method: "token".to_string(),
+            secret: "secret".to_string(),
+        });
+        assert_covered(&schema, &serde_json::to_value(&config).unwrap(), "");
+
+        assert_eq!(
+            schema["properties"]["mcp"]["properties"]["port"]["default"],
+            3001
+        );
+        assert_eq!(
+            schema["properties"]["testing"]["properties"]["framework"]["enum"][0],
+            "kmobile"
+        );
+    }
+}
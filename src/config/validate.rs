@@ -0,0 +1,175 @@
+//! Semantic checks behind `kmobile config validate`, for settings that parse but
+//! can't work
+
+use serde::Serialize;
+use std::path::Path;
+
+use super::Config;
+
+/// Values accepted for `testing.framework`
+pub const KNOWN_TEST_FRAMEWORKS: &[&str] =
+    &["kmobile", "maestro", "gradle", "xctest", "flutter", "jest"];
+
+/// Project platforms `kmobile init` and the build commands understand
+pub const KNOWN_PLATFORMS: &[&str] = &["android", "ios", "flutter", "react-native", "multi"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn error(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    fn warning(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Config {
+    /// Checks paths, ports and enumerated values. An empty list means the config is usable.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        let paths = [
+            ("android.sdk_path", &self.android.sdk_path),
+            ("android.adb_path", &self.android.adb_path),
+            ("android.emulator_path", &self.android.emulator_path),
+            ("ios.xcode_path", &self.ios.xcode_path),
+            ("ios.simctl_path", &self.ios.simctl_path),
+        ];
+        for (key, path) in paths {
+            if let Some(path) = path {
+                check_exists(&mut issues, key, path);
+            }
+        }
+        for (index, project) in self.projects.iter().enumerate() {
+            check_exists(
+                &mut issues,
+                &format!("projects[{index}].path"),
+                &project.path,
+            );
+            if !KNOWN_PLATFORMS.contains(&project.platform.as_str()) {
+                issues.push(ValidationIssue::warning(
+                    format!("projects[{index}].platform"),
+                    format!(
+                        "Unknown platform '{}', expected one of: {}",
+                        project.platform,
+                        KNOWN_PLATFORMS.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        if self.mcp.enabled && self.api.enabled && self.mcp.port == self.api.port {
+            issues.push(ValidationIssue::error(
+                "api.port",
+                format!("Port {} is also used by mcp.port", self.api.port),
+            ));
+        }
+
+        if !KNOWN_TEST_FRAMEWORKS.contains(&self.testing.framework.as_str()) {
+            issues.push(ValidationIssue::error(
+                "testing.framework",
+                format!(
+                    "Unknown framework '{}', expected one of: {}",
+                    self.testing.framework,
+                    KNOWN_TEST_FRAMEWORKS.join(", ")
+                ),
+            ));
+        }
+        if self.testing.timeout == 0 {
+            issues.push(ValidationIssue::error(
+                "testing.timeout",
+                "Must be at least 1 second",
+            ));
+        }
+
+        let visual = &self.testing.visual;
+        for (key, value) in [
+            ("testing.visual.max_diff_ratio", visual.max_diff_ratio),
+            ("testing.visual.min_similarity", visual.min_similarity),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                issues.push(ValidationIssue::error(key, "Must be between 0 and 1"));
+            }
+        }
+
+        issues
+    }
+}
+
+fn check_exists(issues: &mut Vec<ValidationIssue>, key: &str, path: &Path) {
+    if !path.exists() {
+        issues.push(ValidationIssue::error(
+            key,
+            format!("{} does not exist", path.display()),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProjectConfig;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_validate() {
+        assert!(Config::default().validate().is_empty());
+
+        let mut config = Config::default();
+        config.android.adb_path = Some(PathBuf::from("/nonexistent/adb"));
+        config.api.port = config.mcp.port;
+        config.testing.framework = "robotium".to_string();
+        config.projects.push(ProjectConfig {
+            name: "App".to_string(),
+            path: PathBuf::from("."),
+            platform: "windows-phone".to_string(),
+            build_command: None,
+            test_command: None,
+            metadata: HashMap::new(),
+        });
+
+        let issues = config.validate();
+        let keys: Vec<(&str, Severity)> = issues
+            .iter()
+            .map(|issue| (issue.key.as_str(), issue.severity))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("android.adb_path", Severity::Error),
+                ("projects[0].platform", Severity::Warning),
+                ("api.port", Severity::Error),
+                ("testing.framework", Severity::Error),
+            ]
+        );
+
+        config.mcp.enabled = false;
+        assert!(!config
+            .validate()
+            .iter()
+            .any(|issue| issue.key == "api.port"));
+    }
+}
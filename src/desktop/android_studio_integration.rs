@@ -171,7 +171,7 @@ impl AndroidStudioIntegration {
 
         let instance = EmulatorInstance {
             avd_name: avd_name.to_string(),
-            port: 5554,    // Default port
+            port: crate::emulator::console_port(&device_id).unwrap_or(5554),
             api_level: 30, // Would be parsed from AVD info
             target: "Android 11".to_string(),
             status: EmulatorStatus::Running,
@@ -438,10 +438,12 @@ impl AndroidStudioIntegration {
         Ok(())
     }
 
-    async fn wait_for_emulator_boot(&self, _avd_name: &str, _pid: Option<u32>) -> Result<String> {
-        // Wait for emulator to appear in device list and become ready
-        tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-        Ok("emulator-5554".to_string()) // Placeholder
+    async fn wait_for_emulator_boot(&self, avd_name: &str, _pid: Option<u32>) -> Result<String> {
+        let adb = self
+            .adb_path
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("ADB not found"))?;
+        crate::emulator::wait_for_avd(adb, avd_name, crate::emulator::DEFAULT_BOOT_TIMEOUT).await
     }
 
     async fn check_android_studio_installed(&self) -> bool {
@@ -454,22 +456,13 @@ impl AndroidStudioIntegration {
         Ok(HashMap::new()) // Placeholder
     }
 
-    fn parse_avd_list(&self, _output: &str) -> Result<Vec<AvdInfo>> {
-        // Parse AVD manager output
-        Ok(vec![]) // Placeholder
+    fn parse_avd_list(&self, output: &str) -> Result<Vec<AvdInfo>> {
+        Ok(crate::avd::parse_avd_list(output))
     }
 }
 
 // Additional structs and implementations
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AvdInfo {
-    pub name: String,
-    pub target: String,
-    pub api_level: i32,
-    pub abi: String,
-    pub device_type: String,
-    pub storage: String,
-}
+pub use crate::avd::AvdInfo;
 
 #[derive(Debug, Clone)]
 pub struct BuildResult {
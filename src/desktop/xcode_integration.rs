@@ -11,6 +11,7 @@ use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
 use crate::device_bridge::DeviceBridge;
+use crate::hardware_emulator::route::{PlaybackOptions, Route, RoutePoint};
 use crate::hardware_emulator::HardwareEmulator;
 
 /// Revolutionary Xcode Integration System
@@ -996,25 +997,7 @@ impl XcodeIntegration {
             udid, latitude, longitude
         );
 
-        let output = Command::new("xcrun")
-            .args([
-                "simctl",
-                "location",
-                udid,
-                "set",
-                &latitude.to_string(),
-                &longitude.to_string(),
-            ])
-            .output()?;
-
-        if !output.status.success() {
-            return Err(anyhow::anyhow!(
-                "Failed to simulate location: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-
-        // Update hardware emulator
+        // The hardware emulator sets it with `simctl location`
         let hardware_emulator = self.hardware_emulator.read().await;
         let location_data = serde_json::json!({
             "latitude": latitude,
@@ -1030,6 +1013,62 @@ impl XcodeIntegration {
         Ok(())
     }
 
+    /// Play a location scenario. Routes play in the background through the hardware
+    /// emulator; `Custom` names a GPX, KML or GeoJSON file, and `Freeway` and `City`
+    /// run the simulator's built-in drives.
+    pub async fn play_location_scenario(
+        &mut self,
+        udid: &str,
+        scenario: &LocationScenario,
+        options: &PlaybackOptions,
+    ) -> Result<()> {
+        let route = match scenario {
+            LocationScenario::Static(location) => {
+                return self
+                    .simulate_location(udid, location.latitude, location.longitude)
+                    .await;
+            }
+            LocationScenario::Freeway | LocationScenario::City => {
+                let name = match scenario {
+                    LocationScenario::Freeway => "Freeway Drive",
+                    _ => "City Run",
+                };
+                let output = Command::new("xcrun")
+                    .args(["simctl", "location", udid, "run", name])
+                    .output()?;
+                if !output.status.success() {
+                    return Err(anyhow::anyhow!(
+                        "Failed to run location scenario: {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    ));
+                }
+                return Ok(());
+            }
+            LocationScenario::Route(locations) => Route {
+                points: locations
+                    .iter()
+                    .map(|location| RoutePoint {
+                        latitude: location.latitude,
+                        longitude: location.longitude,
+                        altitude: Some(location.altitude),
+                        time: Some(location.timestamp),
+                    })
+                    .collect(),
+            },
+            LocationScenario::Custom(path) => Route::load(Path::new(path))?,
+        };
+
+        info!(
+            "🗺️ Playing a {}-point route on device {}",
+            route.points.len(),
+            udid
+        );
+        self.hardware_emulator
+            .write()
+            .await
+            .start_route(udid, &route, options)
+    }
+
     /// Toggle hardware keyboard
     pub async fn toggle_hardware_keyboard(&mut self, udid: &str, enabled: bool) -> Result<()> {
         info!(
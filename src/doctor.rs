@@ -0,0 +1,437 @@
+//! Environment checks behind `kmobile doctor`: SDK tools and their versions, the adb
+//! server and hardware acceleration for emulators
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::avd;
+use crate::config::Config;
+use crate::utils::{self, SystemInfo};
+
+/// How long `adb start-server` and `adb devices` may take before the server counts as hung
+const ADB_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Oldest versions KMobile is tested with
+pub const MIN_PLATFORM_TOOLS: &str = "30.0.0";
+pub const MIN_EMULATOR: &str = "30.0.0";
+pub const MIN_JAVA: &str = "17";
+pub const MIN_GRADLE: &str = "7.5";
+pub const MIN_XCODE: &str = "14.0";
+pub const MIN_FLUTTER: &str = "3.10.0";
+pub const MIN_NODE: &str = "18.0.0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+    /// Not applicable on this OS
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            version: None,
+            minimum: None,
+            path: None,
+            message: message.into(),
+        }
+    }
+
+    fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+/// A path found on this machine for a config key that isn't set
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredPath {
+    pub key: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub system: SystemInfo,
+    pub checks: Vec<Check>,
+    pub discovered: Vec<DiscoveredPath>,
+    /// Config file the discovered paths were written to, if they were
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written_config: Option<PathBuf>,
+}
+
+impl DoctorReport {
+    pub fn errors(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Error)
+            .count()
+    }
+}
+
+pub async fn run_doctor(config: &Config) -> Result<DoctorReport> {
+    let system = utils::detect_system_info().await?;
+
+    let mut detected = config.clone();
+    detected.detect_android_sdk()?;
+    detected.detect_ios_tools()?;
+    let discovered = discovered_paths(config, &detected);
+
+    let android = &detected.android;
+    let mut checks = Vec::new();
+    checks.push(match &android.sdk_path {
+        Some(path) if path.is_dir() => {
+            Check::new("Android SDK", CheckStatus::Ok, "found").with_path(path)
+        }
+        Some(path) => Check::new(
+            "Android SDK",
+            CheckStatus::Error,
+            "directory does not exist",
+        )
+        .with_path(path),
+        None => Check::new(
+            "Android SDK",
+            CheckStatus::Error,
+            "not found; set ANDROID_HOME or android.sdk_path",
+        ),
+    });
+    checks.push(
+        tool_check(
+            "adb",
+            android.adb_path.as_deref(),
+            Some(MIN_PLATFORM_TOOLS),
+            true,
+        )
+        .await,
+    );
+    checks.push(
+        tool_check(
+            "emulator",
+            android.emulator_path.as_deref(),
+            Some(MIN_EMULATOR),
+            true,
+        )
+        .await,
+    );
+    let avdmanager = android
+        .sdk_path
+        .as_deref()
+        .and_then(|sdk| avd::find_sdk_tool(sdk, "avdmanager"));
+    checks.push(match avdmanager {
+        Some(path) => Check::new("avdmanager", CheckStatus::Ok, "found").with_path(path),
+        None => Check::new(
+            "avdmanager",
+            CheckStatus::Warning,
+            "not found; install the SDK command-line tools to create emulators",
+        ),
+    });
+
+    checks.push(path_tool_check(&system, "java", "Java", Some(MIN_JAVA), true).await);
+    checks.push(path_tool_check(&system, "gradle", "Gradle", Some(MIN_GRADLE), false).await);
+
+    if cfg!(target_os = "macos") {
+        checks.push(path_tool_check(&system, "xcodebuild", "Xcode", Some(MIN_XCODE), true).await);
+        checks.push(match &detected.ios.simctl_path {
+            Some(path) => Check::new("simctl", CheckStatus::Ok, "found").with_path(path),
+            None => Check::new(
+                "simctl",
+                CheckStatus::Error,
+                "not found; run `xcode-select --install`",
+            ),
+        });
+    } else {
+        checks.push(Check::new("Xcode", CheckStatus::Skipped, "macOS only"));
+        checks.push(Check::new("simctl", CheckStatus::Skipped, "macOS only"));
+    }
+
+    checks.push(path_tool_check(&system, "flutter", "Flutter", Some(MIN_FLUTTER), false).await);
+    checks.push(path_tool_check(&system, "node", "Node", Some(MIN_NODE), false).await);
+
+    checks.push(match &android.adb_path {
+        Some(adb_path) if adb_path.exists() => adb_server_check(adb_path).await,
+        _ => Check::new("adb server", CheckStatus::Skipped, "adb not found"),
+    });
+    checks.push(acceleration_check().await);
+
+    Ok(DoctorReport {
+        system,
+        checks,
+        discovered,
+        written_config: None,
+    })
+}
+
+fn discovered_paths(config: &Config, detected: &Config) -> Vec<DiscoveredPath> {
+    [
+        (
+            "android.sdk_path",
+            &config.android.sdk_path,
+            &detected.android.sdk_path,
+        ),
+        (
+            "android.adb_path",
+            &config.android.adb_path,
+            &detected.android.adb_path,
+        ),
+        (
+            "android.emulator_path",
+            &config.android.emulator_path,
+            &detected.android.emulator_path,
+        ),
+        (
+            "ios.xcode_path",
+            &config.ios.xcode_path,
+            &detected.ios.xcode_path,
+        ),
+        (
+            "ios.simctl_path",
+            &config.ios.simctl_path,
+            &detected.ios.simctl_path,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(key, configured, detected)| match (configured, detected) {
+        (None, Some(path)) => Some(DiscoveredPath {
+            key: key.to_string(),
+            path: path.clone(),
+        }),
+        _ => None,
+    })
+    .collect()
+}
+
+async fn tool_check(
+    name: &str,
+    path: Option<&Path>,
+    minimum: Option<&str>,
+    required: bool,
+) -> Check {
+    let missing = if required {
+        CheckStatus::Error
+    } else {
+        CheckStatus::Warning
+    };
+    let Some(path) = path.filter(|path| path.exists()) else {
+        return Check::new(name, missing, "not found");
+    };
+    let version = utils::get_tool_version(name, path).await.ok();
+    versioned(name, path, version, minimum)
+}
+
+async fn path_tool_check(
+    system: &SystemInfo,
+    tool: &str,
+    name: &str,
+    minimum: Option<&str>,
+    required: bool,
+) -> Check {
+    match system.available_tools.get(tool) {
+        Some(info) => versioned(
+            name,
+            Path::new(&info.path),
+            Some(info.version.clone()),
+            minimum,
+        ),
+        None if required => Check::new(name, CheckStatus::Error, "not found on PATH"),
+        None => Check::new(name, CheckStatus::Warning, "not found on PATH (optional)"),
+    }
+}
+
+fn versioned(name: &str, path: &Path, version: Option<String>, minimum: Option<&str>) -> Check {
+    let version = version.filter(|version| version != "unknown");
+    let (status, message) = match (&version, minimum) {
+        (Some(version), Some(minimum)) if !utils::version_at_least(version, minimum) => (
+            CheckStatus::Error,
+            format!("{version} is older than the required {minimum}"),
+        ),
+        (None, Some(_)) => (
+            CheckStatus::Warning,
+            "could not read the version".to_string(),
+        ),
+        _ => (CheckStatus::Ok, "found".to_string()),
+    };
+    let mut check = Check::new(name, status, message).with_path(path);
+    check.version = version;
+    check.minimum = minimum.map(String::from);
+    check
+}
+
+/// Starts the server if needed, then lists devices. A hung server is the most common
+/// reason every adb command stalls.
+async fn adb_server_check(adb_path: &Path) -> Check {
+    let run = |args: &'static [&'static str]| async move {
+        tokio::time::timeout(
+            ADB_TIMEOUT,
+            tokio::process::Command::new(adb_path)
+                .args(args)
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+    };
+
+    if let Ok(Ok(output)) = run(&["start-server"]).await {
+        if !output.status.success() {
+            return Check::new(
+                "adb server",
+                CheckStatus::Error,
+                format!(
+                    "failed to start: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            );
+        }
+    }
+
+    match run(&["devices"]).await {
+        Err(_) => Check::new(
+            "adb server",
+            CheckStatus::Error,
+            "not responding; try `adb kill-server`",
+        ),
+        Ok(Err(e)) => Check::new("adb server", CheckStatus::Error, e.to_string()),
+        Ok(Ok(output)) if output.status.success() => {
+            let devices = count_adb_devices(&String::from_utf8_lossy(&output.stdout));
+            Check::new(
+                "adb server",
+                CheckStatus::Ok,
+                format!("running, {devices} device(s) attached"),
+            )
+        }
+        Ok(Ok(output)) => Check::new(
+            "adb server",
+            CheckStatus::Error,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ),
+    }
+}
+
+fn count_adb_devices(output: &str) -> usize {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("List of devices"))
+        .skip(1)
+        .filter(|line| line.contains('\t'))
+        .count()
+}
+
+/// KVM on Linux, Hypervisor.framework on macOS. Without it the emulator falls back to
+/// software rendering and is too slow for tests.
+async fn acceleration_check() -> Check {
+    if cfg!(target_os = "linux") {
+        let kvm = Path::new("/dev/kvm");
+        if !kvm.exists() {
+            return Check::new(
+                "KVM",
+                CheckStatus::Error,
+                "/dev/kvm missing; enable virtualization in the BIOS or the VM host",
+            );
+        }
+        return match std::fs::OpenOptions::new().read(true).write(true).open(kvm) {
+            Ok(_) => Check::new("KVM", CheckStatus::Ok, "available").with_path(kvm),
+            Err(e) => Check::new(
+                "KVM",
+                CheckStatus::Error,
+                format!("{e}; add your user to the kvm group"),
+            )
+            .with_path(kvm),
+        };
+    }
+
+    if cfg!(target_os = "macos") {
+        let output = tokio::process::Command::new("sysctl")
+            .args(["-n", "kern.hv_support"])
+            .output()
+            .await;
+        return match output {
+            Ok(output) if String::from_utf8_lossy(&output.stdout).trim() == "1" => {
+                Check::new("Hypervisor", CheckStatus::Ok, "available")
+            }
+            _ => Check::new(
+                "Hypervisor",
+                CheckStatus::Error,
+                "Hypervisor.framework is not supported on this Mac",
+            ),
+        };
+    }
+
+    Check::new(
+        "Acceleration",
+        CheckStatus::Skipped,
+        "not checked on this OS",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versioned_against_minimum() {
+        let check = versioned(
+            "Java",
+            Path::new("/usr/bin/java"),
+            Some("11.0.2".into()),
+            Some(MIN_JAVA),
+        );
+        assert_eq!(check.status, CheckStatus::Error);
+        assert!(check.message.contains("older than the required 17"));
+
+        let check = versioned(
+            "Java",
+            Path::new("/usr/bin/java"),
+            Some("21.0.1".into()),
+            Some(MIN_JAVA),
+        );
+        assert_eq!(check.status, CheckStatus::Ok);
+
+        let check = versioned(
+            "Gradle",
+            Path::new("/usr/bin/gradle"),
+            Some("unknown".into()),
+            Some(MIN_GRADLE),
+        );
+        assert_eq!(check.status, CheckStatus::Warning);
+        assert_eq!(check.version, None);
+    }
+
+    #[test]
+    fn test_count_adb_devices() {
+        let output = "* daemon started successfully\nList of devices attached\nemulator-5554\tdevice\nR58M\tunauthorized\n\n";
+        assert_eq!(count_adb_devices(output), 2);
+        assert_eq!(count_adb_devices("List of devices attached\n\n"), 0);
+    }
+
+    #[test]
+    fn test_discovered_paths_only_fill_unset_keys() {
+        let mut config = Config::default();
+        config.android.adb_path = Some(PathBuf::from("/custom/adb"));
+        let mut detected = config.clone();
+        detected.android.sdk_path = Some(PathBuf::from("/opt/sdk"));
+        detected.android.adb_path = Some(PathBuf::from("/opt/sdk/platform-tools/adb"));
+
+        let discovered = discovered_paths(&config, &detected);
+        assert_eq!(discovered.len(), 1);
+        assert_eq!(discovered[0].key, "android.sdk_path");
+    }
+}
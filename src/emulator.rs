@@ -0,0 +1,686 @@
+//! Running Android emulators. adb only knows serials like `emulator-5554`; the AVD
+//! behind each one comes from `adb -s <serial> emu avd name`, and the process from the
+//! emulator's `-avd` and `-port` arguments. Snapshots are managed over the same console.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use sysinfo::{PidExt, ProcessExt, System, SystemExt};
+use tokio::process::Command;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::config::AndroidConfig;
+use crate::error::KMobileError;
+
+pub mod console;
+
+/// Console ports the emulator picks from; the adb port is always the console port + 1
+pub const FIRST_CONSOLE_PORT: u16 = 5554;
+pub const LAST_CONSOLE_PORT: u16 = 5682;
+
+/// An emulator that hasn't finished booting can take a while to answer on its console
+const CONSOLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default for `simulator start --wait`; a cold boot without acceleration is slow
+pub const DEFAULT_BOOT_TIMEOUT: Duration = Duration::from_secs(300);
+
+const BOOT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Saving a snapshot writes out the guest's RAM
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How the emulator boots. The default is a quick boot from the snapshot it saved on
+/// its last exit.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BootOptions {
+    /// Boot from scratch, ignoring snapshots
+    pub cold_boot: bool,
+    /// Boot from this snapshot instead of the quick-boot one
+    pub snapshot: Option<String>,
+    /// Don't save a quick-boot snapshot on exit
+    pub no_snapshot_save: bool,
+}
+
+impl BootOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `emulator` arguments for these options
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.cold_boot {
+            args.push("-no-snapshot-load".to_string());
+        } else if let Some(snapshot) = &self.snapshot {
+            args.extend(["-snapshot".to_string(), snapshot.clone()]);
+        }
+        if self.no_snapshot_save {
+            args.push("-no-snapshot-save".to_string());
+        }
+        args
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Guest uptime when the snapshot was taken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vm_clock: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunningEmulator {
+    pub avd_name: String,
+    pub serial: String,
+    pub console_port: u16,
+    pub adb_port: u16,
+    /// `device` once adb can talk to it, `offline` while it boots
+    pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+}
+
+impl RunningEmulator {
+    pub fn is_online(&self) -> bool {
+        self.state == "device"
+    }
+}
+
+/// Emulators adb knows about, with their AVD names and PIDs. Emulators whose console
+/// doesn't answer are left out, since they can't be told apart.
+pub async fn running_emulators(adb_path: &Path) -> Result<Vec<RunningEmulator>> {
+    let output = Command::new(adb_path).arg("devices").output().await?;
+    if !output.status.success() {
+        return Err(KMobileError::CommandError(format!(
+            "adb devices failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+
+    let serials = parse_emulator_serials(&String::from_utf8_lossy(&output.stdout));
+    if serials.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let processes = emulator_processes();
+    let mut emulators = Vec::new();
+    for (serial, state) in serials {
+        let Some(console_port) = console_port(&serial) else {
+            continue;
+        };
+        let Some(avd_name) = query_avd_name(adb_path, &serial).await else {
+            debug!("{} did not report its AVD name", serial);
+            continue;
+        };
+        let pid = find_emulator_pid(&processes, &avd_name, console_port);
+        emulators.push(RunningEmulator {
+            avd_name,
+            serial,
+            console_port,
+            adb_port: console_port + 1,
+            state,
+            pid,
+        });
+    }
+    Ok(emulators)
+}
+
+async fn query_avd_name(adb_path: &Path, serial: &str) -> Option<String> {
+    let output = tokio::time::timeout(
+        CONSOLE_TIMEOUT,
+        Command::new(adb_path)
+            .args(["-s", serial, "emu", "avd", "name"])
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_avd_name(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Waits until the emulator running `avd_name` shows up in adb, then until it's ready
+/// (see `wait_for_boot`). Returns its serial.
+pub async fn wait_for_avd(adb_path: &Path, avd_name: &str, timeout: Duration) -> Result<String> {
+    let deadline = Instant::now() + timeout;
+    let serial = loop {
+        let running = running_emulators(adb_path).await.unwrap_or_default();
+        if let Some(emulator) = running.into_iter().find(|e| e.avd_name == avd_name) {
+            break emulator.serial;
+        }
+        sleep_until_next_poll(deadline, || {
+            format!("{avd_name} did not appear in adb devices")
+        })
+        .await?;
+    };
+    wait_for_boot(
+        adb_path,
+        &serial,
+        deadline.saturating_duration_since(Instant::now()),
+    )
+    .await?;
+    Ok(serial)
+}
+
+/// Waits until `sys.boot_completed` is set and the package manager answers, then
+/// dismisses the lock screen so tests start on the home screen
+pub async fn wait_for_boot(adb_path: &Path, serial: &str, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    let shell = |args: &'static [&'static str]| adb_shell(adb_path, serial, args);
+
+    info!("Waiting for {} to finish booting", serial);
+    while !is_boot_completed(&shell(&["getprop", "sys.boot_completed"]).await) {
+        sleep_until_next_poll(deadline, || format!("{serial} did not finish booting")).await?;
+    }
+    while !is_package_manager_ready(&shell(&["pm", "path", "android"]).await) {
+        sleep_until_next_poll(deadline, || {
+            format!("Package manager on {serial} is not ready")
+        })
+        .await?;
+    }
+
+    // `wm dismiss-keyguard` needs API 23; the menu key unlocks older images
+    if shell(&["wm", "dismiss-keyguard"]).await.is_none() {
+        shell(&["input", "keyevent", "82"]).await;
+    }
+    info!("{} is ready", serial);
+    Ok(())
+}
+
+/// Output of `adb shell`, or `None` if the command failed or the device isn't up yet
+async fn adb_shell(adb_path: &Path, serial: &str, args: &[&str]) -> Option<String> {
+    let output = tokio::time::timeout(
+        CONSOLE_TIMEOUT,
+        Command::new(adb_path)
+            .args(["-s", serial, "shell"])
+            .args(args)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+async fn sleep_until_next_poll(deadline: Instant, message: impl FnOnce() -> String) -> Result<()> {
+    let now = Instant::now();
+    if now >= deadline {
+        return Err(KMobileError::TimeoutError(message()).into());
+    }
+    tokio::time::sleep(BOOT_POLL_INTERVAL.min(deadline - now)).await;
+    Ok(())
+}
+
+pub fn is_boot_completed(getprop: &Option<String>) -> bool {
+    getprop.as_deref().is_some_and(|value| value.trim() == "1")
+}
+
+/// `pm path android` prints `package:/system/framework/framework-res.apk` once the
+/// package manager service is up
+pub fn is_package_manager_ready(output: &Option<String>) -> bool {
+    output
+        .as_deref()
+        .is_some_and(|value| value.trim_start().starts_with("package:"))
+}
+
+/// Lowest console port no running emulator uses, for starting one with a known serial
+pub fn next_console_port(running: &[RunningEmulator]) -> Option<u16> {
+    free_console_ports(running, 1).first().copied()
+}
+
+/// The `count` lowest console ports no running emulator uses; fewer if the range
+/// runs out
+pub fn free_console_ports(running: &[RunningEmulator], count: usize) -> Vec<u16> {
+    (FIRST_CONSOLE_PORT..=LAST_CONSOLE_PORT)
+        .step_by(2)
+        .filter(|port| {
+            running
+                .iter()
+                .all(|emulator| emulator.console_port != *port)
+        })
+        .take(count)
+        .collect()
+}
+
+/// The `emulator` binary, from the config or under the SDK
+pub fn emulator_binary(android: &AndroidConfig) -> Result<PathBuf> {
+    if let Some(path) = &android.emulator_path {
+        Ok(path.clone())
+    } else if let Some(sdk_path) = &android.sdk_path {
+        Ok(sdk_path.join("emulator/emulator"))
+    } else {
+        Err(KMobileError::ConfigError("Emulator path not configured".to_string()).into())
+    }
+}
+
+pub async fn save_snapshot(adb_path: &Path, serial: &str, name: &str) -> Result<()> {
+    check_snapshot_name(name)?;
+    snapshot_command(adb_path, serial, &["save", name]).await?;
+    Ok(())
+}
+
+/// Restores the emulator to `name` in place. adb reconnects afterwards, so this waits
+/// until the device answers again.
+pub async fn load_snapshot(adb_path: &Path, serial: &str, name: &str) -> Result<()> {
+    check_snapshot_name(name)?;
+    snapshot_command(adb_path, serial, &["load", name]).await?;
+    tokio::time::timeout(
+        SNAPSHOT_TIMEOUT,
+        Command::new(adb_path)
+            .args(["-s", serial, "wait-for-device"])
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| {
+        KMobileError::TimeoutError(format!("{serial} did not come back after loading {name}"))
+    })??;
+    Ok(())
+}
+
+pub async fn delete_snapshot(adb_path: &Path, serial: &str, name: &str) -> Result<()> {
+    check_snapshot_name(name)?;
+    snapshot_command(adb_path, serial, &["delete", name]).await?;
+    Ok(())
+}
+
+pub async fn list_snapshots(adb_path: &Path, serial: &str) -> Result<Vec<Snapshot>> {
+    let output = snapshot_command(adb_path, serial, &["list"]).await?;
+    Ok(parse_snapshot_list(&output))
+}
+
+/// Snapshots of an AVD that isn't running, read from its `snapshots` directory
+pub fn snapshots_on_disk(avd_path: &Path) -> Result<Vec<Snapshot>> {
+    let dir = avd_path.join("snapshots");
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots: Vec<Snapshot> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| Snapshot {
+            name: entry.file_name().to_string_lossy().to_string(),
+            size: None,
+            date: None,
+            vm_clock: None,
+        })
+        .collect();
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(snapshots)
+}
+
+/// `adb emu avd snapshot ...`. The console answers `OK` or `KO: <reason>`, and adb
+/// exits with 0 either way.
+async fn snapshot_command(adb_path: &Path, serial: &str, args: &[&str]) -> Result<String> {
+    let output = tokio::time::timeout(
+        SNAPSHOT_TIMEOUT,
+        Command::new(adb_path)
+            .args(["-s", serial, "emu", "avd", "snapshot"])
+            .args(args)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| {
+        KMobileError::TimeoutError(format!("snapshot {} on {serial} timed out", args.join(" ")))
+    })??;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    if let Some(reason) = stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("KO"))
+    {
+        return Err(KMobileError::CommandError(format!(
+            "snapshot {} failed: {}",
+            args.join(" "),
+            reason.trim_start_matches(':').trim()
+        ))
+        .into());
+    }
+    if !output.status.success() {
+        return Err(KMobileError::CommandError(format!(
+            "snapshot {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    Ok(stdout)
+}
+
+/// The console splits its arguments on whitespace
+fn check_snapshot_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(char::is_whitespace) || name.contains(['/', '\\']) {
+        return Err(KMobileError::InvalidInput(format!(
+            "Invalid snapshot name '{name}': use letters, digits, '-' and '_'"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Parses `avd snapshot list`:
+///
+/// ```text
+/// List of snapshots present on all disks:
+/// ID        TAG                 VM SIZE                DATE       VM CLOCK
+/// --        default_boot           137M 2024-03-01 10:15:02   00:02:11.514
+/// OK
+/// ```
+pub fn parse_snapshot_list(output: &str) -> Vec<Snapshot> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let id = fields.first()?;
+            if *id != "--" && id.parse::<u32>().is_err() {
+                return None;
+            }
+            let field = |index: usize| fields.get(index).map(|value| value.to_string());
+            Some(Snapshot {
+                name: fields.get(1)?.to_string(),
+                size: field(2),
+                date: match (fields.get(3), fields.get(4)) {
+                    (Some(day), Some(time)) => Some(format!("{day} {time}")),
+                    _ => None,
+                },
+                vm_clock: field(5),
+            })
+        })
+        .collect()
+}
+
+pub fn serial_for_port(console_port: u16) -> String {
+    format!("emulator-{console_port}")
+}
+
+/// `emulator-5554` → 5554
+pub fn console_port(serial: &str) -> Option<u16> {
+    serial.strip_prefix("emulator-")?.parse().ok()
+}
+
+/// `(serial, state)` for each emulator in `adb devices` output
+pub fn parse_emulator_serials(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let serial = parts.next()?;
+            let state = parts.next()?;
+            serial
+                .starts_with("emulator-")
+                .then(|| (serial.to_string(), state.to_string()))
+        })
+        .collect()
+}
+
+/// The first line of `emu avd name` output, which ends with `OK`
+pub fn parse_avd_name(output: &str) -> Option<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .filter(|line| *line != "OK" && !line.starts_with("KO"))
+        .map(String::from)
+}
+
+/// `(pid, command line)` of processes that look like an emulator
+pub(crate) fn emulator_processes() -> Vec<(u32, Vec<String>)> {
+    let mut system = System::new();
+    system.refresh_processes();
+    system
+        .processes()
+        .iter()
+        .filter(|(_, process)| {
+            process.name().contains("emulator") || process.name().contains("qemu")
+        })
+        .map(|(pid, process)| (pid.as_u32(), process.cmd().to_vec()))
+        .collect()
+}
+
+/// The emulator is started as `emulator -avd <name>` or `emulator @<name>` and runs
+/// its qemu child with the same arguments. The qemu process is the one to signal.
+/// Instances of one AVD, as in a farm, are told apart by their `-port`/`-ports`;
+/// a process without one may be on any port, so it only matches when no process
+/// names `console_port` explicitly.
+pub fn find_emulator_pid(
+    processes: &[(u32, Vec<String>)],
+    avd_name: &str,
+    console_port: u16,
+) -> Option<u32> {
+    let runs_avd = |cmd: &[String]| {
+        cmd.windows(2)
+            .any(|pair| pair[0] == "-avd" && pair[1] == avd_name)
+            || cmd
+                .iter()
+                .any(|arg| arg.strip_prefix('@') == Some(avd_name))
+    };
+    let port_of = |cmd: &[String]| {
+        cmd.windows(2).find_map(|pair| match pair[0].as_str() {
+            "-port" | "-ports" => pair[1].split(',').next()?.parse::<u16>().ok(),
+            _ => None,
+        })
+    };
+    processes
+        .iter()
+        .filter(|(_, cmd)| runs_avd(cmd))
+        .filter_map(|(pid, cmd)| match port_of(cmd) {
+            Some(port) if port != console_port => None,
+            port => {
+                let is_qemu = cmd.first().is_some_and(|program| program.contains("qemu"));
+                Some((*pid, port.is_some(), is_qemu))
+            }
+        })
+        .min_by_key(|(_, explicit_port, is_qemu)| (!explicit_port, !is_qemu))
+        .map(|(pid, _, _)| pid)
+}
+
+/// Last resort when the console doesn't accept `kill`
+pub fn kill_process(pid: u32) -> bool {
+    let mut system = System::new();
+    system.refresh_processes();
+    system
+        .process(sysinfo::Pid::from_u32(pid))
+        .is_some_and(|process| process.kill())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emulator(avd_name: &str, console_port: u16) -> RunningEmulator {
+        RunningEmulator {
+            avd_name: avd_name.to_string(),
+            serial: serial_for_port(console_port),
+            console_port,
+            adb_port: console_port + 1,
+            state: "device".to_string(),
+            pid: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_adb_output() {
+        let devices = "List of devices attached\nemulator-5554\tdevice\nR58M123ABC\tdevice\nemulator-5556\toffline\n\n";
+        assert_eq!(
+            parse_emulator_serials(devices),
+            vec![
+                ("emulator-5554".to_string(), "device".to_string()),
+                ("emulator-5556".to_string(), "offline".to_string()),
+            ]
+        );
+        assert_eq!(console_port("emulator-5556"), Some(5556));
+        assert_eq!(console_port("R58M123ABC"), None);
+
+        assert_eq!(
+            parse_avd_name("Pixel_7_API_34\r\nOK\r\n").as_deref(),
+            Some("Pixel_7_API_34")
+        );
+        assert_eq!(parse_avd_name("OK\r\n"), None);
+        assert_eq!(parse_avd_name("KO: unknown command\r\n"), None);
+    }
+
+    #[test]
+    fn test_parse_snapshot_list() {
+        let output = "List of snapshots present on all disks:\r\n\
+            ID        TAG                 VM SIZE                DATE       VM CLOCK\r\n\
+            --        default_boot           137M 2024-03-01 10:15:02   00:02:11.514\r\n\
+            --        logged_in              141M 2024-03-02 09:00:40   00:05:03.100\r\n\
+            OK\r\n";
+        let snapshots = parse_snapshot_list(output);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].name, "logged_in");
+        assert_eq!(snapshots[1].size.as_deref(), Some("141M"));
+        assert_eq!(snapshots[1].date.as_deref(), Some("2024-03-02 09:00:40"));
+        assert_eq!(snapshots[1].vm_clock.as_deref(), Some("00:05:03.100"));
+        assert!(parse_snapshot_list("There is no snapshot available.\r\nOK\r\n").is_empty());
+
+        assert!(check_snapshot_name("logged_in").is_ok());
+        assert!(check_snapshot_name("logged in").is_err());
+    }
+
+    #[test]
+    fn test_boot_options_args() {
+        assert!(BootOptions::default().args().is_empty());
+        let options = BootOptions {
+            snapshot: Some("logged_in".to_string()),
+            no_snapshot_save: true,
+            ..BootOptions::default()
+        };
+        assert_eq!(
+            options.args(),
+            ["-snapshot", "logged_in", "-no-snapshot-save"]
+        );
+        let cold = BootOptions {
+            cold_boot: true,
+            ..BootOptions::default()
+        };
+        assert_eq!(cold.args(), ["-no-snapshot-load"]);
+    }
+
+    #[test]
+    fn test_boot_readiness() {
+        assert!(is_boot_completed(&Some("1\r\n".to_string())));
+        assert!(!is_boot_completed(&Some("\r\n".to_string())));
+        assert!(!is_boot_completed(&None));
+        assert!(is_package_manager_ready(&Some(
+            "package:/system/framework/framework-res.apk\n".to_string()
+        )));
+        assert!(!is_package_manager_ready(&Some(
+            "Error: Could not access the Package Manager.".to_string()
+        )));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_wait_for_boot() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Reports the boot as complete on the third poll
+        let dir = tempfile::tempdir().unwrap();
+        let adb = dir.path().join("adb");
+        let polls = dir.path().join("polls");
+        let script = format!(
+            "#!/bin/sh\n\
+             case \"$*\" in\n\
+             *boot_completed*) echo x >> {polls}; [ $(wc -l < {polls}) -ge 3 ] && echo 1 ;;\n\
+             *\"pm path\"*) echo package:/system/framework/framework-res.apk ;;\n\
+             *dismiss-keyguard*) echo \"$*\" > {log} ;;\n\
+             esac\n",
+            polls = polls.display(),
+            log = dir.path().join("keyguard").display(),
+        );
+        std::fs::write(&adb, script).unwrap();
+        std::fs::set_permissions(&adb, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        wait_for_boot(&adb, "emulator-5554", Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&polls).unwrap().lines().count(), 3);
+        assert!(dir.path().join("keyguard").exists());
+
+        std::fs::write(&polls, "").unwrap();
+        let error = wait_for_boot(&adb, "emulator-5554", Duration::from_millis(500))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::TimeoutError(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_emulator_pid() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        let processes = vec![
+            (100, args("/sdk/emulator/emulator -avd Pixel_7 -no-window")),
+            (
+                101,
+                args("/sdk/emulator/qemu/linux-x86_64/qemu-system-x86_64 -avd Pixel_7 -no-window"),
+            ),
+            (200, args("/sdk/emulator/emulator @Tablet")),
+            (300, args("/sdk/emulator/emulator -avd Pixel_7_API_34")),
+        ];
+        assert_eq!(find_emulator_pid(&processes, "Pixel_7", 5554), Some(101));
+        assert_eq!(find_emulator_pid(&processes, "Tablet", 5556), Some(200));
+        assert_eq!(
+            find_emulator_pid(&processes, "Pixel_7_API_34", 5554),
+            Some(300)
+        );
+        assert_eq!(find_emulator_pid(&processes, "Wear", 5554), None);
+    }
+
+    #[test]
+    fn test_find_emulator_pid_of_farm_instances() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        let processes = vec![
+            (
+                100,
+                args("/sdk/emulator/emulator -avd Farm -port 5554 -read-only"),
+            ),
+            (
+                101,
+                args("/sdk/qemu-system-x86_64 -avd Farm -port 5554 -read-only"),
+            ),
+            (
+                200,
+                args("/sdk/emulator/emulator -avd Farm -port 5556 -read-only"),
+            ),
+            (
+                201,
+                args("/sdk/qemu-system-x86_64 -avd Farm -port 5556 -read-only"),
+            ),
+            (
+                300,
+                args("/sdk/qemu-system-x86_64 -avd Farm -ports 5558,5559"),
+            ),
+        ];
+        assert_eq!(find_emulator_pid(&processes, "Farm", 5554), Some(101));
+        assert_eq!(find_emulator_pid(&processes, "Farm", 5556), Some(201));
+        assert_eq!(find_emulator_pid(&processes, "Farm", 5558), Some(300));
+        assert_eq!(find_emulator_pid(&processes, "Farm", 5560), None);
+    }
+
+    #[test]
+    fn test_next_console_port() {
+        assert_eq!(next_console_port(&[]), Some(5554));
+        let running = vec![emulator("a", 5554), emulator("b", 5558)];
+        assert_eq!(next_console_port(&running), Some(5556));
+        assert_eq!(free_console_ports(&running, 3), [5556, 5560, 5562]);
+    }
+}
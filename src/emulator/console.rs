@@ -0,0 +1,277 @@
+//! The emulator's telnet console on its console port, the number in `emulator-5554`.
+//! One connection stays open per emulator, so sensor values can be streamed without
+//! starting an `adb emu` process for each one.
+
+use anyhow::Result;
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use super::CONSOLE_TIMEOUT;
+use crate::error::KMobileError;
+
+/// Written by the emulator on first start; an empty file turns authentication off
+const AUTH_TOKEN_FILE: &str = ".emulator_console_auth_token";
+
+#[derive(Debug)]
+pub struct EmulatorConsole {
+    port: u16,
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl EmulatorConsole {
+    /// Connects to the emulator on `console_port` of this host, authenticating with
+    /// the token from `~/.emulator_console_auth_token` if the console asks for one
+    pub async fn connect(console_port: u16) -> Result<Self> {
+        let token = read_auth_token()?;
+        Self::connect_with_token(console_port, token.as_deref()).await
+    }
+
+    pub async fn connect_with_token(console_port: u16, token: Option<&str>) -> Result<Self> {
+        let stream = tokio::time::timeout(
+            CONSOLE_TIMEOUT,
+            TcpStream::connect(("127.0.0.1", console_port)),
+        )
+        .await
+        .map_err(|_| {
+            KMobileError::TimeoutError(format!(
+                "Emulator console on port {console_port} did not accept the connection"
+            ))
+        })?
+        .map_err(|e| {
+            KMobileError::DeviceConnectionError(format!(
+                "No emulator console on port {console_port}: {e}"
+            ))
+        })?;
+
+        let (reader, writer) = stream.into_split();
+        let mut console = Self {
+            port: console_port,
+            reader: BufReader::new(reader),
+            writer,
+        };
+
+        let banner = console.read_reply().await?;
+        if banner
+            .iter()
+            .any(|line| line.contains("Authentication required"))
+        {
+            let token = token.ok_or_else(|| {
+                KMobileError::AuthenticationError(format!(
+                    "Emulator console on port {console_port} needs the token in ~/{AUTH_TOKEN_FILE}"
+                ))
+            })?;
+            console.send(&format!("auth {token}")).await?;
+            console.read_reply().await.map_err(|e| {
+                KMobileError::AuthenticationError(format!(
+                    "Emulator console on port {console_port} rejected the auth token: {e}"
+                ))
+            })?;
+        }
+
+        Ok(console)
+    }
+
+    /// Runs a console command and returns the lines it printed before `OK`
+    pub async fn command(&mut self, command: &str) -> Result<Vec<String>> {
+        debug!("Console {}: {}", self.port, command);
+        self.send(command).await?;
+        self.read_reply().await
+    }
+
+    /// Moves the emulated GPS to a position; altitude is in meters
+    pub async fn geo_fix(&mut self, latitude: f64, longitude: f64, altitude: f64) -> Result<()> {
+        // `geo fix` takes longitude first
+        self.command(&format!("geo fix {longitude} {latitude} {altitude}"))
+            .await?;
+        Ok(())
+    }
+
+    /// Sets one of the sensors listed by `sensor status`, e.g. `acceleration` with
+    /// x, y and z, or `light` with a single value
+    pub async fn set_sensor(&mut self, sensor: &str, values: &[f64]) -> Result<()> {
+        let values = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(":");
+        self.command(&format!("sensor set {sensor} {values}"))
+            .await?;
+        Ok(())
+    }
+
+    async fn send(&mut self, line: &str) -> Result<()> {
+        self.writer
+            .write_all(format!("{line}\n").as_bytes())
+            .await?;
+        Ok(())
+    }
+
+    /// Lines up to the `OK` or `KO: <reason>` that ends every reply
+    async fn read_reply(&mut self) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = tokio::time::timeout(CONSOLE_TIMEOUT, self.reader.read_line(&mut line))
+                .await
+                .map_err(|_| {
+                    KMobileError::TimeoutError(format!(
+                        "Emulator console on port {} did not answer",
+                        self.port
+                    ))
+                })??;
+            if read == 0 {
+                return Err(KMobileError::DeviceConnectionError(format!(
+                    "Emulator console on port {} closed the connection",
+                    self.port
+                ))
+                .into());
+            }
+
+            let line = line.trim_end();
+            if line == "OK" {
+                return Ok(lines);
+            }
+            if let Some(reason) = line.strip_prefix("KO") {
+                return Err(KMobileError::CommandError(format!(
+                    "Emulator console: {}",
+                    reason.trim_start_matches(':').trim()
+                ))
+                .into());
+            }
+            lines.push(line.to_string());
+        }
+    }
+}
+
+pub fn auth_token_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(AUTH_TOKEN_FILE))
+}
+
+fn read_auth_token() -> Result<Option<String>> {
+    let Some(path) = auth_token_path() else {
+        return Ok(None);
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(token) => Ok(Some(token.trim().to_string()).filter(|token| !token.is_empty())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// A console on a local port that wants `token` (if any), answers `KO` to
+    /// commands starting with `bad` and records the rest
+    pub(crate) async fn fake_console(
+        token: Option<&'static str>,
+    ) -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let recorded = commands.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let commands = recorded.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    let banner = match token {
+                        Some(_) => "Android Console: Authentication required\r\nAndroid Console: type 'auth <auth_token>' to authenticate\r\nOK\r\n",
+                        None => "Android Console: type 'help' for a list of commands\r\nOK\r\n",
+                    };
+                    writer.write_all(banner.as_bytes()).await.unwrap();
+
+                    let mut authenticated = token.is_none();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let reply = if let Some(given) = line.strip_prefix("auth ") {
+                            authenticated = Some(given) == token;
+                            if authenticated {
+                                "OK"
+                            } else {
+                                "KO: authentication token does not match"
+                            }
+                        } else if !authenticated {
+                            "KO: unknown command, try 'help'"
+                        } else if line.starts_with("bad") {
+                            "KO: bad sensor name"
+                        } else {
+                            commands.lock().unwrap().push(line);
+                            "OK"
+                        };
+                        let reply = format!("{reply}\r\n");
+                        if writer.write_all(reply.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        (port, commands)
+    }
+
+    #[tokio::test]
+    async fn test_console_authenticates_and_sends_commands() {
+        let (port, commands) = fake_console(Some("s3cret")).await;
+        let mut console = EmulatorConsole::connect_with_token(port, Some("s3cret"))
+            .await
+            .unwrap();
+
+        console.geo_fix(37.7749, -122.4194, 52.0).await.unwrap();
+        console
+            .set_sensor("acceleration", &[0.0, 9.81, 0.5])
+            .await
+            .unwrap();
+        console.set_sensor("light", &[300.0]).await.unwrap();
+        let error = console.command("bad command").await.unwrap_err();
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                "geo fix -122.4194 37.7749 52",
+                "sensor set acceleration 0:9.81:0.5",
+                "sensor set light 300",
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "Command execution error: Emulator console: bad sensor name"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_console_auth_failures() {
+        let (port, _) = fake_console(Some("s3cret")).await;
+
+        let missing = EmulatorConsole::connect_with_token(port, None)
+            .await
+            .err()
+            .unwrap();
+        let wrong = EmulatorConsole::connect_with_token(port, Some("guess"))
+            .await
+            .err()
+            .unwrap();
+        for error in [missing, wrong] {
+            assert!(matches!(
+                KMobileError::find(&error),
+                Some(KMobileError::AuthenticationError(_))
+            ));
+        }
+
+        let (open_port, _) = fake_console(None).await;
+        assert!(EmulatorConsole::connect_with_token(open_port, None)
+            .await
+            .is_ok());
+    }
+}
@@ -1,7 +1,6 @@
 use thiserror::Error;
 
 #[derive(Error, Debug)]
-#[allow(dead_code)]
 pub enum KMobileError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
@@ -80,23 +79,30 @@ pub enum KMobileError {
 }
 
 impl KMobileError {
-    #[allow(dead_code)]
+    /// Finds the `KMobileError` in an error chain, including behind added context
+    pub fn find(error: &anyhow::Error) -> Option<&KMobileError> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+
+    /// Whether retrying the same operation may succeed, e.g. a device connection that
+    /// is still coming up. Unknown ids, rejected installs and failed builds fail the
+    /// same way every time.
     pub fn is_recoverable(&self) -> bool {
         match self {
             KMobileError::ConfigError(_) => false,
-            KMobileError::DeviceNotFound(_) => true,
+            KMobileError::DeviceNotFound(_) => false,
             KMobileError::DeviceConnectionError(_) => true,
-            KMobileError::SimulatorNotFound(_) => true,
+            KMobileError::SimulatorNotFound(_) => false,
             KMobileError::SimulatorStartError(_) => true,
             KMobileError::SimulatorStopError(_) => true,
             KMobileError::SimulatorResetError(_) => true,
             KMobileError::ProjectNotFound(_) => false,
             KMobileError::ProjectInitError(_) => false,
             KMobileError::ProjectDeployError(_) => true,
-            KMobileError::BuildError(_) => true,
+            KMobileError::BuildError(_) => false,
             KMobileError::TestExecutionError(_) => true,
             KMobileError::TestFileNotFound(_) => false,
-            KMobileError::AppInstallError(_) => true,
+            KMobileError::AppInstallError(_) => false,
             KMobileError::CommandError(_) => true,
             KMobileError::McpServerError(_) => true,
             KMobileError::ApiServerError(_) => true,
@@ -111,7 +117,6 @@ impl KMobileError {
         }
     }
 
-    #[allow(dead_code)]
     pub fn error_code(&self) -> &'static str {
         match self {
             KMobileError::ConfigError(_) => "CONFIG_ERROR",
@@ -141,6 +146,60 @@ impl KMobileError {
             KMobileError::Unknown(_) => "UNKNOWN_ERROR",
         }
     }
+
+    /// Process exit code, grouped by the area `error_code` points at so scripts can
+    /// branch without parsing messages
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            KMobileError::Unknown(_) => 1,
+            KMobileError::InvalidInput(_) => 2,
+            KMobileError::ConfigError(_) => 3,
+            KMobileError::DeviceNotFound(_) | KMobileError::DeviceConnectionError(_) => 4,
+            KMobileError::SimulatorNotFound(_)
+            | KMobileError::SimulatorStartError(_)
+            | KMobileError::SimulatorStopError(_)
+            | KMobileError::SimulatorResetError(_) => 5,
+            KMobileError::ProjectNotFound(_)
+            | KMobileError::ProjectInitError(_)
+            | KMobileError::ProjectDeployError(_) => 6,
+            KMobileError::BuildError(_) => 7,
+            KMobileError::TestExecutionError(_) | KMobileError::TestFileNotFound(_) => 8,
+            KMobileError::AppInstallError(_) => 9,
+            KMobileError::CommandError(_) => 10,
+            KMobileError::McpServerError(_)
+            | KMobileError::ApiServerError(_)
+            | KMobileError::NetworkError(_) => 11,
+            KMobileError::FileSystemError(_) | KMobileError::SerializationError(_) => 12,
+            KMobileError::AuthenticationError(_) | KMobileError::PermissionError(_) => 13,
+            KMobileError::TimeoutError(_) => 14,
+        }
+    }
+
+    /// HTTP status for API responses
+    pub fn http_status(&self) -> u16 {
+        match self {
+            KMobileError::InvalidInput(_) => 400,
+            KMobileError::AuthenticationError(_) => 401,
+            KMobileError::PermissionError(_) => 403,
+            KMobileError::DeviceNotFound(_)
+            | KMobileError::SimulatorNotFound(_)
+            | KMobileError::ProjectNotFound(_)
+            | KMobileError::TestFileNotFound(_) => 404,
+            KMobileError::DeviceConnectionError(_) | KMobileError::NetworkError(_) => 502,
+            KMobileError::McpServerError(_) | KMobileError::ApiServerError(_) => 503,
+            KMobileError::TimeoutError(_) => 504,
+            _ => 500,
+        }
+    }
+
+    /// JSON-RPC error code for MCP responses. Bad arguments use the standard invalid
+    /// params code; everything else gets its own code in the server error range.
+    pub fn json_rpc_code(&self) -> i32 {
+        match self {
+            KMobileError::InvalidInput(_) => -32602,
+            _ => -32000 - i32::from(self.exit_code()),
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, KMobileError>;
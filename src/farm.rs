@@ -0,0 +1,388 @@
+//! Headless emulator farm for CI. Boots several read-only instances of one AVD on
+//! known console ports, only as many as the host's CPUs and memory can carry, and
+//! relaunches instances whose emulator process exits while the farm is up.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::{System, SystemExt};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::emulator;
+use crate::error::KMobileError;
+use crate::testing::DevicePool;
+
+/// Guest vCPUs the emulator uses by default
+pub const DEFAULT_CPUS_PER_INSTANCE: usize = 2;
+/// Guest RAM plus the emulator's own overhead for a typical phone image
+pub const DEFAULT_RAM_MB_PER_INSTANCE: u64 = 3072;
+pub const DEFAULT_MAX_RESTARTS: u32 = 3;
+
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(2);
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `-read-only` lets several emulators share one AVD; nothing they do is written back
+const INSTANCE_ARGS: &[&str] = &[
+    "-read-only",
+    "-no-window",
+    "-no-audio",
+    "-no-boot-anim",
+    "-no-snapshot-save",
+];
+
+#[derive(Debug, Clone)]
+pub struct FarmOptions {
+    pub avd: String,
+    /// Instances wanted; fewer are started if the host can't carry them
+    pub instances: usize,
+    pub cpus_per_instance: usize,
+    pub ram_mb_per_instance: u64,
+    /// Relaunches allowed per instance before it is left down
+    pub max_restarts: u32,
+    pub boot_timeout: Duration,
+}
+
+/// One emulator of the farm, as reported by `simulator farm`
+#[derive(Debug, Clone, Serialize)]
+pub struct FarmInstance {
+    pub serial: String,
+    pub console_port: u16,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    /// Still running, or relaunched after its last exit
+    pub up: bool,
+}
+
+struct Slot {
+    info: FarmInstance,
+    child: Option<Child>,
+}
+
+/// Instances the host can run at once: the request, capped by CPUs and by
+/// available memory
+pub fn capacity(
+    requested: usize,
+    cpus: usize,
+    available_ram_mb: u64,
+    cpus_per_instance: usize,
+    ram_mb_per_instance: u64,
+) -> usize {
+    let by_cpu = cpus / cpus_per_instance.max(1);
+    let by_ram = (available_ram_mb / ram_mb_per_instance.max(1)) as usize;
+    requested.min(by_cpu).min(by_ram)
+}
+
+/// Logical CPUs and available memory in MB
+fn host_resources() -> (usize, u64) {
+    let cpus = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let mut system = System::new();
+    system.refresh_memory();
+    (cpus, system.available_memory() / (1024 * 1024))
+}
+
+pub struct EmulatorFarm {
+    options: FarmOptions,
+    emulator_path: PathBuf,
+    adb_path: PathBuf,
+    slots: Arc<Mutex<Vec<Slot>>>,
+    supervisor: Option<JoinHandle<()>>,
+}
+
+impl EmulatorFarm {
+    /// Launches the instances and waits for them to boot. Instances that don't boot
+    /// in time stay supervised; the farm fails only if none of them boots.
+    pub async fn start(config: &Config, options: FarmOptions) -> Result<Self> {
+        let adb_path = config
+            .android
+            .adb_path
+            .clone()
+            .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?;
+        let emulator_path = emulator::emulator_binary(&config.android)?;
+
+        let (cpus, ram_mb) = host_resources();
+        let count = capacity(
+            options.instances,
+            cpus,
+            ram_mb,
+            options.cpus_per_instance,
+            options.ram_mb_per_instance,
+        );
+        if count == 0 {
+            return Err(KMobileError::SimulatorStartError(format!(
+                "Not enough resources for one emulator: {cpus} CPUs and {ram_mb} MB available, \
+                 {} CPUs and {} MB needed",
+                options.cpus_per_instance, options.ram_mb_per_instance
+            ))
+            .into());
+        }
+        if count < options.instances {
+            warn!(
+                "Starting {} of {} emulators; {} CPUs and {} MB available",
+                count, options.instances, cpus, ram_mb
+            );
+        }
+
+        let running = emulator::running_emulators(&adb_path)
+            .await
+            .unwrap_or_default();
+        let ports = emulator::free_console_ports(&running, count);
+        if ports.len() < count {
+            return Err(KMobileError::SimulatorStartError(format!(
+                "Only {} free emulator console ports for {} instances",
+                ports.len(),
+                count
+            ))
+            .into());
+        }
+
+        // Instances join the farm as they launch, so a failed launch shuts down the
+        // ones before it instead of leaving their qemu running
+        let mut farm = Self {
+            options,
+            emulator_path,
+            adb_path,
+            slots: Arc::new(Mutex::new(Vec::with_capacity(count))),
+            supervisor: None,
+        };
+        for port in ports {
+            let child = match launch(&farm.emulator_path, &farm.options.avd, port) {
+                Ok(child) => child,
+                Err(e) => {
+                    farm.shutdown().await;
+                    return Err(e);
+                }
+            };
+            farm.slots.lock().await.push(Slot {
+                info: FarmInstance {
+                    serial: emulator::serial_for_port(port),
+                    console_port: port,
+                    pid: child.id(),
+                    restarts: 0,
+                    up: true,
+                },
+                child: Some(child),
+            });
+        }
+        info!(
+            "Launched {} instances of {}; waiting for them to boot",
+            count, farm.options.avd
+        );
+
+        farm.supervisor = Some(farm.supervise());
+
+        let serials = farm.serials().await;
+        let boots: Vec<_> = serials
+            .iter()
+            .map(|serial| {
+                let adb_path = farm.adb_path.clone();
+                let serial = serial.clone();
+                let timeout = farm.options.boot_timeout;
+                tokio::spawn(
+                    async move { emulator::wait_for_boot(&adb_path, &serial, timeout).await },
+                )
+            })
+            .collect();
+        let mut booted = 0;
+        for (serial, boot) in serials.iter().zip(boots) {
+            match boot.await {
+                Ok(Ok(())) => booted += 1,
+                Ok(Err(e)) => warn!("{} did not boot: {}", serial, e),
+                Err(e) => warn!("Waiting for {} to boot failed: {}", serial, e),
+            }
+        }
+        if booted == 0 {
+            farm.shutdown().await;
+            return Err(KMobileError::SimulatorStartError(format!(
+                "No instance of {} finished booting",
+                farm.options.avd
+            ))
+            .into());
+        }
+
+        Ok(farm)
+    }
+
+    pub async fn serials(&self) -> Vec<String> {
+        let slots = self.slots.lock().await;
+        slots.iter().map(|slot| slot.info.serial.clone()).collect()
+    }
+
+    pub async fn instances(&self) -> Vec<FarmInstance> {
+        let slots = self.slots.lock().await;
+        slots.iter().map(|slot| slot.info.clone()).collect()
+    }
+
+    /// The instances as a pool for `TestRunner::run_suite_on_pool`. Before each case
+    /// the runner waits for the instance to be booted, which covers restarts.
+    pub async fn pool(&self) -> DevicePool {
+        DevicePool::new(self.serials().await)
+            .with_boot_check(self.adb_path.clone(), self.options.boot_timeout)
+    }
+
+    /// Polls the instances and relaunches, on the same port, those whose emulator
+    /// exited, until they run out of restarts
+    fn supervise(&self) -> JoinHandle<()> {
+        let slots = self.slots.clone();
+        let emulator_path = self.emulator_path.clone();
+        let avd = self.options.avd.clone();
+        let max_restarts = self.options.max_restarts;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(SUPERVISE_INTERVAL).await;
+                let mut slots = slots.lock().await;
+                for slot in slots.iter_mut() {
+                    let Some(child) = &mut slot.child else {
+                        continue;
+                    };
+                    let status = match child.try_wait() {
+                        Ok(Some(status)) => status,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            warn!("Failed to check {}: {}", slot.info.serial, e);
+                            continue;
+                        }
+                    };
+
+                    slot.child = None;
+                    slot.info.pid = None;
+                    slot.info.up = false;
+                    if slot.info.restarts >= max_restarts {
+                        warn!(
+                            "{} exited ({}) and is out of restarts",
+                            slot.info.serial, status
+                        );
+                        continue;
+                    }
+
+                    warn!("{} exited ({}); restarting", slot.info.serial, status);
+                    match launch(&emulator_path, &avd, slot.info.console_port) {
+                        Ok(child) => {
+                            slot.info.pid = child.id();
+                            slot.info.restarts += 1;
+                            slot.info.up = true;
+                            slot.child = Some(child);
+                        }
+                        Err(e) => warn!("Failed to restart {}: {}", slot.info.serial, e),
+                    }
+                }
+            }
+        })
+    }
+
+    /// Stops supervising and shuts every instance down, killing those that don't
+    /// exit on `emu kill`
+    pub async fn shutdown(&mut self) {
+        if let Some(supervisor) = self.supervisor.take() {
+            supervisor.abort();
+        }
+        let mut slots = self.slots.lock().await;
+        for slot in slots.iter_mut() {
+            if let Some(mut child) = slot.child.take() {
+                debug!("Stopping {}", slot.info.serial);
+                // The launcher runs qemu as a child; killing only the launcher would
+                // leave qemu behind, so ask the console first
+                let _ = tokio::time::timeout(
+                    STOP_TIMEOUT,
+                    Command::new(&self.adb_path)
+                        .args(["-s", &slot.info.serial, "emu", "kill"])
+                        .kill_on_drop(true)
+                        .output(),
+                )
+                .await;
+                if tokio::time::timeout(STOP_TIMEOUT, child.wait())
+                    .await
+                    .is_err()
+                {
+                    if let Err(e) = child.kill().await {
+                        warn!("Failed to stop {}: {}", slot.info.serial, e);
+                    }
+                }
+            }
+            slot.info.pid = None;
+            slot.info.up = false;
+        }
+    }
+}
+
+impl Drop for EmulatorFarm {
+    /// Kills instances left running when the farm is dropped without `shutdown`, e.g.
+    /// on an error. `kill_on_drop` only reaches the launchers, not their qemu children.
+    fn drop(&mut self) {
+        if let Some(supervisor) = self.supervisor.take() {
+            supervisor.abort();
+        }
+        let Ok(mut slots) = self.slots.try_lock() else {
+            warn!("Farm instances are busy; leaving them running");
+            return;
+        };
+        if slots.iter().all(|slot| slot.child.is_none()) {
+            return;
+        }
+
+        let processes = emulator::emulator_processes();
+        for slot in slots.iter_mut() {
+            let Some(launcher) = slot.child.take() else {
+                continue;
+            };
+            warn!("Killing {}, which the farm left running", slot.info.serial);
+            if let Some(pid) =
+                emulator::find_emulator_pid(&processes, &self.options.avd, slot.info.console_port)
+            {
+                emulator::kill_process(pid);
+            }
+            drop(launcher);
+            slot.info.pid = None;
+            slot.info.up = false;
+        }
+    }
+}
+
+fn launch(emulator_path: &std::path::Path, avd: &str, port: u16) -> Result<Child> {
+    let child = Command::new(emulator_path)
+        .args(["-avd", avd])
+        .args(["-port", &port.to_string()])
+        .args(INSTANCE_ARGS)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            KMobileError::SimulatorStartError(format!(
+                "Failed to launch {}: {}",
+                emulator_path.display(),
+                e
+            ))
+        })?;
+    debug!(
+        "Launched {} as {} with PID {:?}",
+        avd,
+        emulator::serial_for_port(port),
+        child.id()
+    );
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity() {
+        // 16 CPUs and 32 GB fit eight 2-CPU, 3 GB instances by CPU, ten by memory
+        assert_eq!(capacity(4, 16, 32768, 2, 3072), 4);
+        assert_eq!(capacity(12, 16, 32768, 2, 3072), 8);
+        assert_eq!(capacity(12, 64, 8192, 2, 3072), 2);
+        assert_eq!(capacity(2, 1, 32768, 2, 3072), 0);
+        assert_eq!(capacity(2, 8, 32768, 0, 0), 2);
+    }
+}
@@ -1,8 +1,23 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
 
+use crate::emulator::{self, console::EmulatorConsole};
+use crate::error::KMobileError;
+
+pub mod route;
+
+use route::{PlaybackOptions, Route, RoutePlayer};
+
+/// Route fixes are at least 1 ms apart
+const MAX_ROUTE_FREQUENCY: f32 = 1000.0;
+
 /// Revolutionary Hardware Emulation System
 /// Simulates mobile device sensors and hardware interfaces
 /// Allows agents to control device hardware programmatically
@@ -28,6 +43,9 @@ pub struct HardwareEmulator {
 
     // Battery simulation
     battery_simulator: BatterySimulator,
+
+    // Routes playing in the background, by device
+    route_playbacks: HashMap<String, JoinHandle<()>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -90,7 +108,7 @@ pub struct NetworkConditions {
     pub jitter_ms: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NetworkType {
     Wifi,
     Cellular4G,
@@ -112,20 +130,22 @@ impl HardwareEmulator {
     pub async fn new() -> Result<Self> {
         info!("🎛️ Initializing Hardware Emulator");
 
+        let consoles = Arc::new(ConsoleConnections::default());
         Ok(Self {
             connected_devices: HashMap::new(),
-            gps_simulator: GpsSimulator::new(),
-            accelerometer_simulator: AccelerometerSimulator::new(),
-            gyroscope_simulator: GyroscopeSimulator::new(),
-            magnetometer_simulator: MagnetometerSimulator::new(),
-            proximity_simulator: ProximitySimulator::new(),
-            light_simulator: AmbientLightSimulator::new(),
+            gps_simulator: GpsSimulator::new(consoles.clone()),
+            accelerometer_simulator: AccelerometerSimulator::new(consoles.clone()),
+            gyroscope_simulator: GyroscopeSimulator::new(consoles.clone()),
+            magnetometer_simulator: MagnetometerSimulator::new(consoles.clone()),
+            proximity_simulator: ProximitySimulator::new(consoles.clone()),
+            light_simulator: AmbientLightSimulator::new(consoles),
             camera_simulator: CameraSimulator::new(),
             microphone_simulator: MicrophoneSimulator::new(),
             speaker_simulator: SpeakerSimulator::new(),
             haptic_simulator: HapticSimulator::new(),
             network_simulator: NetworkSimulator::new(),
             battery_simulator: BatterySimulator::new(),
+            route_playbacks: HashMap::new(),
         })
     }
 
@@ -442,17 +462,8 @@ impl HardwareEmulator {
             device_id
         );
 
-        // Start background tasks for continuous sensor simulation
-        let _device_id_clone = device_id.to_string();
-        tokio::spawn(async move {
-            // GPS simulation loop
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                // Update GPS with small variations
-                // TODO: Implement realistic GPS drift simulation
-            }
-        });
-
+        // Start background tasks for continuous sensor simulation. GPS moves only
+        // while a route plays; see `start_route`.
         let _device_id_clone = device_id.to_string();
         tokio::spawn(async move {
             // Accelerometer simulation loop
@@ -466,6 +477,99 @@ impl HardwareEmulator {
         Ok(())
     }
 
+    /// Plays a route on the device's GPS until its last point, or until cancelled
+    /// when looping. Fixes go out at the GPS sensor's update frequency with its noise
+    /// level, unless the options set their own.
+    pub async fn play_route(
+        &self,
+        device_id: &str,
+        route: &Route,
+        options: &PlaybackOptions,
+    ) -> Result<()> {
+        let player = RoutePlayer::new(route, options)?;
+        let (frequency, noise_level) = self.gps_playback_settings(device_id, options)?;
+        run_route(
+            &self.gps_simulator,
+            device_id,
+            &player,
+            frequency,
+            noise_level,
+        )
+        .await
+    }
+
+    /// Plays a route in the background, replacing any route already playing on the
+    /// device
+    pub fn start_route(
+        &mut self,
+        device_id: &str,
+        route: &Route,
+        options: &PlaybackOptions,
+    ) -> Result<()> {
+        let player = RoutePlayer::new(route, options)?;
+        let (frequency, noise_level) = self.gps_playback_settings(device_id, options)?;
+        let gps = self.gps_simulator.clone();
+        let device = device_id.to_string();
+        let playback = tokio::spawn(async move {
+            if let Err(e) = run_route(&gps, &device, &player, frequency, noise_level).await {
+                warn!("Route playback on {} stopped: {}", device, e);
+            }
+        });
+
+        if let Some(previous) = self.route_playbacks.insert(device_id.to_string(), playback) {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    /// Stops the route playing on the device; false if none was
+    pub fn stop_route(&mut self, device_id: &str) -> bool {
+        match self.route_playbacks.remove(device_id) {
+            Some(playback) => {
+                playback.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Update frequency and noise level for route playback, from the options or
+    /// the device's GPS sensor
+    fn gps_playback_settings(
+        &self,
+        device_id: &str,
+        options: &PlaybackOptions,
+    ) -> Result<(f32, f32)> {
+        let sensor = self
+            .connected_devices
+            .get(device_id)
+            .and_then(|state| state.sensors.get("gps").cloned())
+            .or_else(|| self.initialize_sensors().remove("gps"));
+        let frequency = options
+            .update_frequency
+            .or(sensor.as_ref().map(|sensor| sensor.update_frequency))
+            .unwrap_or(1.0);
+        let noise_level = options
+            .noise_level
+            .or(sensor.as_ref().map(|sensor| sensor.noise_level))
+            .unwrap_or(0.0);
+
+        if !(frequency > 0.0 && frequency <= MAX_ROUTE_FREQUENCY) {
+            return Err(KMobileError::InvalidInput(format!(
+                "GPS update frequency must be above 0 and at most {MAX_ROUTE_FREQUENCY} Hz, \
+                 got {frequency}"
+            ))
+            .into());
+        }
+        if !(noise_level >= 0.0 && noise_level.is_finite()) {
+            return Err(KMobileError::InvalidInput(format!(
+                "GPS noise level must be a distance in meters, got {noise_level}"
+            ))
+            .into());
+        }
+        Ok((frequency, noise_level))
+    }
+
     pub fn get_device_state(&self, device_id: &str) -> Option<&DeviceHardwareState> {
         self.connected_devices.get(device_id)
     }
@@ -477,89 +581,261 @@ impl HardwareEmulator {
     }
 }
 
+/// Open emulator console connections by console port, shared by the sensor
+/// simulators so values can be streamed over one connection per emulator
+#[derive(Debug, Default)]
+struct ConsoleConnections {
+    consoles: Mutex<HashMap<u16, EmulatorConsole>>,
+}
+
+impl ConsoleConnections {
+    async fn console(&self, device_id: &str) -> Result<MappedMutexGuard<'_, EmulatorConsole>> {
+        let port = emulator::console_port(device_id).ok_or_else(|| {
+            KMobileError::InvalidInput(format!(
+                "{device_id} is not an Android emulator; sensors can only be set on emulators"
+            ))
+        })?;
+
+        let mut consoles = self.consoles.lock().await;
+        if let Entry::Vacant(entry) = consoles.entry(port) {
+            entry.insert(EmulatorConsole::connect(port).await?);
+        }
+        Ok(MutexGuard::map(consoles, |consoles| {
+            consoles
+                .get_mut(&port)
+                .expect("console was connected above")
+        }))
+    }
+
+    async fn geo_fix(&self, device_id: &str, fix: [f64; 3]) -> Result<()> {
+        let result = self
+            .console(device_id)
+            .await?
+            .geo_fix(fix[0], fix[1], fix[2])
+            .await;
+        self.drop_if_disconnected(device_id, result).await
+    }
+
+    async fn set_sensor(&self, device_id: &str, sensor: &str, values: &[f64]) -> Result<()> {
+        let result = self
+            .console(device_id)
+            .await?
+            .set_sensor(sensor, values)
+            .await;
+        self.drop_if_disconnected(device_id, result).await
+    }
+
+    /// Forgets a connection that failed for any reason other than the console
+    /// rejecting the command, so the next value reconnects, e.g. after a restart
+    async fn drop_if_disconnected(&self, device_id: &str, result: Result<()>) -> Result<()> {
+        if let Err(e) = &result {
+            let rejected = matches!(KMobileError::find(e), Some(KMobileError::CommandError(_)));
+            if !rejected {
+                if let Some(port) = emulator::console_port(device_id) {
+                    self.consoles.lock().await.remove(&port);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn number(data: &serde_json::Value, sensor: &str, key: &str) -> Result<f64> {
+    data.get(key)
+        .and_then(serde_json::Value::as_f64)
+        .ok_or_else(|| {
+            KMobileError::InvalidInput(format!("{sensor} data needs a numeric '{key}'")).into()
+        })
+}
+
+fn vector(data: &serde_json::Value, sensor: &str) -> Result<[f64; 3]> {
+    Ok([
+        number(data, sensor, "x")?,
+        number(data, sensor, "y")?,
+        number(data, sensor, "z")?,
+    ])
+}
+
 // Sensor Simulators
-#[derive(Debug)]
-struct GpsSimulator;
+#[derive(Debug, Clone)]
+struct GpsSimulator {
+    consoles: Arc<ConsoleConnections>,
+}
 
 impl GpsSimulator {
-    fn new() -> Self {
-        Self
+    fn new(consoles: Arc<ConsoleConnections>) -> Self {
+        Self { consoles }
     }
 
+    /// `latitude` and `longitude` in degrees, optional `altitude` in meters. iOS
+    /// simulators get the position through `simctl location`.
     async fn inject_data(&self, device_id: &str, data: serde_json::Value) -> Result<()> {
         debug!("📍 GPS simulation for {}: {:?}", device_id, data);
-        // TODO: Send GPS data to device via ADB or similar
-        Ok(())
+        let latitude = number(&data, "gps", "latitude")?;
+        let longitude = number(&data, "gps", "longitude")?;
+        let altitude = data
+            .get("altitude")
+            .and_then(serde_json::Value::as_f64)
+            .unwrap_or(0.0);
+
+        if uuid::Uuid::parse_str(device_id).is_ok() {
+            return set_simulator_location(device_id, latitude, longitude).await;
+        }
+        self.consoles
+            .geo_fix(device_id, [latitude, longitude, altitude])
+            .await
     }
 }
 
+/// Sends a fix every `1 / frequency` seconds until the player is done
+async fn run_route(
+    gps: &GpsSimulator,
+    device_id: &str,
+    player: &RoutePlayer,
+    frequency: f32,
+    noise_level: f32,
+) -> Result<()> {
+    info!(
+        "🗺️ Playing route on {} ({:.0}s per pass, {} Hz)",
+        device_id,
+        player.duration().as_secs_f64(),
+        frequency
+    );
+    let mut ticks = tokio::time::interval(Duration::from_secs_f32(1.0 / frequency));
+    // Positions follow the schedule of the ticks, not when each one was handled
+    let start = ticks.tick().await;
+    let mut tick = start;
+
+    loop {
+        let elapsed = tick.duration_since(start);
+        let fix = player.fix_at(elapsed).with_noise(noise_level.into());
+        gps.inject_data(
+            device_id,
+            serde_json::json!({
+                "latitude": fix.latitude,
+                "longitude": fix.longitude,
+                "altitude": fix.altitude,
+            }),
+        )
+        .await?;
+        if player.is_finished(elapsed) {
+            return Ok(());
+        }
+        tick = ticks.tick().await;
+    }
+}
+
+async fn set_simulator_location(udid: &str, latitude: f64, longitude: f64) -> Result<()> {
+    let output = tokio::process::Command::new("xcrun")
+        .args(["simctl", "location", udid, "set"])
+        .arg(format!("{latitude},{longitude}"))
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(KMobileError::CommandError(format!(
+            "Failed to set simulator location: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
-struct AccelerometerSimulator;
+struct AccelerometerSimulator {
+    consoles: Arc<ConsoleConnections>,
+}
 
 impl AccelerometerSimulator {
-    fn new() -> Self {
-        Self
+    fn new(consoles: Arc<ConsoleConnections>) -> Self {
+        Self { consoles }
     }
 
+    /// `x`, `y` and `z` in m/s²
     async fn inject_data(&self, device_id: &str, data: serde_json::Value) -> Result<()> {
         debug!("📱 Accelerometer simulation for {}: {:?}", device_id, data);
-        Ok(())
+        let values = vector(&data, "accelerometer")?;
+        self.consoles
+            .set_sensor(device_id, "acceleration", &values)
+            .await
     }
 }
 
 #[derive(Debug)]
-struct GyroscopeSimulator;
+struct GyroscopeSimulator {
+    consoles: Arc<ConsoleConnections>,
+}
 
 impl GyroscopeSimulator {
-    fn new() -> Self {
-        Self
+    fn new(consoles: Arc<ConsoleConnections>) -> Self {
+        Self { consoles }
     }
 
+    /// `x`, `y` and `z` in rad/s
     async fn inject_data(&self, device_id: &str, data: serde_json::Value) -> Result<()> {
         debug!("🌪️ Gyroscope simulation for {}: {:?}", device_id, data);
-        Ok(())
+        let values = vector(&data, "gyroscope")?;
+        self.consoles
+            .set_sensor(device_id, "gyroscope", &values)
+            .await
     }
 }
 
 #[derive(Debug)]
-struct MagnetometerSimulator;
+struct MagnetometerSimulator {
+    consoles: Arc<ConsoleConnections>,
+}
 
 impl MagnetometerSimulator {
-    fn new() -> Self {
-        Self
+    fn new(consoles: Arc<ConsoleConnections>) -> Self {
+        Self { consoles }
     }
 
+    /// `x`, `y` and `z` in µT
     async fn inject_data(&self, device_id: &str, data: serde_json::Value) -> Result<()> {
         debug!("🧭 Magnetometer simulation for {}: {:?}", device_id, data);
-        Ok(())
+        let values = vector(&data, "magnetometer")?;
+        self.consoles
+            .set_sensor(device_id, "magnetic-field", &values)
+            .await
     }
 }
 
 #[derive(Debug)]
-struct ProximitySimulator;
+struct ProximitySimulator {
+    consoles: Arc<ConsoleConnections>,
+}
 
 impl ProximitySimulator {
-    fn new() -> Self {
-        Self
+    fn new(consoles: Arc<ConsoleConnections>) -> Self {
+        Self { consoles }
     }
 
+    /// `distance` in cm
     async fn inject_data(&self, device_id: &str, data: serde_json::Value) -> Result<()> {
         debug!("👋 Proximity simulation for {}: {:?}", device_id, data);
-        Ok(())
+        let distance = number(&data, "proximity", "distance")?;
+        self.consoles
+            .set_sensor(device_id, "proximity", &[distance])
+            .await
     }
 }
 
 #[derive(Debug)]
-struct AmbientLightSimulator;
+struct AmbientLightSimulator {
+    consoles: Arc<ConsoleConnections>,
+}
 
 impl AmbientLightSimulator {
-    fn new() -> Self {
-        Self
+    fn new(consoles: Arc<ConsoleConnections>) -> Self {
+        Self { consoles }
     }
 
+    /// `lux`
     async fn inject_data(&self, device_id: &str, data: serde_json::Value) -> Result<()> {
         debug!("💡 Light sensor simulation for {}: {:?}", device_id, data);
-        Ok(())
+        let lux = number(&data, "light", "lux")?;
+        self.consoles.set_sensor(device_id, "light", &[lux]).await
     }
 }
 
@@ -739,3 +1015,133 @@ pub enum HapticPattern {
     Heavy,
     Custom { duration_ms: u32, intensity: f32 },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::console::tests::fake_console;
+
+    #[tokio::test]
+    async fn test_sensor_input_reaches_the_emulator_console() {
+        let (port, commands) = fake_console(None).await;
+        let device_id = format!("emulator-{port}");
+        let emulator = HardwareEmulator::new().await.unwrap();
+
+        for (sensor, data) in [
+            (
+                "gps",
+                serde_json::json!({"latitude": 48.8584, "longitude": 2.2945}),
+            ),
+            (
+                "accelerometer",
+                serde_json::json!({"x": 0.0, "y": 9.8, "z": 0.0}),
+            ),
+            (
+                "magnetometer",
+                serde_json::json!({"x": 23.1, "y": -45.2, "z": 12.7}),
+            ),
+            (
+                "proximity",
+                serde_json::json!({"distance": 0.0, "near": true}),
+            ),
+            ("light", serde_json::json!({"lux": 40.0})),
+        ] {
+            emulator
+                .simulate_sensor_input(&device_id, sensor, data)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                "geo fix 2.2945 48.8584 0",
+                "sensor set acceleration 0:9.8:0",
+                "sensor set magnetic-field 23.1:-45.2:12.7",
+                "sensor set proximity 0",
+                "sensor set light 40",
+            ]
+        );
+
+        let error = emulator
+            .simulate_sensor_input("R58M12345", "light", serde_json::json!({"lux": 40.0}))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_playback_sends_fixes_to_the_last_point() {
+        let (port, commands) = fake_console(None).await;
+        let device_id = format!("emulator-{port}");
+        let emulator = HardwareEmulator::new().await.unwrap();
+        let route = Route::parse_geojson(
+            r#"{"type": "LineString", "coordinates": [[2.0, 48.0, 30], [2.0, 48.001, 40]]}"#,
+        )
+        .unwrap();
+
+        emulator
+            .play_route(
+                &device_id,
+                &route,
+                &PlaybackOptions {
+                    // About 111 m at 1 km/s takes a tenth of a second
+                    speed_mps: Some(1000.0),
+                    update_frequency: Some(100.0),
+                    noise_level: Some(0.0),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let commands = commands.lock().unwrap();
+        assert!(commands.len() > 2, "{commands:?}");
+        assert_eq!(commands[0], "geo fix 2 48 30");
+        assert_eq!(commands[commands.len() - 1], "geo fix 2 48.001 40");
+    }
+
+    #[tokio::test]
+    async fn test_route_playback_rejects_unusable_settings() {
+        let emulator = HardwareEmulator::new().await.unwrap();
+        let route = Route::parse_geojson(
+            r#"{"type": "LineString", "coordinates": [[2.0, 48.0], [2.0, 48.001]]}"#,
+        )
+        .unwrap();
+        let settings = [
+            PlaybackOptions {
+                rate: f64::NAN,
+                ..Default::default()
+            },
+            PlaybackOptions {
+                speed_mps: Some(f64::INFINITY),
+                ..Default::default()
+            },
+            PlaybackOptions {
+                update_frequency: Some(1e10),
+                ..Default::default()
+            },
+            PlaybackOptions {
+                noise_level: Some(f32::NAN),
+                ..Default::default()
+            },
+        ];
+
+        for options in settings {
+            let error = emulator
+                .play_route("emulator-5554", &route, &options)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(
+                    KMobileError::find(&error),
+                    Some(KMobileError::InvalidInput(_))
+                ),
+                "{options:?}: {error}"
+            );
+        }
+    }
+}
@@ -0,0 +1,590 @@
+//! GPS routes from GPX, KML or GeoJSON files, and their playback as a series of
+//! position fixes
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::KMobileError;
+use crate::utils::parse_attributes;
+
+/// Used when a route has no timestamps and no speed is given; about 50 km/h
+pub const DEFAULT_SPEED_MPS: f64 = 13.9;
+
+/// Mean Earth radius used for distances and noise offsets
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutePoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Meters above sea level
+    pub altitude: Option<f64>,
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl RoutePoint {
+    fn new(latitude: f64, longitude: f64, altitude: Option<f64>) -> Self {
+        Self {
+            latitude,
+            longitude,
+            altitude,
+            time: None,
+        }
+    }
+
+    /// Great-circle distance in meters
+    pub fn distance_to(&self, other: &RoutePoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub points: Vec<RoutePoint>,
+}
+
+impl Route {
+    /// Reads a `.gpx`, `.kml` or `.geojson`/`.json` file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let route = match extension.as_str() {
+            "gpx" => Self::parse_gpx(&content),
+            "kml" => Self::parse_kml(&content),
+            "geojson" | "json" => Self::parse_geojson(&content)?,
+            _ => {
+                return Err(KMobileError::InvalidInput(format!(
+                    "Unsupported route file {}; expected .gpx, .kml or .geojson",
+                    path.display()
+                ))
+                .into())
+            }
+        };
+        if route.points.is_empty() {
+            return Err(KMobileError::InvalidInput(format!(
+                "No route points in {}",
+                path.display()
+            ))
+            .into());
+        }
+        Ok(route)
+    }
+
+    /// Track points (`<trkpt>`) and route points (`<rtept>`) with their `<ele>` and
+    /// `<time>`
+    pub fn parse_gpx(xml: &str) -> Self {
+        let mut points = Vec::new();
+        let mut in_point = false;
+
+        for (name, tag, text) in xml_elements(xml) {
+            match name {
+                "trkpt" | "rtept" => {
+                    let attrs = parse_attributes(tag);
+                    let coordinate = |key: &str| {
+                        attrs
+                            .iter()
+                            .find(|(attr, _)| attr == key)
+                            .and_then(|(_, value)| value.trim().parse::<f64>().ok())
+                    };
+                    if let (Some(latitude), Some(longitude)) =
+                        (coordinate("lat"), coordinate("lon"))
+                    {
+                        points.push(RoutePoint::new(latitude, longitude, None));
+                        in_point = !tag.ends_with('/');
+                    }
+                }
+                "/trkpt" | "/rtept" => in_point = false,
+                "ele" if in_point => {
+                    if let Some(point) = points.last_mut() {
+                        point.altitude = text.trim().parse().ok();
+                    }
+                }
+                "time" if in_point => {
+                    if let Some(point) = points.last_mut() {
+                        point.time = parse_time(text);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self { points }
+    }
+
+    /// `<coordinates>` of placemarks, or the `<gx:coord>` and `<when>` pairs of a
+    /// `<gx:Track>`
+    pub fn parse_kml(xml: &str) -> Self {
+        let mut points = Vec::new();
+        let mut track = Vec::new();
+        let mut times = Vec::new();
+
+        for (name, _, text) in xml_elements(xml) {
+            match name {
+                "coordinates" => points.extend(text.split_whitespace().filter_map(|tuple| {
+                    let mut values = tuple.split(',').map(|value| value.trim().parse::<f64>());
+                    match (values.next(), values.next(), values.next()) {
+                        (Some(Ok(longitude)), Some(Ok(latitude)), altitude) => Some(
+                            RoutePoint::new(latitude, longitude, altitude.and_then(Result::ok)),
+                        ),
+                        _ => None,
+                    }
+                })),
+                "gx:coord" => {
+                    let values: Vec<f64> = text
+                        .split_whitespace()
+                        .filter_map(|value| value.parse().ok())
+                        .collect();
+                    if let [longitude, latitude, rest @ ..] = values.as_slice() {
+                        track.push(RoutePoint::new(
+                            *latitude,
+                            *longitude,
+                            rest.first().copied(),
+                        ));
+                    }
+                }
+                "when" => times.push(parse_time(text)),
+                _ => {}
+            }
+        }
+
+        if !track.is_empty() {
+            if times.len() == track.len() {
+                for (point, time) in track.iter_mut().zip(times) {
+                    point.time = time;
+                }
+            }
+            points.extend(track);
+        }
+        Self { points }
+    }
+
+    /// `LineString`, `MultiLineString`, `Point` and `MultiPoint` geometries, alone or
+    /// in features. A feature's `coordTimes` property, as written by common GPX
+    /// converters, supplies timestamps.
+    pub fn parse_geojson(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let mut points = Vec::new();
+        collect_geojson(&value, &mut points);
+        Ok(Self { points })
+    }
+
+    /// Length along the route in meters
+    pub fn length(&self) -> f64 {
+        self.points
+            .windows(2)
+            .map(|pair| pair[0].distance_to(&pair[1]))
+            .sum()
+    }
+
+    fn has_times(&self) -> bool {
+        self.points.len() > 1 && self.points.iter().all(|point| point.time.is_some())
+    }
+}
+
+/// `(name, tag, text)` for each tag, where `text` runs up to the next tag. Closing
+/// tags are named `/name`.
+fn xml_elements(xml: &str) -> Vec<(&str, &str, &str)> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('/');
+        elements.push((name, tag, text));
+    }
+
+    elements
+}
+
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text.trim())
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn collect_geojson(value: &serde_json::Value, points: &mut Vec<RoutePoint>) {
+    let position = |position: &serde_json::Value| {
+        let values = position.as_array()?;
+        Some(RoutePoint::new(
+            values.get(1)?.as_f64()?,
+            values.first()?.as_f64()?,
+            values.get(2).and_then(serde_json::Value::as_f64),
+        ))
+    };
+    let positions = |list: &serde_json::Value| -> Vec<RoutePoint> {
+        list.as_array()
+            .map(|list| list.iter().filter_map(position).collect())
+            .unwrap_or_default()
+    };
+    let coordinates = &value["coordinates"];
+
+    match value["type"].as_str() {
+        Some("FeatureCollection") => {
+            for feature in value["features"].as_array().into_iter().flatten() {
+                collect_geojson(feature, points);
+            }
+        }
+        Some("Feature") => {
+            let first = points.len();
+            collect_geojson(&value["geometry"], points);
+            let times: Vec<_> = value["properties"]["coordTimes"]
+                .as_array()
+                .map(|times| times.iter().flat_map(flatten_times).collect())
+                .unwrap_or_default();
+            if times.len() == points.len() - first {
+                for (point, time) in points[first..].iter_mut().zip(times) {
+                    point.time = time;
+                }
+            }
+        }
+        Some("GeometryCollection") => {
+            for geometry in value["geometries"].as_array().into_iter().flatten() {
+                collect_geojson(geometry, points);
+            }
+        }
+        Some("Point") => points.extend(position(coordinates)),
+        Some("LineString") | Some("MultiPoint") => points.extend(positions(coordinates)),
+        Some("MultiLineString") => {
+            for line in coordinates.as_array().into_iter().flatten() {
+                points.extend(positions(line));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `coordTimes` is a list of times, or a list of lists for multi-line geometries
+fn flatten_times(value: &serde_json::Value) -> Vec<Option<DateTime<Utc>>> {
+    match value {
+        serde_json::Value::Array(times) => times.iter().flat_map(flatten_times).collect(),
+        _ => vec![value.as_str().and_then(parse_time)],
+    }
+}
+
+/// How a route is played back
+#[derive(Debug, Clone)]
+pub struct PlaybackOptions {
+    /// Constant ground speed in m/s. Without it the route's timestamps set the pace,
+    /// or `DEFAULT_SPEED_MPS` if it has none.
+    pub speed_mps: Option<f64>,
+    /// Multiplies the pace, e.g. 10 plays a recorded drive ten times as fast
+    pub rate: f64,
+    /// Moves smoothly between points instead of jumping from one to the next
+    pub interpolate: bool,
+    /// Starts over from the first point after the last one
+    pub looping: bool,
+    /// Fixes per second; defaults to the GPS sensor's `update_frequency`
+    pub update_frequency: Option<f32>,
+    /// Random offset in meters added to each fix; defaults to the GPS sensor's
+    /// `noise_level`
+    pub noise_level: Option<f32>,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            speed_mps: None,
+            rate: 1.0,
+            interpolate: true,
+            looping: false,
+            update_frequency: None,
+            noise_level: None,
+        }
+    }
+}
+
+/// A position to send to the device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fix {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+impl Fix {
+    fn from_point(point: &RoutePoint) -> Self {
+        Self {
+            latitude: point.latitude,
+            longitude: point.longitude,
+            altitude: point.altitude.unwrap_or(0.0),
+        }
+    }
+
+    /// Moves the fix `radius * sqrt(u)` meters in direction `2π * v`, which spreads
+    /// fixes evenly over a disc of that radius for uniform `u` and `v`
+    pub fn offset(&self, radius: f64, u: f64, v: f64) -> Self {
+        let distance = radius * u.sqrt();
+        let bearing = 2.0 * std::f64::consts::PI * v;
+        let north = distance * bearing.cos();
+        let east = distance * bearing.sin();
+        Self {
+            latitude: self.latitude + (north / EARTH_RADIUS_M).to_degrees(),
+            longitude: self.longitude
+                + (east / (EARTH_RADIUS_M * self.latitude.to_radians().cos())).to_degrees(),
+            altitude: self.altitude,
+        }
+    }
+
+    /// A random offset of up to `noise_level` meters
+    pub fn with_noise(&self, noise_level: f64) -> Self {
+        if noise_level <= 0.0 {
+            return *self;
+        }
+        self.offset(noise_level, rand::random(), rand::random())
+    }
+}
+
+/// Where along a route the device is at any time since playback started
+#[derive(Debug, Clone)]
+pub struct RoutePlayer {
+    points: Vec<RoutePoint>,
+    /// Seconds from the start at which each point is reached
+    offsets: Vec<f64>,
+    interpolate: bool,
+    looping: bool,
+}
+
+impl RoutePlayer {
+    pub fn new(route: &Route, options: &PlaybackOptions) -> Result<Self> {
+        if route.points.is_empty() {
+            return Err(KMobileError::InvalidInput("Route has no points".to_string()).into());
+        }
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(options.rate) || options.speed_mps.is_some_and(|speed| !positive(speed)) {
+            return Err(KMobileError::InvalidInput(
+                "Playback speed and rate must be positive".to_string(),
+            )
+            .into());
+        }
+
+        let offsets = match (options.speed_mps, route.has_times()) {
+            (None, true) => {
+                let start = route.points[0].time.unwrap_or_default();
+                route
+                    .points
+                    .iter()
+                    .map(|point| {
+                        let since_start = point.time.unwrap_or_default() - start;
+                        since_start.num_milliseconds().max(0) as f64 / 1000.0 / options.rate
+                    })
+                    .collect()
+            }
+            (speed, _) => {
+                let speed = speed.unwrap_or(DEFAULT_SPEED_MPS) * options.rate;
+                let mut travelled = 0.0;
+                let mut offsets = vec![0.0];
+                for pair in route.points.windows(2) {
+                    travelled += pair[0].distance_to(&pair[1]);
+                    offsets.push(travelled / speed);
+                }
+                offsets
+            }
+        };
+
+        Ok(Self {
+            points: route.points.clone(),
+            offsets,
+            interpolate: options.interpolate,
+            looping: options.looping,
+        })
+    }
+
+    /// Time from the first point to the last
+    pub fn duration(&self) -> Duration {
+        // A very slow rate can stretch a route past what a Duration holds
+        Duration::try_from_secs_f64(self.offsets.last().copied().unwrap_or_default())
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Whether a non-looping playback has reached the last point
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        !self.looping && elapsed >= self.duration()
+    }
+
+    pub fn fix_at(&self, elapsed: Duration) -> Fix {
+        let total = self.offsets.last().copied().unwrap_or_default();
+        let mut time = elapsed.as_secs_f64();
+        if time >= total {
+            if !self.looping || total <= 0.0 {
+                return Fix::from_point(&self.points[self.points.len() - 1]);
+            }
+            time %= total;
+        }
+
+        // Index of the last point already reached
+        let index = self
+            .offsets
+            .partition_point(|offset| *offset <= time)
+            .saturating_sub(1);
+        let from = &self.points[index];
+        let Some(to) = self.points.get(index + 1).filter(|_| self.interpolate) else {
+            return Fix::from_point(from);
+        };
+
+        let span = self.offsets[index + 1] - self.offsets[index];
+        let t = if span > 0.0 {
+            (time - self.offsets[index]) / span
+        } else {
+            0.0
+        };
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Fix {
+            latitude: lerp(from.latitude, to.latitude),
+            longitude: lerp(from.longitude, to.longitude),
+            altitude: lerp(from.altitude.unwrap_or(0.0), to.altitude.unwrap_or(0.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPX: &str = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test">
+  <metadata><time>2024-01-01T00:00:00Z</time></metadata>
+  <trk><name>Morning run</name><trkseg>
+    <trkpt lat="48.0" lon="2.0"><ele>35.5</ele><time>2024-05-01T08:00:00Z</time></trkpt>
+    <trkpt lat="48.001" lon="2.0"><ele>36.0</ele><time>2024-05-01T08:00:10Z</time></trkpt>
+    <trkpt lat="48.002" lon="2.001"><time>2024-05-01T08:00:30Z</time></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    #[test]
+    fn test_parse_gpx() {
+        let route = Route::parse_gpx(GPX);
+        assert_eq!(route.points.len(), 3);
+        assert_eq!(route.points[0].altitude, Some(35.5));
+        assert_eq!(route.points[2].altitude, None);
+        assert_eq!(
+            route.points[1].time.unwrap().to_rfc3339(),
+            "2024-05-01T08:00:10+00:00"
+        );
+        // 0.001° of latitude is about 111 m
+        let length = route.points[0].distance_to(&route.points[1]);
+        assert!((length - 111.2).abs() < 0.5, "{length}");
+    }
+
+    #[test]
+    fn test_parse_kml_and_geojson() {
+        let kml = Route::parse_kml(
+            "<kml><Placemark><LineString><coordinates>
+               -122.08,37.42,10 -122.09,37.43,12
+             </coordinates></LineString></Placemark></kml>",
+        );
+        assert_eq!(
+            kml.points,
+            [
+                RoutePoint::new(37.42, -122.08, Some(10.0)),
+                RoutePoint::new(37.43, -122.09, Some(12.0)),
+            ]
+        );
+
+        let track = Route::parse_kml(
+            "<gx:Track><when>2024-05-01T08:00:00Z</when><when>2024-05-01T08:01:00Z</when>
+             <gx:coord>-122.08 37.42 10</gx:coord><gx:coord>-122.09 37.43 12</gx:coord></gx:Track>",
+        );
+        assert!(track.has_times());
+
+        let geojson = Route::parse_geojson(
+            r#"{"type": "FeatureCollection", "features": [{
+                "type": "Feature",
+                "properties": {"coordTimes": ["2024-05-01T08:00:00Z", "2024-05-01T08:01:00Z"]},
+                "geometry": {"type": "LineString", "coordinates": [[-122.08, 37.42], [-122.09, 37.43, 12]]}
+            }]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            geojson.points[1],
+            RoutePoint {
+                latitude: 37.43,
+                longitude: -122.09,
+                altitude: Some(12.0),
+                time: parse_time("2024-05-01T08:01:00Z"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_playback_follows_timestamps_and_rate() {
+        let route = Route::parse_gpx(GPX);
+        let player = RoutePlayer::new(&route, &PlaybackOptions::default()).unwrap();
+        assert_eq!(player.duration(), Duration::from_secs(30));
+
+        let halfway = player.fix_at(Duration::from_secs(5));
+        assert!((halfway.latitude - 48.0005).abs() < 1e-9);
+        assert!((halfway.altitude - 35.75).abs() < 1e-9);
+        assert_eq!(player.fix_at(Duration::from_secs(60)).longitude, 2.001);
+        assert!(player.is_finished(Duration::from_secs(30)));
+
+        let fast = RoutePlayer::new(
+            &route,
+            &PlaybackOptions {
+                rate: 10.0,
+                interpolate: false,
+                looping: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(fast.duration(), Duration::from_secs(3));
+        // Without interpolation the fix stays on the last point reached
+        assert_eq!(fast.fix_at(Duration::from_millis(500)).latitude, 48.0);
+        // Looping wraps around to the start
+        assert_eq!(fast.fix_at(Duration::from_millis(3500)).latitude, 48.0);
+        assert!(!fast.is_finished(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_playback_at_constant_speed() {
+        let route = Route::parse_gpx(GPX);
+        let player = RoutePlayer::new(
+            &route,
+            &PlaybackOptions {
+                speed_mps: Some(10.0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let expected = route.length() / 10.0;
+        assert!((player.duration().as_secs_f64() - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_noise_stays_within_radius() {
+        let fix = Fix {
+            latitude: 48.0,
+            longitude: 2.0,
+            altitude: 0.0,
+        };
+        let origin = RoutePoint::new(fix.latitude, fix.longitude, None);
+        for (u, v) in [(1.0, 0.0), (1.0, 0.25), (0.25, 0.6), (0.0, 0.9)] {
+            let moved = fix.offset(5.0, u, v);
+            let distance =
+                origin.distance_to(&RoutePoint::new(moved.latitude, moved.longitude, None));
+            assert!((distance - 5.0 * u.sqrt()).abs() < 0.01, "{distance}");
+        }
+    }
+}
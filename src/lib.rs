@@ -1,10 +1,21 @@
+#[cfg(feature = "api")]
+pub mod api;
+pub mod avd;
 pub mod cli;
 pub mod config;
+pub mod doctor;
+pub mod emulator;
 pub mod error;
+pub mod farm;
 pub mod mcp;
+pub mod output;
 pub mod project;
 pub mod testing;
+#[cfg(feature = "tui")]
+pub mod tui;
 pub mod utils;
+#[cfg(feature = "webdriver")]
+pub mod webdriver;
 
 // Legacy modules (kept for compatibility)
 pub mod device_basic;
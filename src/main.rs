@@ -1,12 +1,16 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use std::process::ExitCode;
 use tracing::info;
 
+use kmobile::cli::{handle_config_command, handle_doctor_command};
+use kmobile::config::{parse_override, ConfigCommands, ConfigLoader};
 use kmobile::device_basic::DeviceCommands;
+use kmobile::output::{self, Output, OutputFormat};
 use kmobile::project::ProjectCommands;
 use kmobile::simulator_basic::SimulatorCommands;
 use kmobile::testing::TestCommands;
-use kmobile::{Config, KMobileCli};
+use kmobile::KMobileCli;
 
 #[derive(Parser)]
 #[command(name = "kmobile")]
@@ -16,11 +20,24 @@ struct Args {
     #[command(subcommand)]
     command: Commands,
 
+    /// Project config file to use instead of ./kmobile.toml
     #[arg(long, global = true)]
     config: Option<String>,
 
+    /// Apply the `[profiles.<name>]` tables from the config files
+    #[arg(long, global = true, env = "KMOBILE_PROFILE")]
+    profile: Option<String>,
+
+    /// Override a setting, e.g. `--set mcp.port=4000`; may be repeated
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_override)]
+    overrides: Vec<(String, String)>,
+
     #[arg(long, global = true)]
     verbose: bool,
+
+    /// How results are printed; logs always go to stderr
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
 }
 
 #[derive(Subcommand)]
@@ -65,32 +82,99 @@ enum Commands {
         host: String,
     },
 
+    /// Start a W3C WebDriver endpoint for Appium-style clients
+    #[cfg(feature = "webdriver")]
+    Webdriver {
+        #[arg(long, default_value = "4723")]
+        port: u16,
+        #[arg(long, default_value = "localhost")]
+        host: String,
+        #[arg(long, help = "Device used when the session doesn't request one")]
+        device: Option<String>,
+    },
+
     /// Start MCP server
     Mcp {
         #[arg(long, help = "MCP server configuration")]
         config: Option<String>,
     },
 
+    /// Check SDKs, tools and emulator acceleration
+    Doctor {
+        #[arg(
+            long,
+            help = "Write discovered tool paths to the config without asking"
+        )]
+        write_config: bool,
+        #[arg(long, help = "Write to the user config instead of kmobile.toml")]
+        user: bool,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
     /// Start TUI interface
     Tui,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let args = Args::parse();
+    let output = Output::new(args.output);
 
+    match run(args, output).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            output.error(&e);
+            ExitCode::from(output::exit_code(&e))
+        }
+    }
+}
+
+async fn run(args: Args, output: Output) -> Result<()> {
     // Initialize tracing
-    let subscriber = tracing_subscriber::fmt()
-        .with_env_filter(if args.verbose { "debug" } else { "info" })
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
+    let subscriber =
+        tracing_subscriber::fmt().with_env_filter(if args.verbose { "debug" } else { "info" });
+    if matches!(args.command, Commands::Tui) {
+        // Log lines would draw over the dashboard, so the TUI logs to a file
+        let log_file = std::fs::File::create(std::env::temp_dir().join("kmobile-tui.log"))?;
+        tracing::subscriber::set_global_default(
+            subscriber
+                .with_ansi(false)
+                .with_writer(std::sync::Mutex::new(log_file))
+                .finish(),
+        )?;
+    } else {
+        tracing::subscriber::set_global_default(subscriber.with_writer(std::io::stderr).finish())?;
+    }
 
     // Load configuration
-    let config = Config::load(args.config.as_deref())?;
+    let mut loader = ConfigLoader::new().with_profile(args.profile);
+    if let Some(path) = &args.config {
+        loader = loader.with_project_file(path);
+    }
+    for (key, value) in args.overrides {
+        loader = loader.with_override(key, value);
+    }
+    let loaded = loader.load()?;
+    let config = loaded.config.clone();
     info!("KMobile started with config: {}", config.name());
 
+    // These must work when the SDK setup is broken, so they run before the managers
+    // are created
+    match args.command {
+        Commands::Config { command } => return handle_config_command(&loaded, command, &output),
+        Commands::Doctor { write_config, user } => {
+            return handle_doctor_command(&loaded, write_config, user, &output).await
+        }
+        _ => {}
+    }
+
     // Initialize CLI
-    let cli = KMobileCli::new(config).await?;
+    let cli = KMobileCli::new(config, output).await?;
 
     match args.command {
         Commands::Init { name, template } => {
@@ -111,12 +195,19 @@ async fn main() -> Result<()> {
         Commands::Serve { port, host } => {
             cli.start_api_server(&host, port).await?;
         }
+        #[cfg(feature = "webdriver")]
+        Commands::Webdriver { port, host, device } => {
+            cli.start_webdriver_server(&host, port, device).await?;
+        }
         Commands::Mcp { config } => {
             cli.start_mcp_server(config.as_deref()).await?;
         }
         Commands::Tui => {
             cli.start_tui().await?;
         }
+        Commands::Config { .. } | Commands::Doctor { .. } => {
+            unreachable!("handled before the CLI is created")
+        }
     }
 
     Ok(())
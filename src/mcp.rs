@@ -2,15 +2,18 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
 use crate::config::Config;
 use crate::device_basic::DeviceManager;
+use crate::emulator::DEFAULT_BOOT_TIMEOUT;
 use crate::error::KMobileError;
 use crate::project::ProjectManager;
 use crate::simulator_basic::SimulatorManager;
-use crate::testing::TestRunner;
+use crate::testing::{ReporterKind, Shard, TestFilter, TestRunOptions, TestRunner};
+use crate::utils::{retry_with_backoff, DEVICE_RETRIES, DEVICE_RETRY_DELAY};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpTool {
@@ -60,6 +63,31 @@ pub struct McpError {
     pub data: Option<serde_json::Value>,
 }
 
+impl McpError {
+    /// Maps an error to its JSON-RPC form. Errors that aren't a `KMobileError` are
+    /// reported as internal errors.
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        match KMobileError::find(error) {
+            Some(kind) => Self {
+                code: kind.json_rpc_code(),
+                message: error.to_string(),
+                data: Some(serde_json::json!({
+                    "code": kind.error_code(),
+                    "recoverable": kind.is_recoverable(),
+                })),
+            },
+            None => Self {
+                code: -32603,
+                message: error.to_string(),
+                data: Some(serde_json::json!({
+                    "code": "UNKNOWN_ERROR",
+                    "recoverable": false,
+                })),
+            },
+        }
+    }
+}
+
 pub struct McpServer {
     config: Config,
     #[allow(dead_code)]
@@ -181,6 +209,14 @@ impl McpServer {
                         "simulator_id": {
                             "type": "string",
                             "description": "Simulator ID to start"
+                        },
+                        "wait": {
+                            "type": "boolean",
+                            "description": "Return once the simulator has booted and is unlocked"
+                        },
+                        "timeout": {
+                            "type": "integer",
+                            "description": "Seconds to wait for the boot (default 300)"
                         }
                     },
                     "required": ["simulator_id"]
@@ -252,6 +288,36 @@ impl McpServer {
                         "device_id": {
                             "type": "string",
                             "description": "Device ID to run tests on (optional)"
+                        },
+                        "reporters": {
+                            "type": "array",
+                            "items": {
+                                "type": "string",
+                                "enum": ["json", "junit", "html", "tap"]
+                            },
+                            "description": "Report formats to write (optional, defaults to json)"
+                        },
+                        "grep": {
+                            "type": "string",
+                            "description": "Only run cases whose name or description contains this text (optional)"
+                        },
+                        "tags": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Only run cases with one of these tags (optional)"
+                        },
+                        "exclude_tags": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Skip cases with any of these tags (optional)"
+                        },
+                        "shard": {
+                            "type": "string",
+                            "description": "Slice of the suite to run, e.g. \"2/4\" (optional)"
+                        },
+                        "only_failed": {
+                            "type": "boolean",
+                            "description": "Rerun only the cases that failed last time (optional)"
                         }
                     }
                 }),
@@ -262,13 +328,21 @@ impl McpServer {
             "test_record".to_string(),
             McpTool {
                 name: "test_record".to_string(),
-                description: "Record a test".to_string(),
+                description: "Record a test from live touch input on an Android device".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
                         "output": {
                             "type": "string",
                             "description": "Output file path"
+                        },
+                        "device_id": {
+                            "type": "string",
+                            "description": "Android device to record from (optional)"
+                        },
+                        "duration_secs": {
+                            "type": "integer",
+                            "description": "How long to record for (default: 30)"
                         }
                     },
                     "required": ["output"]
@@ -383,9 +457,21 @@ impl McpServer {
         }
     }
 
+    /// Dispatches a request. Failures come back as a JSON-RPC error on the response
+    /// rather than as `Err`, keeping the `KMobileError` code in `error.data.code`.
     pub async fn handle_request(&self, request: McpRequest) -> Result<McpResponse> {
         debug!("Handling MCP request: {}", request.method);
 
+        match self.dispatch(request).await {
+            Ok(response) => Ok(response),
+            Err(error) => Ok(McpResponse {
+                result: None,
+                error: Some(McpError::from_error(&error)),
+            }),
+        }
+    }
+
+    async fn dispatch(&self, request: McpRequest) -> Result<McpResponse> {
         match request.method.as_str() {
             "tools/list" => self.handle_tools_list().await,
             "tools/call" => self.handle_tool_call(request.params).await,
@@ -419,7 +505,7 @@ impl McpServer {
         let tool_name = params
             .get("name")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Tool name not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Tool name not provided".to_string()))?;
 
         let default_args = serde_json::json!({});
         let arguments = params.get("arguments").unwrap_or(&default_args);
@@ -448,10 +534,7 @@ impl McpServer {
 
     async fn handle_device_list(&self) -> Result<McpResponse> {
         let device_manager = self.device_manager.read().await;
-        let devices = device_manager
-            .list_devices()
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        let devices = device_manager.list_devices().await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -465,13 +548,15 @@ impl McpServer {
         let device_id = arguments
             .get("device_id")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Device ID not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Device ID not provided".to_string()))?;
 
         let device_manager = self.device_manager.read().await;
-        device_manager
-            .connect_device(device_id)
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        retry_with_backoff(
+            || device_manager.connect_device(device_id),
+            DEVICE_RETRIES,
+            DEVICE_RETRY_DELAY,
+        )
+        .await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -486,18 +571,20 @@ impl McpServer {
         let device_id = arguments
             .get("device_id")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Device ID not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Device ID not provided".to_string()))?;
 
         let app_path = arguments
             .get("app_path")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("App path not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("App path not provided".to_string()))?;
 
         let device_manager = self.device_manager.read().await;
-        device_manager
-            .install_app(device_id, app_path)
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        retry_with_backoff(
+            || device_manager.install_app(device_id, app_path),
+            DEVICE_RETRIES,
+            DEVICE_RETRY_DELAY,
+        )
+        .await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -510,10 +597,7 @@ impl McpServer {
 
     async fn handle_simulator_list(&self) -> Result<McpResponse> {
         let simulator_manager = self.simulator_manager.read().await;
-        let simulators = simulator_manager
-            .list_simulators()
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        let simulators = simulator_manager.list_simulators().await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -527,13 +611,24 @@ impl McpServer {
         let simulator_id = arguments
             .get("simulator_id")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Simulator ID not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Simulator ID not provided".to_string()))?;
 
         let simulator_manager = self.simulator_manager.read().await;
-        simulator_manager
-            .start_simulator(simulator_id)
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        retry_with_backoff(
+            || simulator_manager.start_simulator(simulator_id),
+            DEVICE_RETRIES,
+            DEVICE_RETRY_DELAY,
+        )
+        .await?;
+        if arguments.get("wait").and_then(|v| v.as_bool()) == Some(true) {
+            let timeout = arguments
+                .get("timeout")
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_BOOT_TIMEOUT, Duration::from_secs);
+            simulator_manager
+                .wait_until_booted(simulator_id, timeout)
+                .await?;
+        }
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -548,13 +643,15 @@ impl McpServer {
         let simulator_id = arguments
             .get("simulator_id")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Simulator ID not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Simulator ID not provided".to_string()))?;
 
         let simulator_manager = self.simulator_manager.read().await;
-        simulator_manager
-            .stop_simulator(simulator_id)
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        retry_with_backoff(
+            || simulator_manager.stop_simulator(simulator_id),
+            DEVICE_RETRIES,
+            DEVICE_RETRY_DELAY,
+        )
+        .await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -569,10 +666,7 @@ impl McpServer {
         let target = arguments.get("target").and_then(|v| v.as_str());
 
         let project_manager = self.project_manager.read().await;
-        project_manager
-            .build_project(target)
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        project_manager.build_project(target).await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -585,10 +679,7 @@ impl McpServer {
 
     async fn handle_project_status(&self) -> Result<McpResponse> {
         let project_manager = self.project_manager.read().await;
-        let status = project_manager
-            .get_project_status()
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        let status = project_manager.get_project_status().await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -603,16 +694,51 @@ impl McpServer {
 
         let device_id = arguments.get("device_id").and_then(|v| v.as_str());
 
+        let mut options = TestRunOptions::default();
+        if let Some(reporters) = arguments.get("reporters") {
+            options.reporters = serde_json::from_value::<Vec<ReporterKind>>(reporters.clone())
+                .map_err(|e| KMobileError::InvalidInput(format!("Invalid reporters: {e}")))?;
+        }
+
+        let strings = |key: &str| -> Vec<String> {
+            arguments
+                .get(key)
+                .and_then(|v| v.as_array())
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        options.filter = TestFilter {
+            grep: arguments
+                .get("grep")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            tags: strings("tags"),
+            exclude_tags: strings("exclude_tags"),
+            shard: arguments
+                .get("shard")
+                .and_then(|v| v.as_str())
+                .map(|shard| shard.parse::<Shard>())
+                .transpose()
+                .map_err(|e| KMobileError::InvalidInput(format!("Invalid shard: {e}")))?,
+            only_failed: arguments
+                .get("only_failed")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        };
+
         let test_runner = self.test_runner.read().await;
-        test_runner
-            .run_tests(suite, device_id)
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+        let report = test_runner.run_tests(suite, device_id, &options).await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
-                "success": true,
-                "message": "Tests completed successfully"
+                "success": report.summary.failed + report.summary.timeout == 0,
+                "message": "Tests completed",
+                "report": report
             })),
             error: None,
         })
@@ -622,13 +748,22 @@ impl McpServer {
         let output = arguments
             .get("output")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Output path not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Output path not provided".to_string()))?;
+
+        let device_id = arguments.get("device_id").and_then(|v| v.as_str());
+        let duration_secs = arguments
+            .get("duration_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(30);
 
         let test_runner = self.test_runner.read().await;
         test_runner
-            .record_test(output)
-            .await
-            .map_err(|e| KMobileError::McpServerError(e.to_string()))?;
+            .record_test(
+                output,
+                device_id,
+                Some(std::time::Duration::from_secs(duration_secs)),
+            )
+            .await?;
 
         Ok(McpResponse {
             result: Some(serde_json::json!({
@@ -654,7 +789,7 @@ impl McpServer {
         let uri = params
             .get("uri")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Resource URI not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Resource URI not provided".to_string()))?;
 
         // TODO: Implement actual resource reading based on URI
         warn!("Resource reading not yet implemented for URI: {}", uri);
@@ -682,7 +817,7 @@ impl McpServer {
         let name = params
             .get("name")
             .and_then(|v| v.as_str())
-            .ok_or_else(|| KMobileError::McpServerError("Prompt name not provided".to_string()))?;
+            .ok_or_else(|| KMobileError::InvalidInput("Prompt name not provided".to_string()))?;
 
         if let Some(prompt) = self.prompts.get(name) {
             Ok(McpResponse {
@@ -704,3 +839,27 @@ impl McpServer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mcp_error_keeps_error_code() {
+        let error: anyhow::Error =
+            KMobileError::InvalidInput("Device ID not provided".into()).into();
+        let mcp_error = McpError::from_error(&error);
+        assert_eq!(mcp_error.code, -32602);
+        assert_eq!(mcp_error.data.unwrap()["code"], "INVALID_INPUT");
+
+        let error: anyhow::Error = KMobileError::DeviceNotFound("emulator-5554".into()).into();
+        let mcp_error = McpError::from_error(&error.context("device_install"));
+        assert_eq!(mcp_error.code, -32004);
+        let data = mcp_error.data.unwrap();
+        assert_eq!(data["code"], "DEVICE_NOT_FOUND");
+        assert_eq!(data["recoverable"], false);
+
+        let mcp_error = McpError::from_error(&anyhow::anyhow!("boom"));
+        assert_eq!(mcp_error.code, -32603);
+    }
+}
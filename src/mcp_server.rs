@@ -3,6 +3,7 @@ use clap::Parser;
 use std::io::{self, BufRead, BufReader, Write};
 use tracing::{debug, error, info};
 
+use kmobile::mcp::McpError;
 use kmobile::{Config, McpRequest, McpServer};
 
 #[derive(Parser)]
@@ -73,11 +74,7 @@ async fn main() -> Result<()> {
                             Err(e) => {
                                 error!("Error handling request: {}", e);
                                 let error_response = serde_json::json!({
-                                    "error": {
-                                        "code": -32603,
-                                        "message": "Internal error",
-                                        "data": e.to_string()
-                                    }
+                                    "error": McpError::from_error(&e)
                                 });
                                 writeln!(stdout, "{}", serde_json::to_string(&error_response)?)?;
                                 stdout.flush()?;
@@ -0,0 +1,510 @@
+//! Rendering of command results for the global `--output` flag
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::config::{ConfigEntry, ValidationIssue};
+use crate::device_basic::Device;
+use crate::doctor::{Check, CheckStatus};
+use crate::emulator::Snapshot;
+use crate::error::KMobileError;
+use crate::farm::FarmInstance;
+use crate::project::ProjectStatus;
+use crate::simulator_basic::Simulator;
+use crate::testing::history::CaseStats;
+use crate::testing::{TestReport, TestResult};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    /// Aligned columns with headers, for people
+    #[default]
+    Table,
+    /// Tab-separated rows without headers, for shell pipelines
+    Plain,
+}
+
+/// A record that can be shown as a table row in `table` and `plain` output
+pub trait Tabular: Serialize {
+    fn headers() -> &'static [&'static str];
+
+    fn row(&self) -> Vec<String>;
+}
+
+/// Result of a command that changes something rather than listing data
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub message: String,
+    /// Files or items the command produced
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl Outcome {
+    pub fn new(action: &str, target: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            action: action.to_string(),
+            target: target.map(String::from),
+            message: message.into(),
+            details: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_details(mut self, details: Vec<String>) -> Self {
+        self.details = details;
+        self
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+}
+
+/// Prints command results in the selected format
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Output {
+    format: OutputFormat,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Prints `title` above tables; other formats stay pure data
+    pub fn heading(&self, title: &str) {
+        if self.format == OutputFormat::Table {
+            println!("{title}");
+        }
+    }
+
+    pub fn list<T: Tabular>(&self, items: &[T]) -> Result<()> {
+        print!("{}", self.render_list(items)?);
+        Ok(())
+    }
+
+    pub fn item<T: Tabular>(&self, item: &T) -> Result<()> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => print!("{}", self.serialize(item)?),
+            _ => print!("{}", self.render_rows(std::slice::from_ref(item))),
+        }
+        Ok(())
+    }
+
+    /// Prints a whole document such as the effective config; TOML for people
+    pub fn document<T: Serialize>(&self, value: &T) -> Result<()> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => print!("{}", self.serialize(value)?),
+            _ => print!("{}", toml::to_string_pretty(value)?),
+        }
+        Ok(())
+    }
+
+    pub fn outcome(&self, outcome: &Outcome) -> Result<()> {
+        print!("{}", self.render_outcome(outcome)?);
+        Ok(())
+    }
+
+    pub fn report(&self, report: &TestReport) -> Result<()> {
+        print!("{}", self.render_report(report)?);
+        Ok(())
+    }
+
+    /// Writes a failed command's error to stderr, structured for `json` and `yaml`
+    pub fn error(&self, error: &anyhow::Error) {
+        let code = KMobileError::find(error).map_or("UNKNOWN_ERROR", KMobileError::error_code);
+        let body = serde_json::json!({
+            "error": { "code": code, "message": error.to_string() }
+        });
+        match self.format {
+            OutputFormat::Json => eprintln!("{body:#}"),
+            OutputFormat::Yaml => eprint!("{}", serde_yaml::to_string(&body).unwrap_or_default()),
+            OutputFormat::Table | OutputFormat::Plain => eprintln!("Error: {error:#}"),
+        }
+    }
+
+    pub fn render_list<T: Tabular>(&self, items: &[T]) -> Result<String> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => self.serialize(&items),
+            _ => Ok(self.render_rows(items)),
+        }
+    }
+
+    pub fn render_outcome(&self, outcome: &Outcome) -> Result<String> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => self.serialize(outcome),
+            OutputFormat::Table => {
+                let mut text = String::new();
+                for warning in &outcome.warnings {
+                    text.push_str(&format!("⚠️  {warning}\n"));
+                }
+                for detail in &outcome.details {
+                    text.push_str(&format!("  {detail}\n"));
+                }
+                text.push_str(&format!("✅ {}\n", outcome.message));
+                Ok(text)
+            }
+            OutputFormat::Plain => {
+                let mut lines: Vec<&str> = outcome.details.iter().map(String::as_str).collect();
+                if lines.is_empty() {
+                    lines.push(&outcome.message);
+                }
+                Ok(lines.iter().map(|line| format!("{line}\n")).collect())
+            }
+        }
+    }
+
+    pub fn render_report(&self, report: &TestReport) -> Result<String> {
+        match self.format {
+            OutputFormat::Json | OutputFormat::Yaml => self.serialize(report),
+            OutputFormat::Plain => Ok(self.render_rows(&report.results)),
+            OutputFormat::Table => {
+                let summary = &report.summary;
+                let mut text = format!("📊 Test Summary for '{}':\n", report.suite_name);
+                text.push_str(&self.render_rows(&report.results));
+                text.push_str(&format!(
+                    "   Total: {}  ✅ Passed: {}  ❌ Failed: {}  ⏭️  Skipped: {}  ⏱️  Timeout: {}\n",
+                    summary.total, summary.passed, summary.failed, summary.skipped, summary.timeout
+                ));
+                Ok(text)
+            }
+        }
+    }
+
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<String> {
+        Ok(match self.format {
+            OutputFormat::Yaml => serde_yaml::to_string(value)?,
+            _ => format!("{}\n", serde_json::to_string_pretty(value)?),
+        })
+    }
+
+    fn render_rows<T: Tabular>(&self, items: &[T]) -> String {
+        let rows: Vec<Vec<String>> = items.iter().map(Tabular::row).collect();
+        if self.format == OutputFormat::Plain {
+            return rows
+                .iter()
+                .map(|row| format!("{}\n", row.join("\t")))
+                .collect();
+        }
+
+        let headers = T::headers();
+        let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let line = |cells: Vec<&str>| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            format!("  {}\n", padded.join("  ").trim_end())
+        };
+
+        let mut text = line(headers.to_vec());
+        for row in &rows {
+            text.push_str(&line(row.iter().map(String::as_str).collect()));
+        }
+        text
+    }
+}
+
+/// Process exit code for a failed command. Errors that aren't a `KMobileError` exit with 1.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    KMobileError::find(error).map_or(1, KMobileError::exit_code)
+}
+
+impl Tabular for Device {
+    fn headers() -> &'static [&'static str] {
+        &["ID", "NAME", "PLATFORM", "VERSION", "STATUS"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            self.platform.clone(),
+            self.version.clone(),
+            format!("{:?}", self.status),
+        ]
+    }
+}
+
+impl Tabular for Simulator {
+    fn headers() -> &'static [&'static str] {
+        &["ID", "NAME", "PLATFORM", "VERSION", "TYPE", "STATUS"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            self.platform.clone(),
+            self.version.clone(),
+            self.device_type.clone(),
+            format!("{:?}", self.status),
+        ]
+    }
+}
+
+impl Tabular for Snapshot {
+    fn headers() -> &'static [&'static str] {
+        &["NAME", "SIZE", "DATE", "VM CLOCK"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.size.clone().unwrap_or_default(),
+            self.date.clone().unwrap_or_default(),
+            self.vm_clock.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl Tabular for FarmInstance {
+    fn headers() -> &'static [&'static str] {
+        &["SERIAL", "PORT", "PID", "RESTARTS", "STATE"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.serial.clone(),
+            self.console_port.to_string(),
+            self.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+            self.restarts.to_string(),
+            if self.up { "running" } else { "down" }.to_string(),
+        ]
+    }
+}
+
+impl Tabular for ProjectStatus {
+    fn headers() -> &'static [&'static str] {
+        &["NAME", "PLATFORM", "PATH", "BUILD", "TESTS", "DEPENDENCIES"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.platform.clone(),
+            self.path.display().to_string(),
+            format!("{:?}", self.build_status),
+            format!("{:?}", self.tests_status),
+            self.dependencies.len().to_string(),
+        ]
+    }
+}
+
+impl Tabular for TestResult {
+    fn headers() -> &'static [&'static str] {
+        &["TEST", "STATUS", "DURATION", "ERROR"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.test_name.clone(),
+            format!("{:?}", self.status),
+            format!("{:.2}s", self.duration.as_secs_f64()),
+            self.error_message
+                .as_deref()
+                .unwrap_or_default()
+                .replace('\n', " "),
+        ]
+    }
+}
+
+impl Tabular for ConfigEntry {
+    fn headers() -> &'static [&'static str] {
+        &["KEY", "VALUE", "ORIGIN"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let value = match &self.value {
+            serde_json::Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        vec![self.key.clone(), value, self.origin.clone()]
+    }
+}
+
+impl Tabular for ValidationIssue {
+    fn headers() -> &'static [&'static str] {
+        &["SEVERITY", "KEY", "MESSAGE"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            format!("{:?}", self.severity),
+            self.key.clone(),
+            self.message.clone(),
+        ]
+    }
+}
+
+impl Tabular for Check {
+    fn headers() -> &'static [&'static str] {
+        &["CHECK", "STATUS", "VERSION", "MINIMUM", "DETAILS"]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let status = match self.status {
+            CheckStatus::Ok => "✅ ok",
+            CheckStatus::Warning => "⚠️  warning",
+            CheckStatus::Error => "❌ error",
+            CheckStatus::Skipped => "- skipped",
+        };
+        let details = match &self.path {
+            Some(path) => format!("{} ({})", self.message, path.display()),
+            None => self.message.clone(),
+        };
+        vec![
+            self.name.clone(),
+            status.to_string(),
+            self.version.clone().unwrap_or_default(),
+            self.minimum.clone().unwrap_or_default(),
+            details,
+        ]
+    }
+}
+
+impl Tabular for CaseStats {
+    fn headers() -> &'static [&'static str] {
+        &[
+            "TEST",
+            "RUNS",
+            "PASS %",
+            "AVG TIME",
+            "TREND",
+            "FLAKINESS",
+            "FLAKY",
+        ]
+    }
+
+    fn row(&self) -> Vec<String> {
+        let flaky = if self.newly_flaky {
+            "new"
+        } else if self.is_flaky() {
+            "yes"
+        } else {
+            "no"
+        };
+        vec![
+            format!("{}/{}", self.suite_name, self.name),
+            self.runs.to_string(),
+            format!("{:.1}", self.pass_rate * 100.0),
+            format!("{:.2}s", self.mean_duration.as_secs_f64()),
+            self.duration_trend
+                .map(|trend| format!("{:+.0}%", trend * 100.0))
+                .unwrap_or_else(|| "-".to_string()),
+            format!("{:.2}", self.flakiness),
+            flaky.to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_basic::DeviceStatus;
+    use std::collections::HashMap;
+
+    fn device(id: &str, name: &str) -> Device {
+        Device {
+            id: id.to_string(),
+            name: name.to_string(),
+            platform: "android".to_string(),
+            version: "14".to_string(),
+            status: DeviceStatus::Connected,
+            capabilities: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_device_list() {
+        let devices = [
+            device("emulator-5554", "sdk_gphone64"),
+            device("R58M", "Galaxy"),
+        ];
+
+        let table = Output::new(OutputFormat::Table)
+            .render_list(&devices)
+            .unwrap();
+        assert_eq!(
+            table,
+            "  ID             NAME          PLATFORM  VERSION  STATUS\n  \
+             emulator-5554  sdk_gphone64  android   14       Connected\n  \
+             R58M           Galaxy        android   14       Connected\n"
+        );
+
+        let plain = Output::new(OutputFormat::Plain)
+            .render_list(&devices)
+            .unwrap();
+        assert_eq!(
+            plain.lines().next(),
+            Some("emulator-5554\tsdk_gphone64\tandroid\t14\tConnected")
+        );
+
+        let json: serde_json::Value = serde_json::from_str(
+            &Output::new(OutputFormat::Json)
+                .render_list(&devices)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json[1]["id"], "R58M");
+
+        let yaml: serde_yaml::Value = serde_yaml::from_str(
+            &Output::new(OutputFormat::Yaml)
+                .render_list(&devices)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(yaml[0]["status"], "Connected");
+    }
+
+    #[test]
+    fn test_render_outcome() {
+        let outcome = Outcome::new("test.approve", None, "Approved 1 baseline(s)")
+            .with_details(vec!["baselines/login.png".to_string()]);
+
+        let json: serde_json::Value = serde_json::from_str(
+            &Output::new(OutputFormat::Json)
+                .render_outcome(&outcome)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["action"], "test.approve");
+        assert!(json.get("target").is_none());
+        assert!(json.get("warnings").is_none());
+
+        assert_eq!(
+            Output::new(OutputFormat::Plain)
+                .render_outcome(&outcome)
+                .unwrap(),
+            "baselines/login.png\n"
+        );
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let error: anyhow::Error = KMobileError::DeviceNotFound("x".to_string()).into();
+        assert_eq!(exit_code(&error), 4);
+        assert_eq!(exit_code(&error.context("while installing")), 4);
+        assert_eq!(exit_code(&anyhow::anyhow!("plain")), 1);
+    }
+}
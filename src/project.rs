@@ -76,6 +76,10 @@ impl ProjectManager {
         })
     }
 
+    pub fn current_project(&self) -> Option<&ProjectConfig> {
+        self.current_project.as_ref()
+    }
+
     async fn detect_current_project(config: &Config) -> Result<Option<ProjectConfig>> {
         let current_dir = std::env::current_dir()?;
 
@@ -500,7 +504,7 @@ A mobile project created with KMobile.
         Ok(())
     }
 
-    pub async fn get_project_status(&self) -> Result<String> {
+    pub async fn get_project_status(&self) -> Result<ProjectStatus> {
         let project = self.current_project.as_ref().ok_or_else(|| {
             KMobileError::ProjectNotFound("No project found in current directory".to_string())
         })?;
@@ -514,7 +518,6 @@ A mobile project created with KMobile.
             dependencies: Vec::new(),
         };
 
-        let status_json = serde_json::to_string_pretty(&status)?;
-        Ok(status_json)
+        Ok(status)
     }
 }
@@ -1,24 +1,221 @@
 use anyhow::Result;
-use clap::Subcommand;
+use clap::{Args, Subcommand};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::process::Command;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+use crate::avd::{self, AvdHardware, AvdInfo, AvdManager, CreateAvdOptions};
 use crate::config::Config;
+use crate::emulator::{self, BootOptions, RunningEmulator, Snapshot};
 use crate::error::KMobileError;
+use crate::farm::{self, FarmOptions};
+use crate::hardware_emulator::route::PlaybackOptions;
+
+/// How long a reset waits for a running emulator to exit
+const EXIT_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Subcommand)]
 pub enum SimulatorCommands {
     /// List all available simulators
     List,
     /// Start a simulator
-    Start { id: String },
+    Start {
+        id: String,
+        /// Wait until it has booted and is unlocked
+        #[arg(long)]
+        wait: bool,
+        /// Seconds to wait with --wait
+        #[arg(long, value_name = "SECONDS", default_value_t = emulator::DEFAULT_BOOT_TIMEOUT.as_secs())]
+        timeout: u64,
+        #[command(flatten)]
+        boot: BootArgs,
+    },
     /// Stop a simulator
     Stop { id: String },
-    /// Reset a simulator
+    /// Reset a simulator. Android emulators are restarted with wiped data.
     Reset { id: String },
+    /// Save, load, list or delete Android emulator snapshots
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
     /// Install app on simulator
     Install { id: String, app: String },
+    /// Create an Android emulator, installing its system image if needed
+    Create {
+        /// AVD name [default: <device>_API_<api>]
+        #[arg(long)]
+        name: Option<String>,
+        /// Android API level, e.g. 34
+        #[arg(long)]
+        api: u32,
+        /// System image ABI [default: x86_64, or arm64-v8a on ARM hosts]
+        #[arg(long)]
+        abi: Option<String>,
+        /// System image variant, e.g. google_apis_playstore
+        #[arg(long, default_value = avd::DEFAULT_TAG)]
+        tag: String,
+        /// Hardware profile from `avdmanager list device`, e.g. pixel_7
+        #[arg(long)]
+        device: Option<String>,
+        /// Replace an existing AVD with the same name
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        hardware: HardwareArgs,
+    },
+    /// Delete a simulator
+    Delete { id: String },
+    /// Change an Android emulator's RAM, heap or GPU mode
+    Configure {
+        id: String,
+        #[command(flatten)]
+        hardware: HardwareArgs,
+    },
+    /// Play a GPX, KML or GeoJSON route on a running simulator's GPS
+    Route {
+        id: String,
+        file: PathBuf,
+        #[command(flatten)]
+        playback: RouteArgs,
+    },
+    /// Boot headless read-only instances of an AVD and keep them up until Ctrl-C
+    Farm {
+        avd: String,
+        #[command(flatten)]
+        farm: FarmArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// Save the running emulator's state
+    Save { id: String, name: String },
+    /// Restore the running emulator to a snapshot
+    Load { id: String, name: String },
+    /// List an emulator's snapshots
+    List { id: String },
+    /// Delete a snapshot
+    Delete { id: String, name: String },
+}
+
+/// How an Android emulator boots; quick boot from the last exit by default
+#[derive(Args, Debug, Clone, Default)]
+pub struct BootArgs {
+    /// Boot from scratch, ignoring snapshots
+    #[arg(long, conflicts_with = "snapshot")]
+    pub cold_boot: bool,
+    /// Boot from this snapshot
+    #[arg(long, value_name = "NAME")]
+    pub snapshot: Option<String>,
+    /// Don't save a quick-boot snapshot on exit
+    #[arg(long)]
+    pub no_snapshot_save: bool,
+}
+
+impl From<BootArgs> for BootOptions {
+    fn from(args: BootArgs) -> Self {
+        Self {
+            cold_boot: args.cold_boot,
+            snapshot: args.snapshot,
+            no_snapshot_save: args.no_snapshot_save,
+        }
+    }
+}
+
+/// Pace and shape of a route playback
+#[derive(Args, Debug, Clone)]
+pub struct RouteArgs {
+    /// Constant speed in m/s [default: the route's timestamps, or 13.9]
+    #[arg(long, value_name = "M/S")]
+    pub speed: Option<f64>,
+    /// Play this many times faster
+    #[arg(long, default_value_t = 1.0)]
+    pub rate: f64,
+    /// Jump from point to point instead of moving smoothly between them
+    #[arg(long)]
+    pub no_interpolation: bool,
+    /// Start over after the last point; runs until Ctrl-C
+    #[arg(long = "loop")]
+    pub looping: bool,
+    /// Fixes per second, at most 1000 [default: the GPS sensor's update frequency]
+    #[arg(long, value_name = "HZ")]
+    pub frequency: Option<f32>,
+    /// Random offset per fix in meters [default: the GPS sensor's noise level]
+    #[arg(long, value_name = "METERS")]
+    pub noise: Option<f32>,
+}
+
+impl From<RouteArgs> for PlaybackOptions {
+    fn from(args: RouteArgs) -> Self {
+        Self {
+            speed_mps: args.speed,
+            rate: args.rate,
+            interpolate: !args.no_interpolation,
+            looping: args.looping,
+            update_frequency: args.frequency,
+            noise_level: args.noise,
+        }
+    }
+}
+
+/// Size and limits of an emulator farm
+#[derive(Args, Debug, Clone)]
+pub struct FarmArgs {
+    /// Emulators to boot; capped by the host's CPUs and available memory
+    #[arg(long, default_value_t = 2)]
+    pub instances: usize,
+    /// CPUs to reserve for each emulator
+    #[arg(long, default_value_t = farm::DEFAULT_CPUS_PER_INSTANCE)]
+    pub cpus_per_instance: usize,
+    /// Memory in MB to reserve for each emulator
+    #[arg(long, value_name = "MB", default_value_t = farm::DEFAULT_RAM_MB_PER_INSTANCE)]
+    pub ram_per_instance: u64,
+    /// Times a crashed emulator is relaunched
+    #[arg(long, default_value_t = farm::DEFAULT_MAX_RESTARTS)]
+    pub max_restarts: u32,
+    /// Seconds to wait for each emulator to boot
+    #[arg(long, value_name = "SECONDS", default_value_t = emulator::DEFAULT_BOOT_TIMEOUT.as_secs())]
+    pub boot_timeout: u64,
+}
+
+impl FarmArgs {
+    pub fn options(&self, avd: &str) -> FarmOptions {
+        FarmOptions {
+            avd: avd.to_string(),
+            instances: self.instances,
+            cpus_per_instance: self.cpus_per_instance,
+            ram_mb_per_instance: self.ram_per_instance,
+            max_restarts: self.max_restarts,
+            boot_timeout: Duration::from_secs(self.boot_timeout),
+        }
+    }
+}
+
+/// Settings written to an AVD's config.ini
+#[derive(Args, Debug, Clone, Default)]
+pub struct HardwareArgs {
+    /// RAM in MB
+    #[arg(long, value_name = "MB")]
+    pub ram: Option<u32>,
+    /// VM heap in MB
+    #[arg(long, value_name = "MB")]
+    pub heap: Option<u32>,
+    /// GPU emulation mode
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(avd::GPU_MODES))]
+    pub gpu: Option<String>,
+}
+
+impl From<HardwareArgs> for AvdHardware {
+    fn from(args: HardwareArgs) -> Self {
+        Self {
+            ram_mb: args.ram,
+            heap_mb: args.heap,
+            gpu_mode: args.gpu,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +226,9 @@ pub struct Simulator {
     pub version: String,
     pub status: SimulatorStatus,
     pub device_type: String,
+    /// adb serial of a running Android emulator, e.g. `emulator-5554`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +243,7 @@ pub struct SimulatorManager {
     config: Config,
     android_emulators: Vec<Simulator>,
     ios_simulators: Vec<Simulator>,
+    running_emulators: Vec<RunningEmulator>,
 }
 
 impl SimulatorManager {
@@ -51,6 +252,7 @@ impl SimulatorManager {
             config: config.clone(),
             android_emulators: Vec::new(),
             ios_simulators: Vec::new(),
+            running_emulators: Vec::new(),
         };
 
         manager.refresh_simulators().await?;
@@ -95,21 +297,50 @@ impl SimulatorManager {
 
         let output_str = String::from_utf8_lossy(&output.stdout);
         self.android_emulators.clear();
+        self.running_emulators = match self.running_android_emulators().await {
+            Ok(running) => running,
+            Err(e) => {
+                debug!("Cannot list running emulators: {}", e);
+                Vec::new()
+            }
+        };
+
+        // avdmanager is optional here; without it versions stay unknown
+        let avds = match AvdManager::new(&self.config) {
+            Ok(manager) => manager.list_avds().await.unwrap_or_else(|e| {
+                debug!("avdmanager list avd failed: {}", e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
 
         for line in output_str.lines() {
             if !line.trim().is_empty() {
                 let avd_name = line.trim();
 
-                // Get emulator status
-                let status = self.get_android_emulator_status(avd_name).await?;
+                let running = self
+                    .running_emulators
+                    .iter()
+                    .find(|emulator| emulator.avd_name == avd_name);
+                let status = match running {
+                    Some(emulator) if emulator.is_online() => SimulatorStatus::Booted,
+                    Some(_) => SimulatorStatus::Booting,
+                    None => SimulatorStatus::Shutdown,
+                };
+                let version = avds
+                    .iter()
+                    .find(|avd| avd.name == avd_name)
+                    .and_then(|avd| avd.api_level)
+                    .map_or_else(|| "unknown".to_string(), |level| format!("API {level}"));
 
                 let simulator = Simulator {
                     id: avd_name.to_string(),
                     name: avd_name.to_string(),
                     platform: "android".to_string(),
-                    version: "unknown".to_string(),
+                    version,
                     status,
                     device_type: "emulator".to_string(),
+                    serial: running.map(|emulator| emulator.serial.clone()),
                 };
 
                 self.android_emulators.push(simulator);
@@ -169,6 +400,7 @@ impl SimulatorManager {
                                         .replace("-", "."),
                                     status,
                                     device_type: "simulator".to_string(),
+                                    serial: None,
                                 };
 
                                 self.ios_simulators.push(simulator);
@@ -183,26 +415,30 @@ impl SimulatorManager {
         Ok(())
     }
 
-    async fn get_android_emulator_status(&self, avd_name: &str) -> Result<SimulatorStatus> {
-        let adb_path = self
+    fn adb_path(&self) -> Result<&std::path::Path> {
+        Ok(self
             .config
             .android
             .adb_path
-            .as_ref()
-            .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?;
-
-        let output = Command::new(adb_path).args(["devices"]).output()?;
+            .as_deref()
+            .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?)
+    }
 
-        if output.status.success() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                if line.contains(avd_name) && line.contains("device") {
-                    return Ok(SimulatorStatus::Booted);
-                }
-            }
-        }
+    /// Emulators that are running now, with their serials, console ports and PIDs
+    pub async fn running_android_emulators(&self) -> Result<Vec<RunningEmulator>> {
+        emulator::running_emulators(self.adb_path()?).await
+    }
 
-        Ok(SimulatorStatus::Shutdown)
+    /// The running emulator for `avd_name`. Looked up again on each call, since
+    /// emulators come and go while the manager is alive.
+    async fn find_running_emulator(&self, avd_name: &str) -> Result<RunningEmulator> {
+        self.running_android_emulators()
+            .await?
+            .into_iter()
+            .find(|emulator| emulator.avd_name == avd_name)
+            .ok_or_else(|| {
+                KMobileError::DeviceNotFound(format!("Emulator {avd_name} is not running")).into()
+            })
     }
 
     pub async fn list_simulators(&self) -> Result<Vec<Simulator>> {
@@ -213,11 +449,20 @@ impl SimulatorManager {
     }
 
     pub async fn start_simulator(&self, simulator_id: &str) -> Result<()> {
+        self.start_simulator_with(simulator_id, &BootOptions::default())
+            .await
+    }
+
+    /// Starts a simulator. Boot options only apply to Android emulators.
+    pub async fn start_simulator_with(&self, simulator_id: &str, boot: &BootOptions) -> Result<()> {
         info!("Starting simulator: {}", simulator_id);
 
         if self.android_emulators.iter().any(|s| s.id == simulator_id) {
-            self.start_android_emulator(simulator_id).await?;
+            self.start_android_emulator(simulator_id, boot).await?;
         } else if self.ios_simulators.iter().any(|s| s.id == simulator_id) {
+            if !boot.is_default() {
+                warn!("Boot options are ignored for iOS simulators");
+            }
             self.start_ios_simulator(simulator_id).await?;
         } else {
             return Err(KMobileError::SimulatorNotFound(simulator_id.to_string()).into());
@@ -226,30 +471,87 @@ impl SimulatorManager {
         Ok(())
     }
 
-    async fn start_android_emulator(&self, avd_name: &str) -> Result<()> {
-        let emulator_path = if let Some(path) = &self.config.android.emulator_path {
-            path
-        } else if let Some(sdk_path) = &self.config.android.sdk_path {
-            &sdk_path.join("emulator/emulator")
-        } else {
-            return Err(
-                KMobileError::ConfigError("Emulator path not configured".to_string()).into(),
-            );
-        };
+    async fn start_android_emulator(&self, avd_name: &str, boot: &BootOptions) -> Result<()> {
+        let running = self.running_android_emulators().await.unwrap_or_default();
+        if let Some(emulator) = running
+            .iter()
+            .find(|emulator| emulator.avd_name == avd_name)
+        {
+            info!("{} is already running as {}", avd_name, emulator.serial);
+            return Ok(());
+        }
+        self.launch_android_emulator(avd_name, &running, &boot.args())
+    }
 
-        let mut cmd = Command::new(emulator_path);
-        cmd.args(["-avd", avd_name, "-no-audio", "-no-window"]);
+    /// Spawns the emulator in the background on a free console port
+    fn launch_android_emulator(
+        &self,
+        avd_name: &str,
+        running: &[RunningEmulator],
+        args: &[String],
+    ) -> Result<()> {
+        // Choosing the port up front fixes the serial the emulator will get
+        let port = emulator::next_console_port(running).ok_or_else(|| {
+            KMobileError::SimulatorStartError("No free emulator console port".to_string())
+        })?;
+
+        let mut cmd = Command::new(emulator::emulator_binary(&self.config.android)?);
+        cmd.args(["-avd", avd_name, "-no-audio", "-no-window"])
+            .args(["-port", &port.to_string()])
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
 
         let child = cmd.spawn()?;
         debug!(
-            "Started Android emulator {} with PID {}",
+            "Started Android emulator {} as {} with PID {}",
             avd_name,
+            emulator::serial_for_port(port),
             child.id()
         );
 
         Ok(())
     }
 
+    /// Waits until a started simulator is ready for tests: for Android, booted with
+    /// the package manager up and the lock screen dismissed; for iOS, what
+    /// `simctl bootstatus` reports
+    pub async fn wait_until_booted(&self, simulator_id: &str, timeout: Duration) -> Result<()> {
+        if self.android_emulators.iter().any(|s| s.id == simulator_id) {
+            emulator::wait_for_avd(self.adb_path()?, simulator_id, timeout).await?;
+        } else if self.ios_simulators.iter().any(|s| s.id == simulator_id) {
+            self.wait_for_ios_simulator(simulator_id, timeout).await?;
+        } else {
+            return Err(KMobileError::SimulatorNotFound(simulator_id.to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    async fn wait_for_ios_simulator(&self, simulator_id: &str, timeout: Duration) -> Result<()> {
+        let output = tokio::time::timeout(
+            timeout,
+            tokio::process::Command::new("xcrun")
+                .args(["simctl", "bootstatus", simulator_id, "-b"])
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .map_err(|_| {
+            KMobileError::TimeoutError(format!("{simulator_id} did not finish booting"))
+        })??;
+
+        if !output.status.success() {
+            let error_msg = String::from_utf8_lossy(&output.stderr);
+            return Err(KMobileError::SimulatorStartError(format!(
+                "Failed to boot iOS simulator: {error_msg}"
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
     async fn start_ios_simulator(&self, simulator_id: &str) -> Result<()> {
         let output = Command::new("xcrun")
             .args(["simctl", "boot", simulator_id])
@@ -257,6 +559,10 @@ impl SimulatorManager {
 
         if !output.status.success() {
             let error_msg = String::from_utf8_lossy(&output.stderr);
+            if simctl_state(&error_msg) == Some("Booted") {
+                info!("{} is already booted", simulator_id);
+                return Ok(());
+            }
             return Err(KMobileError::SimulatorStartError(format!(
                 "Failed to start iOS simulator: {error_msg}"
             ))
@@ -280,29 +586,29 @@ impl SimulatorManager {
         Ok(())
     }
 
-    async fn stop_android_emulator(&self, _avd_name: &str) -> Result<()> {
-        let adb_path = self
-            .config
-            .android
-            .adb_path
-            .as_ref()
-            .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?;
-
-        // Find the emulator device ID
-        let output = Command::new(adb_path).args(["devices"]).output()?;
-
-        if output.status.success() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                if line.contains("emulator") && line.contains("device") {
-                    let device_id = line.split_whitespace().next().unwrap();
-
-                    let _ = Command::new(adb_path)
-                        .args(["-s", device_id, "emu", "kill"])
-                        .output()?;
+    async fn stop_android_emulator(&self, avd_name: &str) -> Result<()> {
+        let emulator = self
+            .running_android_emulators()
+            .await?
+            .into_iter()
+            .find(|emulator| emulator.avd_name == avd_name)
+            .ok_or_else(|| {
+                KMobileError::InvalidInput(format!("Emulator {avd_name} is not running"))
+            })?;
+
+        let output = Command::new(self.adb_path()?)
+            .args(["-s", &emulator.serial, "emu", "kill"])
+            .output()?;
 
-                    break;
-                }
+        if !output.status.success() {
+            let killed = emulator.pid.is_some_and(emulator::kill_process);
+            if !killed {
+                let error_msg = String::from_utf8_lossy(&output.stderr);
+                return Err(KMobileError::SimulatorStopError(format!(
+                    "Failed to stop {} ({}): {error_msg}",
+                    avd_name, emulator.serial
+                ))
+                .into());
             }
         }
 
@@ -316,6 +622,12 @@ impl SimulatorManager {
 
         if !output.status.success() {
             let error_msg = String::from_utf8_lossy(&output.stderr);
+            if simctl_state(&error_msg) == Some("Shutdown") {
+                return Err(KMobileError::InvalidInput(format!(
+                    "Simulator {simulator_id} is not running"
+                ))
+                .into());
+            }
             return Err(KMobileError::SimulatorStopError(format!(
                 "Failed to stop iOS simulator: {error_msg}"
             ))
@@ -339,25 +651,106 @@ impl SimulatorManager {
         Ok(())
     }
 
+    /// Stops the emulator if it's running, then boots it again with `-wipe-data`. The
+    /// emulator keeps running afterwards, like after `simulator start`.
     async fn reset_android_emulator(&self, avd_name: &str) -> Result<()> {
-        let emulator_path = if let Some(path) = &self.config.android.emulator_path {
-            path
-        } else if let Some(sdk_path) = &self.config.android.sdk_path {
-            &sdk_path.join("emulator/emulator")
+        let mut running = self.running_android_emulators().await.unwrap_or_default();
+        if running.iter().any(|emulator| emulator.avd_name == avd_name) {
+            self.stop_android_emulator(avd_name).await?;
+            let deadline = tokio::time::Instant::now() + EXIT_TIMEOUT;
+            loop {
+                running = self.running_android_emulators().await?;
+                if !running.iter().any(|emulator| emulator.avd_name == avd_name) {
+                    break;
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    return Err(KMobileError::SimulatorResetError(format!(
+                        "{avd_name} did not exit"
+                    ))
+                    .into());
+                }
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        }
+
+        let args = ["-wipe-data", "-no-snapshot-load"].map(String::from);
+        self.launch_android_emulator(avd_name, &running, &args)
+            .map_err(|e| {
+                KMobileError::SimulatorResetError(format!("Failed to reset Android emulator: {e}"))
+                    .into()
+            })
+    }
+
+    pub async fn save_snapshot(&self, simulator_id: &str, name: &str) -> Result<()> {
+        let emulator = self.snapshot_target(simulator_id).await?;
+        info!("Saving snapshot {} of {}", name, simulator_id);
+        emulator::save_snapshot(self.adb_path()?, &emulator.serial, name).await
+    }
+
+    pub async fn load_snapshot(&self, simulator_id: &str, name: &str) -> Result<()> {
+        let emulator = self.snapshot_target(simulator_id).await?;
+        info!("Loading snapshot {} on {}", name, simulator_id);
+        emulator::load_snapshot(self.adb_path()?, &emulator.serial, name).await
+    }
+
+    pub async fn delete_snapshot(&self, simulator_id: &str, name: &str) -> Result<()> {
+        let emulator = self.snapshot_target(simulator_id).await?;
+        info!("Deleting snapshot {} of {}", name, simulator_id);
+        emulator::delete_snapshot(self.adb_path()?, &emulator.serial, name).await
+    }
+
+    /// Asks the running emulator, or reads the AVD's snapshot directory when it's
+    /// stopped
+    pub async fn list_snapshots(&self, simulator_id: &str) -> Result<Vec<Snapshot>> {
+        self.check_android(simulator_id)?;
+        if let Ok(emulator) = self.find_running_emulator(simulator_id).await {
+            return emulator::list_snapshots(self.adb_path()?, &emulator.serial).await;
+        }
+        let path = avd::avd_path(simulator_id).ok_or_else(|| {
+            KMobileError::SimulatorNotFound(format!("AVD directory of {simulator_id}"))
+        })?;
+        emulator::snapshots_on_disk(&path)
+    }
+
+    async fn snapshot_target(&self, simulator_id: &str) -> Result<RunningEmulator> {
+        self.check_android(simulator_id)?;
+        self.find_running_emulator(simulator_id).await
+    }
+
+    /// Device id the hardware emulator sets the location on: the serial of a running
+    /// Android emulator, or an iOS simulator's UDID
+    pub async fn location_target(&self, simulator_id: &str) -> Result<String> {
+        if self.ios_simulators.iter().any(|s| s.id == simulator_id) {
+            return Ok(simulator_id.to_string());
+        }
+        if !self.android_emulators.iter().any(|s| s.id == simulator_id) {
+            return Err(KMobileError::SimulatorNotFound(simulator_id.to_string()).into());
+        }
+        Ok(self.find_running_emulator(simulator_id).await?.serial)
+    }
+
+    fn check_android(&self, simulator_id: &str) -> Result<()> {
+        if self.android_emulators.iter().any(|s| s.id == simulator_id) {
+            Ok(())
+        } else if self.ios_simulators.iter().any(|s| s.id == simulator_id) {
+            Err(KMobileError::InvalidInput(
+                "Snapshots are only supported on Android emulators".to_string(),
+            )
+            .into())
         } else {
-            return Err(
-                KMobileError::ConfigError("Emulator path not configured".to_string()).into(),
-            );
-        };
+            Err(KMobileError::SimulatorNotFound(simulator_id.to_string()).into())
+        }
+    }
 
-        let output = Command::new(emulator_path)
-            .args(["-avd", avd_name, "-wipe-data", "-no-audio", "-no-window"])
+    async fn reset_ios_simulator(&self, simulator_id: &str) -> Result<()> {
+        let output = Command::new("xcrun")
+            .args(["simctl", "erase", simulator_id])
             .output()?;
 
         if !output.status.success() {
             let error_msg = String::from_utf8_lossy(&output.stderr);
             return Err(KMobileError::SimulatorResetError(format!(
-                "Failed to reset Android emulator: {error_msg}"
+                "Failed to reset iOS simulator: {error_msg}"
             ))
             .into());
         }
@@ -365,15 +758,38 @@ impl SimulatorManager {
         Ok(())
     }
 
-    async fn reset_ios_simulator(&self, simulator_id: &str) -> Result<()> {
+    /// Creates an AVD with `sdkmanager` and `avdmanager`. It shows up in the simulator
+    /// list on the next refresh.
+    pub async fn create_android_emulator(&self, options: &CreateAvdOptions) -> Result<AvdInfo> {
+        info!("Creating Android emulator: {}", options.avd_name());
+        AvdManager::new(&self.config)?.create_avd(options).await
+    }
+
+    pub async fn delete_simulator(&self, simulator_id: &str) -> Result<()> {
+        info!("Deleting simulator: {}", simulator_id);
+
+        if self.android_emulators.iter().any(|s| s.id == simulator_id) {
+            AvdManager::new(&self.config)?
+                .delete_avd(simulator_id)
+                .await?;
+        } else if self.ios_simulators.iter().any(|s| s.id == simulator_id) {
+            self.delete_ios_simulator(simulator_id).await?;
+        } else {
+            return Err(KMobileError::SimulatorNotFound(simulator_id.to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    async fn delete_ios_simulator(&self, simulator_id: &str) -> Result<()> {
         let output = Command::new("xcrun")
-            .args(["simctl", "erase", simulator_id])
+            .args(["simctl", "delete", simulator_id])
             .output()?;
 
         if !output.status.success() {
             let error_msg = String::from_utf8_lossy(&output.stderr);
-            return Err(KMobileError::SimulatorResetError(format!(
-                "Failed to reset iOS simulator: {error_msg}"
+            return Err(KMobileError::CommandError(format!(
+                "Failed to delete iOS simulator: {error_msg}"
             ))
             .into());
         }
@@ -381,6 +797,29 @@ impl SimulatorManager {
         Ok(())
     }
 
+    /// Edits an Android emulator's config.ini. Takes effect on its next boot.
+    pub async fn configure_simulator(
+        &self,
+        simulator_id: &str,
+        hardware: &AvdHardware,
+    ) -> Result<AvdInfo> {
+        if self.ios_simulators.iter().any(|s| s.id == simulator_id) {
+            return Err(KMobileError::InvalidInput(
+                "Hardware settings only apply to Android emulators".to_string(),
+            )
+            .into());
+        }
+        if hardware.is_empty() {
+            return Err(KMobileError::InvalidInput(
+                "Nothing to change; pass --ram, --heap or --gpu".to_string(),
+            )
+            .into());
+        }
+        AvdManager::new(&self.config)?
+            .configure_avd(simulator_id, hardware)
+            .await
+    }
+
     pub async fn install_app(&self, simulator_id: &str, app_path: &str) -> Result<()> {
         info!("Installing app {} on simulator {}", app_path, simulator_id);
 
@@ -395,38 +834,19 @@ impl SimulatorManager {
         Ok(())
     }
 
-    async fn install_android_app(&self, _avd_name: &str, app_path: &str) -> Result<()> {
-        let adb_path = self
-            .config
-            .android
-            .adb_path
-            .as_ref()
-            .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?;
-
-        // Find the emulator device ID
-        let output = Command::new(adb_path).args(["devices"]).output()?;
-
-        if output.status.success() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                if line.contains("emulator") && line.contains("device") {
-                    let device_id = line.split_whitespace().next().unwrap();
-
-                    let install_output = Command::new(adb_path)
-                        .args(["-s", device_id, "install", "-r", app_path])
-                        .output()?;
-
-                    if !install_output.status.success() {
-                        let error_msg = String::from_utf8_lossy(&install_output.stderr);
-                        return Err(KMobileError::AppInstallError(format!(
-                            "Failed to install app: {error_msg}"
-                        ))
-                        .into());
-                    }
+    async fn install_android_app(&self, avd_name: &str, app_path: &str) -> Result<()> {
+        let emulator = self.find_running_emulator(avd_name).await?;
 
-                    break;
-                }
-            }
+        let install_output = Command::new(self.adb_path()?)
+            .args(["-s", &emulator.serial, "install", "-r", app_path])
+            .output()?;
+
+        if !install_output.status.success() {
+            let error_msg = String::from_utf8_lossy(&install_output.stderr);
+            return Err(KMobileError::AppInstallError(format!(
+                "Failed to install app: {error_msg}"
+            ))
+            .into());
         }
 
         Ok(())
@@ -448,3 +868,22 @@ impl SimulatorManager {
         Ok(())
     }
 }
+
+/// The device state `simctl` names when it refuses a command, e.g. `Booted` in
+/// "Unable to boot device in current state: Booted"
+fn simctl_state(stderr: &str) -> Option<&str> {
+    let (_, rest) = stderr.split_once("in current state: ")?;
+    rest.split_whitespace().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simctl_state() {
+        let stderr = "An error was encountered processing the command (domain=com.apple.CoreSimulator.SimError, code=405):\nUnable to boot device in current state: Booted\n";
+        assert_eq!(simctl_state(stderr), Some("Booted"));
+        assert_eq!(simctl_state("Invalid device: 1234"), None);
+    }
+}
@@ -3,13 +3,41 @@ use chrono::{DateTime, Utc};
 use clap::Subcommand;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::UnboundedSender;
 use tracing::{debug, info, warn};
 
 use crate::config::Config;
 use crate::error::KMobileError;
+use crate::simulator_basic::FarmArgs;
+
+pub mod device_logs;
+pub mod history;
+pub mod lifecycle;
+pub mod maestro;
+pub mod native;
+pub mod pool;
+pub mod recorder;
+pub mod reporters;
+pub mod selection;
+pub mod step_debugger;
+pub mod ui_hierarchy;
+pub mod video;
+#[cfg(feature = "visual")]
+pub mod visual;
+
+pub use lifecycle::{Fixture, TestHooks};
+pub use pool::DevicePool;
+pub use reporters::ReporterKind;
+pub use selection::{Shard, TestFilter};
+
+use device_logs::LogCapture;
+use recorder::{GestureDetector, InputScale, MAX_RECORDED_WAIT};
+use ui_hierarchy::{Selector, UiHierarchy};
+use video::VideoRecorder;
 
 #[derive(Subcommand)]
 pub enum TestCommands {
@@ -17,11 +45,120 @@ pub enum TestCommands {
     Run {
         suite: Option<String>,
         device: Option<String>,
+        /// Report formats to write, e.g. `--reporter junit,html`
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "json")]
+        reporter: Vec<ReporterKind>,
+        #[arg(
+            long,
+            help = "Only run cases whose name or description contains this text"
+        )]
+        grep: Option<String>,
+        #[arg(
+            long = "tag",
+            value_delimiter = ',',
+            help = "Only run cases with one of these tags"
+        )]
+        tags: Vec<String>,
+        #[arg(
+            long = "exclude-tag",
+            value_delimiter = ',',
+            help = "Skip cases with any of these tags"
+        )]
+        exclude_tags: Vec<String>,
+        #[arg(long, help = "Run one slice of the suite, e.g. `--shard 2/4`")]
+        shard: Option<Shard>,
+        #[arg(long, help = "Rerun only the cases that failed in the last report")]
+        only_failed: bool,
+        #[arg(
+            long,
+            value_name = "AVD",
+            conflicts_with = "device",
+            help = "Boot a headless emulator farm of this AVD and spread the cases across it"
+        )]
+        farm: Option<String>,
+        #[command(flatten)]
+        farm_args: FarmArgs,
+    },
+    /// Record a test from live touch input on an Android device
+    Record {
+        output: String,
+        #[arg(
+            long,
+            help = "Device to record from (defaults to the first adb device)"
+        )]
+        device: Option<String>,
+        #[arg(
+            long,
+            help = "Stop recording after this many seconds (default: until Ctrl-C)"
+        )]
+        duration: Option<u64>,
+    },
+    /// Replay a recorded test on a device
+    Replay {
+        file: String,
+        #[arg(long, help = "Device to replay on (defaults to the first adb device)")]
+        device: Option<String>,
+        #[arg(
+            long,
+            help = "Pause before each step to continue, skip, retry or edit it"
+        )]
+        step: bool,
+    },
+    /// Accept pending screenshots as the new visual baselines
+    Approve {
+        suite: Option<String>,
+        #[arg(long, help = "Only approve screenshots from this test case")]
+        case: Option<String>,
+        #[arg(long, help = "Only approve screenshots from this device profile")]
+        profile: Option<String>,
+    },
+    /// Run Maestro YAML flows as a suite
+    Maestro {
+        #[arg(required = true)]
+        flows: Vec<PathBuf>,
+        #[arg(long, help = "Device to run on")]
+        device: Option<String>,
+        #[arg(long, help = "Drop unsupported commands instead of refusing to run")]
+        skip_unsupported: bool,
+        /// Report formats to write, e.g. `--reporter junit,html`
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "json")]
+        reporter: Vec<ReporterKind>,
+    },
+    /// Convert a Maestro flow into a kmobile test case
+    ImportMaestro {
+        flow: PathBuf,
+        /// Where to write the test case JSON
+        output: PathBuf,
+    },
+    /// Run the project's own test command (Gradle, XCTest, Flutter or Jest)
+    Native {
+        #[arg(long, help = "Device or simulator to run on")]
+        device: Option<String>,
+        #[arg(long, help = "Run connectedAndroidTest instead of JVM unit tests")]
+        connected: bool,
+        /// Report formats to write, e.g. `--reporter junit,html`
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "json")]
+        reporter: Vec<ReporterKind>,
+    },
+    /// Show pass rates, duration trends and flaky tests from the run history
+    Stats {
+        suite: Option<String>,
+        #[arg(
+            long,
+            default_value_t = 20,
+            help = "Number of recent runs per case to analyse"
+        )]
+        window: usize,
+        #[arg(long, help = "Only list flaky test cases")]
+        flaky: bool,
+    },
+    /// Save an app's private data for use in an `app_data_snapshot` fixture
+    SaveAppData {
+        package: String,
+        output: String,
+        #[arg(long, help = "Device to read from (defaults to the first adb device)")]
+        device: Option<String>,
     },
-    /// Record a test
-    Record { output: String },
-    /// Replay a test
-    Replay { file: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +166,11 @@ pub struct TestSuite {
     pub name: String,
     pub tests: Vec<TestCase>,
     pub config: TestConfig,
+    #[serde(default, skip_serializing_if = "TestHooks::is_empty")]
+    pub hooks: TestHooks,
+    /// Applied before every test case in the suite
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixtures: Vec<Fixture>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +180,12 @@ pub struct TestCase {
     pub steps: Vec<TestStep>,
     pub expected_result: Option<String>,
     pub timeout: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "TestHooks::is_empty")]
+    pub hooks: TestHooks,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixtures: Vec<Fixture>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +199,7 @@ pub struct TestStep {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TestAction {
     Tap,
+    LongPress,
     Swipe,
     Type,
     Wait,
@@ -59,6 +208,8 @@ pub enum TestAction {
     Launch,
     Background,
     Foreground,
+    /// Swipes until `target` is on screen; `value` is `direction[,timeout_ms]`
+    ScrollUntilVisible,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,6 +229,9 @@ pub struct TestResult {
     pub error_message: Option<String>,
     pub screenshots: Vec<String>,
     pub video_path: Option<String>,
+    /// Device log captured while the test ran, relative to the test output dir
+    #[serde(default)]
+    pub log_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -106,6 +260,59 @@ pub struct TestSummary {
     pub timeout: u32,
 }
 
+/// Per-run options for `TestRunner::run_tests`
+#[derive(Debug, Clone)]
+pub struct TestRunOptions {
+    pub reporters: Vec<ReporterKind>,
+    pub filter: TestFilter,
+    /// Receives progress as cases run
+    pub progress: Option<UnboundedSender<TestProgress>>,
+}
+
+/// Progress of a running suite, for front ends that show it live
+#[derive(Debug, Clone)]
+pub enum TestProgress {
+    SuiteStarted { suite_name: String, total: usize },
+    CaseStarted { name: String },
+    CaseFinished(TestResult),
+    SuiteFinished(TestSummary),
+}
+
+impl Default for TestRunOptions {
+    fn default() -> Self {
+        Self {
+            reporters: vec![ReporterKind::Json],
+            filter: TestFilter::default(),
+            progress: None,
+        }
+    }
+}
+
+/// Per-device settings shared by every case of a run
+struct CaseContext {
+    record_video: bool,
+    /// Baseline directory name for visual checks
+    #[cfg(feature = "visual")]
+    device_profile: Option<String>,
+}
+
+/// Result for a case that never ran because the suite's `before_all` failed
+fn suite_setup_failed(test_case: &TestCase, error: &anyhow::Error) -> TestResult {
+    TestResult {
+        test_name: test_case.name.clone(),
+        status: TestStatus::Failed,
+        duration: Duration::ZERO,
+        error_message: Some(format!("Suite setup failed: {error}")),
+        screenshots: Vec::new(),
+        video_path: None,
+        log_path: None,
+    }
+}
+
+/// Default time `ScrollUntilVisible` keeps scrolling
+const SCROLL_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Clone)]
 pub struct TestRunner {
     config: Config,
     #[allow(dead_code)]
@@ -125,36 +332,188 @@ impl TestRunner {
         })
     }
 
-    pub async fn run_tests(&self, suite_name: Option<&str>, device_id: Option<&str>) -> Result<()> {
+    pub async fn run_tests(
+        &self,
+        suite_name: Option<&str>,
+        device_id: Option<&str>,
+        options: &TestRunOptions,
+    ) -> Result<TestReport> {
         info!(
             "Running tests - Suite: {:?}, Device: {:?}",
             suite_name, device_id
         );
 
         let suite = self.load_test_suite(suite_name).await?;
+        self.run_suite(suite, device_id, options).await
+    }
+
+    /// Runs an already loaded suite: selection, hooks, artifacts, reports and history
+    pub async fn run_suite(
+        &self,
+        mut suite: TestSuite,
+        device_id: Option<&str>,
+        options: &TestRunOptions,
+    ) -> Result<TestReport> {
+        self.select_cases(&mut suite, options)?;
         let start_time = Utc::now();
+        let progress = |event: TestProgress| {
+            if let Some(sender) = &options.progress {
+                let _ = sender.send(event);
+            }
+        };
+        progress(TestProgress::SuiteStarted {
+            suite_name: suite.name.clone(),
+            total: suite.tests.len(),
+        });
 
         let mut results = Vec::new();
+        let context = self.case_context(&suite, device_id);
+
+        let mut hook_screenshots = Vec::new();
+        let before_all = self
+            .run_hook_steps(
+                &suite.name,
+                "before_all",
+                &suite.hooks.before_all,
+                device_id,
+                &mut hook_screenshots,
+            )
+            .await;
 
         for test_case in &suite.tests {
+            if let Err(e) = &before_all {
+                let result = suite_setup_failed(test_case, e);
+                progress(TestProgress::CaseFinished(result.clone()));
+                results.push(result);
+                continue;
+            }
+
             info!("Running test: {}", test_case.name);
+            progress(TestProgress::CaseStarted {
+                name: test_case.name.clone(),
+            });
+
+            let result = self
+                .run_case(&suite, test_case, device_id, &context)
+                .await?;
 
-            let result = self.run_test_case(test_case, device_id).await?;
+            progress(TestProgress::CaseFinished(result.clone()));
             results.push(result);
         }
 
+        // Suite teardown runs even when its setup failed part way through
+        if let Err(e) = self
+            .run_hook_steps(
+                &suite.name,
+                "after_all",
+                &suite.hooks.after_all,
+                device_id,
+                &mut hook_screenshots,
+            )
+            .await
+        {
+            warn!("Suite teardown failed: {}", e);
+        }
+
+        self.finish_run(&suite, start_time, results, device_id, options)
+            .await
+    }
+
+    /// Applies the run's filter to the suite's cases
+    fn select_cases(&self, suite: &mut TestSuite, options: &TestRunOptions) -> Result<()> {
+        if !options.filter.is_empty() {
+            let failed = if options.filter.only_failed {
+                Some(selection::last_failed_cases(
+                    &self.test_output_dir,
+                    &suite.name,
+                )?)
+            } else {
+                None
+            };
+            let total = suite.tests.len();
+            suite.tests = options
+                .filter
+                .select(std::mem::take(&mut suite.tests), failed.as_ref());
+            info!("Selected {} of {} test cases", suite.tests.len(), total);
+        }
+        Ok(())
+    }
+
+    /// Per-device settings shared by every case of a run
+    fn case_context(&self, suite: &TestSuite, device_id: Option<&str>) -> CaseContext {
+        #[cfg(not(feature = "visual"))]
+        let _ = device_id;
+        CaseContext {
+            record_video: suite.config.video_recording || self.config.testing.video_recording,
+            #[cfg(feature = "visual")]
+            device_profile: device_id
+                .filter(|_| self.config.testing.visual.enabled)
+                .map(|device_id| self.device_profile(device_id)),
+        }
+    }
+
+    /// Runs one case with its fixtures, hooks, video, device log and visual checks
+    async fn run_case(
+        &self,
+        suite: &TestSuite,
+        test_case: &TestCase,
+        device_id: Option<&str>,
+        context: &CaseContext,
+    ) -> Result<TestResult> {
+        let recorder = match device_id {
+            Some(device_id) if context.record_video => {
+                self.start_video_recording(device_id, test_case)
+            }
+            _ => None,
+        };
+
+        let log_capture =
+            device_id.and_then(|device_id| self.start_log_capture(device_id, test_case));
+
+        let mut result = self.run_case_lifecycle(suite, test_case, device_id).await?;
+
+        if let Some(capture) = log_capture {
+            let packages = packages_under_test(suite, test_case);
+            self.finish_log_capture(capture, &packages, &mut result)
+                .await;
+        }
+
+        if let Some(recorder) = recorder {
+            result.video_path = self.finish_video_recording(recorder, &result).await;
+        }
+
+        #[cfg(feature = "visual")]
+        if let Some(profile) = &context.device_profile {
+            self.check_visual_baselines(&suite.name, profile, &mut result);
+        }
+
+        Ok(result)
+    }
+
+    /// Builds, saves and records the report of a finished run
+    async fn finish_run(
+        &self,
+        suite: &TestSuite,
+        start_time: DateTime<Utc>,
+        results: Vec<TestResult>,
+        device_id: Option<&str>,
+        options: &TestRunOptions,
+    ) -> Result<TestReport> {
         let report = TestReport {
             suite_name: suite.name.clone(),
             start_time,
             end_time: Some(Utc::now()),
-            results: results.clone(),
             summary: self.generate_summary(&results),
+            results,
         };
 
-        self.save_test_report(&report).await?;
-        self.print_test_summary(&report);
+        self.save_test_report(&report, &options.reporters).await?;
+        self.record_history(&report, device_id);
+        if let Some(sender) = &options.progress {
+            let _ = sender.send(TestProgress::SuiteFinished(report.summary.clone()));
+        }
 
-        Ok(())
+        Ok(report)
     }
 
     async fn load_test_suite(&self, suite_name: Option<&str>) -> Result<TestSuite> {
@@ -190,6 +549,9 @@ impl TestRunner {
                     ],
                     expected_result: Some("App launches successfully".to_string()),
                     timeout: Some(Duration::from_secs(30)),
+                    tags: Vec::new(),
+                    hooks: TestHooks::default(),
+                    fixtures: Vec::new(),
                 }],
                 config: TestConfig {
                     timeout: Duration::from_secs(30),
@@ -198,6 +560,8 @@ impl TestRunner {
                     parallel_execution: false,
                     retry_count: 0,
                 },
+                hooks: TestHooks::default(),
+                fixtures: Vec::new(),
             };
 
             // Save the default suite
@@ -219,10 +583,32 @@ impl TestRunner {
         debug!("Executing test case: {}", test_case.name);
 
         for (i, step) in test_case.steps.iter().enumerate() {
-            match self
-                .execute_test_step(step, device_id, &mut screenshots)
-                .await
-            {
+            let step_name = format!("{}_step_{}", test_case.name, i + 1);
+            let mut outcome = self
+                .run_hook_steps(
+                    &step_name,
+                    "before_each",
+                    &test_case.hooks.before_each,
+                    device_id,
+                    &mut screenshots,
+                )
+                .await;
+            if outcome.is_ok() {
+                outcome = self
+                    .execute_test_step(step, &step_name, device_id, &mut screenshots)
+                    .await;
+            }
+            let after_each = self
+                .run_hook_steps(
+                    &step_name,
+                    "after_each",
+                    &test_case.hooks.after_each,
+                    device_id,
+                    &mut screenshots,
+                )
+                .await;
+
+            match outcome.and(after_each) {
                 Ok(_) => debug!("Step {} completed successfully", i + 1),
                 Err(e) => {
                     warn!("Step {} failed: {}", i + 1, e);
@@ -245,6 +631,7 @@ impl TestRunner {
                         error_message: Some(e.to_string()),
                         screenshots,
                         video_path: None,
+                        log_path: None,
                     });
                 }
             }
@@ -257,12 +644,129 @@ impl TestRunner {
             error_message: None,
             screenshots,
             video_path: None,
+            log_path: None,
         })
     }
 
+    fn start_log_capture(&self, device_id: &str, test_case: &TestCase) -> Option<LogCapture> {
+        match LogCapture::start(
+            self.config.android.adb_path.as_deref(),
+            device_id,
+            &self.test_output_dir,
+            &test_case.name,
+        ) {
+            Ok(capture) => Some(capture),
+            Err(e) => {
+                warn!("Failed to capture device log for {}: {}", test_case.name, e);
+                None
+            }
+        }
+    }
+
+    /// Stores the captured log on the result and fails the test if one of `packages`
+    /// crashed or stopped responding while it ran
+    async fn finish_log_capture(
+        &self,
+        capture: LogCapture,
+        packages: &[String],
+        result: &mut TestResult,
+    ) {
+        let (path, mut crashes) = match capture.stop().await {
+            Ok(stopped) => stopped,
+            Err(e) => {
+                warn!("Failed to read device log for {}: {}", result.test_name, e);
+                return;
+            }
+        };
+
+        let relative = path.strip_prefix(&self.test_output_dir).unwrap_or(&path);
+        result.log_path = Some(relative.to_string_lossy().to_string());
+
+        crashes.retain(|crash| crash.is_from(packages));
+        if crashes.is_empty() {
+            return;
+        }
+
+        let details = crashes
+            .iter()
+            .map(|crash| format!("{}\n{}", crash.summary(), crash.trace))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        warn!(
+            "{} crashed during test: {}",
+            result.test_name,
+            crashes[0].summary()
+        );
+
+        result.status = TestStatus::Failed;
+        result.error_message = Some(match result.error_message.take() {
+            Some(error) => format!("{error}\n\n{details}"),
+            None => details,
+        });
+    }
+
+    fn start_video_recording(
+        &self,
+        device_id: &str,
+        test_case: &TestCase,
+    ) -> Option<VideoRecorder> {
+        match VideoRecorder::start(
+            self.config.android.adb_path.as_deref(),
+            device_id,
+            &self.test_output_dir,
+            &test_case.name,
+        ) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                warn!(
+                    "Failed to start video recording for {}: {}",
+                    test_case.name, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Stops the recording and returns its path relative to the test output dir.
+    /// Videos of passing tests are discarded when `video_on_failure_only` is set.
+    async fn finish_video_recording(
+        &self,
+        recorder: VideoRecorder,
+        result: &TestResult,
+    ) -> Option<String> {
+        let path = match recorder.stop().await {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Failed to save video for {}: {}", result.test_name, e);
+                return None;
+            }
+        };
+
+        if self.config.testing.video_on_failure_only && matches!(result.status, TestStatus::Passed)
+        {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Failed to remove video {:?}: {}", path, e);
+            }
+            return None;
+        }
+
+        let relative = path.strip_prefix(&self.test_output_dir).unwrap_or(&path);
+        Some(relative.to_string_lossy().to_string())
+    }
+
+    /// Runs a single step outside of a suite, e.g. for a WebDriver command
+    pub async fn perform_step(&self, step: &TestStep, device_id: Option<&str>) -> Result<()> {
+        let mut screenshots = Vec::new();
+        self.execute_test_step(step, "adhoc", device_id, &mut screenshots)
+            .await
+    }
+
+    /// Runs one step. `step_name` names its screenshot when the step doesn't give a
+    /// path, which keeps screenshots stable across runs for visual baselines.
     async fn execute_test_step(
         &self,
         step: &TestStep,
+        step_name: &str,
         device_id: Option<&str>,
         screenshots: &mut Vec<String>,
     ) -> Result<()> {
@@ -274,14 +778,40 @@ impl TestRunner {
                     self.tap_element(device_id, target).await?;
                 }
             }
+            TestAction::LongPress => {
+                if let Some(target) = &step.target {
+                    let duration_ms = step
+                        .value
+                        .as_deref()
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(recorder::LONG_PRESS_THRESHOLD.as_millis() as u64 * 2);
+                    self.long_press_element(device_id, target, duration_ms)
+                        .await?;
+                }
+            }
             TestAction::Swipe => {
                 if let Some(target) = &step.target {
                     self.swipe_element(device_id, target).await?;
                 }
             }
             TestAction::Type => {
-                if let (Some(target), Some(value)) = (&step.target, &step.value) {
-                    self.type_text(device_id, target, value).await?;
+                if let Some(value) = &step.value {
+                    // Element selectors are tapped first to focus them; without a target
+                    // the text goes to whatever has focus
+                    if let Some(target) = step.target.as_deref().filter(|target| {
+                        Selector::parse(target)
+                            .is_some_and(|s| !matches!(s, Selector::Coordinates(..)))
+                    }) {
+                        self.tap_element(device_id, target).await?;
+                    }
+                    self.type_text(device_id, step.target.as_deref(), value)
+                        .await?;
+                }
+            }
+            TestAction::ScrollUntilVisible => {
+                if let Some(target) = &step.target {
+                    self.scroll_until_visible(device_id, target, step.value.as_deref())
+                        .await?;
                 }
             }
             TestAction::Wait => {
@@ -295,8 +825,7 @@ impl TestRunner {
                 }
             }
             TestAction::Screenshot => {
-                let default_screenshot =
-                    format!("screenshot_{}.png", chrono::Utc::now().timestamp());
+                let default_screenshot = format!("{step_name}.png");
                 let screenshot_path = step.value.as_ref().unwrap_or(&default_screenshot);
                 self.take_screenshot(device_id, screenshot_path).await?;
                 screenshots.push(screenshot_path.clone());
@@ -321,14 +850,32 @@ impl TestRunner {
         Ok(())
     }
 
+    /// Resolves `id:`/`text:`/`desc:` selectors to the element's center point via a
+    /// uiautomator dump. Anything else is passed to `input` unchanged.
+    fn resolve_target(&self, adb_path: &Path, device_id: &str, target: &str) -> Result<String> {
+        match Selector::parse(target) {
+            Some(Selector::Coordinates(x, y)) => Ok(format!("{x} {y}")),
+            Some(selector) => {
+                let hierarchy = ui_hierarchy::dump_android_hierarchy(adb_path, device_id)?;
+                let node = hierarchy.find(&selector).ok_or_else(|| {
+                    KMobileError::TestExecutionError(format!("Element not found: {selector}"))
+                })?;
+                let (x, y) = node.bounds.center();
+                Ok(format!("{x} {y}"))
+            }
+            None => Ok(target.to_string()),
+        }
+    }
+
     async fn tap_element(&self, device_id: Option<&str>, target: &str) -> Result<()> {
         debug!("Tapping element: {}", target);
 
         if let Some(device_id) = device_id {
             // Use ADB for Android devices
             if let Some(adb_path) = &self.config.android.adb_path {
+                let point = self.resolve_target(adb_path, device_id, target)?;
                 let output = Command::new(adb_path)
-                    .args(["-s", device_id, "shell", "input", "tap", target])
+                    .args(["-s", device_id, "shell", "input", "tap", &point])
                     .output()?;
 
                 if !output.status.success() {
@@ -344,6 +891,43 @@ impl TestRunner {
         Ok(())
     }
 
+    async fn long_press_element(
+        &self,
+        device_id: Option<&str>,
+        target: &str,
+        duration_ms: u64,
+    ) -> Result<()> {
+        debug!("Long pressing element: {} for {}ms", target, duration_ms);
+
+        if let Some(device_id) = device_id {
+            if let Some(adb_path) = &self.config.android.adb_path {
+                let point = self.resolve_target(adb_path, device_id, target)?;
+                let output = Command::new(adb_path)
+                    .args([
+                        "-s",
+                        device_id,
+                        "shell",
+                        "input",
+                        "swipe",
+                        &point,
+                        &point,
+                        &duration_ms.to_string(),
+                    ])
+                    .output()?;
+
+                if !output.status.success() {
+                    let error_msg = String::from_utf8_lossy(&output.stderr);
+                    return Err(KMobileError::TestExecutionError(format!(
+                        "Long press failed: {error_msg}"
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     async fn swipe_element(&self, device_id: Option<&str>, target: &str) -> Result<()> {
         debug!("Swiping element: {}", target);
 
@@ -366,13 +950,67 @@ impl TestRunner {
         Ok(())
     }
 
-    async fn type_text(&self, device_id: Option<&str>, target: &str, text: &str) -> Result<()> {
-        debug!("Typing text: {} in {}", text, target);
+    /// Swipes until `target` shows up in the uiautomator hierarchy. `options` is
+    /// `direction[,timeout_ms]`, defaulting to scrolling down for 20 seconds.
+    async fn scroll_until_visible(
+        &self,
+        device_id: Option<&str>,
+        target: &str,
+        options: Option<&str>,
+    ) -> Result<()> {
+        let selector = Selector::parse(target).ok_or_else(|| {
+            KMobileError::InvalidInput(format!("Not an element selector: {target}"))
+        })?;
+        let mut options = options.unwrap_or_default().split(',').map(str::trim);
+        let direction = options.next().filter(|d| !d.is_empty()).unwrap_or("down");
+        let timeout = match options.next() {
+            Some(ms) => Duration::from_millis(ms.parse().map_err(|_| {
+                KMobileError::InvalidInput(format!("Invalid scroll timeout: {ms}"))
+            })?),
+            None => SCROLL_TIMEOUT,
+        };
+
+        let (Some(device_id), Some(adb_path)) = (device_id, &self.config.android.adb_path) else {
+            return Ok(());
+        };
+
+        let output = Command::new(adb_path)
+            .args(["-s", device_id, "shell", "wm", "size"])
+            .output()?;
+        let (width, height) = recorder::parse_wm_size(&String::from_utf8_lossy(&output.stdout))
+            .unwrap_or((1080, 1920));
+        let swipe = scroll_swipe(direction, width, height)?;
+
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let hierarchy = ui_hierarchy::dump_android_hierarchy(adb_path, device_id)?;
+            if hierarchy.find(&selector).is_some() {
+                return Ok(());
+            }
+            if std::time::Instant::now() >= deadline {
+                return Err(KMobileError::TestExecutionError(format!(
+                    "{selector} not visible after scrolling {direction} for {}s",
+                    timeout.as_secs()
+                ))
+                .into());
+            }
+            self.swipe_element(Some(device_id), &swipe).await?;
+        }
+    }
+
+    async fn type_text(
+        &self,
+        device_id: Option<&str>,
+        target: Option<&str>,
+        text: &str,
+    ) -> Result<()> {
+        debug!("Typing text: {} in {:?}", text, target);
 
         if let Some(device_id) = device_id {
             if let Some(adb_path) = &self.config.android.adb_path {
                 let output = Command::new(adb_path)
-                    .args(["-s", device_id, "shell", "input", "text", text])
+                    .args(["-s", device_id, "shell", "input", "text"])
+                    .arg(input_text_arg(text))
                     .output()?;
 
                 if !output.status.success() {
@@ -393,6 +1031,19 @@ impl TestRunner {
 
         if let Some(device_id) = device_id {
             if let Some(adb_path) = &self.config.android.adb_path {
+                if let Some(selector) =
+                    Selector::parse(target).filter(|s| !matches!(s, Selector::Coordinates(..)))
+                {
+                    let hierarchy = ui_hierarchy::dump_android_hierarchy(adb_path, device_id)?;
+                    if hierarchy.find(&selector).is_none() {
+                        return Err(KMobileError::TestExecutionError(format!(
+                            "Element not found: {selector}"
+                        ))
+                        .into());
+                    }
+                    return Ok(());
+                }
+
                 let output = Command::new(adb_path)
                     .args(["-s", device_id, "shell", "dumpsys", "window", "windows"])
                     .output()?;
@@ -444,13 +1095,15 @@ impl TestRunner {
         if let Some(device_id) = device_id {
             if let Some(adb_path) = &self.config.android.adb_path {
                 let output = Command::new(adb_path)
-                    .args(["-s", device_id, "shell", "am", "start", "-n", app_id])
+                    .args(launch_args(device_id, app_id))
                     .output()?;
 
-                if !output.status.success() {
+                // monkey exits 0 when the package has no launcher activity
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                if !output.status.success() || stdout.contains("No activities found") {
                     let error_msg = String::from_utf8_lossy(&output.stderr);
                     return Err(KMobileError::TestExecutionError(format!(
-                        "App launch failed: {error_msg}"
+                        "App launch failed: {error_msg}{stdout}"
                     ))
                     .into());
                 }
@@ -539,53 +1192,252 @@ impl TestRunner {
         summary
     }
 
-    async fn save_test_report(&self, report: &TestReport) -> Result<()> {
-        let report_path = self
-            .test_output_dir
-            .join(format!("{}_report.json", report.suite_name));
-        let content = serde_json::to_string_pretty(report)?;
-        fs::write(&report_path, content)?;
+    async fn save_test_report(
+        &self,
+        report: &TestReport,
+        reporters: &[ReporterKind],
+    ) -> Result<()> {
+        // `--only-failed` reads the JSON report, so it is written whatever else is asked
+        let reporters: Vec<ReporterKind> = std::iter::once(ReporterKind::Json)
+            .chain(reporters.iter().copied())
+            .collect();
+        let paths = reporters::write_reports(report, &reporters, &self.test_output_dir)?;
+        for path in paths {
+            info!("Test report written to {:?}", path);
+        }
         Ok(())
     }
 
-    fn print_test_summary(&self, report: &TestReport) {
-        println!("📊 Test Summary for '{}':", report.suite_name);
-        println!("   Total: {}", report.summary.total);
-        println!("   ✅ Passed: {}", report.summary.passed);
-        println!("   ❌ Failed: {}", report.summary.failed);
-        println!("   ⏭️  Skipped: {}", report.summary.skipped);
-        println!("   ⏱️  Timeout: {}", report.summary.timeout);
-
-        if report.summary.failed > 0 {
-            println!("\n❌ Failed tests:");
-            for result in &report.results {
-                if matches!(result.status, TestStatus::Failed) {
-                    println!(
-                        "   - {}: {}",
-                        result.test_name,
-                        result.error_message.as_deref().unwrap_or("Unknown error")
-                    );
-                }
-            }
+    fn record_history(&self, report: &TestReport, device_id: Option<&str>) {
+        let entry = history::HistoryEntry::from_report(report, device_id);
+        if let Err(e) = history::append(&self.test_output_dir, &entry) {
+            warn!("Failed to record test history: {}", e);
         }
     }
 
-    pub async fn run_device_tests(&self, device_id: &str, suite_name: Option<&str>) -> Result<()> {
+    /// Per-case stats from `history.jsonl`, flaky cases first
+    pub fn test_stats(
+        &self,
+        suite_name: Option<&str>,
+        window: usize,
+        flaky_only: bool,
+    ) -> Result<Vec<history::CaseStats>> {
+        let entries = history::load(&self.test_output_dir)?;
+        let mut stats = history::compute_stats(&entries, suite_name, window);
+        if flaky_only {
+            stats.retain(|case| case.is_flaky());
+        }
+        stats.sort_by(|a, b| {
+            b.newly_flaky
+                .cmp(&a.newly_flaky)
+                .then(b.flakiness.total_cmp(&a.flakiness))
+                .then(a.pass_rate.total_cmp(&b.pass_rate))
+                .then(a.name.cmp(&b.name))
+        });
+        Ok(stats)
+    }
+
+    pub async fn run_device_tests(
+        &self,
+        device_id: &str,
+        suite_name: Option<&str>,
+    ) -> Result<TestReport> {
         info!("Running device tests on: {}", device_id);
-        self.run_tests(suite_name, Some(device_id)).await
+        self.run_tests(suite_name, Some(device_id), &TestRunOptions::default())
+            .await
     }
 
-    pub async fn record_test(&self, output_path: &str) -> Result<()> {
+    /// Records touch input from `adb shell getevent -lt` until Ctrl-C or `duration`
+    /// elapses, and writes the resulting `TestCase` to `output_path`
+    pub async fn record_test(
+        &self,
+        output_path: &str,
+        device_id: Option<&str>,
+        duration: Option<Duration>,
+    ) -> Result<()> {
         info!("Recording test to: {}", output_path);
 
-        // TODO: Implement test recording functionality
-        // This would involve capturing user interactions and generating test cases
-        warn!("Test recording not yet implemented");
+        let adb_path = self
+            .config
+            .android
+            .adb_path
+            .as_ref()
+            .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?;
 
+        let device_id = match device_id {
+            Some(id) => id.to_string(),
+            None => first_adb_device(adb_path)?,
+        };
+
+        let scale = self.detect_input_scale(adb_path, &device_id);
+        let mut hierarchy = self.try_dump_hierarchy(adb_path, &device_id);
+
+        let mut child = tokio::process::Command::new(adb_path)
+            .args(["-s", &device_id, "shell", "getevent", "-lt"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            KMobileError::CommandError("Failed to capture getevent output".to_string())
+        })?;
+        let mut lines = BufReader::new(stdout).lines();
+
+        println!("⏺️  Recording input on {device_id}. Press Ctrl-C to stop.");
+
+        let deadline = async {
+            match duration {
+                Some(duration) => tokio::time::sleep(duration).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(deadline);
+
+        let mut detector = GestureDetector::new(scale);
+        let mut steps: Vec<TestStep> = Vec::new();
+        let mut last_gesture_end: Option<f64> = None;
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let Some(line) = line? else {
+                        warn!("getevent exited, stopping recording");
+                        break;
+                    };
+                    let Some(event) = recorder::parse_getevent_line(&line) else {
+                        continue;
+                    };
+                    let Some(gesture) = detector.feed(&event) else {
+                        continue;
+                    };
+
+                    if let (Some(previous), Some(end)) = (steps.last_mut(), last_gesture_end) {
+                        let gap = Duration::from_secs_f64((gesture.started_at - end).max(0.0));
+                        previous.wait_time = Some(gap.min(MAX_RECORDED_WAIT));
+                    }
+                    last_gesture_end = Some(gesture.ended_at);
+
+                    let step = recorder::gesture_to_step(&gesture.gesture, hierarchy.as_ref());
+                    debug!("Recorded {:?} on {:?}", step.action, step.target);
+                    println!("   {:?} {}", step.action, step.target.as_deref().unwrap_or_default());
+                    steps.push(step);
+
+                    // The next gesture is resolved against the screen as it is now
+                    hierarchy = self.try_dump_hierarchy(adb_path, &device_id);
+                }
+                _ = tokio::signal::ctrl_c() => break,
+                _ = &mut deadline => break,
+            }
+        }
+
+        let _ = child.kill().await;
+
+        if detector.is_touching() {
+            warn!("Recording stopped mid-gesture; the last gesture was dropped");
+        }
+
+        let name = Path::new(output_path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("recorded_test")
+            .to_string();
+        let test_case = TestCase {
+            name: name.clone(),
+            description: Some(format!(
+                "Recorded on {device_id} at {}",
+                Utc::now().to_rfc3339()
+            )),
+            steps,
+            expected_result: None,
+            timeout: Some(Duration::from_secs(self.config.testing.timeout)),
+            tags: Vec::new(),
+            hooks: TestHooks::default(),
+            fixtures: Vec::new(),
+        };
+
+        fs::write(output_path, serde_json::to_string_pretty(&test_case)?)?;
+        info!(
+            "Recorded {} steps for test '{}'",
+            test_case.steps.len(),
+            name
+        );
+
+        Ok(())
+    }
+
+    fn detect_input_scale(&self, adb_path: &Path, device_id: &str) -> Option<InputScale> {
+        let getevent = Command::new(adb_path)
+            .args(["-s", device_id, "shell", "getevent", "-lp"])
+            .output()
+            .ok()?;
+        let (max_x, max_y) =
+            recorder::parse_axis_ranges(&String::from_utf8_lossy(&getevent.stdout))?;
+
+        let wm_size = Command::new(adb_path)
+            .args(["-s", device_id, "shell", "wm", "size"])
+            .output()
+            .ok()?;
+        let (screen_width, screen_height) =
+            recorder::parse_wm_size(&String::from_utf8_lossy(&wm_size.stdout))?;
+
+        debug!(
+            "Touch axis range {}x{} mapped to screen {}x{}",
+            max_x, max_y, screen_width, screen_height
+        );
+
+        Some(InputScale {
+            max_x,
+            max_y,
+            screen_width,
+            screen_height,
+        })
+    }
+
+    fn try_dump_hierarchy(&self, adb_path: &Path, device_id: &str) -> Option<UiHierarchy> {
+        match ui_hierarchy::dump_android_hierarchy(adb_path, device_id) {
+            Ok(hierarchy) => Some(hierarchy),
+            Err(e) => {
+                warn!(
+                    "uiautomator dump failed, falling back to coordinates: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Saves an app's private data so tests can start from it with an
+    /// `app_data_snapshot` fixture
+    pub async fn save_app_data(
+        &self,
+        package: &str,
+        output_path: &str,
+        device_id: Option<&str>,
+    ) -> Result<()> {
+        let adb_path = self
+            .config
+            .android
+            .adb_path
+            .as_ref()
+            .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?;
+        let device_id = match device_id {
+            Some(id) => id.to_string(),
+            None => first_adb_device(adb_path)?,
+        };
+
+        lifecycle::save_app_data(adb_path, &device_id, package, Path::new(output_path))?;
+        info!("Saved app data for {} from {}", package, device_id);
         Ok(())
     }
 
-    pub async fn replay_test(&self, test_file: &str) -> Result<()> {
+    /// Replays a recorded `TestCase` on a device. With `step_mode` each step waits
+    /// for confirmation at an interactive prompt.
+    pub async fn replay_test(
+        &self,
+        test_file: &str,
+        device_id: Option<&str>,
+        step_mode: bool,
+    ) -> Result<()> {
         info!("Replaying test from: {}", test_file);
 
         let test_path = PathBuf::from(test_file);
@@ -594,9 +1446,33 @@ impl TestRunner {
         }
 
         let content = fs::read_to_string(&test_path)?;
-        let test_case: TestCase = serde_json::from_str(&content)?;
+        let mut test_case: TestCase = serde_json::from_str(&content)?;
+
+        let device_id = match (device_id, &self.config.android.adb_path) {
+            (Some(id), _) => Some(id.to_string()),
+            (None, Some(adb_path)) => Some(first_adb_device(adb_path)?),
+            (None, None) => {
+                warn!("No device given and ADB path not configured; device steps will be skipped");
+                None
+            }
+        };
+        if let Some(id) = &device_id {
+            info!("Replaying on device: {}", id);
+        }
 
-        let result = self.run_test_case(&test_case, None).await?;
+        let result = if step_mode {
+            let run = self
+                .step_through(&mut test_case, device_id.as_deref())
+                .await?;
+            if run.edited {
+                let backup = crate::utils::create_backup_file(test_file)?;
+                fs::write(&test_path, serde_json::to_string_pretty(&test_case)?)?;
+                println!("💾 Saved edited steps to {test_file} (backup: {backup})");
+            }
+            run.result
+        } else {
+            self.run_test_case(&test_case, device_id.as_deref()).await?
+        };
 
         match result.status {
             TestStatus::Passed => println!("✅ Test '{}' passed", test_case.name),
@@ -611,3 +1487,106 @@ impl TestRunner {
         Ok(())
     }
 }
+
+/// Apps a case exercises: those it launches, in its own steps or in hooks, and
+/// those its fixtures prepare
+fn packages_under_test(suite: &TestSuite, test_case: &TestCase) -> Vec<String> {
+    let hooks = [&suite.hooks, &test_case.hooks];
+    let steps = test_case.steps.iter().chain(hooks.iter().flat_map(|hooks| {
+        hooks
+            .before_all
+            .iter()
+            .chain(&hooks.before_each)
+            .chain(&hooks.after_each)
+            .chain(&hooks.after_all)
+    }));
+    let launched = steps
+        .filter(|step| matches!(step.action, TestAction::Launch))
+        .filter_map(|step| step.target.as_deref())
+        .map(|target| target.split('/').next().unwrap_or(target));
+    let prepared = suite
+        .fixtures
+        .iter()
+        .chain(&test_case.fixtures)
+        .filter_map(Fixture::package);
+
+    let mut packages: Vec<String> = launched.chain(prepared).map(str::to_string).collect();
+    packages.sort();
+    packages.dedup();
+    packages
+}
+
+/// `input swipe` arguments that scroll the content in `direction`. Scrolling down
+/// means dragging the finger up.
+fn scroll_swipe(direction: &str, width: i32, height: i32) -> Result<String> {
+    let (cx, cy) = (width / 2, height / 2);
+    let (dx, dy) = (width * 3 / 10, height * 3 / 10);
+    let (x1, y1, x2, y2) = match direction.to_lowercase().as_str() {
+        "down" => (cx, cy + dy, cx, cy - dy),
+        "up" => (cx, cy - dy, cx, cy + dy),
+        "right" => (cx + dx, cy, cx - dx, cy),
+        "left" => (cx - dx, cy, cx + dx, cy),
+        other => {
+            return Err(
+                KMobileError::InvalidInput(format!("Unknown scroll direction: {other}")).into(),
+            )
+        }
+    };
+    Ok(format!("{x1} {y1} {x2} {y2} 400"))
+}
+
+/// `input text` argument for `text`, escaped for the device shell that `adb shell`
+/// hands it to. `input text` splits on spaces, which it expects as `%s`.
+pub(crate) fn input_text_arg(text: &str) -> String {
+    let mut arg = String::with_capacity(text.len() * 2);
+    for c in text.chars() {
+        match c {
+            ' ' => arg.push_str("%s"),
+            c if c.is_ascii_alphanumeric() => arg.push(c),
+            c => {
+                arg.push('\\');
+                arg.push(c);
+            }
+        }
+    }
+    arg
+}
+
+/// adb arguments that start an app: `am start -n` for a `package/.Activity`
+/// component, or the package's launcher activity through `monkey` for a bare
+/// package name
+pub(crate) fn launch_args(device_id: &str, app_id: &str) -> Vec<String> {
+    let command = if app_id.contains('/') {
+        vec!["am", "start", "-n", app_id]
+    } else {
+        vec![
+            "monkey",
+            "-p",
+            app_id,
+            "-c",
+            "android.intent.category.LAUNCHER",
+            "1",
+        ]
+    };
+    ["-s", device_id, "shell"]
+        .into_iter()
+        .chain(command)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the serial of the first device listed by `adb devices`
+pub(crate) fn first_adb_device(adb_path: &Path) -> Result<String> {
+    let output = Command::new(adb_path).args(["devices"]).output()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .find_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(serial), Some("device")) => Some(serial.to_string()),
+                _ => None,
+            }
+        })
+        .ok_or_else(|| KMobileError::DeviceNotFound("No adb device connected".to_string()).into())
+}
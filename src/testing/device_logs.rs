@@ -0,0 +1,329 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::KMobileError;
+use crate::utils::sanitize_filename;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrashKind {
+    Crash,
+    Anr,
+}
+
+/// An app crash or ANR found in a device log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub kind: CrashKind,
+    pub process: Option<String>,
+    pub trace: String,
+}
+
+impl CrashReport {
+    pub fn summary(&self) -> String {
+        let process = self.process.as_deref().unwrap_or("unknown process");
+        match self.kind {
+            CrashKind::Crash => format!("App crashed ({process})"),
+            CrashKind::Anr => format!("App not responding ({process})"),
+        }
+    }
+
+    /// Whether the crash belongs to one of `packages`, including their `:service`
+    /// subprocesses. iOS logs name the executable rather than the bundle id, so that
+    /// matches the bundle id's last component. All crashes are kept when no package
+    /// is given, and none whose process isn't known otherwise.
+    pub fn is_from(&self, packages: &[String]) -> bool {
+        if packages.is_empty() {
+            return true;
+        }
+        let Some(process) = &self.process else {
+            return false;
+        };
+        packages.iter().any(|package| {
+            process == package
+                || process
+                    .strip_prefix(package.as_str())
+                    .is_some_and(|rest| rest.starts_with(':'))
+                || package
+                    .rsplit('.')
+                    .next()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(process))
+        })
+    }
+}
+
+/// Streams the device log for one test case into a file next to its screenshots
+pub struct LogCapture {
+    child: tokio::process::Child,
+    path: PathBuf,
+}
+
+impl LogCapture {
+    /// Clears logcat and starts `adb logcat` for Android devices, or
+    /// `simctl spawn log stream` for iOS simulators (UUID device ids)
+    pub fn start(
+        adb_path: Option<&Path>,
+        device_id: &str,
+        output_dir: &Path,
+        test_name: &str,
+    ) -> Result<Self> {
+        let name = sanitize_filename(test_name);
+        let is_simulator = uuid::Uuid::parse_str(device_id).is_ok();
+        let path = if is_simulator {
+            output_dir.join(format!("{name}_device.log"))
+        } else {
+            output_dir.join(format!("{name}_logcat.txt"))
+        };
+        let file = fs::File::create(&path)?;
+
+        let mut command = if is_simulator {
+            let mut command = tokio::process::Command::new("xcrun");
+            command.args([
+                "simctl", "spawn", device_id, "log", "stream", "--style", "compact",
+            ]);
+            command
+        } else {
+            let adb_path = adb_path
+                .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?;
+            let cleared = Command::new(adb_path)
+                .args(["-s", device_id, "logcat", "-c"])
+                .output()?;
+            if !cleared.status.success() {
+                warn!(
+                    "Failed to clear logcat: {}",
+                    String::from_utf8_lossy(&cleared.stderr)
+                );
+            }
+
+            let mut command = tokio::process::Command::new(adb_path);
+            command.args(["-s", device_id, "logcat", "-v", "threadtime"]);
+            command
+        };
+
+        let child = command
+            .stdout(Stdio::from(file))
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        debug!("Capturing device log for {} into {:?}", test_name, path);
+        Ok(Self { child, path })
+    }
+
+    /// Stops the capture and returns the log file with any crashes found in it
+    pub async fn stop(mut self) -> Result<(PathBuf, Vec<CrashReport>)> {
+        // Give the log a moment to flush anything written right at the end of the test
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let _ = self.child.kill().await;
+
+        let bytes = fs::read(&self.path)?;
+        let crashes = detect_crashes(&String::from_utf8_lossy(&bytes));
+        Ok((self.path, crashes))
+    }
+}
+
+/// Finds `FATAL EXCEPTION` and `ANR in` blocks in Android logs (threadtime or brief
+/// format), and uncaught exception terminations in iOS simulator logs
+pub fn detect_crashes(log: &str) -> Vec<CrashReport> {
+    let lines: Vec<&str> = log.lines().collect();
+    let mut crashes = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let kind = if line.contains("FATAL EXCEPTION") {
+            Some(CrashKind::Crash)
+        } else if line.contains("ANR in ") {
+            Some(CrashKind::Anr)
+        } else if line.contains("Terminating app due to uncaught exception") {
+            Some(CrashKind::Crash)
+        } else {
+            None
+        };
+
+        let Some(kind) = kind else {
+            i += 1;
+            continue;
+        };
+
+        let tag = logcat_tag(line);
+        let mut block = vec![logcat_message(line)];
+        i += 1;
+
+        // Android blocks continue for as long as the same tag keeps logging
+        if let Some(tag) = tag {
+            while i < lines.len() && logcat_tag(lines[i]) == Some(tag) {
+                let message = logcat_message(lines[i]);
+                if message.contains("FATAL EXCEPTION") || message.contains("ANR in ") {
+                    break;
+                }
+                block.push(message);
+                i += 1;
+            }
+        }
+
+        let process = tag
+            .is_none()
+            .then(|| ios_process(line))
+            .flatten()
+            .or_else(|| {
+                block.iter().find_map(|message| {
+                    if let Some(rest) = message.strip_prefix("Process: ") {
+                        return rest.split(',').next().map(|p| p.trim().to_string());
+                    }
+                    message
+                        .split("ANR in ")
+                        .nth(1)
+                        .and_then(|rest| rest.split_whitespace().next())
+                        .map(|p| p.to_string())
+                })
+            });
+
+        crashes.push(CrashReport {
+            kind,
+            process,
+            trace: block.join("\n"),
+        });
+    }
+
+    crashes
+}
+
+/// Returns the tag of a logcat line in threadtime (`date time pid tid L tag: msg`)
+/// or brief (`L/tag( pid): msg`) format
+fn logcat_tag(line: &str) -> Option<&str> {
+    let header = &line[..line.find(": ")?];
+
+    if let Some((level, rest)) = header.split_once('/') {
+        if level.len() == 1 {
+            return Some(rest.split('(').next().unwrap_or(rest).trim());
+        }
+    }
+
+    let mut parts = header.split_whitespace();
+    let level = parts.nth(4)?;
+    if level.len() != 1 {
+        return None;
+    }
+    let tag: Vec<&str> = parts.collect();
+    if tag.is_empty() {
+        None
+    } else {
+        let start = header.find(tag[0])?;
+        Some(header[start..].trim())
+    }
+}
+
+/// Returns the process of an iOS compact log line
+/// (`date time Ty Process[pid:tid] message`)
+fn ios_process(line: &str) -> Option<String> {
+    line.split_whitespace().find_map(|word| {
+        let (name, ids) = word.strip_suffix(']')?.split_once('[')?;
+        let is_ids = !ids.is_empty() && ids.chars().all(|c| c.is_ascii_digit() || c == ':');
+        (is_ids && !name.is_empty()).then(|| name.to_string())
+    })
+}
+
+fn logcat_message(line: &str) -> String {
+    match (logcat_tag(line), line.find(": ")) {
+        (Some(_), Some(index)) => line[index + 2..].to_string(),
+        _ => line.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGCAT: &str = "\
+10-18 12:00:00.100  1234  1234 I ActivityManager: Start proc 1234:com.example.app/u0a123
+10-18 12:00:01.000  1234  1234 E AndroidRuntime: FATAL EXCEPTION: main
+10-18 12:00:01.000  1234  1234 E AndroidRuntime: Process: com.example.app, PID: 1234
+10-18 12:00:01.000  1234  1234 E AndroidRuntime: java.lang.NullPointerException: boom
+10-18 12:00:01.000  1234  1234 E AndroidRuntime: \tat com.example.app.MainActivity.onCreate(MainActivity.kt:42)
+10-18 12:00:01.100   500   520 I ActivityManager: Process com.example.app (pid 1234) has died
+10-18 12:00:09.000   500   520 E ActivityManager: ANR in com.example.other (com.example.other/.MainActivity)
+10-18 12:00:09.000   500   520 E ActivityManager: PID: 4321
+10-18 12:00:09.000   500   520 E ActivityManager: Reason: Input dispatching timed out
+10-18 12:00:09.100   500   520 I WindowManager: Focus changed
+";
+
+    #[test]
+    fn test_detect_crash_and_anr() {
+        let crashes = detect_crashes(LOGCAT);
+        assert_eq!(crashes.len(), 2);
+
+        assert_eq!(crashes[0].kind, CrashKind::Crash);
+        assert_eq!(crashes[0].process.as_deref(), Some("com.example.app"));
+        assert_eq!(crashes[0].trace.lines().count(), 4);
+        assert!(crashes[0]
+            .trace
+            .contains("at com.example.app.MainActivity.onCreate"));
+
+        assert_eq!(crashes[1].kind, CrashKind::Anr);
+        assert_eq!(crashes[1].process.as_deref(), Some("com.example.other"));
+        assert!(crashes[1]
+            .trace
+            .ends_with("Reason: Input dispatching timed out"));
+    }
+
+    #[test]
+    fn test_crashes_of_other_processes_are_ignored() {
+        let log = "\
+10-18 12:00:01.000   900   900 E AndroidRuntime: FATAL EXCEPTION: main
+10-18 12:00:01.000   900   900 E AndroidRuntime: Process: com.android.systemui, PID: 900
+10-18 12:00:02.000  1234  1250 E AndroidRuntime: FATAL EXCEPTION: sync
+10-18 12:00:02.000  1234  1250 E AndroidRuntime: Process: com.example.app:sync, PID: 1234
+";
+        let packages = vec!["com.example.app".to_string()];
+        let crashes: Vec<_> = detect_crashes(log)
+            .into_iter()
+            .filter(|crash| crash.is_from(&packages))
+            .collect();
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].process.as_deref(), Some("com.example.app:sync"));
+
+        assert!(detect_crashes(LOGCAT)
+            .iter()
+            .all(|crash| crash.is_from(&[])));
+    }
+
+    #[test]
+    fn test_ios_crashes_are_attributed_to_their_process() {
+        let log = "\
+2026-10-18 12:00:01.000 E  SpringBoard[88:1203] *** Terminating app due to uncaught exception 'NSRangeException'
+2026-10-18 12:00:02.000 E  Example[4321:9876] *** Terminating app due to uncaught exception 'NSInvalidArgumentException'
+2026-10-18 12:00:03.000 E  *** Terminating app due to uncaught exception 'NSGenericException'
+";
+        let crashes = detect_crashes(log);
+        assert_eq!(crashes.len(), 3);
+        assert_eq!(crashes[0].process.as_deref(), Some("SpringBoard"));
+        assert_eq!(crashes[2].process, None);
+
+        let packages = vec!["com.example.Example".to_string()];
+        let ours: Vec<_> = crashes
+            .iter()
+            .filter(|crash| crash.is_from(&packages))
+            .collect();
+        assert_eq!(ours.len(), 1);
+        assert_eq!(ours[0].process.as_deref(), Some("Example"));
+        assert!(crashes.iter().all(|crash| crash.is_from(&[])));
+    }
+
+    #[test]
+    fn test_detect_crash_brief_format() {
+        let log = "E/AndroidRuntime( 1234): FATAL EXCEPTION: main\nE/AndroidRuntime( 1234): Process: com.example.app, PID: 1234\nI/Other( 1): done";
+        let crashes = detect_crashes(log);
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].trace.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_no_crashes() {
+        assert!(detect_crashes("10-18 12:00:00.100  1  1 I Tag: all good").is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use super::{TestReport, TestStatus};
+
+/// Append-only run history kept next to the reports
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// Flakiness score from which a case is reported as flaky
+pub const FLAKY_THRESHOLD: f64 = 0.1;
+
+/// One line of `history.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub run_id: String,
+    pub suite_name: String,
+    pub start_time: DateTime<Utc>,
+    pub device_id: Option<String>,
+    pub results: Vec<CaseRun>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseRun {
+    pub name: String,
+    pub status: TestStatus,
+    pub duration_ms: u64,
+}
+
+impl HistoryEntry {
+    pub fn from_report(report: &TestReport, device_id: Option<&str>) -> Self {
+        Self {
+            run_id: uuid::Uuid::new_v4().to_string(),
+            suite_name: report.suite_name.clone(),
+            start_time: report.start_time,
+            device_id: device_id.map(String::from),
+            results: report
+                .results
+                .iter()
+                .map(|result| CaseRun {
+                    name: result.test_name.clone(),
+                    status: result.status.clone(),
+                    duration_ms: result.duration.as_millis() as u64,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub fn append(output_dir: &Path, entry: &HistoryEntry) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(output_dir.join(HISTORY_FILE))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Reads the history oldest first. Lines that don't parse (e.g. a run killed mid-write)
+/// are skipped.
+pub fn load(output_dir: &Path) -> Result<Vec<HistoryEntry>> {
+    let path = output_dir.join(HISTORY_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for (i, line) in fs::read_to_string(&path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("Skipping line {} of {:?}: {}", i + 1, path, e),
+        }
+    }
+    entries.sort_by_key(|entry| entry.start_time);
+    Ok(entries)
+}
+
+/// Aggregated history of one test case
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseStats {
+    pub suite_name: String,
+    pub name: String,
+    pub runs: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub pass_rate: f64,
+    pub mean_duration: Duration,
+    /// Mean duration of the newer half of the window relative to the older half,
+    /// e.g. `0.25` means 25% slower
+    pub duration_trend: Option<f64>,
+    /// Share of consecutive runs whose outcome flipped between pass and fail
+    pub flakiness: f64,
+    /// Flaky in the window but stable in the window before it
+    pub newly_flaky: bool,
+}
+
+impl CaseStats {
+    pub fn is_flaky(&self) -> bool {
+        self.flakiness >= FLAKY_THRESHOLD
+    }
+}
+
+/// Computes stats over the last `window` runs of every case, optionally for one suite
+pub fn compute_stats(
+    entries: &[HistoryEntry],
+    suite_name: Option<&str>,
+    window: usize,
+) -> Vec<CaseStats> {
+    let mut runs: BTreeMap<(String, String), Vec<&CaseRun>> = BTreeMap::new();
+    for entry in entries {
+        if suite_name.is_some_and(|suite| suite != entry.suite_name) {
+            continue;
+        }
+        for run in &entry.results {
+            runs.entry((entry.suite_name.clone(), run.name.clone()))
+                .or_default()
+                .push(run);
+        }
+    }
+
+    let window = window.max(2);
+    runs.into_iter()
+        .map(|((suite_name, name), all_runs)| {
+            let split = all_runs.len().saturating_sub(window);
+            let (previous, recent) = all_runs.split_at(split);
+            let previous = &previous[previous.len().saturating_sub(window)..];
+
+            let passed = count(recent, |s| matches!(s, TestStatus::Passed));
+            let failed = count(recent, |s| {
+                matches!(s, TestStatus::Failed | TestStatus::Timeout)
+            });
+            let executed = passed + failed;
+
+            let total_ms: u64 = recent.iter().map(|run| run.duration_ms).sum();
+            let mean_duration = Duration::from_millis(total_ms / recent.len().max(1) as u64);
+
+            let recent_flakiness = flakiness(recent);
+            let newly_flaky = recent_flakiness >= FLAKY_THRESHOLD
+                && previous.len() >= window / 2
+                && flakiness(previous) < FLAKY_THRESHOLD;
+
+            CaseStats {
+                suite_name,
+                name,
+                runs: recent.len(),
+                passed,
+                failed,
+                pass_rate: if executed == 0 {
+                    0.0
+                } else {
+                    passed as f64 / executed as f64
+                },
+                mean_duration,
+                duration_trend: duration_trend(recent),
+                flakiness: recent_flakiness,
+                newly_flaky,
+            }
+        })
+        .collect()
+}
+
+fn count(runs: &[&CaseRun], predicate: impl Fn(&TestStatus) -> bool) -> usize {
+    runs.iter().filter(|run| predicate(&run.status)).count()
+}
+
+/// Flip rate between pass and fail over consecutive executed runs; skips are ignored
+pub fn flakiness(runs: &[&CaseRun]) -> f64 {
+    let outcomes: Vec<bool> = runs
+        .iter()
+        .filter_map(|run| match run.status {
+            TestStatus::Passed => Some(true),
+            TestStatus::Failed | TestStatus::Timeout => Some(false),
+            TestStatus::Skipped => None,
+        })
+        .collect();
+
+    if outcomes.len() < 2 {
+        return 0.0;
+    }
+    let flips = outcomes
+        .windows(2)
+        .filter(|pair| pair[0] != pair[1])
+        .count();
+    flips as f64 / (outcomes.len() - 1) as f64
+}
+
+fn duration_trend(runs: &[&CaseRun]) -> Option<f64> {
+    if runs.len() < 4 {
+        return None;
+    }
+    let (older, newer) = runs.split_at(runs.len() / 2);
+    let mean = |runs: &[&CaseRun]| {
+        runs.iter().map(|run| run.duration_ms as f64).sum::<f64>() / runs.len() as f64
+    };
+    let older = mean(older);
+    if older == 0.0 {
+        return None;
+    }
+    Some((mean(newer) - older) / older)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(run: i64, results: &[(&str, TestStatus, u64)]) -> HistoryEntry {
+        HistoryEntry {
+            run_id: run.to_string(),
+            suite_name: "smoke".to_string(),
+            start_time: Utc.timestamp_opt(1_700_000_000 + run * 60, 0).unwrap(),
+            device_id: None,
+            results: results
+                .iter()
+                .map(|(name, status, duration_ms)| CaseRun {
+                    name: name.to_string(),
+                    status: status.clone(),
+                    duration_ms: *duration_ms,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_append_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        append(dir.path(), &entry(2, &[("login", TestStatus::Passed, 10)])).unwrap();
+        append(dir.path(), &entry(1, &[("login", TestStatus::Failed, 10)])).unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(HISTORY_FILE))
+            .unwrap()
+            .write_all(b"{\"truncated\n")
+            .unwrap();
+
+        let entries = load(dir.path()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].run_id, "1");
+    }
+
+    #[test]
+    fn test_compute_stats_flags_newly_flaky() {
+        let mut entries = Vec::new();
+        for run in 0..8 {
+            // `checkout` is stable for four runs, then alternates
+            let checkout = if run >= 4 && run % 2 == 1 {
+                TestStatus::Failed
+            } else {
+                TestStatus::Passed
+            };
+            entries.push(entry(
+                run,
+                &[
+                    ("login", TestStatus::Passed, 100 + run as u64 * 100),
+                    ("checkout", checkout, 50),
+                ],
+            ));
+        }
+
+        let stats = compute_stats(&entries, Some("smoke"), 4);
+        let checkout = stats.iter().find(|s| s.name == "checkout").unwrap();
+        let login = stats.iter().find(|s| s.name == "login").unwrap();
+
+        assert_eq!(checkout.runs, 4);
+        assert_eq!(checkout.pass_rate, 0.5);
+        assert_eq!(checkout.flakiness, 1.0);
+        assert!(checkout.newly_flaky);
+
+        assert_eq!(login.pass_rate, 1.0);
+        assert!(!login.is_flaky());
+        assert_eq!(login.mean_duration, Duration::from_millis(650));
+        assert!(login.duration_trend.unwrap() > 0.0);
+
+        assert!(compute_stats(&entries, Some("other"), 4).is_empty());
+    }
+}
@@ -0,0 +1,565 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+use super::{TestCase, TestResult, TestRunner, TestStatus, TestStep, TestSuite};
+use crate::emulator;
+use crate::error::KMobileError;
+use crate::hardware_emulator::NetworkType;
+
+/// Step lists run around a suite or a test case. At suite level `before_each` and
+/// `after_each` wrap every test case; at case level they wrap every step.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestHooks {
+    pub before_all: Vec<TestStep>,
+    pub before_each: Vec<TestStep>,
+    pub after_each: Vec<TestStep>,
+    pub after_all: Vec<TestStep>,
+}
+
+impl TestHooks {
+    pub fn is_empty(&self) -> bool {
+        self.before_all.is_empty()
+            && self.before_each.is_empty()
+            && self.after_each.is_empty()
+            && self.after_all.is_empty()
+    }
+}
+
+/// Reusable device state applied before a test case and undone after it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Fixture {
+    /// Uninstalls `package` and installs `apk` again
+    FreshInstall { package: String, apk: PathBuf },
+    /// Wipes the app's data with `pm clear`
+    ClearData { package: String },
+    /// Restores app data saved with `kmobile test save-app-data`, e.g. a logged-in session.
+    /// Requires a debuggable build, since it goes through `run-as`.
+    AppDataSnapshot { package: String, snapshot: PathBuf },
+    /// Loads an emulator snapshot saved with `kmobile simulator snapshot save`. This
+    /// replaces the whole device state, so list it before other fixtures.
+    EmulatorSnapshot { snapshot: String },
+    /// Battery, network, rotation and theme overrides
+    Hardware(HardwarePreset),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HardwarePreset {
+    /// Battery level in percent
+    pub battery_level: Option<u8>,
+    pub charging: Option<bool>,
+    pub network: Option<NetworkType>,
+    pub airplane_mode: Option<bool>,
+    pub orientation: Option<Orientation>,
+    pub dark_mode: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+    ReversePortrait,
+    ReverseLandscape,
+}
+
+impl Orientation {
+    fn user_rotation(self) -> u8 {
+        match self {
+            Orientation::Portrait => 0,
+            Orientation::Landscape => 1,
+            Orientation::ReversePortrait => 2,
+            Orientation::ReverseLandscape => 3,
+        }
+    }
+}
+
+impl Fixture {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fixture::FreshInstall { .. } => "fresh_install",
+            Fixture::ClearData { .. } => "clear_data",
+            Fixture::AppDataSnapshot { .. } => "app_data_snapshot",
+            Fixture::EmulatorSnapshot { .. } => "emulator_snapshot",
+            Fixture::Hardware(_) => "hardware",
+        }
+    }
+
+    /// The app the fixture prepares, if any
+    pub fn package(&self) -> Option<&str> {
+        match self {
+            Fixture::FreshInstall { package, .. }
+            | Fixture::ClearData { package }
+            | Fixture::AppDataSnapshot { package, .. } => Some(package),
+            Fixture::EmulatorSnapshot { .. } | Fixture::Hardware(_) => None,
+        }
+    }
+
+    /// `adb shell` commands that apply the fixture. Snapshot restores also stream the
+    /// archive into the app's data dir, see [`restore_app_data`].
+    pub fn setup_commands(&self) -> Vec<Vec<String>> {
+        let shell = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        match self {
+            Fixture::FreshInstall { package, .. } => vec![shell(&["pm", "uninstall", package])],
+            Fixture::ClearData { package } => vec![shell(&["pm", "clear", package])],
+            Fixture::AppDataSnapshot { package, .. } => {
+                vec![shell(&["am", "force-stop", package])]
+            }
+            Fixture::EmulatorSnapshot { .. } => Vec::new(),
+            Fixture::Hardware(preset) => {
+                let mut commands = Vec::new();
+                if let Some(level) = preset.battery_level {
+                    commands.push(shell(&[
+                        "dumpsys",
+                        "battery",
+                        "set",
+                        "level",
+                        &level.to_string(),
+                    ]));
+                }
+                if let Some(charging) = preset.charging {
+                    let value = if charging { "1" } else { "0" };
+                    commands.push(shell(&["dumpsys", "battery", "set", "ac", value]));
+                    commands.push(shell(&[
+                        "dumpsys",
+                        "battery",
+                        "set",
+                        "status",
+                        if charging { "2" } else { "3" },
+                    ]));
+                }
+                if let Some(network) = &preset.network {
+                    let (wifi, data) = match network {
+                        NetworkType::Wifi | NetworkType::Ethernet => ("enable", "disable"),
+                        NetworkType::Cellular4G | NetworkType::Cellular5G => ("disable", "enable"),
+                        NetworkType::Offline => ("disable", "disable"),
+                    };
+                    commands.push(shell(&["svc", "wifi", wifi]));
+                    commands.push(shell(&["svc", "data", data]));
+                }
+                if let Some(airplane) = preset.airplane_mode {
+                    let value = if airplane { "enable" } else { "disable" };
+                    commands.push(shell(&["cmd", "connectivity", "airplane-mode", value]));
+                }
+                if let Some(orientation) = preset.orientation {
+                    commands.push(shell(&[
+                        "settings",
+                        "put",
+                        "system",
+                        "accelerometer_rotation",
+                        "0",
+                    ]));
+                    commands.push(shell(&[
+                        "settings",
+                        "put",
+                        "system",
+                        "user_rotation",
+                        &orientation.user_rotation().to_string(),
+                    ]));
+                }
+                if let Some(dark) = preset.dark_mode {
+                    let value = if dark { "yes" } else { "no" };
+                    commands.push(shell(&["cmd", "uimode", "night", value]));
+                }
+                commands
+            }
+        }
+    }
+
+    /// `adb shell` commands that put the device back the way tests expect to find it
+    pub fn teardown_commands(&self) -> Vec<Vec<String>> {
+        let shell = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        let Fixture::Hardware(preset) = self else {
+            return Vec::new();
+        };
+
+        let mut commands = Vec::new();
+        if preset.battery_level.is_some() || preset.charging.is_some() {
+            commands.push(shell(&["dumpsys", "battery", "reset"]));
+        }
+        if preset.network.is_some() {
+            commands.push(shell(&["svc", "wifi", "enable"]));
+            commands.push(shell(&["svc", "data", "enable"]));
+        }
+        if preset.airplane_mode.is_some() {
+            commands.push(shell(&["cmd", "connectivity", "airplane-mode", "disable"]));
+        }
+        if preset.orientation.is_some() {
+            commands.push(shell(&["settings", "put", "system", "user_rotation", "0"]));
+            commands.push(shell(&[
+                "settings",
+                "put",
+                "system",
+                "accelerometer_rotation",
+                "1",
+            ]));
+        }
+        if preset.dark_mode.is_some() {
+            commands.push(shell(&["cmd", "uimode", "night", "auto"]));
+        }
+        commands
+    }
+}
+
+/// Saves the app's private data as a tar archive, for use in an `app_data_snapshot` fixture
+pub fn save_app_data(adb_path: &Path, device_id: &str, package: &str, output: &Path) -> Result<()> {
+    let output_data = Command::new(adb_path)
+        .args([
+            "-s", device_id, "exec-out", "run-as", package, "tar", "-cf", "-", ".",
+        ])
+        .output()?;
+
+    if !output_data.status.success() || output_data.stdout.is_empty() {
+        let error_msg = String::from_utf8_lossy(&output_data.stderr);
+        return Err(KMobileError::CommandError(format!(
+            "Failed to read app data for {package} (is the build debuggable?): {error_msg}"
+        ))
+        .into());
+    }
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, &output_data.stdout)?;
+    Ok(())
+}
+
+/// Streams a snapshot made by [`save_app_data`] back into the app's data dir
+pub fn restore_app_data(
+    adb_path: &Path,
+    device_id: &str,
+    package: &str,
+    snapshot: &Path,
+) -> Result<()> {
+    let archive = fs::File::open(snapshot)
+        .map_err(|_| KMobileError::TestFileNotFound(snapshot.display().to_string()))?;
+
+    let output = Command::new(adb_path)
+        .args([
+            "-s", device_id, "exec-in", "run-as", package, "tar", "-xf", "-",
+        ])
+        .stdin(Stdio::from(archive))
+        .output()?;
+
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(KMobileError::CommandError(format!(
+            "Failed to restore app data for {package}: {error_msg}"
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+impl TestRunner {
+    /// Runs hook steps under `label`. Setup hooks stop at the first failing step;
+    /// teardown hooks (`after_*`) run every step and report the first failure.
+    pub(super) async fn run_hook_steps(
+        &self,
+        owner: &str,
+        label: &str,
+        steps: &[TestStep],
+        device_id: Option<&str>,
+        screenshots: &mut Vec<String>,
+    ) -> Result<()> {
+        let teardown = label.starts_with("after");
+        let mut first_error = None;
+
+        for (i, step) in steps.iter().enumerate() {
+            let step_name = format!("{owner}_{label}_{}", i + 1);
+            if let Err(e) = self
+                .execute_test_step(step, &step_name, device_id, screenshots)
+                .await
+            {
+                let error = format!("{label} step {} failed: {e}", i + 1);
+                if !teardown {
+                    return Err(KMobileError::TestExecutionError(error).into());
+                }
+                warn!("{}: {}", owner, error);
+                first_error.get_or_insert(error);
+            }
+        }
+
+        match first_error {
+            Some(error) => Err(KMobileError::TestExecutionError(error).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs one test case with its fixtures and hooks. Everything that was set up is
+    /// torn down again, even when setup or the test itself failed.
+    pub(super) async fn run_case_lifecycle(
+        &self,
+        suite: &TestSuite,
+        test_case: &TestCase,
+        device_id: Option<&str>,
+    ) -> Result<TestResult> {
+        let start_time = Instant::now();
+        let mut screenshots = Vec::new();
+        let mut applied = Vec::new();
+        let mut setup_error = None;
+
+        for fixture in suite.fixtures.iter().chain(&test_case.fixtures) {
+            match self.apply_fixture(fixture, device_id).await {
+                Ok(()) => applied.push(fixture),
+                Err(e) => {
+                    setup_error = Some(format!("Fixture {} failed: {e}", fixture.name()));
+                    break;
+                }
+            }
+        }
+
+        if setup_error.is_none() {
+            let hooks = [
+                (&suite.name, "before_each", &suite.hooks.before_each),
+                (&test_case.name, "before_all", &test_case.hooks.before_all),
+            ];
+            for (owner, label, steps) in hooks {
+                if let Err(e) = self
+                    .run_hook_steps(owner, label, steps, device_id, &mut screenshots)
+                    .await
+                {
+                    setup_error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+
+        let mut result = match setup_error {
+            Some(error) => TestResult {
+                test_name: test_case.name.clone(),
+                status: TestStatus::Failed,
+                duration: start_time.elapsed(),
+                error_message: Some(format!("Setup failed: {error}")),
+                screenshots: Vec::new(),
+                video_path: None,
+                log_path: None,
+            },
+            None => self.run_test_case(test_case, device_id).await?,
+        };
+
+        let mut teardown_errors = Vec::new();
+        let hooks = [
+            (&test_case.name, "after_all", &test_case.hooks.after_all),
+            (&suite.name, "after_each", &suite.hooks.after_each),
+        ];
+        for (owner, label, steps) in hooks {
+            if let Err(e) = self
+                .run_hook_steps(owner, label, steps, device_id, &mut screenshots)
+                .await
+            {
+                teardown_errors.push(e.to_string());
+            }
+        }
+        for fixture in applied.into_iter().rev() {
+            if let Err(e) = self.teardown_fixture(fixture, device_id) {
+                teardown_errors.push(format!("Fixture {} teardown failed: {e}", fixture.name()));
+            }
+        }
+
+        screenshots.append(&mut result.screenshots);
+        result.screenshots = screenshots;
+        result.duration = start_time.elapsed();
+
+        if !teardown_errors.is_empty() {
+            let details = format!("Teardown failed:\n{}", teardown_errors.join("\n"));
+            result.status = TestStatus::Failed;
+            result.error_message = Some(match result.error_message.take() {
+                Some(error) => format!("{error}\n\n{details}"),
+                None => details,
+            });
+        }
+
+        Ok(result)
+    }
+
+    async fn apply_fixture(&self, fixture: &Fixture, device_id: Option<&str>) -> Result<()> {
+        let Some((adb_path, device_id)) = self.fixture_target(device_id)? else {
+            return Ok(());
+        };
+        info!("Applying fixture {} on {}", fixture.name(), device_id);
+
+        for command in fixture.setup_commands() {
+            // Uninstalling an app that isn't installed is fine for a fresh install
+            let allow_failure = matches!(fixture, Fixture::FreshInstall { .. });
+            adb_shell(adb_path, device_id, &command, allow_failure)?;
+        }
+
+        match fixture {
+            Fixture::FreshInstall { apk, .. } => {
+                let output = Command::new(adb_path)
+                    .args(["-s", device_id, "install", "-r", "-g"])
+                    .arg(apk)
+                    .output()?;
+                if !output.status.success() {
+                    let error_msg = String::from_utf8_lossy(&output.stderr);
+                    return Err(KMobileError::AppInstallError(format!(
+                        "{}: {error_msg}",
+                        apk.display()
+                    ))
+                    .into());
+                }
+            }
+            Fixture::AppDataSnapshot { package, snapshot } => {
+                restore_app_data(adb_path, device_id, package, snapshot)?;
+            }
+            Fixture::EmulatorSnapshot { snapshot } => {
+                if emulator::console_port(device_id).is_none() {
+                    return Err(KMobileError::TestExecutionError(format!(
+                        "Emulator snapshots need an emulator, not {device_id}"
+                    ))
+                    .into());
+                }
+                emulator::load_snapshot(adb_path, device_id, snapshot).await?;
+            }
+            Fixture::ClearData { .. } | Fixture::Hardware(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn teardown_fixture(&self, fixture: &Fixture, device_id: Option<&str>) -> Result<()> {
+        let Some((adb_path, device_id)) = self.fixture_target(device_id)? else {
+            return Ok(());
+        };
+
+        for command in fixture.teardown_commands() {
+            adb_shell(adb_path, device_id, &command, false)?;
+        }
+        Ok(())
+    }
+
+    /// Fixtures drive Android devices over adb; without a device they are skipped
+    fn fixture_target<'a>(
+        &'a self,
+        device_id: Option<&'a str>,
+    ) -> Result<Option<(&'a Path, &'a str)>> {
+        let Some(device_id) = device_id else {
+            return Ok(None);
+        };
+        if uuid::Uuid::parse_str(device_id).is_ok() {
+            return Err(KMobileError::TestExecutionError(
+                "Fixtures are only supported on Android devices".to_string(),
+            )
+            .into());
+        }
+        match &self.config.android.adb_path {
+            Some(adb_path) => Ok(Some((adb_path.as_path(), device_id))),
+            None => {
+                debug!("ADB path not configured; skipping fixture");
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn adb_shell(
+    adb_path: &Path,
+    device_id: &str,
+    command: &[String],
+    allow_failure: bool,
+) -> Result<()> {
+    let output = Command::new(adb_path)
+        .args(["-s", device_id, "shell"])
+        .args(command)
+        .output()?;
+
+    if !output.status.success() && !allow_failure {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(KMobileError::CommandError(format!(
+            "`{}` failed: {error_msg}",
+            command.join(" ")
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_deserialize() {
+        let fixtures: Vec<Fixture> = serde_json::from_str(
+            r#"[
+                {"type": "fresh_install", "package": "com.example.app", "apk": "app.apk"},
+                {"type": "app_data_snapshot", "package": "com.example.app", "snapshot": "logged_in.tar"},
+                {"type": "emulator_snapshot", "snapshot": "logged_in"},
+                {"type": "hardware", "battery_level": 5, "network": "Offline", "orientation": "landscape"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(fixtures[0].name(), "fresh_install");
+        assert_eq!(
+            fixtures[2],
+            Fixture::EmulatorSnapshot {
+                snapshot: "logged_in".to_string()
+            }
+        );
+        assert_eq!(
+            fixtures[3],
+            Fixture::Hardware(HardwarePreset {
+                battery_level: Some(5),
+                network: Some(NetworkType::Offline),
+                orientation: Some(Orientation::Landscape),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_hardware_preset_commands_are_undone() {
+        let fixture = Fixture::Hardware(HardwarePreset {
+            battery_level: Some(15),
+            network: Some(NetworkType::Offline),
+            dark_mode: Some(true),
+            ..Default::default()
+        });
+
+        let setup: Vec<String> = fixture
+            .setup_commands()
+            .iter()
+            .map(|c| c.join(" "))
+            .collect();
+        assert_eq!(
+            setup,
+            [
+                "dumpsys battery set level 15",
+                "svc wifi disable",
+                "svc data disable",
+                "cmd uimode night yes",
+            ]
+        );
+
+        let teardown: Vec<String> = fixture
+            .teardown_commands()
+            .iter()
+            .map(|c| c.join(" "))
+            .collect();
+        assert_eq!(
+            teardown,
+            [
+                "dumpsys battery reset",
+                "svc wifi enable",
+                "svc data enable",
+                "cmd uimode night auto",
+            ]
+        );
+        assert!(Fixture::ClearData {
+            package: "com.example.app".to_string()
+        }
+        .teardown_commands()
+        .is_empty());
+    }
+}
@@ -0,0 +1,535 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
+
+use super::{
+    TestAction, TestCase, TestConfig, TestHooks, TestReport, TestRunOptions, TestRunner, TestStep,
+    TestSuite,
+};
+use crate::error::KMobileError;
+
+/// A Maestro flow converted to a `TestCase`, with every command that couldn't be
+/// translated
+#[derive(Debug, Clone)]
+pub struct MaestroFlow {
+    pub app_id: Option<String>,
+    pub test_case: TestCase,
+    pub unsupported: Vec<UnsupportedCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedCommand {
+    pub file: PathBuf,
+    /// 1-based position of the command in its file
+    pub index: usize,
+    pub command: String,
+    pub reason: String,
+}
+
+impl fmt::Display for UnsupportedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: command {} `{}`: {}",
+            self.file.display(),
+            self.index,
+            self.command,
+            self.reason
+        )
+    }
+}
+
+/// Optional first document of a flow file
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FlowHeader {
+    app_id: Option<String>,
+    name: Option<String>,
+}
+
+/// Reads a Maestro flow file, following `runFlow` references relative to it
+pub fn import_flow(path: &Path) -> Result<MaestroFlow> {
+    let mut importer = Importer {
+        steps: Vec::new(),
+        unsupported: Vec::new(),
+        stack: Vec::new(),
+    };
+    let header = importer.import_file(path, None)?;
+
+    let name = header
+        .name
+        .or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "maestro_flow".to_string());
+
+    Ok(MaestroFlow {
+        app_id: header.app_id,
+        test_case: TestCase {
+            name,
+            description: Some(format!("Imported from Maestro flow {}", path.display())),
+            steps: importer.steps,
+            expected_result: None,
+            timeout: None,
+            tags: vec!["maestro".to_string()],
+            hooks: TestHooks::default(),
+            fixtures: Vec::new(),
+        },
+        unsupported: importer.unsupported,
+    })
+}
+
+struct Importer {
+    steps: Vec<TestStep>,
+    unsupported: Vec<UnsupportedCommand>,
+    /// Files being imported, to catch `runFlow` cycles
+    stack: Vec<PathBuf>,
+}
+
+impl Importer {
+    fn import_file(&mut self, path: &Path, parent_app_id: Option<&str>) -> Result<FlowHeader> {
+        let canonical = fs::canonicalize(path)
+            .map_err(|_| KMobileError::TestFileNotFound(path.display().to_string()))?;
+        if self.stack.contains(&canonical) {
+            return Err(KMobileError::InvalidInput(format!(
+                "runFlow cycle through {}",
+                path.display()
+            ))
+            .into());
+        }
+
+        let content = fs::read_to_string(path)?;
+        let (mut header, commands) = parse_documents(&content)?;
+        if header.app_id.is_none() {
+            header.app_id = parent_app_id.map(String::from);
+        }
+
+        self.stack.push(canonical);
+        for (i, command) in commands.iter().enumerate() {
+            self.import_command(path, i + 1, command, header.app_id.as_deref())?;
+        }
+        self.stack.pop();
+
+        Ok(header)
+    }
+
+    fn import_command(
+        &mut self,
+        file: &Path,
+        index: usize,
+        command: &Value,
+        app_id: Option<&str>,
+    ) -> Result<()> {
+        let (name, args) = match command {
+            Value::String(name) => (name.as_str(), &Value::Null),
+            Value::Mapping(map) if map.len() == 1 => {
+                let (key, value) = map.iter().next().expect("map has one entry");
+                (key.as_str().unwrap_or_default(), value)
+            }
+            _ => {
+                self.unsupported(file, index, &yaml_summary(command), "not a Maestro command");
+                return Ok(());
+            }
+        };
+
+        let step = |action: TestAction, target: Option<String>, value: Option<String>| TestStep {
+            action,
+            target,
+            value,
+            wait_time: None,
+        };
+
+        match name {
+            "launchApp" => {
+                let target = args
+                    .as_str()
+                    .or_else(|| args.get("appId").and_then(Value::as_str))
+                    .or(app_id);
+                let Some(target) = target else {
+                    self.unsupported(file, index, name, "no appId in the flow header or command");
+                    return Ok(());
+                };
+                if args.get("clearState").and_then(Value::as_bool) == Some(true) {
+                    self.unsupported(
+                        file,
+                        index,
+                        "launchApp.clearState",
+                        "use a clear_data fixture instead; launching without clearing",
+                    );
+                }
+                self.steps
+                    .push(step(TestAction::Launch, Some(target.to_string()), None));
+            }
+            "tapOn" | "longPressOn" => match selector(args) {
+                Ok(target) => {
+                    let action = if name == "tapOn" {
+                        TestAction::Tap
+                    } else {
+                        TestAction::LongPress
+                    };
+                    self.steps.push(step(action, Some(target), None));
+                }
+                Err(reason) => self.unsupported(file, index, name, &reason),
+            },
+            "inputText" => match args.as_str() {
+                Some(text) => self
+                    .steps
+                    .push(step(TestAction::Type, None, Some(text.to_string()))),
+                None => self.unsupported(file, index, name, "expected the text to type"),
+            },
+            "assertVisible" => match selector(args) {
+                Ok(target) => self
+                    .steps
+                    .push(step(TestAction::Assert, Some(target), None)),
+                Err(reason) => self.unsupported(file, index, name, &reason),
+            },
+            "scrollUntilVisible" => {
+                let element = args.get("element").unwrap_or(args);
+                match selector(element) {
+                    Ok(target) => {
+                        let direction = args
+                            .get("direction")
+                            .and_then(Value::as_str)
+                            .unwrap_or("DOWN")
+                            .to_lowercase();
+                        let value = match args.get("timeout").and_then(Value::as_u64) {
+                            Some(timeout) => format!("{direction},{timeout}"),
+                            None => direction,
+                        };
+                        self.steps.push(step(
+                            TestAction::ScrollUntilVisible,
+                            Some(target),
+                            Some(value),
+                        ));
+                    }
+                    Err(reason) => self.unsupported(file, index, name, &reason),
+                }
+            }
+            "takeScreenshot" => {
+                let value = args.as_str().map(|name| format!("{name}.png"));
+                self.steps.push(step(TestAction::Screenshot, None, value));
+            }
+            "waitForAnimationToEnd" => {
+                let mut wait = step(TestAction::Wait, None, None);
+                wait.wait_time = Some(Duration::from_millis(
+                    args.get("timeout").and_then(Value::as_u64).unwrap_or(1000),
+                ));
+                self.steps.push(wait);
+            }
+            "runFlow" => {
+                let flow = args
+                    .as_str()
+                    .or_else(|| args.get("file").and_then(Value::as_str));
+                let Some(flow) = flow else {
+                    self.unsupported(file, index, name, "inline `commands` are not supported");
+                    return Ok(());
+                };
+                if args.get("when").is_some() {
+                    self.unsupported(file, index, "runFlow.when", "conditions are not supported");
+                    return Ok(());
+                }
+                let nested = file.parent().unwrap_or(Path::new(".")).join(flow);
+                self.import_file(&nested, app_id)?;
+            }
+            other => self.unsupported(file, index, other, "command is not supported"),
+        }
+
+        Ok(())
+    }
+
+    fn unsupported(&mut self, file: &Path, index: usize, command: &str, reason: &str) {
+        self.unsupported.push(UnsupportedCommand {
+            file: file.to_path_buf(),
+            index,
+            command: command.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+impl TestRunner {
+    /// Imports Maestro flows as one suite and runs it. Flows with unsupported commands
+    /// are refused unless `skip_unsupported` is set, in which case those commands are dropped.
+    pub async fn run_maestro_flows(
+        &self,
+        flow_paths: &[PathBuf],
+        device_id: Option<&str>,
+        skip_unsupported: bool,
+        options: &TestRunOptions,
+    ) -> Result<TestReport> {
+        let mut tests = Vec::new();
+        let mut unsupported = Vec::new();
+        for path in flow_paths {
+            let flow = import_flow(path)?;
+            unsupported.extend(flow.unsupported);
+            tests.push(flow.test_case);
+        }
+
+        if !unsupported.is_empty() {
+            for command in &unsupported {
+                warn!("Unsupported Maestro command: {}", command);
+            }
+            if !skip_unsupported {
+                return Err(KMobileError::InvalidInput(format!(
+                    "{} unsupported Maestro command(s); rerun with --skip-unsupported to drop them",
+                    unsupported.len()
+                ))
+                .into());
+            }
+        }
+
+        let suite = TestSuite {
+            name: "maestro".to_string(),
+            tests,
+            config: TestConfig {
+                timeout: Duration::from_secs(self.config.testing.timeout),
+                screenshot_on_failure: self.config.testing.screenshot_on_failure,
+                video_recording: false,
+                parallel_execution: false,
+                retry_count: 0,
+            },
+            hooks: TestHooks::default(),
+            fixtures: Vec::new(),
+        };
+        self.run_suite(suite, device_id, options).await
+    }
+}
+
+/// Splits a flow file into its optional header and the command list
+fn parse_documents(content: &str) -> Result<(FlowHeader, Vec<Value>)> {
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(content) {
+        documents.push(Value::deserialize(document)?);
+    }
+    documents.retain(|doc| !doc.is_null());
+
+    match documents.as_slice() {
+        [Value::Sequence(commands)] => Ok((FlowHeader::default(), commands.clone())),
+        [header, Value::Sequence(commands)] => {
+            Ok((serde_yaml::from_value(header.clone())?, commands.clone()))
+        }
+        [header] => Ok((serde_yaml::from_value(header.clone())?, Vec::new())),
+        _ => Err(KMobileError::InvalidInput(
+            "Expected a Maestro flow: an optional header, `---`, then a list of commands"
+                .to_string(),
+        )
+        .into()),
+    }
+}
+
+/// Converts a Maestro element selector into a kmobile `TestStep.target`
+/// Maestro matches text selectors as regexes over the text and accessibility text
+fn text_selector(text: &str) -> std::result::Result<String, String> {
+    regex::Regex::new(text).map_err(|e| format!("invalid text regex `{text}`: {e}"))?;
+    Ok(format!("match:{text}"))
+}
+
+fn selector(args: &Value) -> std::result::Result<String, String> {
+    if let Some(text) = args.as_str() {
+        return text_selector(text);
+    }
+    let Value::Mapping(map) = args else {
+        return Err("expected a selector".to_string());
+    };
+
+    for key in map.keys().filter_map(Value::as_str) {
+        if !matches!(key, "id" | "text" | "point" | "label") {
+            return Err(format!("selector option `{key}` is not supported"));
+        }
+    }
+
+    if let Some(id) = args.get("id").and_then(Value::as_str) {
+        return Ok(format!("id:{id}"));
+    }
+    if let Some(text) = args.get("text").and_then(Value::as_str) {
+        return text_selector(text);
+    }
+    if let Some(point) = args.get("point").and_then(Value::as_str) {
+        if point.contains('%') {
+            return Err("percentage points are not supported".to_string());
+        }
+        let coords: Vec<&str> = point.split(',').map(str::trim).collect();
+        if let [x, y] = coords.as_slice() {
+            if x.parse::<i32>().is_ok() && y.parse::<i32>().is_ok() {
+                return Ok(format!("{x} {y}"));
+            }
+        }
+        return Err(format!("invalid point `{point}`"));
+    }
+    Err("selector needs `id`, `text` or `point`".to_string())
+}
+
+fn yaml_summary(value: &Value) -> String {
+    serde_yaml::to_string(value)
+        .unwrap_or_default()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ui_hierarchy::{Selector, UiHierarchy};
+
+    #[test]
+    fn test_import_flow_with_run_flow() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("login.yaml"),
+            "- tapOn:\n    id: \"username\"\n- inputText: \"jane doe\"\n",
+        )
+        .unwrap();
+        let flow_path = dir.path().join("checkout.yaml");
+        fs::write(
+            &flow_path,
+            r#"appId: com.example.app
+---
+- launchApp
+- runFlow: login.yaml
+- assertVisible: "Welcome"
+- scrollUntilVisible:
+    element:
+      text: "Buy now"
+    direction: DOWN
+    timeout: 5000
+- tapOn:
+    point: "540,1200"
+- swipe:
+    direction: LEFT
+- tapOn:
+    text: "Pay"
+    index: 1
+"#,
+        )
+        .unwrap();
+
+        let flow = import_flow(&flow_path).unwrap();
+        assert_eq!(flow.app_id.as_deref(), Some("com.example.app"));
+        assert_eq!(flow.test_case.name, "checkout");
+
+        let targets: Vec<(String, Option<&str>, Option<&str>)> = flow
+            .test_case
+            .steps
+            .iter()
+            .map(|step| {
+                (
+                    format!("{:?}", step.action),
+                    step.target.as_deref(),
+                    step.value.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                ("Launch".to_string(), Some("com.example.app"), None),
+                ("Tap".to_string(), Some("id:username"), None),
+                ("Type".to_string(), None, Some("jane doe")),
+                ("Assert".to_string(), Some("match:Welcome"), None),
+                (
+                    "ScrollUntilVisible".to_string(),
+                    Some("match:Buy now"),
+                    Some("down,5000")
+                ),
+                ("Tap".to_string(), Some("540 1200"), None),
+            ]
+        );
+
+        assert_eq!(flow.unsupported.len(), 2);
+        assert_eq!(flow.unsupported[0].command, "swipe");
+        assert_eq!(flow.unsupported[0].index, 6);
+        assert!(flow.unsupported[1].reason.contains("`index`"));
+    }
+
+    #[test]
+    fn test_launch_app_starts_the_launcher_activity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("open.yaml");
+        fs::write(
+            &path,
+            "appId: com.example.app\n---\n- launchApp\n- launchApp: com.example.app/.Settings\n",
+        )
+        .unwrap();
+
+        let flow = import_flow(&path).unwrap();
+        let commands: Vec<Vec<String>> = flow
+            .test_case
+            .steps
+            .iter()
+            .map(|step| {
+                crate::testing::launch_args("emulator-5554", step.target.as_deref().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                vec![
+                    "-s",
+                    "emulator-5554",
+                    "shell",
+                    "monkey",
+                    "-p",
+                    "com.example.app",
+                    "-c",
+                    "android.intent.category.LAUNCHER",
+                    "1"
+                ],
+                vec![
+                    "-s",
+                    "emulator-5554",
+                    "shell",
+                    "am",
+                    "start",
+                    "-n",
+                    "com.example.app/.Settings"
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_selectors_are_regexes_and_input_is_escaped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("search.yaml");
+        fs::write(
+            &path,
+            "- tapOn: \"Sign.*\"\n- assertVisible:\n    text: \"Total: \\\\$5.00\"\n- inputText: \"it's a&b\"\n- tapOn: \"((\"\n",
+        )
+        .unwrap();
+        let flow = import_flow(&path).unwrap();
+        let steps = &flow.test_case.steps;
+
+        let window = UiHierarchy::parse(
+            r#"<hierarchy><node index="0" text="Sign in" resource-id="" class="android.widget.Button" package="com.example" content-desc="" clickable="true" enabled="true" focused="false" bounds="[0,0][100,100]" /><node index="1" text="Total: $5.00" resource-id="" class="android.widget.TextView" package="com.example" content-desc="" clickable="false" enabled="true" focused="false" bounds="[0,100][100,200]" /></hierarchy>"#,
+        );
+        let find = |step: &TestStep| {
+            let selector = Selector::parse(step.target.as_deref().unwrap()).unwrap();
+            window.find(&selector).map(|node| node.text.clone())
+        };
+        assert_eq!(find(&steps[0]).as_deref(), Some("Sign in"));
+        assert_eq!(find(&steps[1]).as_deref(), Some("Total: $5.00"));
+
+        assert_eq!(
+            crate::testing::input_text_arg(steps[2].value.as_deref().unwrap()),
+            r"it\'s%sa\&b"
+        );
+        assert_eq!(flow.unsupported.len(), 1);
+        assert!(flow.unsupported[0].reason.contains("invalid text regex"));
+    }
+
+    #[test]
+    fn test_run_flow_cycle_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("loop.yaml");
+        fs::write(&path, "- runFlow: loop.yaml\n").unwrap();
+        assert!(import_flow(&path).is_err());
+    }
+}
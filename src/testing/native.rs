@@ -0,0 +1,624 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info};
+
+use super::{TestReport, TestResult, TestRunOptions, TestRunner, TestStatus};
+use crate::config::ProjectConfig;
+use crate::error::KMobileError;
+use crate::utils::parse_attributes;
+
+/// Test framework behind a project's `test_command`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeFramework {
+    /// Gradle unit tests or `connectedAndroidTest` (Espresso), reported as JUnit XML
+    Gradle,
+    /// `xcodebuild test`, read back from the result bundle
+    XcTest,
+    /// `flutter test --machine`
+    Flutter,
+    /// `npm test` running Jest with `--json`
+    Jest,
+}
+
+impl NativeFramework {
+    pub fn for_project(project: &ProjectConfig) -> Option<Self> {
+        let command = project.test_command.as_deref().unwrap_or_default();
+        if command.contains("gradlew") || command.contains("gradle ") {
+            return Some(NativeFramework::Gradle);
+        }
+        if command.contains("xcodebuild") {
+            return Some(NativeFramework::XcTest);
+        }
+        if command.starts_with("flutter") {
+            return Some(NativeFramework::Flutter);
+        }
+        if command.starts_with("npm") || command.starts_with("yarn") || command.contains("jest") {
+            return Some(NativeFramework::Jest);
+        }
+
+        match project.platform.as_str() {
+            "android" => Some(NativeFramework::Gradle),
+            "ios" => Some(NativeFramework::XcTest),
+            "flutter" => Some(NativeFramework::Flutter),
+            "react-native" => Some(NativeFramework::Jest),
+            _ => None,
+        }
+    }
+
+    fn default_command(self) -> &'static str {
+        match self {
+            NativeFramework::Gradle => "./gradlew test",
+            NativeFramework::XcTest => "xcodebuild test -scheme Debug",
+            NativeFramework::Flutter => "flutter test",
+            NativeFramework::Jest => "npm test",
+        }
+    }
+}
+
+/// Options for `kmobile test native`
+#[derive(Debug, Clone, Default)]
+pub struct NativeTestOptions {
+    pub device_id: Option<String>,
+    /// Run `connectedAndroidTest` on the device instead of the JVM unit tests
+    pub connected: bool,
+    pub run: TestRunOptions,
+}
+
+impl TestRunner {
+    /// Runs the project's own test command and converts the framework's results into
+    /// a `TestReport`, so native tests get the same reports and history as kmobile tests
+    pub async fn run_native_tests(
+        &self,
+        project: &ProjectConfig,
+        options: &NativeTestOptions,
+    ) -> Result<TestReport> {
+        let framework = NativeFramework::for_project(project).ok_or_else(|| {
+            KMobileError::ConfigError(format!(
+                "Don't know how to run tests for {} project '{}'",
+                project.platform, project.name
+            ))
+        })?;
+
+        // The command runs inside the project, so result paths must not be relative
+        let output_dir = self.test_output_dir.join("native");
+        fs::create_dir_all(&output_dir)?;
+        let output_dir = fs::canonicalize(&output_dir)?;
+        let xcresult = output_dir.join(format!("{}.xcresult", project.name));
+        let jest_json = output_dir.join(format!("{}_jest.json", project.name));
+
+        let command = project
+            .test_command
+            .as_deref()
+            .unwrap_or(framework.default_command());
+        let mut args = shell_words::split(command).map_err(|e| {
+            KMobileError::ConfigError(format!("Invalid test_command '{command}': {e}"))
+        })?;
+        if args.is_empty() {
+            return Err(KMobileError::ConfigError(format!(
+                "test_command of project '{}' is empty",
+                project.name
+            ))
+            .into());
+        }
+        let mut envs = Vec::new();
+
+        match framework {
+            NativeFramework::Gradle => {
+                if options.connected {
+                    args.retain(|arg| arg != "test");
+                    args.push("connectedAndroidTest".to_string());
+                }
+                if let Some(device_id) = &options.device_id {
+                    envs.push(("ANDROID_SERIAL", device_id.clone()));
+                }
+            }
+            NativeFramework::XcTest => {
+                let _ = fs::remove_dir_all(&xcresult);
+                args.push("-resultBundlePath".to_string());
+                args.push(xcresult.to_string_lossy().to_string());
+                if let Some(device_id) = &options.device_id {
+                    args.push("-destination".to_string());
+                    args.push(format!("id={device_id}"));
+                }
+            }
+            NativeFramework::Flutter => {
+                if !args.iter().any(|arg| arg == "--machine") {
+                    args.push("--machine".to_string());
+                }
+                if let Some(device_id) = &options.device_id {
+                    args.push("-d".to_string());
+                    args.push(device_id.clone());
+                }
+            }
+            NativeFramework::Jest => {
+                if args.first().is_some_and(|program| program == "npm") {
+                    args.push("--".to_string());
+                }
+                args.push("--json".to_string());
+                args.push(format!("--outputFile={}", jest_json.display()));
+            }
+        }
+
+        info!("Running native tests: {}", shell_words::join(&args));
+        let started = SystemTime::now();
+        let start_time = Utc::now();
+        let output = Command::new(&args[0])
+            .args(&args[1..])
+            .envs(envs)
+            .current_dir(&project.path)
+            .output()?;
+
+        let results = match framework {
+            NativeFramework::Gradle => {
+                let mut results = Vec::new();
+                for file in junit_result_files(&project.path, started)? {
+                    results.extend(parse_junit_xml(&fs::read_to_string(&file)?));
+                }
+                results
+            }
+            NativeFramework::XcTest => read_xcresult(&xcresult)?,
+            NativeFramework::Flutter => {
+                parse_flutter_machine(&String::from_utf8_lossy(&output.stdout))
+            }
+            NativeFramework::Jest => match fs::read_to_string(&jest_json) {
+                Ok(json) => parse_jest_json(&json)?,
+                Err(_) => Vec::new(),
+            },
+        };
+
+        if results.is_empty() && !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+            return Err(KMobileError::TestExecutionError(format!(
+                "`{}` failed without producing results:\n{}",
+                args.join(" "),
+                tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+            ))
+            .into());
+        }
+
+        let report = TestReport {
+            suite_name: format!("{}_native", project.name),
+            start_time,
+            end_time: Some(Utc::now()),
+            summary: self.generate_summary(&results),
+            results,
+        };
+
+        self.save_test_report(&report, &options.run.reporters)
+            .await?;
+        self.record_history(&report, options.device_id.as_deref());
+
+        Ok(report)
+    }
+}
+
+/// Gradle's JUnit XML for unit tests and connected tests written since `since`
+fn junit_result_files(project_path: &Path, since: SystemTime) -> Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, since: SystemTime, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if path.is_dir() {
+                if !matches!(
+                    name.as_ref(),
+                    ".git" | ".gradle" | "node_modules" | "intermediates"
+                ) {
+                    walk(&path, since, files)?;
+                }
+                continue;
+            }
+
+            let path_str = path.to_string_lossy().replace('\\', "/");
+            let is_result = path_str.contains("/build/test-results/")
+                || path_str.contains("/build/outputs/androidTest-results/");
+            let fresh = fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .is_ok_and(|modified| modified >= since);
+            if is_result && fresh && name.ends_with(".xml") {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(project_path, since, &mut files)?;
+    files.sort();
+    debug!("Found {} JUnit result files", files.len());
+    Ok(files)
+}
+
+/// Parses `<testcase>` elements from JUnit XML as written by Gradle and most runners
+pub fn parse_junit_xml(xml: &str) -> Vec<TestResult> {
+    let mut results = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find("<testcase") {
+        rest = &rest[start..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..tag_end];
+        let attrs: HashMap<String, String> = parse_attributes(tag).into_iter().collect();
+        let self_closing = tag.ends_with('/');
+
+        let body = if self_closing {
+            ""
+        } else {
+            let body_end = rest.find("</testcase>").unwrap_or(rest.len());
+            &rest[tag_end + 1..body_end]
+        };
+        rest = &rest[tag_end + 1..];
+
+        let name = attrs.get("name").cloned().unwrap_or_default();
+        let test_name = match attrs.get("classname").filter(|c| !c.is_empty()) {
+            Some(class) => format!("{class}.{name}"),
+            None => name,
+        };
+        let duration = attrs
+            .get("time")
+            .and_then(|time| time.parse::<f64>().ok())
+            .map(Duration::from_secs_f64)
+            .unwrap_or_default();
+
+        let failure = ["<failure", "<error"]
+            .iter()
+            .find_map(|element| body.find(element).map(|i| &body[i..]));
+        let (status, error_message) = if let Some(failure) = failure {
+            (TestStatus::Failed, Some(failure_message(failure)))
+        } else if body.contains("<skipped") {
+            (TestStatus::Skipped, None)
+        } else {
+            (TestStatus::Passed, None)
+        };
+
+        results.push(native_result(test_name, status, duration, error_message));
+    }
+
+    results
+}
+
+fn failure_message(element: &str) -> String {
+    let tag_end = element.find('>').unwrap_or(element.len());
+    let attrs: HashMap<String, String> =
+        parse_attributes(&element[..tag_end]).into_iter().collect();
+
+    let body = element
+        .get(tag_end + 1..)
+        .and_then(|rest| rest.find("</").map(|end| &rest[..end]))
+        .unwrap_or_default()
+        .trim()
+        .trim_start_matches("<![CDATA[")
+        .trim_end_matches("]]>")
+        .trim();
+
+    match attrs.get("message").filter(|m| !m.is_empty()) {
+        Some(message) if body.is_empty() => message.clone(),
+        Some(message) if !body.contains(message.as_str()) => format!("{message}\n{body}"),
+        _ => body.to_string(),
+    }
+}
+
+/// Reads an `.xcresult` bundle through `xcresulttool`
+fn read_xcresult(bundle: &Path) -> Result<Vec<TestResult>> {
+    if !bundle.exists() {
+        return Ok(Vec::new());
+    }
+
+    let output = Command::new("xcrun")
+        .args(["xcresulttool", "get", "test-results", "tests", "--path"])
+        .arg(bundle)
+        .output()?;
+
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(KMobileError::CommandError(format!("xcresulttool failed: {error_msg}")).into());
+    }
+
+    parse_xcresult_tests(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses the JSON of `xcresulttool get test-results tests`
+pub fn parse_xcresult_tests(json: &str) -> Result<Vec<TestResult>> {
+    fn walk(node: &Value, suite: Option<&str>, results: &mut Vec<TestResult>) {
+        let name = node["name"].as_str().unwrap_or_default();
+        let node_type = node["nodeType"].as_str().unwrap_or_default();
+
+        if node_type == "Test Case" {
+            let status = match node["result"].as_str() {
+                Some("Passed") | Some("Expected Failure") => TestStatus::Passed,
+                Some("Skipped") => TestStatus::Skipped,
+                _ => TestStatus::Failed,
+            };
+            let failures: Vec<&str> = node["children"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|child| child["nodeType"] == "Failure Message")
+                .filter_map(|child| child["name"].as_str())
+                .collect();
+            let duration = node["durationInSeconds"]
+                .as_f64()
+                .map(Duration::from_secs_f64)
+                .unwrap_or_default();
+
+            let test_name = match suite {
+                Some(suite) => format!("{suite}/{name}"),
+                None => name.to_string(),
+            };
+            let error_message = matches!(status, TestStatus::Failed)
+                .then(|| failures.join("\n"))
+                .filter(|message| !message.is_empty());
+            results.push(native_result(test_name, status, duration, error_message));
+            return;
+        }
+
+        let suite = if node_type == "Test Suite" {
+            Some(name)
+        } else {
+            suite
+        };
+        for child in node["children"].as_array().into_iter().flatten() {
+            walk(child, suite, results);
+        }
+    }
+
+    let root: Value = serde_json::from_str(json)?;
+    let mut results = Vec::new();
+    for node in root["testNodes"].as_array().into_iter().flatten() {
+        walk(node, None, &mut results);
+    }
+    Ok(results)
+}
+
+/// Parses the event stream of `flutter test --machine`
+pub fn parse_flutter_machine(output: &str) -> Vec<TestResult> {
+    struct Started {
+        name: String,
+        time: u64,
+        errors: Vec<String>,
+    }
+
+    let mut started: HashMap<u64, Started> = HashMap::new();
+    let mut results = Vec::new();
+
+    for line in output.lines() {
+        let Ok(event) = serde_json::from_str::<Value>(line.trim()) else {
+            continue;
+        };
+
+        match event["type"].as_str() {
+            Some("testStart") => {
+                let test = &event["test"];
+                if let Some(id) = test["id"].as_u64() {
+                    started.insert(
+                        id,
+                        Started {
+                            name: test["name"].as_str().unwrap_or_default().to_string(),
+                            time: event["time"].as_u64().unwrap_or_default(),
+                            errors: Vec::new(),
+                        },
+                    );
+                }
+            }
+            Some("error") => {
+                if let Some(test) = event["testID"].as_u64().and_then(|id| started.get_mut(&id)) {
+                    test.errors
+                        .push(event["error"].as_str().unwrap_or_default().to_string());
+                }
+            }
+            Some("testDone") => {
+                // Hidden tests are the loading/teardown pseudo-tests flutter reports
+                if event["hidden"].as_bool() == Some(true) {
+                    continue;
+                }
+                let Some(test) = event["testID"].as_u64().and_then(|id| started.remove(&id)) else {
+                    continue;
+                };
+
+                let status = if event["skipped"].as_bool() == Some(true) {
+                    TestStatus::Skipped
+                } else if event["result"] == "success" {
+                    TestStatus::Passed
+                } else {
+                    TestStatus::Failed
+                };
+                let duration = Duration::from_millis(
+                    event["time"]
+                        .as_u64()
+                        .unwrap_or_default()
+                        .saturating_sub(test.time),
+                );
+                let error_message = (!test.errors.is_empty()).then(|| test.errors.join("\n"));
+                results.push(native_result(test.name, status, duration, error_message));
+            }
+            _ => {}
+        }
+    }
+
+    results
+}
+
+/// Parses the report Jest writes with `--json --outputFile`
+pub fn parse_jest_json(json: &str) -> Result<Vec<TestResult>> {
+    let root: Value = serde_json::from_str(json)?;
+    let mut results = Vec::new();
+
+    for file in root["testResults"].as_array().into_iter().flatten() {
+        for assertion in file["assertionResults"].as_array().into_iter().flatten() {
+            let status = match assertion["status"].as_str() {
+                Some("passed") => TestStatus::Passed,
+                Some("pending") | Some("skipped") | Some("todo") | Some("disabled") => {
+                    TestStatus::Skipped
+                }
+                _ => TestStatus::Failed,
+            };
+            let failures: Vec<&str> = assertion["failureMessages"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|message| message.as_str())
+                .collect();
+
+            results.push(native_result(
+                assertion["fullName"]
+                    .as_str()
+                    .or(assertion["title"].as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                status,
+                Duration::from_millis(assertion["duration"].as_u64().unwrap_or_default()),
+                (!failures.is_empty()).then(|| failures.join("\n")),
+            ));
+        }
+    }
+
+    Ok(results)
+}
+
+fn native_result(
+    test_name: String,
+    status: TestStatus,
+    duration: Duration,
+    error_message: Option<String>,
+) -> TestResult {
+    TestResult {
+        test_name,
+        status,
+        duration,
+        error_message,
+        screenshots: Vec::new(),
+        video_path: None,
+        log_path: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_junit_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="com.example.LoginTest" tests="3" failures="1">
+  <testcase name="validLogin" classname="com.example.LoginTest" time="0.25"/>
+  <testcase name="invalidLogin" classname="com.example.LoginTest" time="1.5">
+    <failure message="expected:&lt;true&gt; but was:&lt;false&gt;" type="java.lang.AssertionError"><![CDATA[java.lang.AssertionError: expected:<true> but was:<false>
+	at com.example.LoginTest.invalidLogin(LoginTest.kt:20)]]></failure>
+  </testcase>
+  <testcase name="ignored" classname="com.example.LoginTest" time="0"><skipped/></testcase>
+</testsuite>"#;
+
+        let results = parse_junit_xml(xml);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].test_name, "com.example.LoginTest.validLogin");
+        assert!(matches!(results[0].status, TestStatus::Passed));
+        assert_eq!(results[1].duration, Duration::from_millis(1500));
+        assert!(matches!(results[1].status, TestStatus::Failed));
+        let message = results[1].error_message.as_deref().unwrap();
+        assert!(message.starts_with("java.lang.AssertionError: expected:<true> but was:<false>"));
+        assert!(message.contains("LoginTest.kt:20"));
+        assert!(matches!(results[2].status, TestStatus::Skipped));
+    }
+
+    #[test]
+    fn test_parse_xcresult_tests() {
+        let json = r#"{"testNodes": [{"nodeType": "Test Plan", "name": "App", "children": [
+            {"nodeType": "Unit test bundle", "name": "AppTests", "children": [
+                {"nodeType": "Test Suite", "name": "LoginTests", "children": [
+                    {"nodeType": "Test Case", "name": "testValid()", "result": "Passed", "durationInSeconds": 0.5},
+                    {"nodeType": "Test Case", "name": "testInvalid()", "result": "Failed", "durationInSeconds": 1.0,
+                     "children": [{"nodeType": "Failure Message", "name": "LoginTests.swift:12: XCTAssertTrue failed"}]}
+                ]}
+            ]}
+        ]}]}"#;
+
+        let results = parse_xcresult_tests(json).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].test_name, "LoginTests/testValid()");
+        assert!(matches!(results[1].status, TestStatus::Failed));
+        assert_eq!(
+            results[1].error_message.as_deref(),
+            Some("LoginTests.swift:12: XCTAssertTrue failed")
+        );
+    }
+
+    #[test]
+    fn test_parse_flutter_machine() {
+        let output = r#"{"protocolVersion":"0.1.1","runnerVersion":"1.24.3","pid":1,"type":"start","time":0}
+{"test":{"id":1,"name":"loading /app/test/widget_test.dart","suiteID":0},"type":"testStart","time":5}
+{"testID":1,"result":"success","skipped":false,"hidden":true,"type":"testDone","time":120}
+{"test":{"id":3,"name":"counter increments","suiteID":0},"type":"testStart","time":130}
+{"testID":3,"result":"success","skipped":false,"hidden":false,"type":"testDone","time":400}
+{"test":{"id":4,"name":"counter resets","suiteID":0},"type":"testStart","time":410}
+{"testID":4,"error":"Expected: <0>\n  Actual: <1>","stackTrace":"","isFailure":true,"type":"error","time":450}
+{"testID":4,"result":"failure","skipped":false,"hidden":false,"type":"testDone","time":460}
+{"success":false,"type":"done","time":470}"#;
+
+        let results = parse_flutter_machine(output);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].test_name, "counter increments");
+        assert_eq!(results[0].duration, Duration::from_millis(270));
+        assert!(matches!(results[1].status, TestStatus::Failed));
+        assert!(results[1]
+            .error_message
+            .as_deref()
+            .unwrap()
+            .contains("Actual: <1>"));
+    }
+
+    #[tokio::test]
+    async fn test_test_command_is_split_like_a_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = crate::config::Config::default();
+        config.testing.output_dir = dir.path().join("results");
+        let runner = TestRunner::new(&config).await.unwrap();
+        let project = |test_command: &str| ProjectConfig {
+            name: "app".to_string(),
+            path: dir.path().to_path_buf(),
+            platform: "react-native".to_string(),
+            build_command: None,
+            test_command: Some(test_command.to_string()),
+            metadata: HashMap::new(),
+        };
+        let options = NativeTestOptions::default();
+
+        // With whitespace splitting the quotes would reach `sh` and fail to parse
+        let report = runner
+            .run_native_tests(&project(r#"sh -c "exit 0""#), &options)
+            .await
+            .unwrap();
+        assert!(report.results.is_empty());
+
+        for command in ["", "   ", r#"sh -c "exit 0"#] {
+            let error = runner
+                .run_native_tests(&project(command), &options)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                KMobileError::find(&error),
+                Some(KMobileError::ConfigError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_jest_json() {
+        let json = r#"{"numTotalTests": 2, "testResults": [{"name": "/app/App.test.js", "assertionResults": [
+            {"fullName": "App renders", "title": "renders", "status": "passed", "duration": 12, "failureMessages": []},
+            {"fullName": "App logs in", "title": "logs in", "status": "failed", "duration": 30, "failureMessages": ["Error: boom"]}
+        ]}]}"#;
+
+        let results = parse_jest_json(json).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[0].status, TestStatus::Passed));
+        assert_eq!(results[1].error_message.as_deref(), Some("Error: boom"));
+    }
+}
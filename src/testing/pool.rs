@@ -0,0 +1,311 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{info, warn};
+
+use super::{
+    TestCase, TestProgress, TestReport, TestResult, TestRunOptions, TestRunner, TestStatus,
+    TestSuite,
+};
+use crate::emulator;
+use crate::error::KMobileError;
+
+/// Devices a suite is spread across, each taking the next case as soon as it is free
+#[derive(Debug, Clone)]
+pub struct DevicePool {
+    devices: Vec<String>,
+    /// adb and timeout used to wait for an emulator to be booted before each case
+    boot_check: Option<(PathBuf, Duration)>,
+}
+
+impl DevicePool {
+    pub fn new(devices: Vec<String>) -> Self {
+        Self {
+            devices,
+            boot_check: None,
+        }
+    }
+
+    /// Waits for each emulator to finish booting before handing it a case, so an
+    /// instance restarted after a crash rejoins the pool once it is back up
+    pub fn with_boot_check(mut self, adb_path: PathBuf, timeout: Duration) -> Self {
+        self.boot_check = Some((adb_path, timeout));
+        self
+    }
+
+    pub fn devices(&self) -> &[String] {
+        &self.devices
+    }
+
+    async fn ready(&self, serial: &str) -> Result<()> {
+        match &self.boot_check {
+            Some((adb_path, timeout)) => emulator::wait_for_boot(adb_path, serial, *timeout).await,
+            None => Ok(()),
+        }
+    }
+}
+
+type CaseQueue = Arc<Mutex<VecDeque<usize>>>;
+
+fn next_case(queue: &CaseQueue) -> Option<usize> {
+    queue.lock().ok()?.pop_front()
+}
+
+/// Result for a case left over after every device dropped out of the pool
+fn no_device_available(test_case: &TestCase) -> TestResult {
+    TestResult {
+        test_name: test_case.name.clone(),
+        status: TestStatus::Failed,
+        duration: Duration::ZERO,
+        error_message: Some("No device in the pool was available to run it".to_string()),
+        screenshots: Vec::new(),
+        video_path: None,
+        log_path: None,
+    }
+}
+
+impl TestRunner {
+    /// `run_tests` across a device pool
+    pub async fn run_tests_on_pool(
+        &self,
+        suite_name: Option<&str>,
+        pool: &DevicePool,
+        options: &TestRunOptions,
+    ) -> Result<TestReport> {
+        let suite = self.load_test_suite(suite_name).await?;
+        self.run_suite_on_pool(suite, pool, options).await
+    }
+
+    /// Runs a suite on every device of the pool at once. Each device runs the suite's
+    /// `before_all` and `after_all` hooks itself and then pulls cases from a shared
+    /// queue; the report lists results in suite order.
+    pub async fn run_suite_on_pool(
+        &self,
+        mut suite: TestSuite,
+        pool: &DevicePool,
+        options: &TestRunOptions,
+    ) -> Result<TestReport> {
+        if pool.devices.is_empty() {
+            return Err(KMobileError::DeviceNotFound("Device pool is empty".to_string()).into());
+        }
+
+        self.select_cases(&mut suite, options)?;
+        let start_time = Utc::now();
+        if let Some(sender) = &options.progress {
+            let _ = sender.send(TestProgress::SuiteStarted {
+                suite_name: suite.name.clone(),
+                total: suite.tests.len(),
+            });
+        }
+        info!(
+            "Running {} test cases on {} devices",
+            suite.tests.len(),
+            pool.devices.len()
+        );
+
+        let suite = Arc::new(suite);
+        let queue: CaseQueue = Arc::new(Mutex::new((0..suite.tests.len()).collect()));
+        let workers: Vec<_> = pool
+            .devices
+            .iter()
+            .map(|device_id| {
+                let runner = self.clone();
+                let suite = suite.clone();
+                let pool = pool.clone();
+                let device_id = device_id.clone();
+                let queue = queue.clone();
+                let progress = options.progress.clone();
+                tokio::spawn(async move {
+                    runner
+                        .run_pool_worker(&suite, &pool, &device_id, &queue, progress)
+                        .await
+                })
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for worker in workers {
+            results.extend(worker.await??);
+        }
+
+        while let Some(index) = next_case(&queue) {
+            let result = no_device_available(&suite.tests[index]);
+            if let Some(sender) = &options.progress {
+                let _ = sender.send(TestProgress::CaseFinished(result.clone()));
+            }
+            results.push((index, result));
+        }
+        results.sort_by_key(|(index, _)| *index);
+        let results = results.into_iter().map(|(_, result)| result).collect();
+
+        self.finish_run(&suite, start_time, results, None, options)
+            .await
+    }
+
+    /// Runs queued cases on one device until the queue is empty or the device drops
+    /// out, either because it never became ready or because its suite setup failed
+    async fn run_pool_worker(
+        &self,
+        suite: &TestSuite,
+        pool: &DevicePool,
+        device_id: &str,
+        queue: &CaseQueue,
+        progress: Option<UnboundedSender<TestProgress>>,
+    ) -> Result<Vec<(usize, TestResult)>> {
+        let progress = |event: TestProgress| {
+            if let Some(sender) = &progress {
+                let _ = sender.send(event);
+            }
+        };
+        let mut results = Vec::new();
+
+        if let Err(e) = pool.ready(device_id).await {
+            warn!("{} left the device pool: {}", device_id, e);
+            return Ok(results);
+        }
+
+        let context = self.case_context(suite, Some(device_id));
+        let mut hook_screenshots = Vec::new();
+        let before_all = self
+            .run_hook_steps(
+                &suite.name,
+                "before_all",
+                &suite.hooks.before_all,
+                Some(device_id),
+                &mut hook_screenshots,
+            )
+            .await;
+
+        match before_all {
+            Err(e) => warn!("Suite setup failed on {}: {}", device_id, e),
+            Ok(()) => {
+                while let Some(index) = next_case(queue) {
+                    if let Err(e) = pool.ready(device_id).await {
+                        warn!("{} left the device pool: {}", device_id, e);
+                        if let Ok(mut queue) = queue.lock() {
+                            queue.push_front(index);
+                        }
+                        break;
+                    }
+
+                    let test_case = &suite.tests[index];
+                    info!("Running test: {} on {}", test_case.name, device_id);
+                    progress(TestProgress::CaseStarted {
+                        name: test_case.name.clone(),
+                    });
+
+                    let result = self
+                        .run_case(suite, test_case, Some(device_id), &context)
+                        .await?;
+
+                    progress(TestProgress::CaseFinished(result.clone()));
+                    results.push((index, result));
+                }
+            }
+        }
+
+        // Suite teardown runs even when its setup failed part way through
+        if let Err(e) = self
+            .run_hook_steps(
+                &suite.name,
+                "after_all",
+                &suite.hooks.after_all,
+                Some(device_id),
+                &mut hook_screenshots,
+            )
+            .await
+        {
+            warn!("Suite teardown failed on {}: {}", device_id, e);
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::testing::{TestConfig, TestHooks};
+
+    fn pooled_suite(names: &[&str]) -> TestSuite {
+        TestSuite {
+            name: "pooled".to_string(),
+            tests: names
+                .iter()
+                .map(|name| TestCase {
+                    name: name.to_string(),
+                    description: None,
+                    steps: Vec::new(),
+                    expected_result: None,
+                    timeout: None,
+                    tags: Vec::new(),
+                    hooks: TestHooks::default(),
+                    fixtures: Vec::new(),
+                })
+                .collect(),
+            config: TestConfig {
+                timeout: Duration::from_secs(30),
+                screenshot_on_failure: false,
+                video_recording: false,
+                parallel_execution: true,
+                retry_count: 0,
+            },
+            hooks: TestHooks::default(),
+            fixtures: Vec::new(),
+        }
+    }
+
+    async fn runner(dir: &tempfile::TempDir) -> TestRunner {
+        let mut config = Config::default();
+        config.testing.output_dir = dir.path().to_path_buf();
+        TestRunner::new(&config).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pool_runs_every_case_once_in_suite_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = runner(&dir).await;
+
+        let names = ["a", "b", "c", "d", "e"];
+        let suite = pooled_suite(&names);
+        let pool = DevicePool::new(vec!["emulator-5554".into(), "emulator-5556".into()]);
+
+        let report = runner
+            .run_suite_on_pool(suite, &pool, &TestRunOptions::default())
+            .await
+            .unwrap();
+
+        let ran: Vec<_> = report
+            .results
+            .iter()
+            .map(|r| r.test_name.as_str())
+            .collect();
+        assert_eq!(ran, names);
+        assert_eq!(report.summary.passed, 5);
+    }
+
+    #[tokio::test]
+    async fn test_empty_pool_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = runner(&dir).await;
+        let suite = pooled_suite(&["a"]);
+
+        let error = runner
+            .run_suite_on_pool(
+                suite,
+                &DevicePool::new(Vec::new()),
+                &TestRunOptions::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::DeviceNotFound(_))
+        ));
+    }
+}
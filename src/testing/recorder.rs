@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::ui_hierarchy::UiHierarchy;
+use super::{TestAction, TestStep};
+
+/// Movement (in screen pixels) below which a touch still counts as a tap
+pub const TAP_SLOP_PX: i32 = 24;
+/// Hold time after which a stationary touch becomes a long press
+pub const LONG_PRESS_THRESHOLD: Duration = Duration::from_millis(500);
+/// Gaps between gestures longer than this are capped when emitted as waits
+pub const MAX_RECORDED_WAIT: Duration = Duration::from_secs(10);
+
+/// One line of `getevent -lt` output
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawInputEvent {
+    pub timestamp: f64,
+    pub device: String,
+    pub event_type: String,
+    pub code: String,
+    pub value: String,
+}
+
+/// Parses lines such as
+/// `[   1234.567890] /dev/input/event2: EV_ABS       ABS_MT_POSITION_X    0000021c`
+pub fn parse_getevent_line(line: &str) -> Option<RawInputEvent> {
+    let line = line.trim();
+    let rest = line.strip_prefix('[')?;
+    let close = rest.find(']')?;
+    let timestamp = rest[..close].trim().parse().ok()?;
+    let rest = rest[close + 1..].trim();
+
+    let colon = rest.find(": ")?;
+    let device = rest[..colon].to_string();
+    let mut fields = rest[colon + 2..].split_whitespace();
+
+    Some(RawInputEvent {
+        timestamp,
+        device,
+        event_type: fields.next()?.to_string(),
+        code: fields.next()?.to_string(),
+        value: fields.next()?.to_string(),
+    })
+}
+
+/// Maps raw touchscreen axis values onto screen pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputScale {
+    pub max_x: i32,
+    pub max_y: i32,
+    pub screen_width: i32,
+    pub screen_height: i32,
+}
+
+impl InputScale {
+    pub fn apply(&self, x: i32, y: i32) -> (i32, i32) {
+        let scale = |value: i32, max: i32, screen: i32| {
+            if max <= 0 || screen <= 0 {
+                value
+            } else {
+                ((value as i64 * screen as i64) / (max as i64 + 1)) as i32
+            }
+        };
+        (
+            scale(x, self.max_x, self.screen_width),
+            scale(y, self.max_y, self.screen_height),
+        )
+    }
+}
+
+/// Extracts the ABS_MT_POSITION_X/Y maxima from `getevent -lp` output
+pub fn parse_axis_ranges(getevent_lp: &str) -> Option<(i32, i32)> {
+    let max_of = |axis: &str| {
+        getevent_lp
+            .lines()
+            .find(|line| line.contains(axis))
+            .and_then(|line| line.split("max ").nth(1))
+            .and_then(|rest| rest.split(',').next())
+            .and_then(|value| value.trim().parse::<i32>().ok())
+    };
+
+    Some((max_of("ABS_MT_POSITION_X")?, max_of("ABS_MT_POSITION_Y")?))
+}
+
+/// Parses `wm size` output, preferring an override size when present
+pub fn parse_wm_size(output: &str) -> Option<(i32, i32)> {
+    let line = output
+        .lines()
+        .find(|line| line.starts_with("Override size:"))
+        .or_else(|| output.lines().find(|line| line.contains("size:")))?;
+    let (width, height) = line.split(':').nth(1)?.trim().split_once('x')?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Gesture {
+    Tap {
+        x: i32,
+        y: i32,
+    },
+    LongPress {
+        x: i32,
+        y: i32,
+        duration: Duration,
+    },
+    Swipe {
+        from: (i32, i32),
+        to: (i32, i32),
+        duration: Duration,
+    },
+}
+
+/// A gesture along with the touch-down/touch-up timestamps from the kernel clock
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedGesture {
+    pub gesture: Gesture,
+    pub started_at: f64,
+    pub ended_at: f64,
+}
+
+impl TimedGesture {
+    pub fn start_point(&self) -> (i32, i32) {
+        match &self.gesture {
+            Gesture::Tap { x, y } | Gesture::LongPress { x, y, .. } => (*x, *y),
+            Gesture::Swipe { from, .. } => *from,
+        }
+    }
+}
+
+/// Turns a stream of raw getevent events into tap, swipe and long-press gestures.
+/// Only the first touch slot is tracked; multi-touch input is folded into it.
+#[derive(Debug, Default)]
+pub struct GestureDetector {
+    scale: Option<InputScale>,
+    touching: bool,
+    pending_down: bool,
+    pending_up: bool,
+    current: (Option<i32>, Option<i32>),
+    start: Option<(f64, (i32, i32))>,
+    last: Option<(i32, i32)>,
+}
+
+impl GestureDetector {
+    pub fn new(scale: Option<InputScale>) -> Self {
+        Self {
+            scale,
+            ..Default::default()
+        }
+    }
+
+    /// True while a finger is down, i.e. a gesture is in progress
+    pub fn is_touching(&self) -> bool {
+        self.touching
+    }
+
+    pub fn feed(&mut self, event: &RawInputEvent) -> Option<TimedGesture> {
+        match (event.event_type.as_str(), event.code.as_str()) {
+            ("EV_ABS", "ABS_MT_POSITION_X") | ("EV_ABS", "ABS_X") => {
+                self.current.0 = parse_event_value(&event.value);
+            }
+            ("EV_ABS", "ABS_MT_POSITION_Y") | ("EV_ABS", "ABS_Y") => {
+                self.current.1 = parse_event_value(&event.value);
+            }
+            ("EV_ABS", "ABS_MT_TRACKING_ID") => {
+                if event.value == "ffffffff" {
+                    self.pending_up = true;
+                } else if !self.touching {
+                    self.pending_down = true;
+                }
+            }
+            ("EV_KEY", "BTN_TOUCH") => match event.value.as_str() {
+                "DOWN" if !self.touching => self.pending_down = true,
+                "UP" => self.pending_up = true,
+                _ => {}
+            },
+            ("EV_SYN", "SYN_REPORT") => return self.sync(event.timestamp),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn sync(&mut self, timestamp: f64) -> Option<TimedGesture> {
+        let point = match self.current {
+            (Some(x), Some(y)) => Some(match &self.scale {
+                Some(scale) => scale.apply(x, y),
+                None => (x, y),
+            }),
+            _ => None,
+        };
+
+        if self.pending_down {
+            self.pending_down = false;
+            if let Some(point) = point {
+                self.touching = true;
+                self.start = Some((timestamp, point));
+                self.last = Some(point);
+            }
+        } else if self.touching {
+            if let Some(point) = point {
+                self.last = Some(point);
+            }
+        }
+
+        if !self.pending_up {
+            return None;
+        }
+        self.pending_up = false;
+        self.touching = false;
+
+        let (started_at, from) = self.start.take()?;
+        let to = self.last.take().unwrap_or(from);
+        let duration = Duration::from_secs_f64((timestamp - started_at).max(0.0));
+
+        let moved = (to.0 - from.0).abs().max((to.1 - from.1).abs());
+        let gesture = if moved > TAP_SLOP_PX {
+            Gesture::Swipe { from, to, duration }
+        } else if duration >= LONG_PRESS_THRESHOLD {
+            Gesture::LongPress {
+                x: from.0,
+                y: from.1,
+                duration,
+            }
+        } else {
+            Gesture::Tap {
+                x: from.0,
+                y: from.1,
+            }
+        };
+
+        Some(TimedGesture {
+            gesture,
+            started_at,
+            ended_at: timestamp,
+        })
+    }
+}
+
+fn parse_event_value(value: &str) -> Option<i32> {
+    u32::from_str_radix(value, 16).ok().map(|v| v as i32)
+}
+
+/// Replays a captured `getevent -lt` log through a `GestureDetector`
+pub fn gestures_from_log(log: &str, scale: Option<InputScale>) -> Vec<TimedGesture> {
+    let mut detector = GestureDetector::new(scale);
+    log.lines()
+        .filter_map(parse_getevent_line)
+        .filter_map(|event| detector.feed(&event))
+        .collect()
+}
+
+/// Converts a gesture into a test step. Taps and long presses are resolved to a
+/// selector through the hierarchy captured when the gesture started.
+pub fn gesture_to_step(gesture: &Gesture, hierarchy: Option<&UiHierarchy>) -> TestStep {
+    let selector_for = |x: i32, y: i32| match hierarchy {
+        Some(hierarchy) => hierarchy.selector_at(x, y).to_string(),
+        None => format!("{x} {y}"),
+    };
+
+    match gesture {
+        Gesture::Tap { x, y } => TestStep {
+            action: TestAction::Tap,
+            target: Some(selector_for(*x, *y)),
+            value: None,
+            wait_time: None,
+        },
+        Gesture::LongPress { x, y, duration } => TestStep {
+            action: TestAction::LongPress,
+            target: Some(selector_for(*x, *y)),
+            value: Some(duration.as_millis().to_string()),
+            wait_time: None,
+        },
+        Gesture::Swipe { from, to, duration } => TestStep {
+            action: TestAction::Swipe,
+            target: Some(format!(
+                "{} {} {} {} {}",
+                from.0,
+                from.1,
+                to.0,
+                to.1,
+                duration.as_millis()
+            )),
+            value: None,
+            wait_time: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAP_THEN_SWIPE: &str = "\
+[   100.000000] /dev/input/event2: EV_ABS       ABS_MT_TRACKING_ID   00000001
+[   100.000000] /dev/input/event2: EV_ABS       ABS_MT_POSITION_X    0000021c
+[   100.000000] /dev/input/event2: EV_ABS       ABS_MT_POSITION_Y    000004b0
+[   100.000000] /dev/input/event2: EV_KEY       BTN_TOUCH            DOWN
+[   100.000000] /dev/input/event2: EV_SYN       SYN_REPORT           00000000
+[   100.080000] /dev/input/event2: EV_ABS       ABS_MT_POSITION_X    0000021e
+[   100.080000] /dev/input/event2: EV_SYN       SYN_REPORT           00000000
+[   100.120000] /dev/input/event2: EV_ABS       ABS_MT_TRACKING_ID   ffffffff
+[   100.120000] /dev/input/event2: EV_KEY       BTN_TOUCH            UP
+[   100.120000] /dev/input/event2: EV_SYN       SYN_REPORT           00000000
+[   102.000000] /dev/input/event2: EV_ABS       ABS_MT_TRACKING_ID   00000002
+[   102.000000] /dev/input/event2: EV_ABS       ABS_MT_POSITION_X    000001f4
+[   102.000000] /dev/input/event2: EV_ABS       ABS_MT_POSITION_Y    00000708
+[   102.000000] /dev/input/event2: EV_KEY       BTN_TOUCH            DOWN
+[   102.000000] /dev/input/event2: EV_SYN       SYN_REPORT           00000000
+[   102.100000] /dev/input/event2: EV_ABS       ABS_MT_POSITION_Y    000004b0
+[   102.100000] /dev/input/event2: EV_SYN       SYN_REPORT           00000000
+[   102.200000] /dev/input/event2: EV_ABS       ABS_MT_POSITION_Y    000001f4
+[   102.200000] /dev/input/event2: EV_SYN       SYN_REPORT           00000000
+[   102.250000] /dev/input/event2: EV_ABS       ABS_MT_TRACKING_ID   ffffffff
+[   102.250000] /dev/input/event2: EV_KEY       BTN_TOUCH            UP
+[   102.250000] /dev/input/event2: EV_SYN       SYN_REPORT           00000000
+";
+
+    const LONG_PRESS: &str = "\
+[    50.000000] /dev/input/event1: EV_ABS       ABS_MT_TRACKING_ID   0000000a
+[    50.000000] /dev/input/event1: EV_ABS       ABS_MT_POSITION_X    00000064
+[    50.000000] /dev/input/event1: EV_ABS       ABS_MT_POSITION_Y    000000c8
+[    50.000000] /dev/input/event1: EV_SYN       SYN_REPORT           00000000
+[    50.900000] /dev/input/event1: EV_ABS       ABS_MT_TRACKING_ID   ffffffff
+[    50.900000] /dev/input/event1: EV_SYN       SYN_REPORT           00000000
+";
+
+    #[test]
+    fn test_parse_getevent_line() {
+        let event = parse_getevent_line(
+            "[   1234.567890] /dev/input/event2: EV_ABS       ABS_MT_POSITION_X    0000021c",
+        )
+        .unwrap();
+        assert_eq!(event.device, "/dev/input/event2");
+        assert_eq!(event.event_type, "EV_ABS");
+        assert_eq!(event.code, "ABS_MT_POSITION_X");
+        assert_eq!(parse_event_value(&event.value), Some(540));
+        assert!(parse_getevent_line("add device 1: /dev/input/event2").is_none());
+    }
+
+    #[test]
+    fn test_tap_and_swipe_from_log() {
+        let gestures = gestures_from_log(TAP_THEN_SWIPE, None);
+        assert_eq!(gestures.len(), 2);
+        assert_eq!(gestures[0].gesture, Gesture::Tap { x: 540, y: 1200 });
+        match &gestures[1].gesture {
+            Gesture::Swipe { from, to, duration } => {
+                assert_eq!(*from, (500, 1800));
+                assert_eq!(*to, (500, 500));
+                assert_eq!(duration.as_millis(), 250);
+            }
+            other => panic!("expected swipe, got {other:?}"),
+        }
+        assert!((gestures[1].started_at - 102.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_long_press_without_btn_touch() {
+        let gestures = gestures_from_log(LONG_PRESS, None);
+        assert_eq!(gestures.len(), 1);
+        assert!(matches!(
+            gestures[0].gesture,
+            Gesture::LongPress { x: 100, y: 200, .. }
+        ));
+    }
+
+    #[test]
+    fn test_input_scaling() {
+        let lp = "    ABS (0003): ABS_MT_POSITION_X     : value 0, min 0, max 4095, fuzz 0, flat 0, resolution 0\n    ABS (0003): ABS_MT_POSITION_Y     : value 0, min 0, max 4095, fuzz 0, flat 0, resolution 0";
+        assert_eq!(parse_axis_ranges(lp), Some((4095, 4095)));
+        assert_eq!(
+            parse_wm_size("Physical size: 1080x2400\nOverride size: 720x1600"),
+            Some((720, 1600))
+        );
+
+        let scale = InputScale {
+            max_x: 4095,
+            max_y: 4095,
+            screen_width: 1080,
+            screen_height: 2400,
+        };
+        assert_eq!(scale.apply(2048, 2048), (540, 1200));
+    }
+}
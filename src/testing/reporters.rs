@@ -0,0 +1,386 @@
+use anyhow::Result;
+use base64::Engine;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use super::{TestReport, TestResult, TestStatus};
+
+/// Output formats a `TestReport` can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReporterKind {
+    Json,
+    Junit,
+    Html,
+    Tap,
+}
+
+impl ReporterKind {
+    pub fn file_name(&self, suite_name: &str) -> String {
+        match self {
+            ReporterKind::Json => format!("{suite_name}_report.json"),
+            ReporterKind::Junit => format!("{suite_name}_junit.xml"),
+            ReporterKind::Html => format!("{suite_name}_report.html"),
+            ReporterKind::Tap => format!("{suite_name}_report.tap"),
+        }
+    }
+}
+
+/// Writes `report` once per reporter into `output_dir` and returns the written paths.
+/// Screenshot paths in the report are resolved relative to `output_dir`.
+pub fn write_reports(
+    report: &TestReport,
+    reporters: &[ReporterKind],
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut seen = Vec::new();
+    let mut written = Vec::new();
+
+    for reporter in reporters {
+        if seen.contains(reporter) {
+            continue;
+        }
+        seen.push(*reporter);
+
+        let content = match reporter {
+            ReporterKind::Json => serde_json::to_string_pretty(report)?,
+            ReporterKind::Junit => render_junit(report),
+            ReporterKind::Html => render_html(report, output_dir),
+            ReporterKind::Tap => render_tap(report),
+        };
+
+        let path = output_dir.join(reporter.file_name(&report.suite_name));
+        fs::write(&path, content)?;
+        debug!("Wrote {:?} report to {:?}", reporter, path);
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+pub fn render_junit(report: &TestReport) -> String {
+    let total_time: f64 = report
+        .results
+        .iter()
+        .map(|r| r.duration.as_secs_f64())
+        .sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+        escape_markup(&report.suite_name),
+        report.summary.total,
+        report.summary.failed,
+        report.summary.timeout,
+        report.summary.skipped,
+        total_time
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\" timestamp=\"{}\">",
+        escape_markup(&report.suite_name),
+        report.summary.total,
+        report.summary.failed,
+        report.summary.timeout,
+        report.summary.skipped,
+        total_time,
+        report.start_time.format("%Y-%m-%dT%H:%M:%S")
+    );
+
+    for result in &report.results {
+        let _ = write!(
+            xml,
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape_markup(&result.test_name),
+            escape_markup(&report.suite_name),
+            result.duration.as_secs_f64()
+        );
+
+        let message = escape_markup(result.error_message.as_deref().unwrap_or_default());
+        let body = match result.status {
+            TestStatus::Passed => None,
+            TestStatus::Failed => Some(format!(
+                "      <failure message=\"{message}\">{message}</failure>\n"
+            )),
+            TestStatus::Timeout => Some(format!(
+                "      <error type=\"timeout\" message=\"{message}\">{message}</error>\n"
+            )),
+            TestStatus::Skipped => Some("      <skipped/>\n".to_string()),
+        };
+
+        let attachments = junit_attachments(result);
+
+        if body.is_none() && attachments.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+
+        xml.push_str(">\n");
+        if let Some(body) = body {
+            xml.push_str(&body);
+        }
+        if !attachments.is_empty() {
+            let _ = writeln!(xml, "      <system-out>{attachments}</system-out>");
+        }
+        xml.push_str("    </testcase>\n");
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// Jenkins and GitLab pick up `[[ATTACHMENT|path]]` markers from `system-out`
+fn junit_attachments(result: &TestResult) -> String {
+    result
+        .screenshots
+        .iter()
+        .chain(result.video_path.iter())
+        .chain(result.log_path.iter())
+        .map(|path| format!("[[ATTACHMENT|{}]]", escape_markup(path)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn render_tap(report: &TestReport) -> String {
+    let mut tap = String::from("TAP version 13\n");
+    let _ = writeln!(tap, "1..{}", report.results.len());
+
+    for (i, result) in report.results.iter().enumerate() {
+        let number = i + 1;
+        let name = result.test_name.replace('#', "\\#");
+        match result.status {
+            TestStatus::Passed => {
+                let _ = writeln!(tap, "ok {number} - {name}");
+            }
+            TestStatus::Skipped => {
+                let _ = writeln!(tap, "ok {number} - {name} # SKIP");
+            }
+            TestStatus::Failed | TestStatus::Timeout => {
+                let _ = writeln!(tap, "not ok {number} - {name}");
+                tap.push_str("  ---\n");
+                let _ = writeln!(tap, "  status: {:?}", result.status);
+                if let Some(message) = &result.error_message {
+                    let _ = writeln!(tap, "  message: {}", serde_json::json!(message));
+                }
+                let _ = writeln!(tap, "  duration_ms: {}", result.duration.as_millis());
+                tap.push_str("  ...\n");
+            }
+        }
+    }
+
+    tap
+}
+
+pub fn render_html(report: &TestReport, output_dir: &Path) -> String {
+    let mut html = String::new();
+    let title = escape_markup(&report.suite_name);
+
+    let _ = writeln!(
+        html,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>KMobile Test Report - {title}</title>
+<style>
+body {{ font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", sans-serif; margin: 2rem; color: #222; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 0.5rem; text-align: left; vertical-align: top; }}
+.passed {{ color: #1a7f37; }}
+.failed, .timeout {{ color: #cf222e; }}
+.skipped {{ color: #9a6700; }}
+.screenshots img {{ max-width: 240px; margin: 0.25rem; border: 1px solid #ccc; }}
+pre {{ white-space: pre-wrap; margin: 0; }}
+</style>
+</head>
+<body>
+<h1>Test Report: {title}</h1>"#
+    );
+
+    let _ = writeln!(
+        html,
+        "<p>Started {} &middot; Finished {}</p>",
+        report.start_time.to_rfc3339(),
+        report
+            .end_time
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| "-".to_string())
+    );
+    let _ = writeln!(
+        html,
+        "<p>Total: {} &middot; <span class=\"passed\">Passed: {}</span> &middot; <span class=\"failed\">Failed: {}</span> &middot; <span class=\"skipped\">Skipped: {}</span> &middot; <span class=\"timeout\">Timeout: {}</span></p>",
+        report.summary.total,
+        report.summary.passed,
+        report.summary.failed,
+        report.summary.skipped,
+        report.summary.timeout
+    );
+
+    html.push_str(
+        "<table>\n<tr><th>Test</th><th>Status</th><th>Duration</th><th>Details</th></tr>\n",
+    );
+
+    for result in &report.results {
+        let status_class = format!("{:?}", result.status).to_lowercase();
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td class=\"{status_class}\">{:?}</td><td>{:.2}s</td><td>",
+            escape_markup(&result.test_name),
+            result.status,
+            result.duration.as_secs_f64()
+        );
+
+        if let Some(message) = &result.error_message {
+            let _ = write!(html, "<pre>{}</pre>", escape_markup(message));
+        }
+
+        if !result.screenshots.is_empty() {
+            html.push_str("<div class=\"screenshots\">");
+            for screenshot in &result.screenshots {
+                html.push_str(&embed_screenshot(output_dir, screenshot));
+            }
+            html.push_str("</div>");
+        }
+
+        if let Some(log) = &result.log_path {
+            let _ = write!(
+                html,
+                "<p><a href=\"{}\">Device log</a></p>",
+                escape_markup(log)
+            );
+        }
+
+        if let Some(video) = &result.video_path {
+            let _ = write!(
+                html,
+                "<p><a href=\"{}\">Video recording</a></p>",
+                escape_markup(video)
+            );
+        }
+
+        html.push_str("</td></tr>\n");
+    }
+
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+fn embed_screenshot(output_dir: &Path, screenshot: &str) -> String {
+    let path = output_dir.join(screenshot);
+    match fs::read(&path) {
+        Ok(bytes) => {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+            format!(
+                "<img src=\"data:image/png;base64,{encoded}\" alt=\"{}\">",
+                escape_markup(screenshot)
+            )
+        }
+        Err(e) => {
+            warn!("Could not embed screenshot {:?}: {}", path, e);
+            format!("<p>Missing screenshot: {}</p>", escape_markup(screenshot))
+        }
+    }
+}
+
+fn escape_markup(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if (c as u32) < 0x20 && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestSummary;
+    use chrono::Utc;
+    use std::time::Duration;
+
+    fn sample_report() -> TestReport {
+        TestReport {
+            suite_name: "smoke".to_string(),
+            start_time: Utc::now(),
+            end_time: Some(Utc::now()),
+            results: vec![
+                TestResult {
+                    test_name: "login".to_string(),
+                    status: TestStatus::Passed,
+                    duration: Duration::from_millis(1500),
+                    error_message: None,
+                    screenshots: Vec::new(),
+                    video_path: None,
+                    log_path: None,
+                },
+                TestResult {
+                    test_name: "checkout <cart>".to_string(),
+                    status: TestStatus::Failed,
+                    duration: Duration::from_millis(250),
+                    error_message: Some("Element not found: \"Pay\"".to_string()),
+                    screenshots: vec!["checkout_1_failure.png".to_string()],
+                    video_path: None,
+                    log_path: None,
+                },
+            ],
+            summary: TestSummary {
+                total: 2,
+                passed: 1,
+                failed: 1,
+                skipped: 0,
+                timeout: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_render_junit() {
+        let xml = render_junit(&sample_report());
+        assert!(xml.contains("<testsuite name=\"smoke\" tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("<testcase name=\"login\" classname=\"smoke\" time=\"1.500\"/>"));
+        assert!(xml.contains("name=\"checkout &lt;cart&gt;\""));
+        assert!(xml.contains("<failure message=\"Element not found: &quot;Pay&quot;\">"));
+        assert!(xml.contains("[[ATTACHMENT|checkout_1_failure.png]]"));
+    }
+
+    #[test]
+    fn test_render_tap() {
+        let tap = render_tap(&sample_report());
+        let lines: Vec<&str> = tap.lines().collect();
+        assert_eq!(lines[0], "TAP version 13");
+        assert_eq!(lines[1], "1..2");
+        assert_eq!(lines[2], "ok 1 - login");
+        assert_eq!(lines[3], "not ok 2 - checkout <cart>");
+        assert!(tap.contains("message: \"Element not found: \\\"Pay\\\"\""));
+    }
+
+    #[test]
+    fn test_write_reports_embeds_screenshots() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("checkout_1_failure.png"), b"png").unwrap();
+
+        let paths = write_reports(
+            &sample_report(),
+            &[ReporterKind::Html, ReporterKind::Json, ReporterKind::Html],
+            dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(paths.len(), 2);
+        let html = fs::read_to_string(dir.path().join("smoke_report.html")).unwrap();
+        assert!(html.contains("data:image/png;base64,cG5n"));
+        assert!(dir.path().join("smoke_report.json").exists());
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::reporters::ReporterKind;
+use super::{TestCase, TestReport, TestStatus};
+use crate::error::KMobileError;
+
+/// One slice of a sharded run, written `index/total` with a 1-based index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub total: usize,
+}
+
+impl Shard {
+    /// Cases are dealt round-robin so every shard gets a similar mix of the suite
+    pub fn contains(&self, position: usize) -> bool {
+        position % self.total == self.index - 1
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let (index, total) = value
+            .split_once('/')
+            .ok_or_else(|| format!("expected INDEX/TOTAL, got '{value}'"))?;
+        let index: usize = index
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid shard index '{index}'"))?;
+        let total: usize = total
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid shard total '{total}'"))?;
+
+        if total == 0 || index == 0 || index > total {
+            return Err(format!(
+                "shard index must be between 1 and {total}, got {index}"
+            ));
+        }
+        Ok(Self { index, total })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.total)
+    }
+}
+
+/// Narrows a suite down to the test cases a run should execute
+#[derive(Debug, Clone, Default)]
+pub struct TestFilter {
+    /// Case-insensitive substring matched against the case name and description
+    pub grep: Option<String>,
+    /// Keep cases carrying at least one of these tags
+    pub tags: Vec<String>,
+    /// Drop cases carrying any of these tags
+    pub exclude_tags: Vec<String>,
+    pub shard: Option<Shard>,
+    /// Only rerun cases that failed in the suite's last JSON report
+    pub only_failed: bool,
+}
+
+impl TestFilter {
+    pub fn is_empty(&self) -> bool {
+        self.grep.is_none()
+            && self.tags.is_empty()
+            && self.exclude_tags.is_empty()
+            && self.shard.is_none()
+            && !self.only_failed
+    }
+
+    pub fn matches(&self, test_case: &TestCase) -> bool {
+        if let Some(pattern) = &self.grep {
+            let pattern = pattern.to_lowercase();
+            let in_name = test_case.name.to_lowercase().contains(&pattern);
+            let in_description = test_case
+                .description
+                .as_ref()
+                .is_some_and(|d| d.to_lowercase().contains(&pattern));
+            if !in_name && !in_description {
+                return false;
+            }
+        }
+
+        let has_tag = |tag: &String| test_case.tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+        if !self.tags.is_empty() && !self.tags.iter().any(has_tag) {
+            return false;
+        }
+        !self.exclude_tags.iter().any(has_tag)
+    }
+
+    /// Applies the filters, then the shard. `failed` holds the names of the cases that
+    /// failed last time and is only consulted with `only_failed`.
+    pub fn select(&self, tests: Vec<TestCase>, failed: Option<&HashSet<String>>) -> Vec<TestCase> {
+        tests
+            .into_iter()
+            .filter(|test_case| self.matches(test_case))
+            .filter(|test_case| {
+                !self.only_failed || failed.is_some_and(|failed| failed.contains(&test_case.name))
+            })
+            .enumerate()
+            .filter(|(position, _)| self.shard.is_none_or(|shard| shard.contains(*position)))
+            .map(|(_, test_case)| test_case)
+            .collect()
+    }
+}
+
+/// Names of the cases that failed or timed out in the last JSON report written for
+/// `suite_name`
+pub fn last_failed_cases(output_dir: &Path, suite_name: &str) -> Result<HashSet<String>> {
+    let path = output_dir.join(ReporterKind::Json.file_name(suite_name));
+    if !path.exists() {
+        return Err(KMobileError::TestFileNotFound(format!(
+            "{} (--only-failed needs the JSON report of a previous run)",
+            path.display()
+        ))
+        .into());
+    }
+
+    let report: TestReport = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    Ok(report
+        .results
+        .into_iter()
+        .filter(|result| matches!(result.status, TestStatus::Failed | TestStatus::Timeout))
+        .map(|result| result.test_name)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestHooks, TestResult, TestSummary};
+
+    fn case(name: &str, tags: &[&str]) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            description: None,
+            steps: Vec::new(),
+            expected_result: None,
+            timeout: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            hooks: TestHooks::default(),
+            fixtures: Vec::new(),
+        }
+    }
+
+    fn names(tests: &[TestCase]) -> Vec<&str> {
+        tests.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_shard_parse() {
+        assert_eq!("2/4".parse(), Ok(Shard { index: 2, total: 4 }));
+        assert!("0/4".parse::<Shard>().is_err());
+        assert!("5/4".parse::<Shard>().is_err());
+        assert!("2".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn test_select_with_tags_grep_and_shard() {
+        let tests = vec![
+            case("login_valid", &["smoke", "auth"]),
+            case("login_invalid", &["auth"]),
+            case("checkout", &["smoke", "slow"]),
+            case("search", &["smoke"]),
+        ];
+
+        let filter = TestFilter {
+            tags: vec!["smoke".to_string()],
+            exclude_tags: vec!["SLOW".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&filter.select(tests.clone(), None)),
+            ["login_valid", "search"]
+        );
+
+        let filter = TestFilter {
+            grep: Some("LOGIN".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&filter.select(tests.clone(), None)),
+            ["login_valid", "login_invalid"]
+        );
+
+        let shard = |index| TestFilter {
+            shard: Some(Shard { index, total: 2 }),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&shard(1).select(tests.clone(), None)),
+            ["login_valid", "checkout"]
+        );
+        assert_eq!(
+            names(&shard(2).select(tests.clone(), None)),
+            ["login_invalid", "search"]
+        );
+
+        let failed = HashSet::from(["checkout".to_string()]);
+        let filter = TestFilter {
+            only_failed: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&filter.select(tests, Some(&failed))), ["checkout"]);
+    }
+
+    #[test]
+    fn test_last_failed_cases_include_timeouts() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(last_failed_cases(dir.path(), "smoke").is_err());
+
+        let result = |name: &str, status| TestResult {
+            test_name: name.to_string(),
+            status,
+            duration: std::time::Duration::ZERO,
+            error_message: None,
+            screenshots: Vec::new(),
+            video_path: None,
+            log_path: None,
+        };
+        let report = TestReport {
+            suite_name: "smoke".to_string(),
+            start_time: chrono::Utc::now(),
+            end_time: None,
+            results: vec![
+                result("login", TestStatus::Passed),
+                result("checkout", TestStatus::Failed),
+                result("search", TestStatus::Timeout),
+                result("share", TestStatus::Skipped),
+            ],
+            summary: TestSummary {
+                total: 4,
+                passed: 1,
+                failed: 1,
+                skipped: 1,
+                timeout: 1,
+            },
+        };
+        fs::write(
+            dir.path().join("smoke_report.json"),
+            serde_json::to_string(&report).unwrap(),
+        )
+        .unwrap();
+
+        let failed = last_failed_cases(dir.path(), "smoke").unwrap();
+        assert_eq!(
+            failed,
+            HashSet::from(["checkout".to_string(), "search".to_string()])
+        );
+    }
+}
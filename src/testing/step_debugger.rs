@@ -0,0 +1,252 @@
+use anyhow::Result;
+use std::io::{self, BufRead, Write};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::{TestCase, TestResult, TestRunner, TestStatus, TestStep};
+
+/// Commands accepted at the `--step` prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepCommand {
+    Continue,
+    Skip,
+    Retry,
+    Edit,
+    Quit,
+}
+
+impl StepCommand {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "" | "c" | "continue" => Some(StepCommand::Continue),
+            "s" | "skip" => Some(StepCommand::Skip),
+            "r" | "retry" => Some(StepCommand::Retry),
+            "e" | "edit" => Some(StepCommand::Edit),
+            "q" | "quit" => Some(StepCommand::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of an interactive replay
+pub struct SteppedRun {
+    pub result: TestResult,
+    /// True when any step was edited at the prompt
+    pub edited: bool,
+}
+
+impl TestRunner {
+    /// Runs `test_case` one step at a time, pausing before each step with a fresh
+    /// screenshot and letting the user continue, skip, retry or edit it
+    pub(super) async fn step_through(
+        &self,
+        test_case: &mut TestCase,
+        device_id: Option<&str>,
+    ) -> Result<SteppedRun> {
+        let start_time = Instant::now();
+        let mut screenshots = Vec::new();
+        let mut edited = false;
+        let mut failure: Option<String> = None;
+        let mut skipped_failures = Vec::new();
+        let total = test_case.steps.len();
+        let mut index = 0;
+
+        'steps: while index < total {
+            if device_id.is_some() {
+                let screenshot = format!("{}_step_{}_before.png", test_case.name, index + 1);
+                match self.take_screenshot(device_id, &screenshot).await {
+                    Ok(()) => println!(
+                        "📸 Screen before step {}: {}",
+                        index + 1,
+                        self.test_output_dir.join(&screenshot).display()
+                    ),
+                    Err(e) => warn!("Failed to take step screenshot: {}", e),
+                }
+            }
+
+            print_step(index, total, &test_case.steps[index]);
+            let mut command = prompt("[c]ontinue, [s]kip, [e]dit, [q]uit").await?;
+
+            loop {
+                match command {
+                    StepCommand::Continue | StepCommand::Retry => {
+                        let step = &test_case.steps[index];
+                        let step_name = format!("{}_step_{}", test_case.name, index + 1);
+                        match self
+                            .execute_test_step(step, &step_name, device_id, &mut screenshots)
+                            .await
+                        {
+                            Ok(()) => {
+                                println!("✅ Step {} passed", index + 1);
+                                failure = None;
+                                break;
+                            }
+                            Err(e) => {
+                                println!("❌ Step {} failed: {}", index + 1, e);
+                                failure = Some(format!("Step {} failed: {}", index + 1, e));
+                                command = prompt("[r]etry, [s]kip, [e]dit, [q]uit").await?;
+                            }
+                        }
+                    }
+                    StepCommand::Skip => {
+                        println!("⏭️  Skipped step {}", index + 1);
+                        skipped_failures.extend(failure.take());
+                        break;
+                    }
+                    StepCommand::Edit => {
+                        let step = test_case.steps[index].clone();
+                        test_case.steps[index] = blocking(move || edit_step(step)).await?;
+                        edited = true;
+                        print_step(index, total, &test_case.steps[index]);
+                        command = prompt("[c]ontinue, [s]kip, [e]dit, [q]uit").await?;
+                    }
+                    StepCommand::Quit => break 'steps,
+                }
+            }
+
+            index += 1;
+        }
+
+        let failures: Vec<String> = skipped_failures.into_iter().chain(failure).collect();
+        let status = stepped_status(&failures, index < total);
+
+        Ok(SteppedRun {
+            result: TestResult {
+                test_name: test_case.name.clone(),
+                status,
+                duration: start_time.elapsed(),
+                error_message: (!failures.is_empty()).then(|| failures.join("; ")),
+                screenshots,
+                video_path: None,
+                log_path: None,
+            },
+            edited,
+        })
+    }
+}
+
+fn print_step(index: usize, total: usize, step: &TestStep) {
+    println!(
+        "\n▶️  Step {}/{}: {:?} target={} value={} wait={}",
+        index + 1,
+        total,
+        step.action,
+        step.target.as_deref().unwrap_or("-"),
+        step.value.as_deref().unwrap_or("-"),
+        step.wait_time
+            .map(|w| format!("{}ms", w.as_millis()))
+            .unwrap_or_else(|| "-".to_string())
+    );
+}
+
+/// A failed step stays a failure when it's skipped, so the case only passes when
+/// every step ran and passed
+fn stepped_status(failures: &[String], stopped_early: bool) -> TestStatus {
+    if !failures.is_empty() {
+        TestStatus::Failed
+    } else if stopped_early {
+        TestStatus::Skipped
+    } else {
+        TestStatus::Passed
+    }
+}
+
+/// Runs a stdin read on the blocking pool so it doesn't stall the runtime
+async fn blocking<T: Send + 'static>(
+    read: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(read).await?
+}
+
+async fn prompt(options: &str) -> Result<StepCommand> {
+    let options = options.to_string();
+    blocking(move || read_command(&options)).await
+}
+
+fn read_command(options: &str) -> Result<StepCommand> {
+    loop {
+        let input = read_line(&format!("{options} > "))?;
+        match StepCommand::parse(&input) {
+            Some(command) => return Ok(command),
+            None => println!("Unknown command: {}", input.trim()),
+        }
+    }
+}
+
+fn read_line(label: &str) -> Result<String> {
+    print!("{label}");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    if io::stdin().lock().read_line(&mut input)? == 0 {
+        // stdin closed, treat like quitting the session
+        return Ok("q".to_string());
+    }
+    Ok(input.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Prompts for new target, value and wait time; empty input keeps the current value
+/// and `-` clears it
+fn edit_step(mut step: TestStep) -> Result<TestStep> {
+    let edit = |label: &str, current: Option<String>| -> Result<Option<String>> {
+        let input = read_line(&format!(
+            "  {label} [{}]: ",
+            current.as_deref().unwrap_or("-")
+        ))?;
+        Ok(match input.trim() {
+            "" => current,
+            "-" => None,
+            value => Some(value.to_string()),
+        })
+    };
+
+    step.target = edit("target", step.target.clone())?;
+    step.value = edit("value", step.value.clone())?;
+
+    let wait_ms = edit(
+        "wait (ms)",
+        step.wait_time.map(|w| w.as_millis().to_string()),
+    )?;
+    step.wait_time = match wait_ms {
+        Some(ms) => match ms.parse() {
+            Ok(ms) => Some(Duration::from_millis(ms)),
+            Err(_) => {
+                println!("Invalid wait time '{ms}', keeping previous value");
+                step.wait_time
+            }
+        },
+        None => None,
+    };
+
+    Ok(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_command_parse() {
+        assert_eq!(StepCommand::parse(""), Some(StepCommand::Continue));
+        assert_eq!(StepCommand::parse(" S\n"), Some(StepCommand::Skip));
+        assert_eq!(StepCommand::parse("retry"), Some(StepCommand::Retry));
+        assert_eq!(StepCommand::parse("e"), Some(StepCommand::Edit));
+        assert_eq!(StepCommand::parse("quit"), Some(StepCommand::Quit));
+        assert_eq!(StepCommand::parse("x"), None);
+    }
+
+    #[test]
+    fn test_skipped_failures_fail_the_case() {
+        let failures = vec!["Step 2 failed: element not found".to_string()];
+        assert!(matches!(
+            stepped_status(&failures, false),
+            TestStatus::Failed
+        ));
+        assert!(matches!(
+            stepped_status(&failures, true),
+            TestStatus::Failed
+        ));
+        assert!(matches!(stepped_status(&[], true), TestStatus::Skipped));
+        assert!(matches!(stepped_status(&[], false), TestStatus::Passed));
+    }
+}
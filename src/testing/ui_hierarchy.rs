@@ -0,0 +1,388 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::KMobileError;
+use crate::utils::parse_attributes;
+
+/// Device-side path used for `uiautomator dump`
+const DUMP_PATH: &str = "/sdcard/kmobile_window_dump.xml";
+
+/// A single `<node>` from a uiautomator window dump
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UiNode {
+    pub index: usize,
+    pub depth: usize,
+    pub resource_id: String,
+    pub text: String,
+    pub content_desc: String,
+    pub class: String,
+    pub package: String,
+    pub clickable: bool,
+    pub enabled: bool,
+    pub focused: bool,
+    pub bounds: Bounds,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Bounds {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        ((self.left + self.right) / 2, (self.top + self.bottom) / 2)
+    }
+
+    pub fn area(&self) -> i64 {
+        (self.right - self.left).max(0) as i64 * (self.bottom - self.top).max(0) as i64
+    }
+
+    /// Parses the uiautomator `[x1,y1][x2,y2]` bounds format
+    pub fn parse(bounds: &str) -> Option<Self> {
+        let numbers: Vec<i32> = bounds
+            .split(['[', ']', ','])
+            .filter(|part| !part.is_empty())
+            .map(|part| part.trim().parse().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        match numbers.as_slice() {
+            [left, top, right, bottom] => Some(Self {
+                left: *left,
+                top: *top,
+                right: *right,
+                bottom: *bottom,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Element selector used in `TestStep.target`: `id:`, `text:`, `desc:`, `match:` or raw
+/// `x y` coordinates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    ResourceId(String),
+    Text(String),
+    ContentDesc(String),
+    /// A regex matching the whole text or content description, as Maestro selectors
+    /// do; text equal to the pattern also matches, so literal text needs no escaping
+    Matching(String),
+    Coordinates(i32, i32),
+}
+
+impl Selector {
+    pub fn parse(target: &str) -> Option<Self> {
+        let target = target.trim();
+        if let Some(id) = target.strip_prefix("id:") {
+            return Some(Selector::ResourceId(id.to_string()));
+        }
+        if let Some(text) = target.strip_prefix("text:") {
+            return Some(Selector::Text(text.to_string()));
+        }
+        if let Some(desc) = target.strip_prefix("desc:") {
+            return Some(Selector::ContentDesc(desc.to_string()));
+        }
+        if let Some(pattern) = target.strip_prefix("match:") {
+            return Some(Selector::Matching(pattern.to_string()));
+        }
+
+        let parts: Vec<&str> = target
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|p| !p.is_empty())
+            .collect();
+        if let [x, y] = parts.as_slice() {
+            if let (Ok(x), Ok(y)) = (x.parse(), y.parse()) {
+                return Some(Selector::Coordinates(x, y));
+            }
+        }
+
+        None
+    }
+
+    pub fn matches(&self, node: &UiNode) -> bool {
+        match self {
+            Selector::ResourceId(id) => {
+                node.resource_id == *id || node.resource_id.ends_with(&format!(":id/{id}"))
+            }
+            Selector::Text(text) => node.text == *text,
+            Selector::ContentDesc(desc) => node.content_desc == *desc,
+            Selector::Matching(pattern) => {
+                let regex = regex::Regex::new(&format!("^(?:{pattern})$")).ok();
+                [&node.text, &node.content_desc].into_iter().any(|value| {
+                    !value.is_empty()
+                        && (value == pattern || regex.as_ref().is_some_and(|r| r.is_match(value)))
+                })
+            }
+            Selector::Coordinates(x, y) => node.bounds.contains(*x, *y),
+        }
+    }
+}
+
+impl std::fmt::Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Selector::ResourceId(id) => write!(f, "id:{id}"),
+            Selector::Text(text) => write!(f, "text:{text}"),
+            Selector::ContentDesc(desc) => write!(f, "desc:{desc}"),
+            Selector::Matching(pattern) => write!(f, "match:{pattern}"),
+            Selector::Coordinates(x, y) => write!(f, "{x} {y}"),
+        }
+    }
+}
+
+/// Flattened view hierarchy from a uiautomator dump, in document order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UiHierarchy {
+    pub nodes: Vec<UiNode>,
+}
+
+impl UiHierarchy {
+    pub fn parse(xml: &str) -> Self {
+        let mut nodes = Vec::new();
+        let mut depth = 0usize;
+        let mut rest = xml;
+
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start..];
+            let Some(end) = rest.find('>') else {
+                break;
+            };
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            if let Some(name) = tag.strip_prefix('/') {
+                if name.trim() == "node" {
+                    depth = depth.saturating_sub(1);
+                }
+                continue;
+            }
+
+            if !tag.starts_with("node") {
+                continue;
+            }
+
+            let self_closing = tag.ends_with('/');
+            let attrs = parse_attributes(tag.trim_end_matches('/'));
+            let attr = |name: &str| {
+                attrs
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default()
+            };
+
+            nodes.push(UiNode {
+                index: nodes.len(),
+                depth,
+                resource_id: attr("resource-id"),
+                text: attr("text"),
+                content_desc: attr("content-desc"),
+                class: attr("class"),
+                package: attr("package"),
+                clickable: attr("clickable") == "true",
+                enabled: attr("enabled") == "true",
+                focused: attr("focused") == "true",
+                bounds: Bounds::parse(&attr("bounds")).unwrap_or_default(),
+            });
+
+            if !self_closing {
+                depth += 1;
+            }
+        }
+
+        Self { nodes }
+    }
+
+    pub fn find(&self, selector: &Selector) -> Option<&UiNode> {
+        match selector {
+            Selector::Coordinates(x, y) => self.node_at(*x, *y),
+            _ => self.nodes.iter().find(|node| selector.matches(node)),
+        }
+    }
+
+    pub fn find_all(&self, selector: &Selector) -> Vec<&UiNode> {
+        self.nodes
+            .iter()
+            .filter(|node| selector.matches(node))
+            .collect()
+    }
+
+    /// Returns the smallest node whose bounds contain the point
+    pub fn node_at(&self, x: i32, y: i32) -> Option<&UiNode> {
+        self.nodes
+            .iter()
+            .filter(|node| node.bounds.contains(x, y))
+            .min_by_key(|node| (node.bounds.area(), std::cmp::Reverse(node.depth)))
+    }
+
+    /// Picks the most stable selector for the element under a point: the hit node, or
+    /// its nearest clickable ancestor when the hit node itself isn't clickable. Other
+    /// containers are never used, since tapping their center would miss the element.
+    /// Falls back to the raw coordinates when neither is identifiable (and unique).
+    pub fn selector_at(&self, x: i32, y: i32) -> Selector {
+        let Some(hit) = self.node_at(x, y) else {
+            return Selector::Coordinates(x, y);
+        };
+        let clickable_ancestor = (!hit.clickable)
+            .then(|| self.ancestors(hit).find(|node| node.clickable))
+            .flatten();
+
+        for node in std::iter::once(hit).chain(clickable_ancestor) {
+            let options = [
+                (!node.resource_id.is_empty())
+                    .then(|| Selector::ResourceId(node.resource_id.clone())),
+                (!node.text.is_empty()).then(|| Selector::Text(node.text.clone())),
+                (!node.content_desc.is_empty())
+                    .then(|| Selector::ContentDesc(node.content_desc.clone())),
+            ];
+
+            for selector in options.into_iter().flatten() {
+                if self.find_all(&selector).len() == 1 {
+                    return selector;
+                }
+            }
+        }
+
+        Selector::Coordinates(x, y)
+    }
+
+    /// Enclosing nodes of `node`, innermost first
+    fn ancestors<'a>(&'a self, node: &UiNode) -> impl Iterator<Item = &'a UiNode> {
+        let position = self
+            .nodes
+            .iter()
+            .position(|candidate| std::ptr::eq(candidate, node))
+            .unwrap_or(0);
+        let mut depth = node.depth;
+        self.nodes[..position]
+            .iter()
+            .rev()
+            .filter(move |candidate| {
+                let encloses = candidate.depth < depth;
+                if encloses {
+                    depth = candidate.depth;
+                }
+                encloses
+            })
+    }
+}
+
+/// Takes a uiautomator dump on an Android device and parses it
+pub fn dump_android_hierarchy(adb_path: &Path, device_id: &str) -> Result<UiHierarchy> {
+    Ok(UiHierarchy::parse(&dump_android_window(
+        adb_path, device_id,
+    )?))
+}
+
+/// Returns the raw uiautomator window dump XML
+pub fn dump_android_window(adb_path: &Path, device_id: &str) -> Result<String> {
+    let output = Command::new(adb_path)
+        .args(["-s", device_id, "shell", "uiautomator", "dump", DUMP_PATH])
+        .output()?;
+
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(
+            KMobileError::CommandError(format!("uiautomator dump failed: {error_msg}")).into(),
+        );
+    }
+
+    let output = Command::new(adb_path)
+        .args(["-s", device_id, "exec-out", "cat", DUMP_PATH])
+        .output()?;
+
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(KMobileError::CommandError(format!(
+            "Failed to read uiautomator dump: {error_msg}"
+        ))
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = r#"<?xml version='1.0' encoding='UTF-8' standalone='yes' ?><hierarchy rotation="0"><node index="0" text="" resource-id="" class="android.widget.FrameLayout" package="com.example.app" content-desc="" clickable="false" enabled="true" focused="false" bounds="[0,0][1080,2400]"><node index="0" text="Sign in" resource-id="com.example.app:id/login" class="android.widget.Button" package="com.example.app" content-desc="" clickable="true" enabled="true" focused="false" bounds="[100,1000][980,1150]" /><node index="1" text="Terms &amp; Conditions" resource-id="" class="android.widget.TextView" package="com.example.app" content-desc="" clickable="true" enabled="true" focused="false" bounds="[100,1200][980,1300]" /></node></hierarchy>"#;
+
+    #[test]
+    fn test_parse_hierarchy() {
+        let hierarchy = UiHierarchy::parse(DUMP);
+        assert_eq!(hierarchy.nodes.len(), 3);
+        assert_eq!(hierarchy.nodes[1].depth, 1);
+        assert_eq!(hierarchy.nodes[2].text, "Terms & Conditions");
+        assert_eq!(
+            hierarchy.nodes[1].bounds,
+            Bounds {
+                left: 100,
+                top: 1000,
+                right: 980,
+                bottom: 1150
+            }
+        );
+    }
+
+    #[test]
+    fn test_selector_at_prefers_unique_identifiers() {
+        let hierarchy = UiHierarchy::parse(DUMP);
+        assert_eq!(
+            hierarchy.selector_at(500, 1050),
+            Selector::ResourceId("com.example.app:id/login".to_string())
+        );
+        assert_eq!(
+            hierarchy.selector_at(500, 1250),
+            Selector::Text("Terms & Conditions".to_string())
+        );
+        assert_eq!(
+            hierarchy.selector_at(500, 2000),
+            Selector::Coordinates(500, 2000)
+        );
+    }
+
+    #[test]
+    fn test_selector_at_ignores_unrelated_containers() {
+        let dump = r#"<hierarchy rotation="0"><node index="0" text="" resource-id="android:id/content" class="android.widget.FrameLayout" package="com.example.app" content-desc="" clickable="false" enabled="true" focused="false" bounds="[0,0][1080,2400]"><node index="0" text="" resource-id="" class="android.widget.LinearLayout" package="com.example.app" content-desc="" clickable="true" enabled="true" focused="false" bounds="[0,900][1080,1100]"><node index="0" text="Delete" resource-id="" class="android.widget.TextView" package="com.example.app" content-desc="" clickable="false" enabled="true" focused="false" bounds="[100,950][400,1050]" /></node><node index="1" text="Delete" resource-id="com.example.app:id/delete_all" class="android.widget.Button" package="com.example.app" content-desc="" clickable="true" enabled="true" focused="false" bounds="[100,1500][400,1600]" /></node></hierarchy>"#;
+        let hierarchy = UiHierarchy::parse(dump);
+
+        // "Delete" is on screen twice and the row has no identifier of its own
+        assert_eq!(
+            hierarchy.selector_at(200, 1000),
+            Selector::Coordinates(200, 1000)
+        );
+        assert_eq!(
+            hierarchy.selector_at(200, 1550),
+            Selector::ResourceId("com.example.app:id/delete_all".to_string())
+        );
+    }
+
+    #[test]
+    fn test_selector_parse() {
+        assert_eq!(
+            Selector::parse("id:login"),
+            Some(Selector::ResourceId("login".to_string()))
+        );
+        assert_eq!(
+            Selector::parse("540 1200"),
+            Some(Selector::Coordinates(540, 1200))
+        );
+        assert_eq!(Selector::parse("Sign in"), None);
+
+        let hierarchy = UiHierarchy::parse(DUMP);
+        let node = hierarchy
+            .find(&Selector::parse("id:login").unwrap())
+            .unwrap();
+        assert_eq!(node.bounds.center(), (540, 1075));
+    }
+}
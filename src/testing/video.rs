@@ -0,0 +1,366 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::error::KMobileError;
+use crate::utils::sanitize_filename;
+
+/// `adb shell screenrecord` refuses to record longer than three minutes per file
+pub const SCREENRECORD_CHUNK_SECS: u64 = 180;
+
+/// Directory under the test output dir where videos are stored
+pub const VIDEO_DIR: &str = "videos";
+
+/// A screen recording running in the background for one test case
+pub struct VideoRecorder {
+    name: String,
+    output_dir: PathBuf,
+    backend: Backend,
+}
+
+enum Backend {
+    Android {
+        adb_path: PathBuf,
+        device_id: String,
+        stop: watch::Sender<bool>,
+        task: JoinHandle<Result<Vec<String>>>,
+    },
+    IosSimulator {
+        child: tokio::process::Child,
+        path: PathBuf,
+    },
+}
+
+impl VideoRecorder {
+    /// Starts recording `device_id`. Android devices are recorded through adb in
+    /// chunks; anything that parses as a UUID is treated as an iOS simulator.
+    pub fn start(
+        adb_path: Option<&Path>,
+        device_id: &str,
+        output_dir: &Path,
+        test_name: &str,
+    ) -> Result<Self> {
+        let name = sanitize_filename(test_name);
+        let output_dir = output_dir.join(VIDEO_DIR);
+        fs::create_dir_all(&output_dir)?;
+
+        let backend = if uuid::Uuid::parse_str(device_id).is_ok() {
+            let path = output_dir.join(format!("{name}.mp4"));
+            let child = tokio::process::Command::new("xcrun")
+                .args(["simctl", "io", device_id, "recordVideo", "--force"])
+                .arg(&path)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .spawn()?;
+            Backend::IosSimulator { child, path }
+        } else {
+            let adb_path = adb_path
+                .ok_or_else(|| KMobileError::ConfigError("ADB path not configured".to_string()))?
+                .to_path_buf();
+            let (stop, stop_rx) = watch::channel(false);
+            let task = tokio::spawn(record_android_chunks(
+                adb_path.clone(),
+                device_id.to_string(),
+                name.clone(),
+                stop_rx,
+            ));
+            Backend::Android {
+                adb_path,
+                device_id: device_id.to_string(),
+                stop,
+                task,
+            }
+        };
+
+        debug!("Started video recording for {} on {}", test_name, device_id);
+        Ok(Self {
+            name,
+            output_dir,
+            backend,
+        })
+    }
+
+    /// Stops the recording, pulls and stitches it, and returns the local file
+    pub async fn stop(self) -> Result<PathBuf> {
+        match self.backend {
+            Backend::IosSimulator { mut child, path } => {
+                if let Some(pid) = child.id() {
+                    // recordVideo only finalizes the file on SIGINT
+                    let _ = Command::new("kill")
+                        .args(["-INT", &pid.to_string()])
+                        .status();
+                }
+                let _ = tokio::time::timeout(Duration::from_secs(10), child.wait()).await;
+                if !path.exists() {
+                    return Err(KMobileError::TestExecutionError(
+                        "Simulator video was not written".to_string(),
+                    )
+                    .into());
+                }
+                Ok(path)
+            }
+            Backend::Android {
+                adb_path,
+                device_id,
+                stop,
+                task,
+            } => {
+                let _ = stop.send(true);
+                let remote_chunks = task.await.map_err(|e| {
+                    KMobileError::TestExecutionError(format!("Video recording task failed: {e}"))
+                })??;
+
+                let mut local_chunks = Vec::new();
+                for (i, remote) in remote_chunks.iter().enumerate() {
+                    let local = self
+                        .output_dir
+                        .join(format!("{}_part{}.mp4", self.name, i + 1));
+                    let output = Command::new(&adb_path)
+                        .args(["-s", &device_id, "pull", remote])
+                        .arg(&local)
+                        .output()?;
+                    let _ = Command::new(&adb_path)
+                        .args(["-s", &device_id, "shell", "rm", "-f", remote])
+                        .output();
+
+                    if output.status.success() {
+                        local_chunks.push(local);
+                    } else {
+                        warn!(
+                            "Failed to pull video chunk {}: {}",
+                            remote,
+                            String::from_utf8_lossy(&output.stderr)
+                        );
+                    }
+                }
+
+                let target = self.output_dir.join(format!("{}.mp4", self.name));
+                stitch_chunks(&local_chunks, &target)
+            }
+        }
+    }
+}
+
+async fn record_android_chunks(
+    adb_path: PathBuf,
+    device_id: String,
+    name: String,
+    mut stop: watch::Receiver<bool>,
+) -> Result<Vec<String>> {
+    let mut chunks = Vec::new();
+
+    loop {
+        let remote = format!("/sdcard/kmobile_{}_{}.mp4", name, chunks.len() + 1);
+        let started = Instant::now();
+        let mut child = tokio::process::Command::new(&adb_path)
+            .args(["-s", &device_id, "shell", &screenrecord_command(&remote)])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        chunks.push(remote);
+
+        // Kept open until the chunk ends so screenrecord never writes to a closed pipe
+        let mut stdout = child.stdout.take().map(BufReader::new);
+        let mut first_line = String::new();
+        if let Some(stdout) = stdout.as_mut() {
+            let read = stdout.read_line(&mut first_line);
+            let _ = tokio::time::timeout(Duration::from_secs(10), read).await;
+        }
+        let pid: Option<u32> = first_line.trim().parse().ok();
+
+        tokio::select! {
+            status = child.wait() => {
+                let status = status?;
+                if *stop.borrow() {
+                    break;
+                }
+                // A chunk that dies straight away means screenrecord is unusable
+                if !status.success() && started.elapsed() < Duration::from_secs(2) {
+                    return Err(KMobileError::TestExecutionError(
+                        "screenrecord exited immediately".to_string(),
+                    )
+                    .into());
+                }
+                debug!("screenrecord chunk {} finished, starting next", chunks.len());
+            }
+            _ = stop.changed() => {
+                // screenrecord only writes the moov atom when interrupted. Only ours is,
+                // other tools may be recording the same device.
+                match pid {
+                    Some(pid) => {
+                        let _ = Command::new(&adb_path)
+                            .args(["-s", &device_id, "shell", "kill", "-INT", &pid.to_string()])
+                            .output();
+                    }
+                    None => warn!("screenrecord PID unknown, the last video chunk may be unplayable"),
+                }
+                let _ = tokio::time::timeout(Duration::from_secs(10), child.wait()).await;
+                break;
+            }
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Device shell command that prints its PID and then becomes screenrecord, so the
+/// recording can be stopped without touching anyone else's
+fn screenrecord_command(remote: &str) -> String {
+    format!(
+        "echo $$; exec screenrecord --time-limit {SCREENRECORD_CHUNK_SECS} {}",
+        shell_words::quote(remote)
+    )
+}
+
+/// Joins recorded chunks into `target` with ffmpeg's concat demuxer. A single chunk
+/// is simply renamed; without ffmpeg the first chunk is kept and the rest left beside it.
+pub fn stitch_chunks(chunks: &[PathBuf], target: &Path) -> Result<PathBuf> {
+    match chunks {
+        [] => Err(KMobileError::TestExecutionError("No video was recorded".to_string()).into()),
+        [single] => {
+            fs::rename(single, target)?;
+            Ok(target.to_path_buf())
+        }
+        _ => {
+            let Ok(ffmpeg) = which::which("ffmpeg") else {
+                warn!(
+                    "ffmpeg not found; keeping {} separate video chunks",
+                    chunks.len()
+                );
+                return Ok(chunks[0].clone());
+            };
+
+            let list_path = target.with_extension("txt");
+            fs::write(&list_path, concat_list(chunks))?;
+            let output = Command::new(ffmpeg)
+                .args([
+                    "-y",
+                    "-loglevel",
+                    "error",
+                    "-f",
+                    "concat",
+                    "-safe",
+                    "0",
+                    "-i",
+                ])
+                .arg(&list_path)
+                .args(["-c", "copy"])
+                .arg(target)
+                .output()?;
+            let _ = fs::remove_file(&list_path);
+
+            if !output.status.success() {
+                warn!(
+                    "ffmpeg failed to stitch video: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                return Ok(chunks[0].clone());
+            }
+
+            for chunk in chunks {
+                let _ = fs::remove_file(chunk);
+            }
+            info!("Stitched {} video chunks into {:?}", chunks.len(), target);
+            Ok(target.to_path_buf())
+        }
+    }
+}
+
+/// Builds an ffmpeg concat demuxer file list
+pub fn concat_list(chunks: &[PathBuf]) -> String {
+    chunks
+        .iter()
+        .map(|chunk| {
+            let absolute = fs::canonicalize(chunk).unwrap_or_else(|_| chunk.clone());
+            format!(
+                "file '{}'\n",
+                absolute.to_string_lossy().replace('\'', "'\\''")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stopping_interrupts_only_our_screenrecord() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Reports PID 4242 and records until that PID is interrupted
+        let dir = tempfile::tempdir().unwrap();
+        let adb = dir.path().join("adb");
+        let killed = dir.path().join("killed");
+        let script = format!(
+            "#!/bin/sh\n\
+             case \"$*\" in\n\
+             *\"kill -INT\"*) echo \"$*\" > {killed} ;;\n\
+             *screenrecord*) echo 4242; while [ ! -f {killed} ]; do sleep 0.1; done ;;\n\
+             esac\n",
+            killed = killed.display(),
+        );
+        fs::write(&adb, script).unwrap();
+        fs::set_permissions(&adb, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (stop, stop_rx) = watch::channel(false);
+        let task = tokio::spawn(record_android_chunks(
+            adb,
+            "emulator-5554".to_string(),
+            "login".to_string(),
+            stop_rx,
+        ));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        stop.send(true).unwrap();
+
+        let chunks = task.await.unwrap().unwrap();
+        assert_eq!(chunks, vec!["/sdcard/kmobile_login_1.mp4".to_string()]);
+        assert_eq!(
+            fs::read_to_string(&killed).unwrap().trim(),
+            "-s emulator-5554 shell kill -INT 4242"
+        );
+    }
+
+    #[test]
+    fn test_screenrecord_command_reports_its_pid() {
+        assert_eq!(
+            screenrecord_command("/sdcard/kmobile_it's_1.mp4"),
+            "echo $$; exec screenrecord --time-limit 180 '/sdcard/kmobile_it'\\''s_1.mp4'"
+        );
+    }
+
+    #[test]
+    fn test_concat_list_escapes_quotes() {
+        let list = concat_list(&[
+            PathBuf::from("/tmp/a_part1.mp4"),
+            PathBuf::from("/tmp/it's_part2.mp4"),
+        ]);
+        assert_eq!(
+            list,
+            "file '/tmp/a_part1.mp4'\nfile '/tmp/it'\\''s_part2.mp4'\n"
+        );
+    }
+
+    #[test]
+    fn test_stitch_single_chunk_is_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let chunk = dir.path().join("login_part1.mp4");
+        fs::write(&chunk, b"mp4").unwrap();
+
+        let target = dir.path().join("login.mp4");
+        let stitched = stitch_chunks(std::slice::from_ref(&chunk), &target).unwrap();
+
+        assert_eq!(stitched, target);
+        assert!(target.exists());
+        assert!(!chunk.exists());
+    }
+}
@@ -0,0 +1,539 @@
+use anyhow::Result;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info, warn};
+
+use super::recorder::parse_wm_size;
+use super::{TestResult, TestRunner, TestStatus};
+use crate::config::{DiffMode, IgnoreRegion, VisualConfig};
+use crate::error::KMobileError;
+use crate::utils::sanitize_filename;
+
+/// Directory under the test output dir for diff images and pending candidates
+pub const VISUAL_DIR: &str = "visual";
+
+/// Identifies one screenshot in the baseline store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineKey {
+    pub device_profile: String,
+    pub suite: String,
+    pub case: String,
+    pub step: String,
+}
+
+impl BaselineKey {
+    pub fn relative_path(&self) -> PathBuf {
+        PathBuf::from(sanitize_filename(&self.device_profile))
+            .join(sanitize_filename(&self.suite))
+            .join(sanitize_filename(&self.case))
+            .join(format!("{}.png", sanitize_filename(&self.step)))
+    }
+}
+
+/// Outcome of comparing a screenshot against its baseline
+#[derive(Debug, Clone, PartialEq)]
+pub enum VisualOutcome {
+    Match { score: f64 },
+    Mismatch { score: f64, diff_path: PathBuf },
+    NewBaseline,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffResult {
+    /// Fraction of compared pixels that differ (pixel mode) or 1 - SSIM (perceptual)
+    pub score: f64,
+    pub passed: bool,
+    pub diff_image: RgbaImage,
+}
+
+/// Approved baselines live under `baseline_dir`; candidates that still need a
+/// `kmobile test approve` are kept under `pending_dir`
+pub struct BaselineStore {
+    baseline_dir: PathBuf,
+    pending_dir: PathBuf,
+}
+
+impl BaselineStore {
+    pub fn new(baseline_dir: &Path, output_dir: &Path) -> Self {
+        Self {
+            baseline_dir: baseline_dir.to_path_buf(),
+            pending_dir: output_dir.join(VISUAL_DIR).join("pending"),
+        }
+    }
+
+    pub fn baseline_path(&self, key: &BaselineKey) -> PathBuf {
+        self.baseline_dir.join(key.relative_path())
+    }
+
+    pub fn pending_path(&self, key: &BaselineKey) -> PathBuf {
+        self.pending_dir.join(key.relative_path())
+    }
+
+    /// Compares `actual` to its baseline. Missing baselines and mismatches leave the
+    /// screenshot as a pending candidate; mismatches also write a diff image.
+    pub fn check(
+        &self,
+        key: &BaselineKey,
+        actual_path: &Path,
+        diff_dir: &Path,
+        config: &VisualConfig,
+    ) -> Result<VisualOutcome> {
+        let baseline_path = self.baseline_path(key);
+        let pending_path = self.pending_path(key);
+
+        if !baseline_path.exists() {
+            copy_into(actual_path, &pending_path)?;
+            info!("No baseline for {:?}, saved candidate", key);
+            return Ok(VisualOutcome::NewBaseline);
+        }
+
+        let baseline = load_image(&baseline_path)?;
+        let actual = load_image(actual_path)?;
+        let diff = compare_images(&baseline, &actual, config);
+
+        if diff.passed {
+            let _ = fs::remove_file(&pending_path);
+            return Ok(VisualOutcome::Match { score: diff.score });
+        }
+
+        copy_into(actual_path, &pending_path)?;
+        fs::create_dir_all(diff_dir)?;
+        let diff_path = diff_dir.join(format!(
+            "{}_{}_{}_diff.png",
+            sanitize_filename(&key.suite),
+            sanitize_filename(&key.case),
+            sanitize_filename(&key.step)
+        ));
+        diff.diff_image
+            .save(&diff_path)
+            .map_err(|e| KMobileError::FileSystemError(format!("Failed to write diff: {e}")))?;
+
+        Ok(VisualOutcome::Mismatch {
+            score: diff.score,
+            diff_path,
+        })
+    }
+
+    /// Promotes pending candidates to baselines. Each filter narrows the approval to a
+    /// suite, case or device profile; returns the approved baseline paths.
+    pub fn approve(
+        &self,
+        suite: Option<&str>,
+        case: Option<&str>,
+        device_profile: Option<&str>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut approved = Vec::new();
+        if !self.pending_dir.exists() {
+            return Ok(approved);
+        }
+
+        for pending in collect_pngs(&self.pending_dir)? {
+            let relative = pending.strip_prefix(&self.pending_dir).unwrap_or(&pending);
+            let parts: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let [profile, suite_dir, case_dir, _file] = parts.as_slice() else {
+                continue;
+            };
+
+            let matches = |filter: Option<&str>, value: &str| {
+                filter.is_none_or(|f| sanitize_filename(f) == *value)
+            };
+            if !matches(device_profile, profile)
+                || !matches(suite, suite_dir)
+                || !matches(case, case_dir)
+            {
+                continue;
+            }
+
+            let target = self.baseline_dir.join(relative);
+            copy_into(&pending, &target)?;
+            fs::remove_file(&pending)?;
+            debug!("Approved baseline {:?}", target);
+            approved.push(target);
+        }
+
+        Ok(approved)
+    }
+}
+
+impl TestRunner {
+    fn baseline_store(&self) -> BaselineStore {
+        BaselineStore::new(
+            &self.config.testing.visual.baseline_dir,
+            &self.test_output_dir,
+        )
+    }
+
+    /// Compares the screenshots a test case took against their baselines. Mismatches
+    /// fail the case and attach a diff image; missing baselines only leave a candidate
+    /// for `kmobile test approve`.
+    pub(super) fn check_visual_baselines(
+        &self,
+        suite: &str,
+        device_profile: &str,
+        result: &mut TestResult,
+    ) {
+        let config = &self.config.testing.visual;
+        let store = self.baseline_store();
+        let diff_dir = self.test_output_dir.join(VISUAL_DIR).join("diffs");
+        let mut mismatches = Vec::new();
+
+        for screenshot in result.screenshots.clone() {
+            let path = self.test_output_dir.join(&screenshot);
+            if screenshot.ends_with("_failure.png") || !path.exists() {
+                continue;
+            }
+
+            let key = BaselineKey {
+                device_profile: device_profile.to_string(),
+                suite: suite.to_string(),
+                case: result.test_name.clone(),
+                step: path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_else(|| screenshot.clone()),
+            };
+
+            match store.check(&key, &path, &diff_dir, config) {
+                Ok(VisualOutcome::Match { score }) => {
+                    debug!("{} matches its baseline (score {:.5})", screenshot, score)
+                }
+                Ok(VisualOutcome::NewBaseline) => warn!(
+                    "No baseline for {}; run `kmobile test approve` to accept it",
+                    screenshot
+                ),
+                Ok(VisualOutcome::Mismatch { score, diff_path }) => {
+                    let relative = diff_path
+                        .strip_prefix(&self.test_output_dir)
+                        .unwrap_or(&diff_path);
+                    result
+                        .screenshots
+                        .push(relative.to_string_lossy().to_string());
+                    mismatches.push(format!(
+                        "{screenshot} differs from its baseline (score {score:.5})"
+                    ));
+                }
+                Err(e) => warn!("Failed to compare {} with its baseline: {}", screenshot, e),
+            }
+        }
+
+        if mismatches.is_empty() {
+            return;
+        }
+
+        let details = format!("Visual regression:\n{}", mismatches.join("\n"));
+        result.status = TestStatus::Failed;
+        result.error_message = Some(match result.error_message.take() {
+            Some(error) => format!("{error}\n\n{details}"),
+            None => details,
+        });
+    }
+
+    /// Promotes pending screenshots to baselines
+    pub fn approve_baselines(
+        &self,
+        suite: Option<&str>,
+        case: Option<&str>,
+        device_profile: Option<&str>,
+    ) -> Result<Vec<PathBuf>> {
+        self.baseline_store().approve(suite, case, device_profile)
+    }
+
+    /// Names the device's screen configuration, e.g. `Pixel_7_1080x2400`, so each
+    /// profile keeps its own baselines
+    pub(super) fn device_profile(&self, device_id: &str) -> String {
+        if uuid::Uuid::parse_str(device_id).is_ok() {
+            return sanitize_filename(device_id);
+        }
+
+        let Some(adb_path) = &self.config.android.adb_path else {
+            return sanitize_filename(device_id);
+        };
+        let adb = |args: &[&str]| {
+            Command::new(adb_path)
+                .args(["-s", device_id, "shell"])
+                .args(args)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+
+        let model = adb(&["getprop", "ro.product.model"])
+            .filter(|model| !model.is_empty())
+            .unwrap_or_else(|| device_id.to_string());
+        let profile = match adb(&["wm", "size"]).as_deref().and_then(parse_wm_size) {
+            Some((width, height)) => format!("{model}_{width}x{height}"),
+            None => model,
+        };
+        sanitize_filename(&profile.replace(' ', "_"))
+    }
+}
+
+fn copy_into(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(from, to)?;
+    Ok(())
+}
+
+fn collect_pngs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(collect_pngs(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "png") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn load_image(path: &Path) -> Result<RgbaImage> {
+    let image = image::open(path).map_err(|e| {
+        KMobileError::FileSystemError(format!("Failed to read image {}: {e}", path.display()))
+    })?;
+    Ok(image.to_rgba8())
+}
+
+/// Builds a mask of pixels excluded from comparison
+fn ignore_mask(width: u32, height: u32, regions: &[IgnoreRegion]) -> Vec<bool> {
+    let mut mask = vec![false; (width * height) as usize];
+    for region in regions {
+        let x_end = region.x.saturating_add(region.width).min(width);
+        let y_end = region.y.saturating_add(region.height).min(height);
+        for y in region.y.min(height)..y_end {
+            for x in region.x.min(width)..x_end {
+                mask[(y * width + x) as usize] = true;
+            }
+        }
+    }
+    mask
+}
+
+pub fn compare_images(
+    baseline: &RgbaImage,
+    actual: &RgbaImage,
+    config: &VisualConfig,
+) -> DiffResult {
+    if baseline.dimensions() != actual.dimensions() {
+        let (width, height) = actual.dimensions();
+        return DiffResult {
+            score: 1.0,
+            passed: false,
+            diff_image: RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255])),
+        };
+    }
+
+    let (width, height) = actual.dimensions();
+    let mask = ignore_mask(width, height, &config.ignore_regions);
+    let mut diff_image = RgbaImage::new(width, height);
+    let mut differing = 0u64;
+    let mut compared = 0u64;
+
+    for (x, y, actual_px) in actual.enumerate_pixels() {
+        let index = (y * width + x) as usize;
+        let dimmed = |c: u8| (c / 4) + 160;
+        if mask[index] {
+            diff_image.put_pixel(
+                x,
+                y,
+                Rgba([dimmed(actual_px[0]) / 2, dimmed(actual_px[1]) / 2, 255, 255]),
+            );
+            continue;
+        }
+
+        compared += 1;
+        let baseline_px = baseline.get_pixel(x, y);
+        let delta = (0..4)
+            .map(|c| actual_px[c].abs_diff(baseline_px[c]))
+            .max()
+            .unwrap_or(0);
+
+        if delta > config.pixel_threshold {
+            differing += 1;
+            diff_image.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        } else {
+            let gray = dimmed(luma(actual_px));
+            diff_image.put_pixel(x, y, Rgba([gray, gray, gray, 255]));
+        }
+    }
+
+    let (score, passed) = match config.mode {
+        DiffMode::Pixel => {
+            let ratio = if compared == 0 {
+                0.0
+            } else {
+                differing as f64 / compared as f64
+            };
+            (ratio, ratio <= config.max_diff_ratio)
+        }
+        DiffMode::Perceptual => {
+            let ssim = mean_ssim(baseline, actual, &mask);
+            (1.0 - ssim, ssim >= config.min_similarity)
+        }
+    };
+
+    DiffResult {
+        score,
+        passed,
+        diff_image,
+    }
+}
+
+fn luma(px: &Rgba<u8>) -> u8 {
+    ((px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000) as u8
+}
+
+/// Mean structural similarity over 8x8 luma windows; windows that touch an ignore
+/// region are left out
+pub fn mean_ssim(baseline: &RgbaImage, actual: &RgbaImage, mask: &[bool]) -> f64 {
+    const WINDOW: u32 = 8;
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (width, height) = actual.dimensions();
+    let mut total = 0.0;
+    let mut windows = 0u64;
+
+    for wy in (0..height).step_by(WINDOW as usize) {
+        for wx in (0..width).step_by(WINDOW as usize) {
+            let mut a = Vec::with_capacity((WINDOW * WINDOW) as usize);
+            let mut b = Vec::with_capacity((WINDOW * WINDOW) as usize);
+            let mut masked = false;
+
+            for y in wy..(wy + WINDOW).min(height) {
+                for x in wx..(wx + WINDOW).min(width) {
+                    if mask[(y * width + x) as usize] {
+                        masked = true;
+                    }
+                    a.push(luma(baseline.get_pixel(x, y)) as f64);
+                    b.push(luma(actual.get_pixel(x, y)) as f64);
+                }
+            }
+
+            if masked {
+                continue;
+            }
+
+            let n = a.len() as f64;
+            let mean_a = a.iter().sum::<f64>() / n;
+            let mean_b = b.iter().sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut covar) = (0.0, 0.0, 0.0);
+            for (va, vb) in a.iter().zip(&b) {
+                var_a += (va - mean_a).powi(2);
+                var_b += (vb - mean_b).powi(2);
+                covar += (va - mean_a) * (vb - mean_b);
+            }
+            var_a /= n;
+            var_b /= n;
+            covar /= n;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2))
+                / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+
+    if windows == 0 {
+        1.0
+    } else {
+        total / windows as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn test_pixel_diff_with_ignore_region() {
+        let baseline = checkerboard(32, 32);
+        let mut actual = baseline.clone();
+        for x in 0..32 {
+            actual.put_pixel(x, 0, Rgba([255, 0, 0, 255]));
+        }
+
+        let mut config = VisualConfig {
+            max_diff_ratio: 0.01,
+            ..Default::default()
+        };
+        let diff = compare_images(&baseline, &actual, &config);
+        assert!(!diff.passed);
+        assert!((diff.score - 32.0 / 1024.0).abs() < 1e-9);
+        assert_eq!(diff.diff_image.get_pixel(5, 0), &Rgba([255, 0, 0, 255]));
+
+        config.ignore_regions = vec![IgnoreRegion {
+            x: 0,
+            y: 0,
+            width: 32,
+            height: 2,
+        }];
+        let diff = compare_images(&baseline, &actual, &config);
+        assert!(diff.passed);
+        assert_eq!(diff.score, 0.0);
+    }
+
+    #[test]
+    fn test_perceptual_diff() {
+        let baseline = checkerboard(32, 32);
+        let config = VisualConfig {
+            mode: DiffMode::Perceptual,
+            ..Default::default()
+        };
+        assert!(mean_ssim(&baseline, &baseline, &[false; 1024]) > 0.999);
+
+        let inverted = RgbaImage::from_fn(32, 32, |x, y| {
+            let px = baseline.get_pixel(x, y);
+            Rgba([255 - px[0], 255 - px[1], 255 - px[2], 255])
+        });
+        assert!(!compare_images(&baseline, &inverted, &config).passed);
+    }
+
+    #[test]
+    fn test_store_check_and_approve() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BaselineStore::new(&dir.path().join("baselines"), dir.path());
+        let key = BaselineKey {
+            device_profile: "Pixel_7_1080x2400".to_string(),
+            suite: "smoke".to_string(),
+            case: "login".to_string(),
+            step: "login_step_2".to_string(),
+        };
+        let actual = dir.path().join("shot.png");
+        checkerboard(16, 16).save(&actual).unwrap();
+        let config = VisualConfig::default();
+        let diffs = dir.path().join("diffs");
+
+        assert_eq!(
+            store.check(&key, &actual, &diffs, &config).unwrap(),
+            VisualOutcome::NewBaseline
+        );
+        assert_eq!(store.approve(Some("other"), None, None).unwrap().len(), 0);
+        assert_eq!(store.approve(Some("smoke"), None, None).unwrap().len(), 1);
+        assert!(store.baseline_path(&key).exists());
+
+        assert!(matches!(
+            store.check(&key, &actual, &diffs, &config).unwrap(),
+            VisualOutcome::Match { .. }
+        ));
+    }
+}
@@ -0,0 +1,370 @@
+//! Terminal dashboard behind `kmobile tui`, for machines reached over SSH where the
+//! desktop app can't run.
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::Terminal;
+use std::io::{self, Stdout};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::device_basic::DeviceManager;
+use crate::error::KMobileError;
+use crate::project::ProjectManager;
+use crate::simulator_basic::SimulatorManager;
+use crate::testing::{TestRunOptions, TestRunner};
+
+pub mod app;
+pub mod ui;
+
+use app::{Action, App, AppEvent, Tab};
+
+/// How often the input thread checks whether the dashboard has exited
+const INPUT_POLL: Duration = Duration::from_millis(250);
+
+/// Runs the dashboard until the user quits. The terminal is restored even when the
+/// loop fails.
+pub async fn run(config: Config) -> Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let result = run_loop(&mut terminal, config).await;
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+async fn run_loop(terminal: &mut Terminal<CrosstermBackend<Stdout>>, config: Config) -> Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    spawn_input_thread(tx.clone());
+
+    let mut app = App::default();
+    let mut tasks = Tasks {
+        config,
+        tx,
+        logcat: None,
+    };
+    tasks.refresh();
+
+    while !app.should_quit {
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+
+        let Some(event) = rx.recv().await else {
+            break;
+        };
+        match event {
+            AppEvent::Input(key) => {
+                if let Some(action) = app.handle_key(key) {
+                    tasks.execute(action, &mut app);
+                }
+            }
+            event => app.apply(event),
+        }
+    }
+
+    tasks.stop_logcat();
+    Ok(())
+}
+
+/// crossterm input is blocking, so it's read on its own thread and forwarded
+fn spawn_input_thread(tx: UnboundedSender<AppEvent>) {
+    std::thread::spawn(move || loop {
+        match event::poll(INPUT_POLL) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    if tx.send(AppEvent::Input(key)).is_err() {
+                        break;
+                    }
+                }
+                // Redraw on resize
+                Ok(Event::Resize(..)) => {
+                    if tx.send(AppEvent::Resize).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            },
+            Ok(false) if tx.is_closed() => break,
+            Ok(false) => {}
+            Err(_) => break,
+        }
+    });
+}
+
+/// Runs actions in the background and reports back through `AppEvent`s
+struct Tasks {
+    config: Config,
+    tx: UnboundedSender<AppEvent>,
+    logcat: Option<JoinHandle<()>>,
+}
+
+impl Tasks {
+    fn execute(&mut self, action: Action, app: &mut App) {
+        match action {
+            Action::Quit => app.should_quit = true,
+            Action::Refresh => self.refresh(),
+            Action::StartSimulator(id) => self.simulator_action(id, true),
+            Action::StopSimulator(id) => self.simulator_action(id, false),
+            Action::InstallApp { tab, target, app } => self.install(tab, target, app),
+            Action::ToggleLogcat(device_id) => {
+                if app.logcat_device.is_some() {
+                    self.stop_logcat();
+                    app.logcat_device = None;
+                    app.status = format!("Logcat for {device_id} stopped");
+                } else {
+                    app.logcat.clear();
+                    app.logcat_device = Some(device_id.clone());
+                    self.start_logcat(device_id);
+                }
+            }
+            Action::Build => self.build(),
+            Action::RunTests { suite, device } => self.run_tests(suite, device),
+        }
+    }
+
+    fn refresh(&self) {
+        let config = self.config.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            match DeviceManager::new(&config).await {
+                Ok(manager) => match manager.list_devices().await {
+                    Ok(devices) => send(&tx, AppEvent::Devices(devices)),
+                    Err(e) => send(&tx, AppEvent::Status(format!("Device list failed: {e}"))),
+                },
+                Err(e) => send(&tx, AppEvent::Status(format!("Device list failed: {e}"))),
+            }
+            match SimulatorManager::new(&config).await {
+                Ok(manager) => match manager.list_simulators().await {
+                    Ok(simulators) => send(&tx, AppEvent::Simulators(simulators)),
+                    Err(e) => send(&tx, AppEvent::Status(format!("Simulator list failed: {e}"))),
+                },
+                Err(e) => send(&tx, AppEvent::Status(format!("Simulator list failed: {e}"))),
+            }
+        });
+    }
+
+    fn simulator_action(&self, id: String, start: bool) {
+        let config = self.config.clone();
+        let tx = self.tx.clone();
+        let refresh_tx = self.tx.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let manager = SimulatorManager::new(&config).await?;
+                if start {
+                    manager.start_simulator(&id).await
+                } else {
+                    manager.stop_simulator(&id).await
+                }
+            }
+            .await;
+            let verb = if start { "Started" } else { "Stopped" };
+            send(&tx, status(result.map(|_| format!("{verb} {id}"))));
+            refresh_simulators(&config, &refresh_tx).await;
+        });
+    }
+
+    fn install(&self, tab: Tab, target: String, app_path: String) {
+        let config = self.config.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let result = match tab {
+                Tab::Devices => match DeviceManager::new(&config).await {
+                    Ok(manager) => manager.install_app(&target, &app_path).await,
+                    Err(e) => Err(e),
+                },
+                Tab::Simulators => match SimulatorManager::new(&config).await {
+                    Ok(manager) => manager.install_app(&target, &app_path).await,
+                    Err(e) => Err(e),
+                },
+            };
+            send(
+                &tx,
+                status(result.map(|_| format!("Installed {app_path} on {target}"))),
+            );
+        });
+    }
+
+    fn start_logcat(&mut self, device_id: String) {
+        let adb_path = self.config.android.adb_path.clone();
+        let tx = self.tx.clone();
+        self.logcat = Some(tokio::spawn(async move {
+            // UUIDs are iOS simulators, like everywhere else device ids are interpreted
+            let mut command = if uuid::Uuid::parse_str(&device_id).is_ok() {
+                let mut command = tokio::process::Command::new("xcrun");
+                command.args([
+                    "simctl", "spawn", &device_id, "log", "stream", "--style", "compact",
+                ]);
+                command
+            } else {
+                let Some(adb_path) = adb_path else {
+                    send(&tx, AppEvent::Status("ADB path not configured".to_string()));
+                    send(&tx, AppEvent::LogEnded);
+                    return;
+                };
+                let mut command = tokio::process::Command::new(adb_path);
+                command.args(["-s", &device_id, "logcat", "-v", "threadtime"]);
+                command
+            };
+
+            match command
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .spawn()
+            {
+                Ok(mut child) => {
+                    if let Some(stdout) = child.stdout.take() {
+                        forward_lines(stdout, &tx, AppEvent::LogLine).await;
+                    }
+                    let _ = child.wait().await;
+                }
+                Err(e) => send(&tx, AppEvent::Status(format!("Logcat failed: {e}"))),
+            }
+            send(&tx, AppEvent::LogEnded);
+        }));
+    }
+
+    fn stop_logcat(&mut self) {
+        // Aborting drops the child, which kills it
+        if let Some(handle) = self.logcat.take() {
+            handle.abort();
+        }
+    }
+
+    fn build(&self) {
+        let config = self.config.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let success = match run_build(&config, &tx).await {
+                Ok(success) => success,
+                Err(e) => {
+                    send(&tx, AppEvent::BuildLine(e.to_string()));
+                    false
+                }
+            };
+            send(&tx, AppEvent::BuildFinished { success });
+        });
+    }
+
+    fn run_tests(&self, suite: Option<String>, device: Option<String>) {
+        let config = self.config.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+            let forward_tx = tx.clone();
+            let forward = tokio::spawn(async move {
+                while let Some(progress) = progress_rx.recv().await {
+                    send(&forward_tx, AppEvent::Test(progress));
+                }
+            });
+
+            let options = TestRunOptions {
+                progress: Some(progress_tx),
+                ..Default::default()
+            };
+            let result = async {
+                let runner = TestRunner::new(&config).await?;
+                runner
+                    .run_tests(suite.as_deref(), device.as_deref(), &options)
+                    .await
+            }
+            .await;
+            drop(options);
+            let _ = forward.await;
+
+            if let Err(e) = result {
+                send(&tx, AppEvent::TestRunFailed(e.to_string()));
+            }
+        });
+    }
+}
+
+/// Streams the project's build command into the build pane. Returns whether it succeeded.
+async fn run_build(config: &Config, tx: &UnboundedSender<AppEvent>) -> Result<bool> {
+    let manager = ProjectManager::new(config).await?;
+    let project = manager.current_project().ok_or_else(|| {
+        KMobileError::ProjectNotFound("No project found in current directory".to_string())
+    })?;
+    let build_command = project
+        .build_command
+        .as_ref()
+        .ok_or_else(|| KMobileError::ConfigError("No build command configured".to_string()))?;
+
+    let parts = shell_words::split(build_command).map_err(|e| {
+        KMobileError::ConfigError(format!("Invalid build command `{build_command}`: {e}"))
+    })?;
+    let (command, args) = parts
+        .split_first()
+        .ok_or_else(|| KMobileError::ConfigError("Build command is empty".to_string()))?;
+    send(tx, AppEvent::BuildLine(format!("$ {build_command}")));
+
+    let mut child = tokio::process::Command::new(command)
+        .args(args)
+        .current_dir(&project.path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    tokio::join!(
+        async {
+            if let Some(stdout) = stdout {
+                forward_lines(stdout, tx, AppEvent::BuildLine).await;
+            }
+        },
+        async {
+            if let Some(stderr) = stderr {
+                forward_lines(stderr, tx, AppEvent::BuildLine).await;
+            }
+        }
+    );
+
+    Ok(child.wait().await?.success())
+}
+
+async fn refresh_simulators(config: &Config, tx: &UnboundedSender<AppEvent>) {
+    if let Ok(manager) = SimulatorManager::new(config).await {
+        if let Ok(simulators) = manager.list_simulators().await {
+            send(tx, AppEvent::Simulators(simulators));
+        }
+    }
+}
+
+async fn forward_lines(
+    reader: impl AsyncRead + Unpin,
+    tx: &UnboundedSender<AppEvent>,
+    event: fn(String) -> AppEvent,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        send(tx, event(line));
+    }
+}
+
+fn status(result: Result<String>) -> AppEvent {
+    match result {
+        Ok(message) => AppEvent::Status(message),
+        Err(e) => AppEvent::Status(format!("Error: {e}")),
+    }
+}
+
+fn send(tx: &UnboundedSender<AppEvent>, event: AppEvent) {
+    // The dashboard may already be closing
+    let _ = tx.send(event);
+}
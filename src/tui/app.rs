@@ -0,0 +1,501 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::VecDeque;
+
+use crate::device_basic::Device;
+use crate::simulator_basic::Simulator;
+use crate::testing::{TestProgress, TestResult, TestSummary};
+
+/// Lines kept in the logcat and build panes
+pub const MAX_PANE_LINES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Devices,
+    Simulators,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    InstallApp,
+    RunSuite,
+}
+
+impl PromptKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PromptKind::InstallApp => "App to install (.apk/.app)",
+            PromptKind::RunSuite => "Test suite (empty for default)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+}
+
+/// Work the event loop runs in the background for a key press
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Refresh,
+    StartSimulator(String),
+    StopSimulator(String),
+    InstallApp {
+        tab: Tab,
+        target: String,
+        app: String,
+    },
+    ToggleLogcat(String),
+    Build,
+    RunTests {
+        suite: Option<String>,
+        device: Option<String>,
+    },
+}
+
+/// Results sent back by background tasks
+#[derive(Debug)]
+pub enum AppEvent {
+    Input(KeyEvent),
+    Resize,
+    Devices(Vec<Device>),
+    Simulators(Vec<Simulator>),
+    LogLine(String),
+    LogEnded,
+    BuildLine(String),
+    BuildFinished {
+        success: bool,
+    },
+    Test(TestProgress),
+    /// The test run ended without a summary, e.g. the suite wasn't found
+    TestRunFailed(String),
+    Status(String),
+}
+
+/// Progress of the test run shown in the test pane
+#[derive(Debug, Clone, Default)]
+pub struct TestRunView {
+    pub suite_name: String,
+    pub total: usize,
+    pub current: Option<String>,
+    pub results: Vec<TestResult>,
+    pub summary: Option<TestSummary>,
+    pub error: Option<String>,
+}
+
+impl TestRunView {
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.results.len() as f64 / self.total as f64).min(1.0)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.summary.is_none() && self.error.is_none()
+    }
+}
+
+/// Dashboard state. Rendering lives in `ui`, side effects in the event loop.
+pub struct App {
+    pub tab: Tab,
+    pub devices: Vec<Device>,
+    pub simulators: Vec<Simulator>,
+    pub selected_device: usize,
+    pub selected_simulator: usize,
+    pub logcat: VecDeque<String>,
+    pub logcat_device: Option<String>,
+    pub build_output: VecDeque<String>,
+    pub build_running: bool,
+    pub test_run: Option<TestRunView>,
+    pub prompt: Option<Prompt>,
+    pub status: String,
+    pub should_quit: bool,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            tab: Tab::Devices,
+            devices: Vec::new(),
+            simulators: Vec::new(),
+            selected_device: 0,
+            selected_simulator: 0,
+            logcat: VecDeque::new(),
+            logcat_device: None,
+            build_output: VecDeque::new(),
+            build_running: false,
+            test_run: None,
+            prompt: None,
+            status: "Loading devices...".to_string(),
+            should_quit: false,
+        }
+    }
+}
+
+impl App {
+    /// Id of the highlighted row in the current table
+    pub fn selected_id(&self) -> Option<String> {
+        match self.tab {
+            Tab::Devices => self.devices.get(self.selected_device).map(|d| d.id.clone()),
+            Tab::Simulators => self
+                .simulators
+                .get(self.selected_simulator)
+                .map(|s| s.id.clone()),
+        }
+    }
+
+    fn rows(&self) -> usize {
+        match self.tab {
+            Tab::Devices => self.devices.len(),
+            Tab::Simulators => self.simulators.len(),
+        }
+    }
+
+    fn selected_mut(&mut self) -> &mut usize {
+        match self.tab {
+            Tab::Devices => &mut self.selected_device,
+            Tab::Simulators => &mut self.selected_simulator,
+        }
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        let rows = self.rows();
+        if rows == 0 {
+            return;
+        }
+        let selected = self.selected_mut();
+        *selected = if down {
+            (*selected + 1) % rows
+        } else {
+            (*selected + rows - 1) % rows
+        };
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(Action::Quit);
+        }
+        if self.prompt.is_some() {
+            return self.handle_prompt_key(key);
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.tab = match self.tab {
+                    Tab::Devices => Tab::Simulators,
+                    Tab::Simulators => Tab::Devices,
+                };
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.move_selection(true);
+                None
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.move_selection(false);
+                None
+            }
+            KeyCode::Char('r') => {
+                self.status = "Refreshing...".to_string();
+                Some(Action::Refresh)
+            }
+            KeyCode::Char('s') | KeyCode::Char('x') => {
+                if self.tab != Tab::Simulators {
+                    self.status = "Switch to the simulator table (Tab) to start or stop".into();
+                    return None;
+                }
+                let id = self.require_selection()?;
+                if key.code == KeyCode::Char('s') {
+                    self.status = format!("Starting {id}...");
+                    Some(Action::StartSimulator(id))
+                } else {
+                    self.status = format!("Stopping {id}...");
+                    Some(Action::StopSimulator(id))
+                }
+            }
+            KeyCode::Char('i') => {
+                self.require_selection()?;
+                self.open_prompt(PromptKind::InstallApp);
+                None
+            }
+            KeyCode::Char('l') => {
+                if let Some(device) = self.logcat_device.clone() {
+                    return Some(Action::ToggleLogcat(device));
+                }
+                let id = self.require_selection()?;
+                Some(Action::ToggleLogcat(id))
+            }
+            KeyCode::Char('b') => {
+                if self.build_running {
+                    self.status = "A build is already running".to_string();
+                    return None;
+                }
+                self.build_running = true;
+                self.build_output.clear();
+                Some(Action::Build)
+            }
+            KeyCode::Char('t') => {
+                if self.test_run.as_ref().is_some_and(TestRunView::is_running) {
+                    self.status = "A test run is already in progress".to_string();
+                    return None;
+                }
+                self.open_prompt(PromptKind::RunSuite);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let prompt = self.prompt.as_mut()?;
+        match key.code {
+            KeyCode::Esc => {
+                self.prompt = None;
+                None
+            }
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                None
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                None
+            }
+            KeyCode::Enter => {
+                let prompt = self.prompt.take()?;
+                let input = prompt.input.trim().to_string();
+                match prompt.kind {
+                    PromptKind::InstallApp => {
+                        if input.is_empty() {
+                            return None;
+                        }
+                        let target = self.selected_id()?;
+                        self.status = format!("Installing {input} on {target}...");
+                        Some(Action::InstallApp {
+                            tab: self.tab,
+                            target,
+                            app: input,
+                        })
+                    }
+                    PromptKind::RunSuite => {
+                        self.test_run = Some(TestRunView::default());
+                        Some(Action::RunTests {
+                            suite: (!input.is_empty()).then_some(input),
+                            device: self.selected_id(),
+                        })
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        self.prompt = Some(Prompt {
+            kind,
+            input: String::new(),
+        });
+    }
+
+    fn require_selection(&mut self) -> Option<String> {
+        let id = self.selected_id();
+        if id.is_none() {
+            self.status = "Nothing selected".to_string();
+        }
+        id
+    }
+
+    pub fn apply(&mut self, event: AppEvent) {
+        match event {
+            AppEvent::Input(_) | AppEvent::Resize => {}
+            AppEvent::Devices(devices) => {
+                self.status = format!("{} device(s)", devices.len());
+                self.selected_device = self.selected_device.min(devices.len().saturating_sub(1));
+                self.devices = devices;
+            }
+            AppEvent::Simulators(simulators) => {
+                self.selected_simulator = self
+                    .selected_simulator
+                    .min(simulators.len().saturating_sub(1));
+                self.simulators = simulators;
+            }
+            AppEvent::LogLine(line) => push_line(&mut self.logcat, line),
+            AppEvent::LogEnded => {
+                if let Some(device) = self.logcat_device.take() {
+                    self.status = format!("Logcat for {device} stopped");
+                }
+            }
+            AppEvent::BuildLine(line) => push_line(&mut self.build_output, line),
+            AppEvent::BuildFinished { success } => {
+                self.build_running = false;
+                self.status = if success {
+                    "Build succeeded".to_string()
+                } else {
+                    "Build failed".to_string()
+                };
+            }
+            AppEvent::Test(progress) => self.apply_test_progress(progress),
+            AppEvent::TestRunFailed(error) => {
+                self.status = format!("Test run failed: {error}");
+                if let Some(run) = self.test_run.as_mut().filter(|run| run.is_running()) {
+                    run.current = None;
+                    run.error = Some(error);
+                }
+            }
+            AppEvent::Status(status) => self.status = status,
+        }
+    }
+
+    fn apply_test_progress(&mut self, progress: TestProgress) {
+        let run = self.test_run.get_or_insert_with(TestRunView::default);
+        match progress {
+            TestProgress::SuiteStarted { suite_name, total } => {
+                *run = TestRunView {
+                    suite_name,
+                    total,
+                    ..Default::default()
+                };
+            }
+            TestProgress::CaseStarted { name } => run.current = Some(name),
+            TestProgress::CaseFinished(result) => {
+                run.current = None;
+                run.results.push(result);
+            }
+            TestProgress::SuiteFinished(summary) => {
+                run.current = None;
+                self.status = format!(
+                    "Tests finished: {} passed, {} failed",
+                    summary.passed, summary.failed
+                );
+                run.summary = Some(summary);
+            }
+        }
+    }
+}
+
+fn push_line(lines: &mut VecDeque<String>, line: String) {
+    if lines.len() == MAX_PANE_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator_basic::SimulatorStatus;
+    use crate::testing::TestStatus;
+    use std::time::Duration;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn simulator(id: &str) -> Simulator {
+        Simulator {
+            id: id.to_string(),
+            name: id.to_string(),
+            platform: "android".to_string(),
+            version: "34".to_string(),
+            status: SimulatorStatus::Shutdown,
+            device_type: "emulator".to_string(),
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn test_simulator_actions_and_install_prompt() {
+        let mut app = App::default();
+        app.apply(AppEvent::Simulators(vec![
+            simulator("Pixel_7"),
+            simulator("Pixel_8"),
+        ]));
+
+        assert_eq!(app.handle_key(key(KeyCode::Char('s'))), None);
+        app.handle_key(key(KeyCode::Tab));
+        app.handle_key(key(KeyCode::Up));
+        assert_eq!(
+            app.handle_key(key(KeyCode::Char('s'))),
+            Some(Action::StartSimulator("Pixel_8".to_string()))
+        );
+
+        app.handle_key(key(KeyCode::Char('i')));
+        for c in "app.apk".chars() {
+            // Typing into the prompt must not trigger shortcuts
+            assert_eq!(app.handle_key(key(KeyCode::Char(c))), None);
+        }
+        assert_eq!(
+            app.handle_key(key(KeyCode::Enter)),
+            Some(Action::InstallApp {
+                tab: Tab::Simulators,
+                target: "Pixel_8".to_string(),
+                app: "app.apk".to_string(),
+            })
+        );
+        assert!(app.prompt.is_none());
+    }
+
+    #[test]
+    fn test_progress_and_pane_limits() {
+        let mut app = App::default();
+        app.handle_key(key(KeyCode::Char('t')));
+        assert_eq!(
+            app.handle_key(key(KeyCode::Enter)),
+            Some(Action::RunTests {
+                suite: None,
+                device: None
+            })
+        );
+
+        app.apply(AppEvent::Test(TestProgress::SuiteStarted {
+            suite_name: "smoke".to_string(),
+            total: 2,
+        }));
+        app.apply(AppEvent::Test(TestProgress::CaseStarted {
+            name: "login".to_string(),
+        }));
+        app.apply(AppEvent::Test(TestProgress::CaseFinished(TestResult {
+            test_name: "login".to_string(),
+            status: TestStatus::Passed,
+            duration: Duration::from_millis(10),
+            error_message: None,
+            screenshots: Vec::new(),
+            video_path: None,
+            log_path: None,
+        })));
+        let run = app.test_run.as_ref().unwrap();
+        assert_eq!(run.ratio(), 0.5);
+        assert!(run.is_running());
+        assert_eq!(app.handle_key(key(KeyCode::Char('t'))), None);
+
+        for i in 0..MAX_PANE_LINES + 5 {
+            app.apply(AppEvent::LogLine(i.to_string()));
+        }
+        assert_eq!(app.logcat.len(), MAX_PANE_LINES);
+        assert_eq!(app.logcat.front().map(String::as_str), Some("5"));
+    }
+
+    #[test]
+    fn test_failed_run_can_be_restarted() {
+        let mut app = App::default();
+        app.handle_key(key(KeyCode::Char('t')));
+        app.handle_key(key(KeyCode::Char('x')));
+        app.handle_key(key(KeyCode::Enter));
+        assert!(app.test_run.as_ref().unwrap().is_running());
+
+        app.apply(AppEvent::TestRunFailed(
+            "Test suite 'x' not found".to_string(),
+        ));
+        let run = app.test_run.as_ref().unwrap();
+        assert!(!run.is_running());
+        assert_eq!(run.error.as_deref(), Some("Test suite 'x' not found"));
+        assert_eq!(app.status, "Test run failed: Test suite 'x' not found");
+
+        app.handle_key(key(KeyCode::Char('t')));
+        assert!(app.prompt.is_some());
+    }
+}
@@ -0,0 +1,279 @@
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{
+    Block, Borders, Cell, Gauge, List, ListItem, Paragraph, Row, Table, TableState, Tabs,
+};
+use ratatui::Frame;
+use std::collections::VecDeque;
+
+use super::app::{App, Tab};
+use crate::device_basic::DeviceStatus;
+use crate::simulator_basic::SimulatorStatus;
+use crate::testing::TestStatus;
+
+const HELP: &str =
+    "Tab switch  ↑↓ select  r refresh  s/x start/stop  i install  l logcat  b build  t test  q quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Percentage(45),
+            Constraint::Min(8),
+            Constraint::Length(3),
+        ])
+        .split(frame.size());
+
+    draw_tabs(frame, app, rows[0]);
+
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(rows[1]);
+    match app.tab {
+        Tab::Devices => draw_devices(frame, app, top[0]),
+        Tab::Simulators => draw_simulators(frame, app, top[0]),
+    }
+    draw_test_run(frame, app, top[1]);
+
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(rows[2]);
+    let logcat_title = match &app.logcat_device {
+        Some(device) => format!("Logcat: {device}"),
+        None => "Logcat (l to follow the selected device)".to_string(),
+    };
+    draw_lines(frame, &logcat_title, &app.logcat, bottom[0]);
+    let build_title = if app.build_running {
+        "Build (running)"
+    } else {
+        "Build"
+    };
+    draw_lines(frame, build_title, &app.build_output, bottom[1]);
+
+    draw_footer(frame, app, rows[3]);
+}
+
+fn draw_tabs(frame: &mut Frame, app: &App, area: Rect) {
+    let selected = match app.tab {
+        Tab::Devices => 0,
+        Tab::Simulators => 1,
+    };
+    let tabs = Tabs::new(vec![
+        format!("Devices ({})", app.devices.len()),
+        format!("Simulators ({})", app.simulators.len()),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("KMobile"))
+    .select(selected)
+    .highlight_style(
+        Style::default()
+            .add_modifier(Modifier::BOLD)
+            .fg(Color::Cyan),
+    );
+    frame.render_widget(tabs, area);
+}
+
+fn highlight() -> Style {
+    Style::default().add_modifier(Modifier::REVERSED)
+}
+
+fn draw_devices(frame: &mut Frame, app: &App, area: Rect) {
+    let rows = app.devices.iter().map(|device| {
+        let color = match device.status {
+            DeviceStatus::Connected => Color::Green,
+            DeviceStatus::Unauthorized => Color::Yellow,
+            DeviceStatus::Disconnected | DeviceStatus::Offline => Color::Red,
+        };
+        Row::new(vec![
+            Cell::from(device.id.clone()),
+            Cell::from(device.name.clone()),
+            Cell::from(format!("{} {}", device.platform, device.version)),
+            Cell::from(format!("{:?}", device.status)).style(Style::default().fg(color)),
+        ])
+    });
+    let widths = [
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+        Constraint::Percentage(20),
+        Constraint::Percentage(20),
+    ];
+    let table = Table::new(rows)
+        .header(header(["ID", "Name", "Platform", "Status"]))
+        .block(Block::default().borders(Borders::ALL).title("Devices"))
+        .widths(&widths)
+        .highlight_style(highlight());
+
+    let mut state = TableState::default();
+    state.select((!app.devices.is_empty()).then_some(app.selected_device));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_simulators(frame: &mut Frame, app: &App, area: Rect) {
+    let rows = app.simulators.iter().map(|simulator| {
+        let color = match simulator.status {
+            SimulatorStatus::Booted => Color::Green,
+            SimulatorStatus::Booting | SimulatorStatus::ShuttingDown => Color::Yellow,
+            SimulatorStatus::Shutdown => Color::DarkGray,
+        };
+        Row::new(vec![
+            Cell::from(simulator.name.clone()),
+            Cell::from(format!("{} {}", simulator.platform, simulator.version)),
+            Cell::from(simulator.device_type.clone()),
+            Cell::from(format!("{:?}", simulator.status)).style(Style::default().fg(color)),
+        ])
+    });
+    let widths = [
+        Constraint::Percentage(35),
+        Constraint::Percentage(20),
+        Constraint::Percentage(25),
+        Constraint::Percentage(20),
+    ];
+    let table = Table::new(rows)
+        .header(header(["Name", "Platform", "Type", "Status"]))
+        .block(Block::default().borders(Borders::ALL).title("Simulators"))
+        .widths(&widths)
+        .highlight_style(highlight());
+
+    let mut state = TableState::default();
+    state.select((!app.simulators.is_empty()).then_some(app.selected_simulator));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn header<'a>(titles: [&'a str; 4]) -> Row<'a> {
+    Row::new(titles).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+fn draw_test_run(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Tests (t to run)");
+    let Some(run) = &app.test_run else {
+        frame.render_widget(Paragraph::new("No test run yet").block(block), area);
+        return;
+    };
+
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let label = match (&run.error, &run.summary, &run.current) {
+        (Some(error), _, _) => format!("Failed: {error}"),
+        (None, Some(summary), _) => format!(
+            "{}: {} passed, {} failed, {} skipped",
+            run.suite_name, summary.passed, summary.failed, summary.skipped
+        ),
+        (None, None, Some(current)) => format!("{}/{} {current}", run.results.len(), run.total),
+        (None, None, None) => format!("{}/{}", run.results.len(), run.total),
+    };
+    let failed = run.error.is_some()
+        || run
+            .results
+            .iter()
+            .any(|result| matches!(result.status, TestStatus::Failed | TestStatus::Timeout));
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(if failed { Color::Red } else { Color::Green }))
+        .ratio(run.ratio())
+        .label(label);
+    frame.render_widget(gauge, parts[0]);
+
+    let items: Vec<ListItem> = run
+        .results
+        .iter()
+        .rev()
+        .map(|result| {
+            let (mark, color) = match result.status {
+                TestStatus::Passed => ("✔", Color::Green),
+                TestStatus::Failed => ("✘", Color::Red),
+                TestStatus::Timeout => ("⧗", Color::Red),
+                TestStatus::Skipped => ("-", Color::DarkGray),
+            };
+            let mut spans = vec![
+                Span::styled(format!("{mark} "), Style::default().fg(color)),
+                Span::raw(format!(
+                    "{} ({}ms)",
+                    result.test_name,
+                    result.duration.as_millis()
+                )),
+            ];
+            if let Some(error) = &result.error_message {
+                spans.push(Span::styled(
+                    format!("  {error}"),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    frame.render_widget(List::new(items), parts[1]);
+}
+
+/// Renders the tail of `lines` that fits the pane
+fn draw_lines(frame: &mut Frame, title: &str, lines: &VecDeque<String>, area: Rect) {
+    let height = area.height.saturating_sub(2) as usize;
+    let tail: Vec<Line> = lines
+        .iter()
+        .skip(lines.len().saturating_sub(height))
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    let pane = Paragraph::new(tail).block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(pane, area);
+}
+
+fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL);
+    let paragraph = match &app.prompt {
+        Some(prompt) => {
+            let text = format!("{}: {}", prompt.kind.label(), prompt.input);
+            let cursor_x = area.x + 1 + text.chars().count() as u16;
+            frame.set_cursor(cursor_x.min(area.right().saturating_sub(2)), area.y + 1);
+            Paragraph::new(text).block(block.title("Enter to confirm, Esc to cancel"))
+        }
+        None => Paragraph::new(app.status.as_str()).block(block.title(HELP)),
+    };
+    frame.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tui::app::{Prompt, PromptKind, TestRunView};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_draw_dashboard() {
+        let mut app = App::default();
+        app.logcat
+            .push_back("I ActivityManager: Start proc".to_string());
+        app.test_run = Some(TestRunView {
+            suite_name: "smoke".to_string(),
+            total: 3,
+            current: Some("login".to_string()),
+            ..Default::default()
+        });
+        app.prompt = Some(Prompt {
+            kind: PromptKind::RunSuite,
+            input: "smoke".to_string(),
+        });
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect();
+        assert!(screen.contains("Start proc"));
+        assert!(screen.contains("0/3 login"));
+        assert!(screen.contains("Test suite (empty for default): smoke"));
+    }
+}
@@ -23,43 +23,22 @@ pub struct ToolInfo {
     pub available: bool,
 }
 
-#[allow(dead_code)]
+/// How long a `--version` style probe may take; first runs of Flutter and Gradle are slow
+const TOOL_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Tools looked up on `PATH` for `kmobile doctor`
+pub const PATH_TOOLS: &[&str] = &["java", "gradle", "xcodebuild", "flutter", "node"];
+
 pub async fn detect_system_info() -> Result<SystemInfo> {
     let os = std::env::consts::OS.to_string();
     let arch = std::env::consts::ARCH.to_string();
     let version = get_os_version().await?;
 
     let mut available_tools = HashMap::new();
-
-    // Check for Android tools
-    if let Ok(tool_info) = check_tool_availability("adb").await {
-        available_tools.insert("adb".to_string(), tool_info);
-    }
-
-    // Check for iOS tools
-    if let Ok(tool_info) = check_tool_availability("simctl").await {
-        available_tools.insert("simctl".to_string(), tool_info);
-    }
-
-    if let Ok(tool_info) = check_tool_availability("xcodebuild").await {
-        available_tools.insert("xcodebuild".to_string(), tool_info);
-    }
-
-    // Check for other tools
-    if let Ok(tool_info) = check_tool_availability("instruments").await {
-        available_tools.insert("instruments".to_string(), tool_info);
-    }
-
-    if let Ok(tool_info) = check_tool_availability("ios-deploy").await {
-        available_tools.insert("ios-deploy".to_string(), tool_info);
-    }
-
-    if let Ok(tool_info) = check_tool_availability("flutter").await {
-        available_tools.insert("flutter".to_string(), tool_info);
-    }
-
-    if let Ok(tool_info) = check_tool_availability("react-native").await {
-        available_tools.insert("react-native".to_string(), tool_info);
+    for tool in PATH_TOOLS {
+        if let Ok(tool_info) = check_tool_availability(tool).await {
+            available_tools.insert(tool.to_string(), tool_info);
+        }
     }
 
     Ok(SystemInfo {
@@ -70,23 +49,49 @@ pub async fn detect_system_info() -> Result<SystemInfo> {
     })
 }
 
-#[allow(dead_code)]
-async fn get_os_version() -> Result<String> {
-    let output = Command::new("sw_vers").args(["-productVersion"]).output();
+pub async fn get_os_version() -> Result<String> {
+    if let Ok(output) = Command::new("sw_vers").args(["-productVersion"]).output() {
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+        }
+    }
+
+    // Linux distributions describe themselves in os-release
+    for path in ["/etc/os-release", "/usr/lib/os-release"] {
+        if let Some(version) = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| parse_os_release(&content))
+        {
+            return Ok(version);
+        }
+    }
 
-    match output {
+    match Command::new("uname").arg("-r").output() {
         Ok(output) if output.status.success() => {
             Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
         }
-        _ => {
-            // Fallback for non-macOS systems
-            Ok("unknown".to_string())
-        }
+        _ => Ok("unknown".to_string()),
     }
 }
 
-#[allow(dead_code)]
-async fn check_tool_availability(tool_name: &str) -> Result<ToolInfo> {
+/// `PRETTY_NAME`, falling back to `NAME VERSION_ID`
+fn parse_os_release(content: &str) -> Option<String> {
+    let fields: HashMap<&str, &str> = content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim().trim_matches('"')))
+        .collect();
+    if let Some(pretty) = fields.get("PRETTY_NAME").filter(|name| !name.is_empty()) {
+        return Some(pretty.to_string());
+    }
+    let name = fields.get("NAME")?;
+    Some(match fields.get("VERSION_ID") {
+        Some(version) => format!("{name} {version}"),
+        None => name.to_string(),
+    })
+}
+
+pub async fn check_tool_availability(tool_name: &str) -> Result<ToolInfo> {
     // First, try to find the tool using 'which'
     let which_output = Command::new("which").arg(tool_name).output();
 
@@ -97,11 +102,7 @@ async fn check_tool_availability(tool_name: &str) -> Result<ToolInfo> {
         _ => {
             // Try some common locations
             let common_paths = match tool_name {
-                "adb" => vec![
-                    "/usr/local/bin/adb",
-                    "/opt/android-sdk/platform-tools/adb",
-                    "$ANDROID_HOME/platform-tools/adb",
-                ],
+                "adb" => vec!["/usr/local/bin/adb", "/opt/android-sdk/platform-tools/adb"],
                 "simctl" => vec![
                     "/usr/bin/simctl",
                     "/Applications/Xcode.app/Contents/Developer/usr/bin/simctl",
@@ -127,7 +128,7 @@ async fn check_tool_availability(tool_name: &str) -> Result<ToolInfo> {
     };
 
     // Try to get version
-    let version = get_tool_version(tool_name, &path)
+    let version = get_tool_version(tool_name, Path::new(&path))
         .await
         .unwrap_or_else(|_| "unknown".to_string());
 
@@ -138,56 +139,81 @@ async fn check_tool_availability(tool_name: &str) -> Result<ToolInfo> {
     })
 }
 
-#[allow(dead_code)]
-async fn get_tool_version(tool_name: &str, tool_path: &str) -> Result<String> {
+pub async fn get_tool_version(tool_name: &str, tool_path: &Path) -> Result<String> {
     let version_args = match tool_name {
         "adb" => vec!["version"],
         "simctl" => vec!["help"],
         "xcodebuild" => vec!["-version"],
         "instruments" => vec!["-version"],
-        "ios-deploy" => vec!["--version"],
-        "flutter" => vec!["--version"],
-        "react-native" => vec!["--version"],
+        "emulator" => vec!["-version"],
+        "java" => vec!["-version"],
         _ => vec!["--version"],
     };
 
-    let output = Command::new(tool_path).args(&version_args).output()?;
-
-    let output_str = String::from_utf8_lossy(&output.stdout);
+    let output = tokio::time::timeout(
+        TOOL_PROBE_TIMEOUT,
+        tokio::process::Command::new(tool_path)
+            .args(&version_args)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| KMobileError::TimeoutError(format!("{tool_name} did not report a version")))??;
+
+    // java -version writes to stderr
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(parse_tool_version(tool_name, &text).unwrap_or_else(|| "unknown".to_string()))
+}
 
-    // Extract version from output
-    let version = match tool_name {
-        "adb" => {
-            if let Some(line) = output_str.lines().next() {
-                line.split_whitespace()
-                    .last()
-                    .unwrap_or("unknown")
-                    .to_string()
-            } else {
-                "unknown".to_string()
-            }
+/// Extracts the version number from a tool's version output
+pub fn parse_tool_version(tool_name: &str, output: &str) -> Option<String> {
+    // adb's first line is the protocol version; platform-tools is on the `Version` line
+    if tool_name == "adb" {
+        if let Some(line) = output.lines().find(|line| line.starts_with("Version ")) {
+            return version_token(&line["Version ".len()..]);
         }
-        "xcodebuild" => {
-            if let Some(line) = output_str.lines().next() {
-                line.replace("Xcode ", "").trim().to_string()
-            } else {
-                "unknown".to_string()
-            }
-        }
-        _ => {
-            // Try to extract version from first line
-            if let Some(line) = output_str.lines().next() {
-                line.split_whitespace()
-                    .last()
-                    .unwrap_or("unknown")
-                    .to_string()
-            } else {
-                "unknown".to_string()
-            }
-        }
-    };
+    }
+    output.split_whitespace().find_map(version_token)
+}
+
+/// `v20.10.0`, `"17.0.9"` and `34.0.5-10900879` all read as dotted numbers. A quoted
+/// bare number such as Java's `"17"` is a version too; unquoted ones are not, since
+/// they are usually build numbers or years.
+fn version_token(token: &str) -> Option<String> {
+    let quoted = token.trim_start_matches(',').starts_with(['"', '\'']);
+    let token = token.trim_matches(|c: char| c == '"' || c == '\'' || c == ',');
+    let token = token.strip_prefix('v').unwrap_or(token);
+    let version: String = token
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let version = version.trim_end_matches('.');
+    ((quoted || version.contains('.')) && version.starts_with(|c: char| c.is_ascii_digit()))
+        .then(|| version.to_string())
+}
 
-    Ok(version)
+/// Compares dotted versions numerically, so `1.8.0` < `17` and `8.10` > `8.5`
+pub fn version_at_least(version: &str, minimum: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (version, minimum) = (parts(version), parts(minimum));
+    let len = version.len().max(minimum.len());
+    for index in 0..len {
+        let have = version.get(index).copied().unwrap_or(0);
+        let need = minimum.get(index).copied().unwrap_or(0);
+        if have != need {
+            return have > need;
+        }
+    }
+    true
 }
 
 #[allow(dead_code)]
@@ -262,7 +288,6 @@ pub fn format_duration(duration: std::time::Duration) -> String {
     }
 }
 
-#[allow(dead_code)]
 pub fn create_backup_file(file_path: &str) -> Result<String> {
     let path = Path::new(file_path);
     let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
@@ -306,7 +331,6 @@ pub fn is_port_available(port: u16) -> bool {
     TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port))).is_ok()
 }
 
-#[allow(dead_code)]
 pub fn sanitize_filename(filename: &str) -> String {
     filename
         .chars()
@@ -326,45 +350,216 @@ pub fn truncate_string(s: &str, max_length: usize) -> String {
     }
 }
 
-#[allow(dead_code)]
-pub fn retry_with_backoff<F, R, E>(
+/// Attributes of an XML start tag, in order and with entities unescaped. Enough for
+/// uiautomator dumps, JUnit reports and GPX files, which is all we read.
+pub fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = tag;
+
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq]
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let value_start = eq + 2;
+        let Some(value_len) = rest[value_start..].find('"') else {
+            break;
+        };
+        let value = unescape_xml(&rest[value_start..value_start + value_len]);
+        attrs.push((key, value));
+        rest = &rest[value_start + value_len + 1..];
+    }
+
+    attrs
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#10;", "\n")
+        .replace("&amp;", "&")
+}
+
+/// Retries for device and simulator operations that can fail while a device settles
+pub const DEVICE_RETRIES: usize = 2;
+pub const DEVICE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Retries an async operation with exponential backoff while it fails with a
+/// recoverable `KMobileError`. Anything else, and the last failure, is returned as is.
+pub async fn retry_with_backoff<F, Fut, R>(
     mut operation: F,
     max_retries: usize,
     initial_delay: std::time::Duration,
 ) -> Result<R>
 where
-    F: FnMut() -> std::result::Result<R, E>,
-    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<R>>,
 {
     let mut delay = initial_delay;
-    let mut last_error = None;
+    let mut attempt = 0;
 
-    for attempt in 0..=max_retries {
-        match operation() {
+    loop {
+        match operation().await {
             Ok(result) => return Ok(result),
             Err(e) => {
-                last_error = Some(e);
-
-                if attempt < max_retries {
-                    debug!("Attempt {} failed, retrying in {:?}", attempt + 1, delay);
-                    std::thread::sleep(delay);
-                    delay *= 2; // Exponential backoff
+                let recoverable = KMobileError::find(&e).is_some_and(KMobileError::is_recoverable);
+                if !recoverable || attempt >= max_retries {
+                    return Err(e);
                 }
+                attempt += 1;
+                debug!(
+                    "Attempt {} failed ({}), retrying in {:?}",
+                    attempt, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2; // Exponential backoff
             }
         }
     }
-
-    Err(KMobileError::Unknown(format!(
-        "Operation failed after {} attempts: {}",
-        max_retries + 1,
-        last_error.unwrap()
-    ))
-    .into())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_attributes() {
+        let attrs = parse_attributes(
+            r#"<node text="Terms &amp; &quot;Conditions&quot;" resource-id="" clickable="true""#,
+        );
+        assert_eq!(
+            attrs,
+            vec![
+                ("text".to_string(), r#"Terms & "Conditions""#.to_string()),
+                ("resource-id".to_string(), String::new()),
+                ("clickable".to_string(), "true".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff_only_retries_recoverable_errors() {
+        let calls = AtomicUsize::new(0);
+        let result: Result<()> = retry_with_backoff(
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(KMobileError::DeviceConnectionError("offline".to_string()).into())
+            },
+            2,
+            Duration::from_millis(1),
+        )
+        .await;
+        let error = result.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::DeviceConnectionError(_))
+        ));
+
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<()> = retry_with_backoff(
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(KMobileError::InvalidInput("bad".to_string()).into())
+            },
+            2,
+            Duration::from_millis(1),
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        calls.store(0, Ordering::SeqCst);
+        let value = retry_with_backoff(
+            || async {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(KMobileError::TimeoutError("slow".to_string()).into())
+                } else {
+                    Ok(7)
+                }
+            },
+            2,
+            Duration::from_millis(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(value, 7);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_simulator_is_not_retried() {
+        let manager =
+            crate::simulator_basic::SimulatorManager::new(&crate::config::Config::default())
+                .await
+                .unwrap();
+        let boot = crate::emulator::BootOptions::default();
+        let calls = AtomicUsize::new(0);
+
+        let result = retry_with_backoff(
+            || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                manager.start_simulator_with("No_Such_AVD", &boot)
+            },
+            DEVICE_RETRIES,
+            DEVICE_RETRY_DELAY,
+        )
+        .await;
+        let error = result.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            KMobileError::find(&error),
+            Some(KMobileError::SimulatorNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_tool_version() {
+        let adb = "Android Debug Bridge version 1.0.41\nVersion 34.0.5-10900879\nInstalled as /opt/sdk/platform-tools/adb\n";
+        assert_eq!(parse_tool_version("adb", adb).as_deref(), Some("34.0.5"));
+        let java = "openjdk version \"17.0.9\" 2023-10-17\nOpenJDK Runtime Environment";
+        assert_eq!(parse_tool_version("java", java).as_deref(), Some("17.0.9"));
+        let java_ga =
+            "openjdk version \"17\" 2021-09-14\nOpenJDK Runtime Environment (build 17+35)";
+        assert_eq!(parse_tool_version("java", java_ga).as_deref(), Some("17"));
+        assert_eq!(parse_tool_version("build", "build 2021 ok"), None);
+        let emulator = "INFO    | Android emulator version 33.1.24.0 (build_id 10796419) (CL:N/A)";
+        assert_eq!(
+            parse_tool_version("emulator", emulator).as_deref(),
+            Some("33.1.24.0")
+        );
+        let gradle = "\n------------------------------------------------------------\nGradle 8.5\n";
+        assert_eq!(parse_tool_version("gradle", gradle).as_deref(), Some("8.5"));
+        assert_eq!(
+            parse_tool_version("node", "v20.10.0\n").as_deref(),
+            Some("20.10.0")
+        );
+        assert_eq!(parse_tool_version("node", "command not found"), None);
+
+        assert!(version_at_least("17.0.9", "17"));
+        assert!(version_at_least("8.10", "8.5"));
+        assert!(!version_at_least("1.8.0", "17"));
+        assert!(!version_at_least("3.9.9", "3.10.0"));
+    }
+
+    #[test]
+    fn test_parse_os_release() {
+        let ubuntu = "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nPRETTY_NAME=\"Ubuntu 22.04.3 LTS\"\n";
+        assert_eq!(
+            parse_os_release(ubuntu).as_deref(),
+            Some("Ubuntu 22.04.3 LTS")
+        );
+        assert_eq!(
+            parse_os_release("NAME=Fedora\nVERSION_ID=39\n").as_deref(),
+            Some("Fedora 39")
+        );
+        assert_eq!(parse_os_release(""), None);
+    }
 
     #[test]
     fn test_parse_coordinates() {
@@ -0,0 +1,835 @@
+//! Minimal W3C WebDriver endpoint so Appium-style clients can drive devices through
+//! the same actions as JSON test suites.
+
+use anyhow::Result;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::Engine;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::error::KMobileError;
+use crate::testing::ui_hierarchy::{self, UiHierarchy, UiNode};
+use crate::testing::{self, TestAction, TestRunner, TestStep};
+
+pub mod locator;
+
+pub use locator::Locator;
+
+/// Key W3C clients read element references from
+pub const ELEMENT_KEY: &str = "element-6066-11e4-a52e-4f735466cecf";
+
+/// Device operations the WebDriver endpoint is built on
+pub trait DeviceBackend: Send + Sync + 'static {
+    /// Device used when the session capabilities don't name one
+    fn default_device(&self) -> Result<String>;
+
+    fn perform(&self, device_id: &str, step: TestStep) -> impl Future<Output = Result<()>> + Send;
+
+    /// Raw uiautomator window dump
+    fn page_source(&self, device_id: &str) -> Result<String>;
+
+    /// PNG screenshot
+    fn screenshot(&self, device_id: &str) -> Result<Vec<u8>>;
+}
+
+/// Backend running actions through a `TestRunner` against adb
+pub struct AdbBackend {
+    runner: TestRunner,
+    adb_path: PathBuf,
+    device_id: Option<String>,
+}
+
+impl AdbBackend {
+    /// Without `device_id`, sessions that don't set `appium:udid` use the first adb device
+    pub fn new(runner: TestRunner, adb_path: PathBuf, device_id: Option<String>) -> Self {
+        Self {
+            runner,
+            adb_path,
+            device_id,
+        }
+    }
+}
+
+impl DeviceBackend for AdbBackend {
+    fn default_device(&self) -> Result<String> {
+        match &self.device_id {
+            Some(device_id) => Ok(device_id.clone()),
+            None => testing::first_adb_device(&self.adb_path),
+        }
+    }
+
+    async fn perform(&self, device_id: &str, step: TestStep) -> Result<()> {
+        self.runner.perform_step(&step, Some(device_id)).await
+    }
+
+    fn page_source(&self, device_id: &str) -> Result<String> {
+        ui_hierarchy::dump_android_window(&self.adb_path, device_id)
+    }
+
+    fn screenshot(&self, device_id: &str) -> Result<Vec<u8>> {
+        let output = Command::new(&self.adb_path)
+            .args(["-s", device_id, "exec-out", "screencap", "-p"])
+            .output()?;
+        if !output.status.success() {
+            return Err(KMobileError::DeviceConnectionError(format!(
+                "Screenshot failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ))
+            .into());
+        }
+        Ok(output.stdout)
+    }
+}
+
+struct Session {
+    device_id: String,
+    /// Elements handed out to the client, keyed by element id
+    elements: HashMap<String, UiNode>,
+}
+
+struct WebDriverState<B> {
+    backend: B,
+    sessions: RwLock<HashMap<String, Session>>,
+}
+
+type SharedState<B> = Arc<WebDriverState<B>>;
+
+/// W3C error response: `{"value": {"error", "message", "stacktrace"}}`
+#[derive(Debug)]
+pub struct WebDriverError {
+    status: StatusCode,
+    error: &'static str,
+    message: String,
+}
+
+impl WebDriverError {
+    fn new(status: StatusCode, error: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            error,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_session(id: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "invalid session id",
+            format!("No active session with id {id}"),
+        )
+    }
+
+    fn no_such_element(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "no such element", message)
+    }
+
+    fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid argument", message)
+    }
+}
+
+impl From<anyhow::Error> for WebDriverError {
+    fn from(error: anyhow::Error) -> Self {
+        let (status, code) = match KMobileError::find(&error) {
+            Some(KMobileError::InvalidInput(_)) => (StatusCode::BAD_REQUEST, "invalid argument"),
+            Some(KMobileError::TimeoutError(_)) => (StatusCode::INTERNAL_SERVER_ERROR, "timeout"),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "unknown error"),
+        };
+        Self::new(status, code, error.to_string())
+    }
+}
+
+impl IntoResponse for WebDriverError {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "value": {
+                "error": self.error,
+                "message": self.message,
+                "stacktrace": "",
+            }
+        });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+type WdResult = std::result::Result<Json<Value>, WebDriverError>;
+
+fn value(value: Value) -> WdResult {
+    Ok(Json(json!({ "value": value })))
+}
+
+/// Builds the WebDriver router around `backend`
+pub fn router<B: DeviceBackend>(backend: B) -> Router {
+    let state = Arc::new(WebDriverState {
+        backend,
+        sessions: RwLock::new(HashMap::new()),
+    });
+
+    Router::new()
+        .route("/status", get(status))
+        .route("/session", post(new_session::<B>))
+        .route("/session/:id", axum::routing::delete(delete_session::<B>))
+        .route("/session/:id/element", post(find_element::<B>))
+        .route("/session/:id/elements", post(find_elements::<B>))
+        .route(
+            "/session/:id/element/:eid/element",
+            post(find_child_element::<B>),
+        )
+        .route(
+            "/session/:id/element/:eid/elements",
+            post(find_child_elements::<B>),
+        )
+        .route("/session/:id/element/:eid/click", post(click::<B>))
+        .route("/session/:id/element/:eid/value", post(send_keys::<B>))
+        .route("/session/:id/element/:eid/text", get(element_text::<B>))
+        .route(
+            "/session/:id/element/:eid/attribute/:name",
+            get(element_attribute::<B>),
+        )
+        .route("/session/:id/element/:eid/rect", get(element_rect::<B>))
+        .route("/session/:id/screenshot", get(screenshot::<B>))
+        .route("/session/:id/source", get(page_source::<B>))
+        .with_state(state)
+}
+
+/// Serves the WebDriver endpoint until the process is stopped
+pub async fn serve<B: DeviceBackend>(backend: B, host: &str, port: u16) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind((host, port))
+        .await
+        .map_err(|e| KMobileError::NetworkError(format!("Cannot bind {host}:{port}: {e}")))?;
+    info!("WebDriver endpoint listening on http://{}:{}", host, port);
+    axum::serve(listener, router(backend))
+        .await
+        .map_err(|e| KMobileError::NetworkError(e.to_string()))?;
+    Ok(())
+}
+
+async fn status() -> WdResult {
+    value(json!({ "ready": true, "message": "kmobile WebDriver ready" }))
+}
+
+async fn new_session<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Json(body): Json<Value>,
+) -> WdResult {
+    let capabilities = merged_capabilities(&body);
+    let capability = |name: &str| {
+        capabilities
+            .get(name)
+            .or_else(|| capabilities.get(&format!("appium:{name}")))
+            .and_then(Value::as_str)
+            .map(String::from)
+    };
+
+    let device_id = match capability("udid") {
+        Some(udid) => udid,
+        None => state.backend.default_device()?,
+    };
+
+    if let (Some(package), Some(activity)) = (capability("appPackage"), capability("appActivity")) {
+        state
+            .backend
+            .perform(
+                &device_id,
+                step(
+                    TestAction::Launch,
+                    Some(format!("{package}/{activity}")),
+                    None,
+                ),
+            )
+            .await?;
+    }
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    info!("WebDriver session {} on {}", session_id, device_id);
+    state.sessions.write().await.insert(
+        session_id.clone(),
+        Session {
+            device_id: device_id.clone(),
+            elements: HashMap::new(),
+        },
+    );
+
+    let mut returned = capabilities;
+    returned.insert("platformName".to_string(), json!("Android"));
+    returned.insert("appium:udid".to_string(), json!(device_id));
+    value(json!({ "sessionId": session_id, "capabilities": returned }))
+}
+
+/// `alwaysMatch` merged with the first `firstMatch` entry
+fn merged_capabilities(body: &Value) -> serde_json::Map<String, Value> {
+    let capabilities = &body["capabilities"];
+    let mut merged = capabilities["alwaysMatch"]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+    if let Some(first) = capabilities["firstMatch"]
+        .as_array()
+        .and_then(|matches| matches.first())
+        .and_then(Value::as_object)
+    {
+        merged.extend(first.clone());
+    }
+    merged
+}
+
+async fn delete_session<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path(id): Path<String>,
+) -> WdResult {
+    state
+        .sessions
+        .write()
+        .await
+        .remove(&id)
+        .ok_or_else(|| WebDriverError::invalid_session(&id))?;
+    value(Value::Null)
+}
+
+async fn session_device<B: DeviceBackend>(
+    state: &WebDriverState<B>,
+    id: &str,
+) -> std::result::Result<String, WebDriverError> {
+    state
+        .sessions
+        .read()
+        .await
+        .get(id)
+        .map(|session| session.device_id.clone())
+        .ok_or_else(|| WebDriverError::invalid_session(id))
+}
+
+async fn session_element<B: DeviceBackend>(
+    state: &WebDriverState<B>,
+    id: &str,
+    element_id: &str,
+) -> std::result::Result<(String, UiNode), WebDriverError> {
+    let sessions = state.sessions.read().await;
+    let session = sessions
+        .get(id)
+        .ok_or_else(|| WebDriverError::invalid_session(id))?;
+    let node = session.elements.get(element_id).cloned().ok_or_else(|| {
+        WebDriverError::no_such_element(format!("Unknown element id {element_id}"))
+    })?;
+    Ok((session.device_id.clone(), node))
+}
+
+fn parse_locator(body: &Value) -> std::result::Result<Locator, WebDriverError> {
+    let using = body["using"]
+        .as_str()
+        .ok_or_else(|| WebDriverError::invalid_argument("Missing 'using'"))?;
+    let locator_value = body["value"]
+        .as_str()
+        .ok_or_else(|| WebDriverError::invalid_argument("Missing 'value'"))?;
+    Locator::parse(using, locator_value).map_err(|message| {
+        WebDriverError::new(StatusCode::BAD_REQUEST, "invalid selector", message)
+    })
+}
+
+/// Finds matching nodes, optionally only below `parent`, and registers them on the session
+async fn locate<B: DeviceBackend>(
+    state: &WebDriverState<B>,
+    id: &str,
+    parent: Option<&str>,
+    body: &Value,
+) -> std::result::Result<Vec<Value>, WebDriverError> {
+    let locator = parse_locator(body)?;
+    let (device_id, parent) = match parent {
+        Some(element_id) => {
+            let (device_id, node) = session_element(state, id, element_id).await?;
+            (device_id, Some(node))
+        }
+        None => (session_device(state, id).await?, None),
+    };
+
+    let hierarchy = UiHierarchy::parse(&state.backend.page_source(&device_id)?);
+    let candidates = match &parent {
+        Some(parent) => descendants(&hierarchy, parent),
+        None => hierarchy.nodes.iter().collect(),
+    };
+
+    let mut sessions = state.sessions.write().await;
+    let session = sessions
+        .get_mut(id)
+        .ok_or_else(|| WebDriverError::invalid_session(id))?;
+    Ok(candidates
+        .into_iter()
+        .filter(|node| locator.matches(node))
+        .map(|node| {
+            let element_id = uuid::Uuid::new_v4().to_string();
+            session.elements.insert(element_id.clone(), node.clone());
+            json!({ ELEMENT_KEY: element_id, "ELEMENT": element_id })
+        })
+        .collect())
+}
+
+/// Nodes nested under `parent` in a fresh dump. The parent is matched by position first,
+/// then by class and bounds in case the tree shifted.
+fn descendants<'a>(hierarchy: &'a UiHierarchy, parent: &UiNode) -> Vec<&'a UiNode> {
+    let same = |node: &UiNode| node.class == parent.class && node.bounds == parent.bounds;
+    let start = match hierarchy.nodes.get(parent.index) {
+        Some(node) if same(node) => Some(parent.index),
+        _ => hierarchy.nodes.iter().position(same),
+    };
+    let Some(start) = start else {
+        return Vec::new();
+    };
+
+    let depth = hierarchy.nodes[start].depth;
+    hierarchy.nodes[start + 1..]
+        .iter()
+        .take_while(|node| node.depth > depth)
+        .collect()
+}
+
+async fn find_element<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path(id): Path<String>,
+    Json(body): Json<Value>,
+) -> WdResult {
+    first_match(locate(&state, &id, None, &body).await?, &body)
+}
+
+async fn find_elements<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path(id): Path<String>,
+    Json(body): Json<Value>,
+) -> WdResult {
+    value(Value::Array(locate(&state, &id, None, &body).await?))
+}
+
+async fn find_child_element<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path((id, element_id)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> WdResult {
+    first_match(locate(&state, &id, Some(&element_id), &body).await?, &body)
+}
+
+async fn find_child_elements<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path((id, element_id)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> WdResult {
+    value(Value::Array(
+        locate(&state, &id, Some(&element_id), &body).await?,
+    ))
+}
+
+fn first_match(elements: Vec<Value>, body: &Value) -> WdResult {
+    match elements.into_iter().next() {
+        Some(element) => value(element),
+        None => Err(WebDriverError::no_such_element(format!(
+            "No element matches {} '{}'",
+            body["using"].as_str().unwrap_or_default(),
+            body["value"].as_str().unwrap_or_default()
+        ))),
+    }
+}
+
+fn step(action: TestAction, target: Option<String>, value: Option<String>) -> TestStep {
+    TestStep {
+        action,
+        target,
+        value,
+        wait_time: None,
+    }
+}
+
+fn center_target(node: &UiNode) -> String {
+    let (x, y) = node.bounds.center();
+    format!("{x} {y}")
+}
+
+async fn click<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path((id, element_id)): Path<(String, String)>,
+) -> WdResult {
+    let (device_id, node) = session_element(&state, &id, &element_id).await?;
+    state
+        .backend
+        .perform(
+            &device_id,
+            step(TestAction::Tap, Some(center_target(&node)), None),
+        )
+        .await?;
+    value(Value::Null)
+}
+
+async fn send_keys<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path((id, element_id)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> WdResult {
+    let text = match (&body["text"], &body["value"]) {
+        (Value::String(text), _) => text.clone(),
+        (_, Value::Array(keys)) => keys.iter().filter_map(Value::as_str).collect(),
+        _ => return Err(WebDriverError::invalid_argument("Missing 'text'")),
+    };
+
+    let (device_id, node) = session_element(&state, &id, &element_id).await?;
+    let target = center_target(&node);
+    state
+        .backend
+        .perform(
+            &device_id,
+            step(TestAction::Tap, Some(target.clone()), None),
+        )
+        .await?;
+    state
+        .backend
+        .perform(&device_id, step(TestAction::Type, Some(target), Some(text)))
+        .await?;
+    value(Value::Null)
+}
+
+async fn element_text<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path((id, element_id)): Path<(String, String)>,
+) -> WdResult {
+    let (_, node) = session_element(&state, &id, &element_id).await?;
+    value(json!(node.text))
+}
+
+async fn element_attribute<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path((id, element_id, name)): Path<(String, String, String)>,
+) -> WdResult {
+    let (_, node) = session_element(&state, &id, &element_id).await?;
+    let attribute = match name.as_str() {
+        "resource-id" | "resourceId" => json!(node.resource_id),
+        "text" | "name" => json!(node.text),
+        "content-desc" | "contentDescription" => json!(node.content_desc),
+        "class" | "className" => json!(node.class),
+        "package" => json!(node.package),
+        "clickable" => json!(node.clickable.to_string()),
+        "enabled" => json!(node.enabled.to_string()),
+        "focused" => json!(node.focused.to_string()),
+        "bounds" => json!(format!(
+            "[{},{}][{},{}]",
+            node.bounds.left, node.bounds.top, node.bounds.right, node.bounds.bottom
+        )),
+        _ => Value::Null,
+    };
+    value(attribute)
+}
+
+async fn element_rect<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path((id, element_id)): Path<(String, String)>,
+) -> WdResult {
+    let (_, node) = session_element(&state, &id, &element_id).await?;
+    let bounds = node.bounds;
+    value(json!({
+        "x": bounds.left,
+        "y": bounds.top,
+        "width": bounds.right - bounds.left,
+        "height": bounds.bottom - bounds.top,
+    }))
+}
+
+async fn screenshot<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path(id): Path<String>,
+) -> WdResult {
+    let device_id = session_device(&state, &id).await?;
+    let png = state.backend.screenshot(&device_id)?;
+    value(json!(base64::engine::general_purpose::STANDARD.encode(png)))
+}
+
+async fn page_source<B: DeviceBackend>(
+    State(state): State<SharedState<B>>,
+    Path(id): Path<String>,
+) -> WdResult {
+    let device_id = session_device(&state, &id).await?;
+    value(json!(state.backend.page_source(&device_id)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    const WINDOW: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<hierarchy rotation="0">
+  <node index="0" text="" resource-id="com.example:id/form" class="android.widget.LinearLayout" package="com.example" content-desc="" clickable="false" enabled="true" focused="false" bounds="[0,0][1080,1200]">
+    <node index="0" text="" resource-id="com.example:id/username" class="android.widget.EditText" package="com.example" content-desc="Username" clickable="true" enabled="true" focused="false" bounds="[40,100][1040,200]" />
+    <node index="1" text="Sign in" resource-id="com.example:id/login" class="android.widget.Button" package="com.example" content-desc="" clickable="true" enabled="true" focused="false" bounds="[40,300][1040,400]" />
+  </node>
+  <node index="1" text="Sign in" resource-id="com.example:id/footer" class="android.widget.TextView" package="com.example" content-desc="" clickable="false" enabled="true" focused="false" bounds="[0,1200][1080,1300]" />
+</hierarchy>"#;
+
+    #[derive(Default)]
+    struct MockBackend {
+        steps: Arc<Mutex<Vec<(String, TestStep)>>>,
+    }
+
+    impl DeviceBackend for MockBackend {
+        fn default_device(&self) -> Result<String> {
+            Ok("emulator-5554".to_string())
+        }
+
+        async fn perform(&self, device_id: &str, step: TestStep) -> Result<()> {
+            self.steps
+                .lock()
+                .unwrap()
+                .push((device_id.to_string(), step));
+            Ok(())
+        }
+
+        fn page_source(&self, _device_id: &str) -> Result<String> {
+            Ok(WINDOW.to_string())
+        }
+
+        fn screenshot(&self, _device_id: &str) -> Result<Vec<u8>> {
+            Ok(b"\x89PNG".to_vec())
+        }
+    }
+
+    async fn call(app: &Router, method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_session_find_click_and_type() {
+        let backend = MockBackend::default();
+        let steps = backend.steps.clone();
+        let app = router(backend);
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/session",
+            json!({ "capabilities": { "alwaysMatch": {
+                "appium:udid": "emulator-5556",
+                "appium:appPackage": "com.example",
+                "appium:appActivity": ".MainActivity",
+            }}}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let session = body["value"]["sessionId"].as_str().unwrap().to_string();
+
+        let (_, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element"),
+            json!({ "using": "id", "value": "login" }),
+        )
+        .await;
+        let login = body["value"][ELEMENT_KEY].as_str().unwrap().to_string();
+
+        let (status, _) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element/{login}/click"),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element"),
+            json!({ "using": "accessibility id", "value": "Username" }),
+        )
+        .await;
+        let username = body["value"][ELEMENT_KEY].as_str().unwrap().to_string();
+        call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element/{username}/value"),
+            json!({ "text": "alice" }),
+        )
+        .await;
+
+        let steps = steps.lock().unwrap();
+        let recorded: Vec<_> = steps
+            .iter()
+            .map(|(device, step)| {
+                (
+                    device.as_str(),
+                    format!("{:?}", step.action),
+                    step.target.clone(),
+                    step.value.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            recorded,
+            [
+                (
+                    "emulator-5556",
+                    "Launch".to_string(),
+                    Some("com.example/.MainActivity".to_string()),
+                    None
+                ),
+                (
+                    "emulator-5556",
+                    "Tap".to_string(),
+                    Some("540 350".to_string()),
+                    None
+                ),
+                (
+                    "emulator-5556",
+                    "Tap".to_string(),
+                    Some("540 150".to_string()),
+                    None
+                ),
+                (
+                    "emulator-5556",
+                    "Type".to_string(),
+                    Some("540 150".to_string()),
+                    Some("alice".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_typed_text_is_escaped_for_the_device_shell() {
+        let backend = MockBackend::default();
+        let steps = backend.steps.clone();
+        let app = router(backend);
+        let (_, body) = call(&app, "POST", "/session", json!({ "capabilities": {} })).await;
+        let session = body["value"]["sessionId"].as_str().unwrap().to_string();
+        let (_, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element"),
+            json!({ "using": "id", "value": "username" }),
+        )
+        .await;
+        let username = body["value"][ELEMENT_KEY].as_str().unwrap().to_string();
+
+        call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element/{username}/value"),
+            json!({ "text": "a'b&c;d $(x)" }),
+        )
+        .await;
+
+        let steps = steps.lock().unwrap();
+        let (_, typed) = steps.last().unwrap();
+        assert_eq!(typed.value.as_deref(), Some("a'b&c;d $(x)"));
+        assert_eq!(
+            testing::input_text_arg(typed.value.as_deref().unwrap()),
+            r"a\'b\&c\;d%s\$\(x\)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_child_elements_source_and_errors() {
+        let app = router(MockBackend::default());
+        let (_, body) = call(&app, "POST", "/session", json!({ "capabilities": {} })).await;
+        let session = body["value"]["sessionId"].as_str().unwrap().to_string();
+
+        let (_, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/elements"),
+            json!({ "using": "xpath", "value": "//*[@text='Sign in']" }),
+        )
+        .await;
+        assert_eq!(body["value"].as_array().unwrap().len(), 2);
+
+        let (_, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element"),
+            json!({ "using": "id", "value": "form" }),
+        )
+        .await;
+        let form = body["value"][ELEMENT_KEY].as_str().unwrap().to_string();
+        let (_, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element/{form}/elements"),
+            json!({ "using": "-android uiautomator", "value": "new UiSelector().text(\"Sign in\")" }),
+        )
+        .await;
+        let children = body["value"].as_array().unwrap();
+        assert_eq!(children.len(), 1);
+        let child = children[0][ELEMENT_KEY].as_str().unwrap();
+        let (_, body) = call(
+            &app,
+            "GET",
+            &format!("/session/{session}/element/{child}/attribute/resource-id"),
+            json!({}),
+        )
+        .await;
+        assert_eq!(body["value"], "com.example:id/login");
+
+        let (_, body) = call(
+            &app,
+            "GET",
+            &format!("/session/{session}/source"),
+            json!({}),
+        )
+        .await;
+        assert!(body["value"].as_str().unwrap().contains("<hierarchy"));
+        let (_, body) = call(
+            &app,
+            "GET",
+            &format!("/session/{session}/screenshot"),
+            json!({}),
+        )
+        .await;
+        assert_eq!(body["value"], "iVBORw==");
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element"),
+            json!({ "using": "id", "value": "missing" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["value"]["error"], "no such element");
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            &format!("/session/{session}/element"),
+            json!({ "using": "css selector", "value": "button" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["value"]["error"], "invalid selector");
+
+        call(&app, "DELETE", &format!("/session/{session}"), json!({})).await;
+        let (status, body) = call(
+            &app,
+            "GET",
+            &format!("/session/{session}/source"),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["value"]["error"], "invalid session id");
+    }
+}
@@ -0,0 +1,226 @@
+use crate::testing::ui_hierarchy::{Selector, UiNode};
+
+/// Node attribute a locator condition looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    ResourceId,
+    Text,
+    ContentDesc,
+    Class,
+}
+
+impl Field {
+    fn from_attribute(name: &str) -> Option<Self> {
+        match name {
+            "resource-id" | "resourceId" | "id" => Some(Field::ResourceId),
+            "text" | "name" => Some(Field::Text),
+            "content-desc" | "contentDescription" | "content-description" => {
+                Some(Field::ContentDesc)
+            }
+            "class" | "className" => Some(Field::Class),
+            _ => None,
+        }
+    }
+
+    pub fn value<'a>(&self, node: &'a UiNode) -> &'a str {
+        match self {
+            Field::ResourceId => &node.resource_id,
+            Field::Text => &node.text,
+            Field::ContentDesc => &node.content_desc,
+            Field::Class => &node.class,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub field: Field,
+    pub value: String,
+    pub contains: bool,
+}
+
+impl Condition {
+    fn equals(field: Field, value: &str) -> Self {
+        Self {
+            field,
+            value: value.to_string(),
+            contains: false,
+        }
+    }
+
+    fn matches(&self, node: &UiNode) -> bool {
+        if self.field == Field::ResourceId && !self.contains {
+            // Accept bare ids the same way `id:` selectors do
+            return Selector::ResourceId(self.value.clone()).matches(node);
+        }
+        let actual = self.field.value(node);
+        if self.contains {
+            actual.contains(&self.value)
+        } else {
+            actual == self.value
+        }
+    }
+}
+
+/// A WebDriver locator translated onto uiautomator nodes. All conditions must match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locator {
+    pub conditions: Vec<Condition>,
+}
+
+impl Locator {
+    /// Parses a `using`/`value` pair. Supports `id`, `accessibility id`, `class name`,
+    /// simple `xpath` (`//tag[@attr='v' and contains(@attr,'v')]`) and
+    /// `-android uiautomator` `UiSelector` chains.
+    pub fn parse(using: &str, value: &str) -> Result<Self, String> {
+        let conditions = match using {
+            "id" => vec![Condition::equals(Field::ResourceId, value)],
+            "accessibility id" => vec![Condition::equals(Field::ContentDesc, value)],
+            "class name" => vec![Condition::equals(Field::Class, value)],
+            "xpath" => parse_xpath(value)?,
+            "-android uiautomator" => parse_ui_selector(value)?,
+            other => return Err(format!("locator strategy '{other}' is not supported")),
+        };
+        Ok(Self { conditions })
+    }
+
+    pub fn matches(&self, node: &UiNode) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(node))
+    }
+}
+
+fn parse_xpath(xpath: &str) -> Result<Vec<Condition>, String> {
+    let invalid = || format!("unsupported xpath '{xpath}'");
+    let rest = xpath.trim().strip_prefix("//").ok_or_else(invalid)?;
+
+    let (tag, predicate) = match rest.find('[') {
+        Some(open) => {
+            let predicate = rest[open + 1..].strip_suffix(']').ok_or_else(invalid)?;
+            (&rest[..open], Some(predicate))
+        }
+        None => (rest, None),
+    };
+    if tag.is_empty() || tag.contains('/') {
+        return Err(invalid());
+    }
+
+    let mut conditions = Vec::new();
+    if tag != "*" {
+        conditions.push(Condition::equals(Field::Class, tag));
+    }
+
+    for clause in predicate.into_iter().flat_map(|p| p.split(" and ")) {
+        let clause = clause.trim();
+        let (attribute, value, contains) = if let Some(args) = clause
+            .strip_prefix("contains(")
+            .and_then(|c| c.strip_suffix(')'))
+        {
+            let (attribute, value) = args.split_once(',').ok_or_else(invalid)?;
+            (attribute.trim(), value.trim(), true)
+        } else {
+            let (attribute, value) = clause.split_once('=').ok_or_else(invalid)?;
+            (attribute.trim(), value.trim(), false)
+        };
+
+        let field = attribute
+            .strip_prefix('@')
+            .and_then(Field::from_attribute)
+            .ok_or_else(invalid)?;
+        let value = unquote(value).ok_or_else(invalid)?;
+        conditions.push(Condition {
+            field,
+            value: value.to_string(),
+            contains,
+        });
+    }
+
+    Ok(conditions)
+}
+
+fn parse_ui_selector(selector: &str) -> Result<Vec<Condition>, String> {
+    let invalid = || format!("unsupported UiSelector '{selector}'");
+    let rest = selector
+        .trim()
+        .trim_end_matches(';')
+        .strip_prefix("new UiSelector()")
+        .ok_or_else(invalid)?;
+
+    let mut conditions = Vec::new();
+    for call in rest.split(").").map(|call| call.trim_start_matches('.')) {
+        if call.is_empty() {
+            continue;
+        }
+        let (method, argument) = call.split_once('(').ok_or_else(invalid)?;
+        let value = unquote(argument.trim_end_matches(')')).ok_or_else(invalid)?;
+
+        let (field, contains) = match method {
+            "text" => (Field::Text, false),
+            "textContains" => (Field::Text, true),
+            "resourceId" => (Field::ResourceId, false),
+            "description" => (Field::ContentDesc, false),
+            "descriptionContains" => (Field::ContentDesc, true),
+            "className" => (Field::Class, false),
+            _ => return Err(invalid()),
+        };
+        conditions.push(Condition {
+            field,
+            value: value.to_string(),
+            contains,
+        });
+    }
+
+    Ok(conditions)
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    let value = value.trim();
+    ['\'', '"'].iter().find_map(|quote| {
+        value
+            .strip_prefix(*quote)
+            .and_then(|v| v.strip_suffix(*quote))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(resource_id: &str, text: &str, class: &str) -> UiNode {
+        UiNode {
+            resource_id: resource_id.to_string(),
+            text: text.to_string(),
+            class: class.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_locators() {
+        let button = node("com.example:id/login", "Sign in", "android.widget.Button");
+
+        for (using, value) in [
+            ("id", "login"),
+            ("id", "com.example:id/login"),
+            ("xpath", "//android.widget.Button[@text='Sign in']"),
+            (
+                "xpath",
+                "//*[contains(@text, 'Sign') and @resource-id=\"com.example:id/login\"]",
+            ),
+            (
+                "-android uiautomator",
+                "new UiSelector().className(\"android.widget.Button\").textContains(\"Sign\")",
+            ),
+        ] {
+            let locator = Locator::parse(using, value).unwrap();
+            assert!(locator.matches(&button), "{using} {value}");
+        }
+
+        assert!(!Locator::parse("accessibility id", "login")
+            .unwrap()
+            .matches(&button));
+        assert!(Locator::parse("xpath", "/hierarchy/node[1]").is_err());
+        assert!(Locator::parse("css selector", "button").is_err());
+    }
+}